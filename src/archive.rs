@@ -0,0 +1,131 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::io::atomic_file::AtomicFile;
+
+/// format of the timestamp snapshots are named after
+const SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// ArchiveConfig configures the compressed snapshots kept of every downloaded list
+/// which document what upstream data the outputs of a run were built from
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArchiveConfig {
+    /// the directory the snapshots are stored in, one sub directory per list
+    pub path: String,
+    /// days a snapshot is kept, without retention snapshots are never removed
+    pub retention_days: Option<u64>,
+}
+
+/// Stores a gzip compressed copy of a downloaded list named after the time of the
+/// download and returns its path
+///
+/// * `config`: the archive configuration
+/// * `id`: the list's id
+/// * `list_path`: the downloaded list
+/// * `now`: the time of the download
+pub fn snapshot(
+    config: &ArchiveConfig,
+    id: &str,
+    list_path: &Path,
+    now: DateTime<Utc>,
+) -> anyhow::Result<PathBuf> {
+    let dir = Path::new(&config.path).join(id);
+    fs::create_dir_all(&dir).with_context(|| "could not create archive directory")?;
+    let path = dir.join(format!("{}.gz", now.format(SNAPSHOT_FORMAT)));
+    let mut reader = BufReader::new(
+        File::open(list_path).with_context(|| format!("could not read {}", list_path.display()))?,
+    );
+    let mut snapshot = AtomicFile::create(path.clone())?;
+    let mut encoder = GzEncoder::new(&mut snapshot, Compression::default());
+    let written =
+        std::io::copy(&mut reader, &mut encoder).and_then(|_| encoder.finish().map(|_| ()));
+    if let Err(e) = written {
+        snapshot.discard();
+        return Err(e).with_context(|| format!("could not write {}", path.display()));
+    }
+    snapshot.commit()?;
+    Ok(path)
+}
+
+/// Removes the snapshots of all lists which are older than the retention period and
+/// returns the number of removed snapshots
+///
+/// * `config`: the archive configuration
+/// * `now`: the point in time the retention period ends
+pub fn expire(config: &ArchiveConfig, now: DateTime<Utc>) -> anyhow::Result<usize> {
+    let retention = match config.retention_days {
+        Some(days) => Duration::days(days as i64),
+        None => return Ok(0),
+    };
+    let archive = Path::new(&config.path);
+    if !archive.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for list_dir in fs::read_dir(archive)?.flatten() {
+        if !list_dir.path().is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(list_dir.path())?.flatten() {
+            let path = entry.path();
+            let taken = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| NaiveDateTime::parse_from_str(s, SNAPSHOT_FORMAT).ok());
+            // files not named by harvester are left alone
+            if let Some(taken) = taken
+                && now - taken.and_utc() > retention
+            {
+                fs::remove_file(&path)
+                    .with_context(|| format!("could not remove {}", path.display()))?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use crate::tests::helper::cache_file_creator::TEST_CACHE;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_expire() {
+        let base = PathBuf::from(TEST_CACHE).join("test_snapshot_and_expire");
+        fs::remove_dir_all(&base).ok();
+        fs::create_dir_all(&base).unwrap();
+        let list_path = base.join("list");
+        fs::write(&list_path, "domain.one\n").unwrap();
+        let config = ArchiveConfig {
+            path: base.join("archive").to_str().unwrap().to_string(),
+            retention_days: Some(30),
+        };
+
+        let now = Utc::now();
+        let old = snapshot(&config, "list", &list_path, now - Duration::days(31)).unwrap();
+        let recent = snapshot(&config, "list", &list_path, now).unwrap();
+        let mut contents = String::new();
+        GzDecoder::new(File::open(&recent).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "domain.one\n");
+
+        assert_eq!(expire(&config, now).unwrap(), 1);
+        assert!(!old.exists());
+        assert!(recent.exists());
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// AuditAction tells whether a domain entered or left a category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Added,
+    Removed,
+}
+
+/// AuditRecord documents a single domain added to or removed from a category
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditRecord {
+    /// start of the run which made the change in RFC 3339 format
+    pub run: String,
+    pub category: String,
+    pub action: AuditAction,
+    pub domain: String,
+    /// ids of the lists the domain was taken from, empty for removed domains
+    pub sources: Vec<String>,
+}
+
+/// Returns a record for every domain which was added to or removed from a category
+/// since the last run
+///
+/// * `run`: start of the current run
+/// * `category`: the category's name
+/// * `entries`: the category's new entries
+/// * `previous`: the category's entries of the last run
+/// * `sources`: the ids of the lists each new entry was taken from
+pub fn changes(
+    run: &str,
+    category: &str,
+    entries: &BTreeSet<String>,
+    previous: &HashSet<String>,
+    sources: &HashMap<String, Vec<String>>,
+) -> Vec<AuditRecord> {
+    let record = |action, domain: &String| AuditRecord {
+        run: run.to_string(),
+        category: category.to_string(),
+        action,
+        domain: domain.clone(),
+        sources: match action {
+            AuditAction::Added => sources.get(domain).cloned().unwrap_or_default(),
+            AuditAction::Removed => vec![],
+        },
+    };
+    let mut removed: Vec<&String> = previous.iter().filter(|e| !entries.contains(*e)).collect();
+    removed.sort();
+    entries
+        .iter()
+        .filter(|e| !previous.contains(*e))
+        .map(|e| record(AuditAction::Added, e))
+        .chain(removed.into_iter().map(|e| record(AuditAction::Removed, e)))
+        .collect()
+}
+
+/// Appends the records to the audit log, one JSON document per line. Existing
+/// records are never modified.
+///
+/// * `path`: the audit log file
+/// * `records`: the records to be appended
+pub fn append(path: &Path, records: &[AuditRecord]) -> anyhow::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| "could not create audit log directory")?;
+    }
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("could not open audit log {}", path.display()))?;
+    file.write_all(lines.as_bytes())
+        .with_context(|| format!("could not write audit log {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::tests::helper::cache_file_creator::TEST_CACHE;
+
+    use super::*;
+
+    #[test]
+    fn test_audit_changes() {
+        let entries = BTreeSet::from(["kept.domain".to_string(), "new.domain".to_string()]);
+        let previous = HashSet::from(["kept.domain".to_string(), "old.domain".to_string()]);
+        let sources = HashMap::from([(
+            "new.domain".to_string(),
+            vec!["one".to_string(), "two".to_string()],
+        )]);
+        let records = changes(
+            "2024-05-01T02:00:00Z",
+            "malware",
+            &entries,
+            &previous,
+            &sources,
+        );
+
+        let path = PathBuf::from(TEST_CACHE).join("test_audit_changes.jsonl");
+        fs::remove_file(&path).ok();
+        append(&path, &records).unwrap();
+        append(&path, &records[..1]).unwrap();
+        let log = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"run":"2024-05-01T02:00:00Z","category":"malware","action":"added","domain":"new.domain","sources":["one","two"]}"#,
+                r#"{"run":"2024-05-01T02:00:00Z","category":"malware","action":"removed","domain":"old.domain","sources":[]}"#,
+                r#"{"run":"2024-05-01T02:00:00Z","category":"malware","action":"added","domain":"new.domain","sources":["one","two"]}"#,
+            ]
+        );
+    }
+}
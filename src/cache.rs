@@ -0,0 +1,411 @@
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use async_compression::tokio::{bufread::ZstdDecoder, write::ZstdEncoder};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncWriteExt, BufReader},
+};
+use tokio_tar::{Archive, Builder};
+
+use crate::{
+    config::{Config, CACHED_CONF_FILE_NAME},
+    manifest::{Manifest, MANIFEST_FILE_NAME},
+    run_state::RUN_STATE_FILE_NAME,
+    CATEGORIZE_PATH, DOWNLOAD_PATH, EXTRACT_PATH, SOURCES_PATH, TRANSFORM_PATH,
+};
+
+/// Sub path of the cache directory containing the downloaded lists by content hash
+pub const BLOB_PATH: &str = "blobs";
+
+/// returns the hex encoded SHA-256 hash of a file's content
+///
+/// * `path`: the file to be hashed
+pub fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 8192];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Moves a downloaded list into the content addressed blob store and replaces the
+/// list's file by a hard link to the blob. Identical lists, also of other
+/// configurations sharing the cache directory, are stored only once.
+///
+/// * `cache_dir`: the cache directory
+/// * `list_path`: the freshly downloaded list
+/// * `id`: the list's id
+/// * `manifest`: records which blob belongs to the list
+pub fn store_blob(
+    cache_dir: &Path,
+    list_path: &Path,
+    id: &str,
+    manifest: &mut Manifest,
+) -> anyhow::Result<String> {
+    let hash = hash_file(list_path)?;
+    let blob_dir = cache_dir.join(BLOB_PATH);
+    std::fs::create_dir_all(&blob_dir).with_context(|| "could not create blob directory")?;
+    let blob = blob_dir.join(&hash);
+    if blob.exists() {
+        std::fs::remove_file(list_path)?;
+        std::fs::hard_link(&blob, list_path)
+            .with_context(|| format!("could not link {}", list_path.display()))?;
+    } else {
+        std::fs::hard_link(list_path, &blob)
+            .with_context(|| format!("could not store {}", blob.display()))?;
+    }
+
+    // drop the list's former blob unless another list still refers to it
+    if let Some(old) = manifest.blobs.insert(id.to_string(), hash.clone())
+        && old != hash
+        && !manifest.blobs.values().any(|h| h == &old)
+    {
+        std::fs::remove_file(blob_dir.join(&old)).ok();
+    }
+    Ok(hash)
+}
+
+/// returns the accumulated size of all files within a directory
+///
+/// * `path`: the directory to be measured
+pub fn dir_size(path: &Path) -> u64 {
+    let entries = match std::fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| match e.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&e.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Removes the artifacts of lists and categories which are no longer configured and
+/// blobs which no list refers to. Returns the number of bytes freed.
+///
+/// * `config`: the current configuration
+/// * `manifest`: the manifest mapping the lists to their blobs
+pub fn prune(config: &Config, manifest: &mut Manifest) -> u64 {
+    let cache_dir = Path::new(&config.cache_dir);
+    let ids: Vec<String> = config.lists.iter().map(|l| l.id.clone()).collect();
+    let tags = config.category_names();
+    let mut freed = 0;
+
+    manifest.blobs.retain(|id, _| ids.contains(id));
+    manifest.http.retain(|id, _| ids.contains(id));
+    manifest.fetched.retain(|id, _| ids.contains(id));
+    manifest.ages.retain(|id, _| ids.contains(id));
+    let referenced: Vec<String> = manifest.blobs.values().cloned().collect();
+    for (dir, keep) in [
+        (DOWNLOAD_PATH, &ids),
+        (EXTRACT_PATH, &ids),
+        (TRANSFORM_PATH, &ids),
+        (CATEGORIZE_PATH, &tags),
+        (SOURCES_PATH, &tags),
+        (BLOB_PATH, &referenced),
+    ] {
+        let entries = match std::fs::read_dir(cache_dir.join(dir)) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if keep.contains(&name) {
+                continue;
+            }
+            if let Ok(meta) = entry.metadata()
+                && meta.is_file()
+                && std::fs::remove_file(entry.path()).is_ok()
+            {
+                debug!("pruned {}", entry.path().display());
+                freed += meta.len();
+            }
+        }
+    }
+    freed
+}
+
+/// Checks the integrity of the downloaded lists and returns a description of every
+/// problem found. The content of every blob has to match its hash and every list
+/// has to match the blob the manifest records for it.
+///
+/// * `cache_dir`: the cache directory to be checked
+/// * `manifest`: the manifest mapping the lists to their blobs
+pub fn verify(cache_dir: &Path, manifest: &Manifest) -> anyhow::Result<Vec<String>> {
+    let mut problems = vec![];
+    let blob_dir = cache_dir.join(BLOB_PATH);
+    let blobs: Vec<PathBuf> = match std::fs::read_dir(&blob_dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e).with_context(|| "could not read blob directory"),
+    };
+    for blob in blobs.iter() {
+        let name = blob.file_name().unwrap().to_string_lossy().to_string();
+        if hash_file(blob)? != name {
+            problems.push(format!("blob {name} is corrupt"));
+        }
+        if !manifest.blobs.values().any(|h| h == &name) {
+            problems.push(format!("blob {name} is not referenced by any list"));
+        }
+    }
+    let mut ids: Vec<&String> = manifest.blobs.keys().collect();
+    ids.sort();
+    for id in ids {
+        let hash = &manifest.blobs[id];
+        if !blob_dir.join(hash).exists() {
+            problems.push(format!("{id}: blob {hash} is missing"));
+            continue;
+        }
+        let list_path = cache_dir.join(DOWNLOAD_PATH).join(id);
+        match hash_file(&list_path) {
+            Ok(h) if &h == hash => {}
+            Ok(_) => problems.push(format!("{id}: content doesn't match blob {hash}")),
+            Err(e) => problems.push(format!("{id}: {e}")),
+        }
+    }
+    Ok(problems)
+}
+
+/// Checks whether the cache directory can be used by this version of harvester. The
+/// cache of an older version is migrated when the manifest is loaded. A cache whose
+/// manifest can't be read, e.g. since a newer version wrote it, is rebuilt from the
+/// downloaded lists. Returns true if the cache was rebuilt.
+///
+/// * `cache_dir`: the cache directory to be checked
+pub fn check_compatibility(cache_dir: &Path) -> anyhow::Result<bool> {
+    let error = match Manifest::load(cache_dir) {
+        Ok(manifest) => {
+            if let Some(version) = &manifest.harvester_version
+                && version != env!("CARGO_PKG_VERSION")
+            {
+                info!("migrating the cache of harvester {}", version);
+            }
+            return Ok(false);
+        }
+        Err(e) => e,
+    };
+    warn!("{:?} - rebuilding the cache", error);
+    for dir in [
+        BLOB_PATH,
+        EXTRACT_PATH,
+        TRANSFORM_PATH,
+        CATEGORIZE_PATH,
+        SOURCES_PATH,
+    ] {
+        let path = cache_dir.join(dir);
+        if path.exists() {
+            std::fs::remove_dir_all(&path)
+                .with_context(|| format!("could not remove {}", path.display()))?;
+        }
+    }
+    for file in [
+        MANIFEST_FILE_NAME,
+        CACHED_CONF_FILE_NAME,
+        RUN_STATE_FILE_NAME,
+    ] {
+        let path = cache_dir.join(file);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("could not remove {}", path.display()))?;
+        }
+    }
+    Ok(true)
+}
+
+/// Bundles the downloaded lists and the manifest of the cache directory into a zstd
+/// compressed tar archive, e.g. to rebuild the outputs on a machine without internet
+/// access
+///
+/// * `cache_dir`: the cache directory to be exported
+/// * `bundle`: path of the archive to be written
+pub async fn export(cache_dir: &Path, bundle: &Path) -> anyhow::Result<()> {
+    let download_path = cache_dir.join(DOWNLOAD_PATH);
+    if !download_path.is_dir() {
+        return Err(anyhow::anyhow!(
+            "no downloaded lists found in {}",
+            cache_dir.display()
+        ));
+    }
+    let file = File::create(bundle)
+        .await
+        .with_context(|| format!("could not create {}", bundle.display()))?;
+    let mut builder = Builder::new(ZstdEncoder::new(file));
+    builder
+        .append_dir_all(DOWNLOAD_PATH, &download_path)
+        .await
+        .with_context(|| "could not add downloaded lists")?;
+    let manifest_path = cache_dir.join(MANIFEST_FILE_NAME);
+    if manifest_path.exists() {
+        builder
+            .append_path_with_name(&manifest_path, MANIFEST_FILE_NAME)
+            .await
+            .with_context(|| "could not add manifest")?;
+    }
+    let mut encoder = builder.into_inner().await?;
+    encoder.shutdown().await?;
+    Ok(())
+}
+
+/// Unpacks an archive created by `export` into the cache directory replacing the
+/// downloaded lists and the manifest
+///
+/// * `cache_dir`: the cache directory to be populated
+/// * `bundle`: path of the archive to be read
+pub async fn import(cache_dir: &Path, bundle: &Path) -> anyhow::Result<()> {
+    let file = File::open(bundle)
+        .await
+        .with_context(|| format!("could not open {}", bundle.display()))?;
+    fs::create_dir_all(cache_dir)
+        .await
+        .with_context(|| "could not create cache directory")?;
+    // the lists may be hard links to blobs which must not be overwritten
+    let download_path = cache_dir.join(DOWNLOAD_PATH);
+    fs::remove_dir_all(&download_path).await.ok();
+    let mut archive = Archive::new(ZstdDecoder::new(BufReader::new(file)));
+    archive
+        .unpack(cache_dir)
+        .await
+        .with_context(|| format!("could not unpack {}", bundle.display()))?;
+
+    // move the imported lists into the blob store
+    let mut manifest = Manifest::load(cache_dir)?;
+    let ids: Vec<String> = manifest.blobs.keys().cloned().collect();
+    for id in ids {
+        let list_path = download_path.join(&id);
+        if list_path.exists() {
+            store_blob(cache_dir, &list_path, &id, &mut manifest)?;
+        } else {
+            manifest.blobs.remove(&id);
+        }
+    }
+    manifest.save(cache_dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        filter_list::FilterList,
+        tests::helper::cache_file_creator::{CacheFileCreator, TEST_CACHE},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let base = PathBuf::from(TEST_CACHE).join("test_export_import");
+        let source = base.join("source");
+        let target = base.join("target");
+        let bundle = base.join("cache.tar.zst");
+        fs::remove_dir_all(&target).await.ok();
+        fs::create_dir_all(source.join(DOWNLOAD_PATH))
+            .await
+            .unwrap();
+        fs::write(
+            source.join(DOWNLOAD_PATH).join("list"),
+            "0.0.0.0 domain.one\n",
+        )
+        .await
+        .unwrap();
+        fs::write(source.join(MANIFEST_FILE_NAME), "{}")
+            .await
+            .unwrap();
+
+        export(&source, &bundle).await.unwrap();
+        import(&target, &bundle).await.unwrap();
+
+        let list = fs::read_to_string(target.join(DOWNLOAD_PATH).join("list"))
+            .await
+            .unwrap();
+        assert_eq!(list, "0.0.0.0 domain.one\n");
+        assert!(target.join(MANIFEST_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_store_blob_and_verify() {
+        let base = PathBuf::from(TEST_CACHE).join("test_store_blob_and_verify");
+        std::fs::remove_dir_all(&base).ok();
+        let download = base.join(DOWNLOAD_PATH);
+        std::fs::create_dir_all(&download).unwrap();
+        let mut manifest = Manifest::default();
+
+        // identical lists share a single blob
+        for id in ["one", "two"] {
+            std::fs::write(download.join(id), "domain.one\n").unwrap();
+            store_blob(&base, &download.join(id), id, &mut manifest).unwrap();
+        }
+        assert_eq!(manifest.blobs["one"], manifest.blobs["two"]);
+        assert_eq!(std::fs::read_dir(base.join(BLOB_PATH)).unwrap().count(), 1);
+        assert!(verify(&base, &manifest).unwrap().is_empty());
+
+        // a list modified outside of harvester is detected
+        std::fs::remove_file(download.join("two")).unwrap();
+        std::fs::write(download.join("two"), "domain.two\n").unwrap();
+        let problems = verify(&base, &manifest).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("two: content doesn't match"));
+    }
+
+    #[test]
+    fn test_prune() {
+        let cache = CacheFileCreator::new("test_prune", DOWNLOAD_PATH, EXTRACT_PATH);
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "kept".to_string(),
+            ..Default::default()
+        }];
+        let cache_dir = PathBuf::from(&config.cache_dir);
+        let mut manifest = Manifest::default();
+        for id in ["kept", "removed"] {
+            let path = cache_dir.join(DOWNLOAD_PATH).join(id);
+            std::fs::write(&path, id).unwrap();
+            store_blob(&cache_dir, &path, id, &mut manifest).unwrap();
+            std::fs::write(cache_dir.join(EXTRACT_PATH).join(id), id).unwrap();
+        }
+
+        assert!(prune(&config, &mut manifest) > 0);
+        assert!(cache_dir.join(DOWNLOAD_PATH).join("kept").exists());
+        assert!(!cache_dir.join(DOWNLOAD_PATH).join("removed").exists());
+        assert!(!cache_dir.join(EXTRACT_PATH).join("removed").exists());
+        assert_eq!(manifest.blobs.len(), 1);
+        assert!(verify(&cache_dir, &manifest).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_check_compatibility() {
+        let cache = CacheFileCreator::new("test_check_compatibility", DOWNLOAD_PATH, EXTRACT_PATH);
+        let cache_dir = PathBuf::from(cache.new_test_config().cache_dir);
+        cache.write_input("list", "domain.one\n");
+        Manifest::default().save(&cache_dir).unwrap();
+        assert!(!check_compatibility(&cache_dir).unwrap());
+        let manifest = std::fs::read_to_string(cache_dir.join(MANIFEST_FILE_NAME)).unwrap();
+        assert!(manifest.contains(env!("CARGO_PKG_VERSION")));
+
+        // a cache written by a newer version
+        std::fs::write(cache_dir.join(EXTRACT_PATH).join("list"), "domain.one\n").unwrap();
+        std::fs::write(cache_dir.join(CACHED_CONF_FILE_NAME), "{}").unwrap();
+        std::fs::write(cache_dir.join(MANIFEST_FILE_NAME), r#"{"version": 99}"#).unwrap();
+        assert!(check_compatibility(&cache_dir).unwrap());
+        assert!(!cache_dir.join(EXTRACT_PATH).exists());
+        assert!(!cache_dir.join(CACHED_CONF_FILE_NAME).exists());
+        assert!(cache_dir.join(DOWNLOAD_PATH).join("list").exists());
+        assert!(!check_compatibility(&cache_dir).unwrap());
+    }
+}
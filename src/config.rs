@@ -1,15 +1,47 @@
 use std::io::prelude::*;
 use std::{
+    collections::HashMap,
     fs::{self, File},
+    net::IpAddr,
     path::{Path, PathBuf},
 };
 
 use anyhow::Context;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 
-use crate::{filter_list::FilterList, output::OutputType};
+use crate::{
+    archive::ArchiveConfig,
+    cache_backend::CacheBackendConfig,
+    crowdsec::CrowdSecConfig,
+    dns::DnsConfig,
+    filter_list::FilterList,
+    geoip::GeoIpConfig,
+    input::file::Compression,
+    normalize::IdnPolicy,
+    output::{
+        blocky::BlockyConfig, bloom::BloomConfig, coredns::CoreDnsConfig, json::JsonConfig,
+        suricata::SuricataConfig, template::TemplateConfig, wildcard::WildcardConfig, OutputType,
+    },
+    report::ReportConfig,
+    shutdown::ShutdownPolicy,
+    sqlite::SqliteConfig,
+    syslog::SyslogConfig,
+    theme::Palette,
+    tuning::Tuning,
+};
 
 pub const CACHED_CONF_FILE_NAME: &str = "last_config.json";
+/// id of the built-in filter list containing the top sites
+pub const TOP_SITES_ID: &str = "top_sites";
+/// the special category collecting domains which must never be blocked
+pub const ALLOW_CATEGORY: &str = "allow";
+/// the built-in category merging all other categories but the allow category
+pub const ALL_CATEGORY: &str = "all";
+
+/// the share of a list's average match rate it may lose before a format change is
+/// reported
+const DEFAULT_MATCH_RATE_DROP: f64 = 0.5;
 
 /// Config contains all relevant information to start the data processing.
 /// Relevant information is considered most of all data sources and destinations
@@ -17,24 +49,386 @@ pub const CACHED_CONF_FILE_NAME: &str = "last_config.json";
 pub struct Config {
     pub lists: Vec<FilterList>,
     pub cache_dir: String,
-    pub output_dir: String,
-    pub output_format: OutputType,
+    /// one or several output directories
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    pub output_dir: Vec<String>,
+    /// one or several output formats, each written into its own subdirectory if
+    /// there are several
+    #[serde(alias = "output_formats", deserialize_with = "deserialize_one_or_many")]
+    pub output_format: Vec<OutputType>,
+    /// write the output files gzip compressed with the extension .gz
+    #[serde(default)]
+    pub gzip_output: bool,
+    /// describe the category in comments heading the output files
+    #[serde(default)]
+    pub output_banner: bool,
+    /// the address the domains resolve to in the Hostsfile format, 0.0.0.0 by default
+    pub hosts_ip: Option<IpAddr>,
+    /// the address of a second line per domain in the Hostsfile format
+    pub hosts_ipv6: Option<IpAddr>,
+    pub top_sites: Option<TopSites>,
+    /// options applying to single categories, keyed by category name
+    #[serde(default)]
+    pub categories: HashMap<String, CategoryOptions>,
+    /// an optional human readable report written after each run
+    pub report: Option<ReportConfig>,
+    /// tags attached to lists which don't have any tags configured
+    #[serde(default)]
+    pub default_tags: Vec<String>,
+    /// settings all lists inherit unless they set them themselves
+    #[serde(default)]
+    pub defaults: Map<String, Value>,
+    /// named bundles of settings shared by the lists of the same provider
+    #[serde(default)]
+    pub groups: Map<String, Value>,
+    /// process the downloaded lists found in the cache without accessing the network
+    #[serde(skip)]
+    pub offline: bool,
+    /// continue the interrupted last run without downloading its lists again
+    #[serde(skip)]
+    pub resume: bool,
+    /// what an interrupted run completes before it exits
+    #[serde(default)]
+    pub shutdown: ShutdownPolicy,
+    /// defaults of the performance settings matching the machine's resources
+    #[serde(skip)]
+    pub tuning: Tuning,
+    /// keep the previous version of every category until its changes are approved
+    #[serde(skip)]
+    pub review: bool,
+    /// unique id of the current run, stamped into logs, manifest, reports and outputs
+    #[serde(skip)]
+    pub run_id: String,
+    /// limits applying to the regexes of all lists
+    #[serde(default)]
+    pub regex_options: RegexOptions,
+    /// space limits of the cache directory
+    #[serde(default)]
+    pub disk: DiskOptions,
+    /// timestamped copies of every downloaded list
+    pub archive: Option<ArchiveConfig>,
+    /// where the cache is kept between runs, the cache directory itself if not set
+    pub cache_backend: Option<CacheBackendConfig>,
+    /// file every domain added to or removed from a category is appended to
+    pub audit_log: Option<String>,
+    /// local databases IP entries are filtered and annotated by
+    pub geoip: Option<GeoIpConfig>,
+    /// settings of the Suricata output format
+    pub suricata: Option<SuricataConfig>,
+    /// settings of the Json output format
+    pub json: Option<JsonConfig>,
+    /// settings of the CoreDns output format
+    pub coredns: Option<CoreDnsConfig>,
+    /// settings of the Blocky output format
+    pub blocky: Option<BlockyConfig>,
+    /// settings of the Bloom output format
+    pub bloom: Option<BloomConfig>,
+    /// write the domains as wildcards matching their subdomains as well
+    pub wildcard: Option<WildcardConfig>,
+    /// settings of the Template output format
+    pub template: Option<TemplateConfig>,
+    /// SQLite database all categories are written to after each run
+    pub sqlite: Option<SqliteConfig>,
+    /// additionally write the built-in `all` category merging all other categories
+    #[serde(default)]
+    pub all_category: bool,
+    /// colors of the terminal output
+    #[serde(default)]
+    pub palette: Palette,
+    /// send anonymous usage statistics after each run, opt-in
+    #[serde(default)]
+    pub telemetry: bool,
+    /// categories pushed to a CrowdSec blocklist after each run
+    pub crowdsec: Option<CrowdSecConfig>,
+    /// log daemon a summary of every category is sent to after each run
+    pub syslog: Option<SyslogConfig>,
+    /// DNS servers and pinned addresses the lists are downloaded with
+    pub dns: Option<DnsConfig>,
+    /// number of entries of a single category beyond which the run fails without
+    /// writing any output
+    pub absolute_max_entries: Option<usize>,
+    /// share of a list's average match rate it may lose before a format change is
+    /// reported, half of it if not set
+    pub match_rate_drop: Option<f64>,
+    /// number of lists downloaded at once, all lists at once if not set
+    pub max_parallel_downloads: Option<usize>,
+    /// bytes of a download collected in memory before they are written to disk
+    pub download_chunk_size: Option<usize>,
+    /// number of lists extracted at once, all lists at once if not set
+    pub max_parallel_extracts: Option<usize>,
+    /// the version of harvester which wrote the cached configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub harvester_version: Option<String>,
     pub cached_config: Option<Box<Self>>,
 }
 
+/// deserializes either a single value or a list of values into a Vec
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => Ok(vec![value]),
+        OneOrMany::Many(values) => Ok(values),
+    }
+}
+
+/// Merges the settings of a list's group into the list. The group's `source_prefix`
+/// is prepended to the list's source and the list's tags are added to the group's
+/// tags. All other settings of the list take precedence over the group's.
+///
+/// * `config`: the configuration file's json document
+fn apply_groups(config: &mut Value) -> anyhow::Result<()> {
+    let groups = match config.get("groups") {
+        Some(Value::Object(g)) => g.clone(),
+        _ => return Ok(()),
+    };
+    let lists = match config.get_mut("lists") {
+        Some(Value::Array(l)) => l,
+        _ => return Ok(()),
+    };
+    for list in lists.iter_mut().filter_map(|l| l.as_object_mut()) {
+        // the group is resolved once so the merged list doesn't refer to it anymore
+        let name = match list.remove("group") {
+            Some(Value::String(n)) => n,
+            Some(v) => return Err(anyhow::anyhow!("invalid group {}", v)),
+            None => continue,
+        };
+        let group = groups
+            .get(&name)
+            .and_then(|g| g.as_object())
+            .ok_or_else(|| anyhow::anyhow!("unknown group {}", name))?;
+        for (key, value) in group.iter() {
+            match key.as_str() {
+                "source_prefix" => {
+                    let prefix = value.as_str().unwrap_or_default();
+                    let source = list
+                        .get("source")
+                        .and_then(|s| s.as_str())
+                        .unwrap_or_default();
+                    list.insert(
+                        "source".to_string(),
+                        Value::from(format!("{prefix}{source}")),
+                    );
+                }
+                "tags" => {
+                    let mut tags = value.as_array().cloned().unwrap_or_default();
+                    if let Some(Value::Array(extra)) = list.get("tags") {
+                        tags.extend(
+                            extra
+                                .iter()
+                                .filter(|t| !tags.contains(t))
+                                .cloned()
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                    list.insert("tags".to_string(), Value::Array(tags));
+                }
+                _ => {
+                    list.entry(key).or_insert_with(|| value.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copies the settings of the `defaults` block to every list which doesn't set them
+///
+/// * `config`: the configuration file's json document
+fn apply_defaults(config: &mut Value) {
+    let defaults = match config.get("defaults") {
+        Some(Value::Object(d)) => d.clone(),
+        _ => return,
+    };
+    if let Some(Value::Array(lists)) = config.get_mut("lists") {
+        for list in lists.iter_mut().filter_map(|l| l.as_object_mut()) {
+            for (key, value) in defaults.iter() {
+                list.entry(key).or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+/// EntryOrder determines the order the entries of a category are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EntryOrder {
+    /// sorted alphabetically
+    #[default]
+    Lexicographic,
+    /// sorted by the domains' labels from right to left so a domain and all of its
+    /// subdomains are adjacent
+    Grouped,
+    /// the order the entries were first read in from the included lists
+    Insertion,
+}
+
+/// CategoryOptions contains the settings which can be made per category
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CategoryOptions {
+    /// how internationalized domain names are treated
+    #[serde(default)]
+    pub idn: IdnPolicy,
+    /// the order the entries are written in
+    #[serde(default)]
+    pub order: EntryOrder,
+    /// append the Unicode form of punycode entries as comment in formats read by humans
+    #[serde(default)]
+    pub annotate_idn: bool,
+    /// path of a Lua script rewriting or dropping every entry of the category
+    pub transform: Option<String>,
+    /// ISO codes of the countries IP entries are kept for, all others are dropped
+    #[serde(default)]
+    pub include_countries: Vec<String>,
+    /// ISO codes of the countries IP entries are dropped for
+    #[serde(default)]
+    pub exclude_countries: Vec<String>,
+    /// the first SID of the category's Suricata rules
+    pub sid_start: Option<u64>,
+    /// the formats the category is written in instead of the global output formats
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub output_format: Vec<OutputType>,
+}
+
+impl CategoryOptions {
+    /// returns true if any country filter is set
+    pub fn filters_countries(&self) -> bool {
+        !self.include_countries.is_empty() || !self.exclude_countries.is_empty()
+    }
+
+    /// returns true if an IP entry located in the given country is kept. Entries of
+    /// unknown location are only kept if no countries are included explicitly.
+    ///
+    /// * `country`: ISO code of the entry's country
+    pub fn allows_country(&self, country: Option<&str>) -> bool {
+        let matches = |list: &Vec<String>, c: &str| list.iter().any(|l| l.eq_ignore_ascii_case(c));
+        match country {
+            Some(c) => {
+                (self.include_countries.is_empty() || matches(&self.include_countries, c))
+                    && !matches(&self.exclude_countries, c)
+            }
+            None => self.include_countries.is_empty(),
+        }
+    }
+}
+
+/// DiskOptions keep a run from exhausting the cache partition
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DiskOptions {
+    /// bytes which have to remain free on the cache partition
+    #[serde(default)]
+    pub reserve: u64,
+    /// upper limit of the cache directory's size in bytes
+    pub quota: Option<u64>,
+    /// remove the artifacts of lists which are no longer configured when space runs short
+    #[serde(default)]
+    pub prune: bool,
+    /// when the downloaded lists are flushed to the disk
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+}
+
+/// FsyncPolicy determines when the downloaded lists are flushed to the disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// leave it to the operating system
+    Never,
+    /// once a download is complete, before it's recorded in the manifest
+    #[default]
+    Complete,
+}
+
+/// RegexOptions guard against user supplied regexes which compile to huge automata
+/// or take too long to extract a list
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegexOptions {
+    /// upper limit of a compiled regex in bytes
+    #[serde(default = "default_regex_size_limit")]
+    pub size_limit: usize,
+    /// upper limit of the lazily built DFA of a regex in bytes
+    #[serde(default = "default_regex_dfa_size_limit")]
+    pub dfa_size_limit: usize,
+    /// the extraction of a single list is aborted after this many seconds
+    pub time_limit: Option<u64>,
+}
+
+impl Default for RegexOptions {
+    fn default() -> Self {
+        Self {
+            size_limit: default_regex_size_limit(),
+            dfa_size_limit: default_regex_dfa_size_limit(),
+            time_limit: None,
+        }
+    }
+}
+
+fn default_regex_size_limit() -> usize {
+    1 << 20
+}
+
+fn default_regex_dfa_size_limit() -> usize {
+    2 << 20
+}
+
+/// TopSites configures a list of popular domains (e.g. Tranco) which is subtracted from
+/// categories fed by automated feeds to avoid common false positives
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TopSites {
+    /// where to get the list in `rank,domain` CSV format from
+    pub source: String,
+    /// compression of the downloaded list
+    pub compression: Option<Compression>,
+    /// only domains ranked up to this cutoff are considered popular
+    #[serde(default = "default_rank_cutoff")]
+    pub rank_cutoff: usize,
+    /// the categories the top sites are removed from
+    #[serde(default = "default_subtract_from")]
+    pub subtract_from: Vec<String>,
+}
+
+fn default_rank_cutoff() -> usize {
+    10000
+}
+
+fn default_subtract_from() -> Vec<String> {
+    vec!["phishing".to_string(), "malware".to_string()]
+}
+
 impl Config {
     /// Populates the Config struct from a json file
     ///
     /// * `path`: file system path the the configuration file
     pub fn load(path: &Path) -> anyhow::Result<Self> {
         let contents = fs::read_to_string(path).with_context(|| "error reading config file")?;
-        let mut config: Config = serde_json::from_str(&contents).with_context(|| "invalid json")?;
+        let mut value: Value = serde_json::from_str(&contents).with_context(|| "invalid json")?;
+        apply_groups(&mut value)?;
+        apply_defaults(&mut value);
+        let mut config: Config =
+            serde_json::from_value(value).with_context(|| "invalid configuration")?;
+        config.add_builtin_lists();
+        let untagged = config.apply_default_tags();
 
         // just do one recursion
         if path.ends_with(CACHED_CONF_FILE_NAME) {
             return Ok(config);
         }
 
+        // lists without tags never end up in any category
+        for id in untagged {
+            warn!(
+                "list {} has no tags and will be downloaded without reaching any output, consider setting default_tags",
+                id
+            );
+        }
+
         // load cached config if available
         let cached_config_path =
             PathBuf::from(format!("{}/{}", config.cache_dir, CACHED_CONF_FILE_NAME));
@@ -48,10 +442,42 @@ impl Config {
         Ok(config)
     }
 
+    /// adds the filter lists generated from configuration options to the configured lists
+    pub fn add_builtin_lists(&mut self) {
+        if let Some(top_sites) = &self.top_sites
+            && !self.lists.iter().any(|l| l.id == TOP_SITES_ID)
+        {
+            self.lists.push(FilterList {
+                id: TOP_SITES_ID.to_string(),
+                comment: Some("built-in top sites list".to_string()),
+                compression: top_sites.compression.clone(),
+                source: top_sites.source.clone(),
+                tags: vec![ALLOW_CATEGORY.to_string()],
+                regex: r"^\d+,([^,\s]+)".to_string(),
+                ..Default::default()
+            });
+        }
+    }
+
+    /// attaches the default tags to all lists without tags and returns the ids of the
+    /// lists which remain without tags
+    pub fn apply_default_tags(&mut self) -> Vec<String> {
+        let mut untagged = vec![];
+        for list in self.lists.iter_mut().filter(|l| l.tags.is_empty()) {
+            if self.default_tags.is_empty() {
+                untagged.push(list.id.clone());
+            } else {
+                list.tags = self.default_tags.clone();
+            }
+        }
+        untagged
+    }
+
     /// write used config to the cache folder for use on next run
     pub fn save_to_cache(&mut self) -> anyhow::Result<()> {
         // don't grow recursively
         self.cached_config = None;
+        self.harvester_version = Some(env!("CARGO_PKG_VERSION").to_string());
         let mut last_conf_path = PathBuf::from(&self.cache_dir);
         last_conf_path.push(CACHED_CONF_FILE_NAME);
         let mut last_conf = File::create(&last_conf_path)?;
@@ -73,6 +499,125 @@ impl Config {
         tags
     }
 
+    /// returns the names of the categories written to the cache, the configured tags
+    /// and the built-in `all` category if enabled
+    pub fn category_names(&self) -> Vec<String> {
+        let mut names = self.get_tags();
+        if self.all_category && !names.iter().any(|n| n == ALL_CATEGORY) {
+            names.push(ALL_CATEGORY.to_string());
+        }
+        names
+    }
+
+    /// returns the formats a category is written in, the global output formats unless
+    /// the category has formats of its own
+    ///
+    /// * `category`: the category's name
+    pub fn category_formats(&self, category: &str) -> Vec<OutputType> {
+        match self.categories.get(category) {
+            Some(options) if !options.output_format.is_empty() => options.output_format.clone(),
+            _ => self.output_format.clone(),
+        }
+    }
+
+    /// returns every format written by a run, the global output formats followed by the
+    /// formats of single categories
+    pub fn output_formats(&self) -> Vec<OutputType> {
+        let mut formats = self.output_format.clone();
+        let mut names = self.category_names();
+        names.sort();
+        for format in names.iter().flat_map(|n| self.category_formats(n)) {
+            if !formats.contains(&format) {
+                formats.push(format);
+            }
+        }
+        formats
+    }
+
+    /// Returns the output formats written by a run and the directories their files are
+    /// written to. With several output formats each format gets its own subdirectory in
+    /// every output directory.
+    pub fn output_paths(&self) -> Vec<(OutputType, PathBuf)> {
+        let formats = self.output_formats();
+        let mut output_paths = vec![];
+        for output_dir in self.output_dir.iter().map(PathBuf::from) {
+            if formats.len() == 1 {
+                output_paths.push((formats[0].clone(), output_dir));
+                continue;
+            }
+            for format in formats.iter() {
+                output_paths.push((format.clone(), output_dir.join(format.dir_name())));
+            }
+        }
+        output_paths
+    }
+
+    /// returns the name of the file a category is written to in a format
+    ///
+    /// * `format`: the output format
+    /// * `category`: the category's name
+    pub fn output_file_name(&self, format: &OutputType, category: &str) -> String {
+        match self.gzip_output {
+            true => format!("{}.gz", format.file_name(category)),
+            false => format.file_name(category),
+        }
+    }
+
+    /// returns the output formats a category is written in and the directories its
+    /// files are written to
+    ///
+    /// * `category`: the category's name
+    pub fn category_output_paths(&self, category: &str) -> Vec<(OutputType, PathBuf)> {
+        let formats = self.category_formats(category);
+        self.output_paths()
+            .into_iter()
+            .filter(|(format, _)| formats.contains(format))
+            .collect()
+    }
+
+    /// returns the DNS settings a list is downloaded with, the system's resolver is
+    /// used if None
+    ///
+    /// * `list`: the downloaded list
+    pub fn dns(&self, list: &FilterList) -> Option<DnsConfig> {
+        match (&self.dns, &list.dns) {
+            (Some(global), Some(list)) => Some(global.merge(list)),
+            (global, list) => list.clone().or_else(|| global.clone()),
+        }
+    }
+
+    /// returns the configured download chunk size or the one matching the machine
+    pub fn download_chunk_size(&self) -> Option<usize> {
+        self.download_chunk_size.or(self.tuning.download_chunk_size)
+    }
+
+    /// returns the configured number of parallel downloads or the one matching the
+    /// machine
+    pub fn max_parallel_downloads(&self) -> Option<usize> {
+        self.max_parallel_downloads
+            .or(self.tuning.max_parallel_downloads)
+    }
+
+    /// returns the configured number of parallel extracts or the one matching the
+    /// machine
+    pub fn max_parallel_extracts(&self) -> Option<usize> {
+        self.max_parallel_extracts
+            .or(self.tuning.max_parallel_extracts)
+    }
+
+    /// returns the share of a list's average match rate it may lose before a format
+    /// change is reported
+    pub fn match_rate_drop(&self) -> f64 {
+        self.match_rate_drop.unwrap_or(DEFAULT_MATCH_RATE_DROP)
+    }
+
+    /// returns the options configured for a category or the defaults
+    ///
+    /// * `category`: the category's name
+    pub fn category_options(&self, category: &str) -> CategoryOptions {
+        self.categories.get(category).cloned().unwrap_or_default()
+    }
+
     /// returns a Vec containing list that have the given tag attached
     ///
     /// * `tag`: filter lists by this tag
@@ -81,3 +626,99 @@ impl Config {
         lists
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::tests::helper::cache_file_creator::CacheFileCreator;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_defaults() {
+        let mut value = json!({
+            "defaults": { "regex": "^0\\.0\\.0\\.0 ([^\\s#]+)", "tags": ["malware"] },
+            "lists": [
+                { "id": "one", "source": "https://lists.example/one" },
+                { "id": "two", "source": "https://lists.example/two", "tags": ["ads"] }
+            ]
+        });
+        apply_defaults(&mut value);
+        let lists: Vec<FilterList> = serde_json::from_value(value["lists"].clone()).unwrap();
+        assert_eq!(lists[0].regex, r"^0\.0\.0\.0 ([^\s#]+)");
+        assert_eq!(lists[0].tags, vec!["malware"]);
+        assert_eq!(lists[1].regex, lists[0].regex);
+        assert_eq!(lists[1].tags, vec!["ads"]);
+    }
+
+    #[test]
+    fn test_apply_groups() {
+        let mut value = json!({
+            "groups": {
+                "provider": {
+                    "source_prefix": "https://provider.example/lists/",
+                    "tags": ["malware"],
+                    "regex": "^([^#]+)"
+                }
+            },
+            "lists": [
+                { "id": "one", "group": "provider", "source": "one.txt" },
+                { "id": "two", "group": "provider", "source": "two.txt", "tags": ["phishing"] }
+            ]
+        });
+        apply_groups(&mut value).unwrap();
+        let lists: Vec<FilterList> = serde_json::from_value(value["lists"].clone()).unwrap();
+        assert_eq!(lists[0].source, "https://provider.example/lists/one.txt");
+        assert_eq!(lists[0].tags, vec!["malware"]);
+        assert_eq!(lists[0].regex, "^([^#]+)");
+        assert_eq!(lists[1].tags, vec!["malware", "phishing"]);
+        // applying the groups again leaves the merged lists untouched
+        apply_groups(&mut value).unwrap();
+        assert_eq!(
+            value["lists"][1]["source"],
+            "https://provider.example/lists/two.txt"
+        );
+
+        let mut unknown = json!({ "groups": {}, "lists": [{ "id": "one", "group": "none" }] });
+        assert!(apply_groups(&mut unknown).is_err());
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let cache = CacheFileCreator::new("test_output_formats", "in", "out");
+        let mut value = serde_json::to_value(cache.new_test_config()).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("output_format");
+        object.insert("output_dir".to_string(), json!("/srv/harvester"));
+        object.insert("output_formats".to_string(), json!(["Hostsfile", "Json"]));
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            config.output_paths(),
+            vec![
+                (
+                    OutputType::Hostsfile,
+                    PathBuf::from("/srv/harvester/hostsfile")
+                ),
+                (OutputType::Json, PathBuf::from("/srv/harvester/json")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allows_country() {
+        let options: CategoryOptions =
+            serde_json::from_value(json!({ "include_countries": ["de", "NL"] })).unwrap();
+        assert!(options.allows_country(Some("DE")));
+        assert!(options.allows_country(Some("NL")));
+        assert!(!options.allows_country(Some("US")));
+        assert!(!options.allows_country(None));
+
+        let options: CategoryOptions =
+            serde_json::from_value(json!({ "exclude_countries": ["US"] })).unwrap();
+        assert!(options.allows_country(Some("DE")));
+        assert!(!options.allows_country(Some("us")));
+        assert!(options.allows_country(None));
+        assert!(CategoryOptions::default().allows_country(Some("US")));
+    }
+}
@@ -0,0 +1,122 @@
+use std::{fs, path::Path, str::FromStr, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{entry::Entry, CATEGORIZE_PATH};
+
+/// number of addresses sent per request
+const BATCH_SIZE: usize = 1000;
+
+/// CrowdSecConfig configures the categories whose addresses are pushed to a CrowdSec
+/// blocklist after each run
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CrowdSecConfig {
+    /// the blocklist API endpoint the addresses are posted to
+    pub url: String,
+    /// the key sent in the `x-api-key` header
+    pub api_key: String,
+    /// the categories whose IP and CIDR entries are pushed
+    pub categories: Vec<String>,
+    /// days the pushed addresses remain on the blocklist unless pushed again
+    #[serde(default = "default_expiration_days")]
+    pub expiration_days: u64,
+}
+
+fn default_expiration_days() -> u64 {
+    7
+}
+
+/// returns the IP and CIDR entries of a category
+///
+/// * `contents`: the category's entries, one per line
+fn addresses(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|l| Entry::from_str(l).ok())
+        .filter(|e| matches!(e, Entry::Ip(_) | Entry::Cidr(_, _)))
+        .map(|e| e.to_string())
+        .collect()
+}
+
+/// returns the request bodies adding the addresses to the blocklist
+///
+/// * `addresses`: the addresses to be pushed
+/// * `expiration`: the time the addresses are removed from the blocklist
+fn request_bodies(addresses: &[String], expiration: DateTime<Utc>) -> Vec<serde_json::Value> {
+    addresses
+        .chunks(BATCH_SIZE)
+        .map(|ips| json!({ "ips": ips, "expiration": expiration.to_rfc3339() }))
+        .collect()
+}
+
+/// Pushes the addresses of the configured categories to the CrowdSec blocklist. The
+/// expiration of addresses still listed is extended with every push. Returns the
+/// number of pushed addresses.
+///
+/// * `config`: the CrowdSec configuration
+/// * `cache_dir`: the cache directory containing the categories
+/// * `run_id`: the id of the run, sent in the `x-harvester-run` header
+pub async fn push(
+    config: &CrowdSecConfig,
+    cache_dir: &Path,
+    run_id: &str,
+) -> anyhow::Result<usize> {
+    let cli = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let expiration = Utc::now() + chrono::Duration::days(config.expiration_days as i64);
+    let mut pushed = 0;
+    for category in config.categories.iter() {
+        let path = cache_dir.join(CATEGORIZE_PATH).join(category);
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("could not read category {category}"))?;
+        let addresses = addresses(&contents);
+        for body in request_bodies(&addresses, expiration) {
+            cli.post(&config.url)
+                .header("x-api-key", &config.api_key)
+                .header("x-harvester-run", run_id)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string())
+                .send()
+                .await?
+                .error_for_status()
+                .with_context(|| format!("could not push category {category}"))?;
+        }
+        debug!(
+            "{}: pushed {} addresses to CrowdSec",
+            category,
+            addresses.len()
+        );
+        pushed += addresses.len();
+    }
+    Ok(pushed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_bodies() {
+        let addresses = addresses("domain.one\n10.0.0.1\n10.1.0.0/16\nhttp://10.0.0.2/\n");
+        assert_eq!(addresses, vec!["10.0.0.1", "10.1.0.0/16"]);
+        let expiration = DateTime::parse_from_rfc3339("2024-05-08T02:00:00Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(
+            request_bodies(&addresses, expiration),
+            vec![json!({
+                "ips": ["10.0.0.1", "10.1.0.0/16"],
+                "expiration": "2024-05-08T02:00:00+00:00"
+            })]
+        );
+        let many: Vec<String> = (0..2500)
+            .map(|i| format!("10.0.{}.{}", i / 256, i % 256))
+            .collect();
+        assert_eq!(request_bodies(&many, expiration).len(), 3);
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+
+/// seconds a DNS server has to answer a query
+const QUERY_TIMEOUT: u64 = 5;
+/// the port DNS servers listen on unless configured otherwise
+const DNS_PORT: u16 = 53;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+/// response code of a query for a domain which doesn't exist
+const NXDOMAIN: u16 = 3;
+
+/// DnsConfig overrides how the hosts the lists are downloaded from are resolved,
+/// e.g. when harvester runs on the resolver whose upstream it is configuring
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DnsConfig {
+    /// DNS servers asked in turn instead of the system's resolver, e.g. `9.9.9.9` or
+    /// `[2620:fe::fe]:53`
+    #[serde(default)]
+    pub servers: Vec<String>,
+    /// addresses hosts are pinned to without asking any DNS server
+    #[serde(default)]
+    pub hosts: BTreeMap<String, Vec<IpAddr>>,
+}
+
+impl DnsConfig {
+    /// returns the settings of a list on top of these settings, the list's servers
+    /// replace these servers and its pinned hosts are added
+    ///
+    /// * `list`: the settings of the list
+    pub fn merge(&self, list: &DnsConfig) -> DnsConfig {
+        let mut merged = self.clone();
+        if !list.servers.is_empty() {
+            merged.servers = list.servers.clone();
+        }
+        merged
+            .hosts
+            .extend(list.hosts.iter().map(|(h, ips)| (h.clone(), ips.clone())));
+        merged
+    }
+
+    /// Returns the addresses a connection to the host is opened to. The resolution
+    /// is left to the system if the list is empty.
+    ///
+    /// * `host`: the domain to be resolved
+    /// * `port`: the port of the returned addresses
+    pub async fn resolve(&self, host: &str, port: u16) -> anyhow::Result<Vec<SocketAddr>> {
+        let to_addrs = |ips: &[IpAddr]| ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect();
+        let host = host.trim_end_matches('.').to_lowercase();
+        if let Some(ips) = self.hosts.get(&host) {
+            return Ok(to_addrs(ips));
+        }
+        let mut last_error = None;
+        for server in self.servers.iter() {
+            let server = server_addr(server)?;
+            match lookup(server, &host).await {
+                Ok(ips) => return Ok(to_addrs(&ips)),
+                Err(e) => {
+                    debug!("{}: resolving {} failed: {}", server, host, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) => Err(e).with_context(|| format!("could not resolve {host}")),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// parses the address of a DNS server given with or without port
+///
+/// * `server`: the configured address
+pub fn server_addr(server: &str) -> anyhow::Result<SocketAddr> {
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DNS_PORT));
+    }
+    server
+        .parse()
+        .with_context(|| format!("{server} is not an IP address with optional port"))
+}
+
+/// asks a DNS server for the IPv4 and IPv6 addresses of a host
+///
+/// * `server`: the DNS server
+/// * `host`: the domain to be resolved
+async fn lookup(server: SocketAddr, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+    let local = match server {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    let mut ips = vec![];
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        let random = uuid::Uuid::new_v4();
+        let id = u16::from_be_bytes([random.as_bytes()[0], random.as_bytes()[1]]);
+        socket.send(&query(id, host, qtype)?).await?;
+        let mut buf = [0; 4096];
+        let len = tokio::time::timeout(Duration::from_secs(QUERY_TIMEOUT), socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow::anyhow!("no answer within {} seconds", QUERY_TIMEOUT))??;
+        ips.extend(parse_response(&buf[..len], id)?);
+    }
+    if ips.is_empty() {
+        return Err(anyhow::anyhow!("{host} has no addresses"));
+    }
+    Ok(ips)
+}
+
+/// encodes a recursive query for the records of a type
+///
+/// * `id`: the id the response is matched by
+/// * `host`: the queried domain
+/// * `qtype`: the queried record type
+fn query(id: u16, host: &str, qtype: u16) -> anyhow::Result<Vec<u8>> {
+    let mut message = vec![];
+    // id, recursion desired, a single question
+    for field in [id, 0x0100, 1, 0, 0, 0] {
+        message.extend(field.to_be_bytes());
+    }
+    for label in host.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow::anyhow!("{host} is not a valid domain"));
+        }
+        message.push(label.len() as u8);
+        message.extend(label.as_bytes());
+    }
+    message.push(0);
+    message.extend(qtype.to_be_bytes());
+    message.extend(CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+/// returns the addresses of the A and AAAA records in the answer section
+///
+/// * `message`: the received response
+/// * `id`: the id of the query
+fn parse_response(message: &[u8], id: u16) -> anyhow::Result<Vec<IpAddr>> {
+    let truncated = || anyhow::anyhow!("truncated response");
+    let u16_at = |pos: usize| -> anyhow::Result<u16> {
+        let bytes = message.get(pos..pos + 2).ok_or_else(truncated)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    };
+    if u16_at(0)? != id {
+        return Err(anyhow::anyhow!("response doesn't match the query"));
+    }
+    let flags = u16_at(2)?;
+    match flags & 0x000f {
+        0 | NXDOMAIN => {}
+        rcode => return Err(anyhow::anyhow!("server failed with response code {rcode}")),
+    }
+    let questions = u16_at(4)?;
+    let answers = u16_at(6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos).ok_or_else(truncated)? + 4;
+    }
+    let mut ips = vec![];
+    for _ in 0..answers {
+        pos = skip_name(message, pos).ok_or_else(truncated)?;
+        let rtype = u16_at(pos)?;
+        let rdlength = u16_at(pos + 8)? as usize;
+        let rdata = message
+            .get(pos + 10..pos + 10 + rdlength)
+            .ok_or_else(truncated)?;
+        match (rtype, rdata.len()) {
+            (TYPE_A, 4) => ips.push(IpAddr::from(<[u8; 4]>::try_from(rdata)?)),
+            (TYPE_AAAA, 16) => ips.push(IpAddr::from(<[u8; 16]>::try_from(rdata)?)),
+            // the chain of CNAME records is followed by the server
+            _ => {}
+        }
+        pos += 10 + rdlength;
+    }
+    Ok(ips)
+}
+
+/// returns the position following the possibly compressed name starting at pos
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            // a pointer to a name elsewhere in the message ends the name
+            l if l & 0xc0 == 0xc0 => return Some(pos + 2),
+            l => pos += 1 + l,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// answers every A query with a single address and AAAA queries without records
+    async fn serve(socket: UdpSocket) {
+        let mut buf = [0; 512];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+            let query = &buf[..len];
+            let mut response = query.to_vec();
+            response[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+            let qtype = u16::from_be_bytes([query[len - 4], query[len - 3]]);
+            if qtype == TYPE_A {
+                response[6..8].copy_from_slice(&1u16.to_be_bytes());
+                // the name points at the question
+                response.extend([0xc0, 12]);
+                for field in [TYPE_A, CLASS_IN, 0, 300, 4] {
+                    response.extend(field.to_be_bytes());
+                }
+                response.extend([192, 0, 2, 7]);
+            }
+            socket.send_to(&response, peer).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = socket.local_addr().unwrap();
+        tokio::spawn(serve(socket));
+
+        let global = DnsConfig {
+            servers: vec!["192.0.2.53".to_string()],
+            hosts: BTreeMap::from([(
+                "lists.example.com".to_string(),
+                vec!["192.0.2.1".parse().unwrap()],
+            )]),
+        };
+        let list = DnsConfig {
+            servers: vec![server.to_string()],
+            hosts: BTreeMap::from([(
+                "mirror.example.com".to_string(),
+                vec!["2001:db8::1".parse().unwrap()],
+            )]),
+        };
+        let dns = global.merge(&list);
+        assert_eq!(dns.servers, vec![server.to_string()]);
+
+        let addrs =
+            |a: &[&str]| -> Vec<SocketAddr> { a.iter().map(|a| a.parse().unwrap()).collect() };
+        assert_eq!(
+            dns.resolve("Lists.Example.com.", 443).await.unwrap(),
+            addrs(&["192.0.2.1:443"])
+        );
+        assert_eq!(
+            dns.resolve("mirror.example.com", 80).await.unwrap(),
+            addrs(&["[2001:db8::1]:80"])
+        );
+        assert_eq!(
+            dns.resolve("blocked.example.org", 443).await.unwrap(),
+            addrs(&["192.0.2.7:443"])
+        );
+        assert!(DnsConfig::default()
+            .resolve("blocked.example.org", 443)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            server_addr("[2620:fe::fe]:5353").unwrap(),
+            "[2620:fe::fe]:5353".parse().unwrap()
+        );
+        assert_eq!(
+            server_addr("9.9.9.9").unwrap(),
+            "9.9.9.9:53".parse().unwrap()
+        );
+        assert!(server_addr("dns.quad9.net").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Entry is a single item of a block list as produced by the extract stage
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Entry {
+    /// a fully qualified domain name
+    Domain(String),
+    /// a domain including all of its subdomains, stored without the `*.` prefix
+    Wildcard(String),
+    /// a single IPv4 or IPv6 address
+    Ip(IpAddr),
+    /// an IP network in CIDR notation
+    Cidr(IpAddr, u8),
+    /// a complete URL
+    Url(Url),
+}
+
+/// EntryKind names the variants of an Entry without their data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum EntryKind {
+    Domain,
+    Wildcard,
+    Ip,
+    Cidr,
+    Url,
+}
+
+impl Display for EntryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Entry {
+    /// returns the kind of the entry
+    pub fn kind(&self) -> EntryKind {
+        match self {
+            Entry::Domain(_) => EntryKind::Domain,
+            Entry::Wildcard(_) => EntryKind::Wildcard,
+            Entry::Ip(_) => EntryKind::Ip,
+            Entry::Cidr(_, _) => EntryKind::Cidr,
+            Entry::Url(_) => EntryKind::Url,
+        }
+    }
+}
+
+impl FromStr for Entry {
+    type Err = anyhow::Error;
+
+    /// classifies a line extracted from a filter list
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains("://") {
+            let url = Url::parse(s).map_err(|e| anyhow::anyhow!("invalid URL {}: {}", s, e))?;
+            return Ok(Entry::Url(url));
+        }
+        if let Some((addr, prefix_len)) = s.split_once('/') {
+            let addr: IpAddr = addr
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid network address: {}", s))?;
+            let max_len = if addr.is_ipv4() { 32 } else { 128 };
+            return match prefix_len.parse::<u8>() {
+                Ok(l) if l <= max_len => Ok(Entry::Cidr(addr, l)),
+                _ => Err(anyhow::anyhow!("invalid network prefix length: {}", s)),
+            };
+        }
+        if let Ok(addr) = s.parse::<IpAddr>() {
+            return Ok(Entry::Ip(addr));
+        }
+        // some formats mark subdomain matching with a leading dot
+        if let Some(domain) = s.strip_prefix("*.").or_else(|| s.strip_prefix('.')) {
+            return Ok(Entry::Wildcard(parse_domain(domain)?));
+        }
+        Ok(Entry::Domain(parse_domain(s)?))
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Entry::Domain(d) => write!(f, "{d}"),
+            Entry::Wildcard(d) => write!(f, "*.{d}"),
+            Entry::Ip(ip) => write!(f, "{ip}"),
+            Entry::Cidr(ip, len) => write!(f, "{ip}/{len}"),
+            Entry::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// checks a domain name for valid label lengths and characters
+fn parse_domain(s: &str) -> anyhow::Result<String> {
+    let domain = s.strip_suffix('.').unwrap_or(s);
+    let valid = !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        });
+    if !valid {
+        return Err(anyhow::anyhow!("invalid domain: {}", s));
+    }
+    Ok(domain.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_from_str() {
+        let cases = [
+            ("domain.one", EntryKind::Domain, "domain.one"),
+            ("domain.one.\r\n", EntryKind::Domain, "domain.one"),
+            ("*.domain.one", EntryKind::Wildcard, "*.domain.one"),
+            (".domain.one", EntryKind::Wildcard, "*.domain.one"),
+            ("10.0.0.1", EntryKind::Ip, "10.0.0.1"),
+            ("::1", EntryKind::Ip, "::1"),
+            ("10.0.0.0/8", EntryKind::Cidr, "10.0.0.0/8"),
+            (
+                "http://domain.one/path",
+                EntryKind::Url,
+                "http://domain.one/path",
+            ),
+        ];
+        for (input, kind, display) in cases {
+            let entry: Entry = input.parse().unwrap();
+            assert_eq!(entry.kind(), kind);
+            assert_eq!(entry.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn test_entry_from_str_invalid() {
+        for input in ["", "domain one", "domain..one", "10.0.0.0/33", "# comment"] {
+            assert!(input.parse::<Entry>().is_err(), "{input}");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use crate::io::IoFailure;
+
+/// ErrorCode classifies failures by stable identifiers which are attached to log
+/// messages and the run report. The first digit denotes the stage or subsystem the
+/// failure occurred in. Codes are never reassigned to another meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorCode {
+    /// a list could not be downloaded
+    DownloadFailed,
+    /// a list was never downloaded and can't be fetched in offline mode
+    NotAvailableOffline,
+    /// a certificate transparency log could not be fetched
+    CtLogFailed,
+    /// the server asked to retry later and there is no previous download to fall back on
+    Deferred,
+    /// reading or matching a downloaded list failed
+    ExtractFailed,
+    /// a list's regex failed to compile
+    RegexFailed,
+    /// a list's transform script or command failed
+    TransformFailed,
+    /// the extraction of a list exceeded the configured time limit
+    TimeLimitExceeded,
+    /// a list contains binary data instead of text
+    BinaryContent,
+    /// a category's transform script failed
+    CategoryTransformFailed,
+    /// a category includes a list which could not be written completely
+    ListIncomplete,
+    /// a category has more entries than the configured safety limit
+    TooManyEntries,
+    /// the changes of a category were rejected in review
+    ReviewRejected,
+    /// an output file could not be written or replaced
+    OutputFailed,
+    /// categories could not be pushed to an external service
+    PushFailed,
+    /// the disk or quota is exhausted
+    DiskFull,
+    /// a file could not be written for lack of permissions
+    PermissionDenied,
+    /// any other I/O error
+    IoFailed,
+    /// the run was aborted
+    RunAborted,
+}
+
+impl ErrorCode {
+    /// returns the stable identifier of the error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::DownloadFailed => "HV1001",
+            ErrorCode::NotAvailableOffline => "HV1002",
+            ErrorCode::CtLogFailed => "HV1003",
+            ErrorCode::Deferred => "HV1004",
+            ErrorCode::ExtractFailed => "HV2001",
+            ErrorCode::RegexFailed => "HV2002",
+            ErrorCode::TransformFailed => "HV2003",
+            ErrorCode::TimeLimitExceeded => "HV2004",
+            ErrorCode::BinaryContent => "HV2005",
+            ErrorCode::CategoryTransformFailed => "HV3001",
+            ErrorCode::ListIncomplete => "HV3002",
+            ErrorCode::TooManyEntries => "HV3003",
+            ErrorCode::ReviewRejected => "HV3004",
+            ErrorCode::OutputFailed => "HV4001",
+            ErrorCode::PushFailed => "HV4002",
+            ErrorCode::DiskFull => "HV5001",
+            ErrorCode::PermissionDenied => "HV5002",
+            ErrorCode::IoFailed => "HV5003",
+            ErrorCode::RunAborted => "HV9001",
+        }
+    }
+}
+
+impl From<IoFailure> for ErrorCode {
+    fn from(failure: IoFailure) -> Self {
+        match failure {
+            IoFailure::DiskFull => ErrorCode::DiskFull,
+            IoFailure::PermissionDenied => ErrorCode::PermissionDenied,
+            IoFailure::Other => ErrorCode::IoFailed,
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
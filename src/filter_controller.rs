@@ -1,19 +1,29 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
     io::Write,
     marker::PhantomData,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
 
 use futures::Future;
-use tokio::task::JoinHandle;
+use tokio::{sync::Semaphore, task::JoinHandle};
 
 use crate::{
-    config::Config, filter_list::FilterList, input::Input, io::category_list_io::CategoryListIO,
+    config::{Config, RegexOptions},
+    error_code::ErrorCode,
+    filter_list::FilterList,
+    input::Input,
+    io::category_list_io::CategoryListIO,
     io::filter_list_io::FilterListIO,
+    io::IoFailure,
+    log_throttle,
+    manifest::Manifest,
+    observer::Observers,
+    report::RunReport,
 };
 
 /// These structs represent the stages of a program run
@@ -30,28 +40,154 @@ pub struct FilterController<'config, Stage, R: Input + Send, W: Write + Send> {
     pub cached_lists: Option<HashSet<String>>,
     pub filter_lists: Vec<FilterListIO<R, W>>,
     pub category_lists: Vec<CategoryListIO<R, W>>,
+    pub manifest: Option<Manifest>,
+    pub report: Arc<Mutex<RunReport>>,
+    pub observers: Observers,
     pub is_processing: Arc<AtomicBool>,
 }
 
+/// the number of characters of a line quoted in error messages
+const QUOTED_LINE_LEN: usize = 80;
+
+/// SkipReason tells why a transform function didn't write a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SkipReason {
+    /// the line doesn't match the list's regex
+    NoMatch,
+    /// the matched text isn't a valid entry
+    InvalidEntry,
+    /// the list's transform script dropped the entry
+    Dropped,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::NoMatch => write!(f, "no match"),
+            SkipReason::InvalidEntry => write!(f, "invalid entry"),
+            SkipReason::Dropped => write!(f, "dropped by transform"),
+        }
+    }
+}
+
+/// Transformed is what a transform function made of a single chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transformed {
+    /// the data written to the list's output
+    Write(Vec<u8>),
+    Skip(SkipReason),
+}
+
+/// ChunkContext tells a transform function where a chunk belongs to and how the
+/// list went so far, e.g. to skip a list's header or give up on a list after too
+/// many invalid lines by returning an error
+#[derive(Debug, Clone)]
+pub struct ChunkContext {
+    /// the list the chunk was read from
+    pub list: Arc<FilterList>,
+    /// the chunk's number within the list, starting at 1
+    pub line: usize,
+    /// the number of chunks written so far
+    pub lines_matched: usize,
+    /// the number of chunks skipped so far
+    pub lines_skipped: usize,
+    /// the limits the list's regex is compiled within
+    pub regex_options: RegexOptions,
+}
+
+impl ChunkContext {
+    /// creates the context of the first chunk of a list
+    ///
+    /// * `list`: the list the chunks are read from
+    /// * `regex_options`: the configured regex limits
+    pub fn new(list: Arc<FilterList>, regex_options: RegexOptions) -> Self {
+        Self {
+            list,
+            line: 1,
+            lines_matched: 0,
+            lines_skipped: 0,
+            regex_options,
+        }
+    }
+
+    /// Describes where a chunk was found for error messages: the list, the line
+    /// number and the beginning of the line
+    ///
+    /// * `chunk`: the chunk the error occurred on
+    pub fn describe(&self, chunk: &[u8]) -> String {
+        let line = String::from_utf8_lossy(chunk);
+        let line = line.trim_end();
+        let mut quoted: String = line.chars().take(QUOTED_LINE_LEN).collect();
+        if quoted.len() < line.len() {
+            quoted.push('…');
+        }
+        format!("list {} line {}: {:?}", self.list.id, self.line, quoted)
+    }
+}
+
+/// ProcessResult is the outcome of processing a single filter list
+#[derive(Debug, Default)]
+pub struct ProcessResult {
+    pub id: String,
+    pub lines_matched: usize,
+    pub lines_skipped: usize,
+    /// the number of skipped chunks by the reason they were skipped for
+    pub skip_reasons: BTreeMap<SkipReason, usize>,
+    pub error: Option<String>,
+    /// classifies the error
+    pub code: Option<ErrorCode>,
+    /// set if writing was aborted, the written data is incomplete
+    pub io_failure: Option<IoFailure>,
+    /// set if the pipeline was cancelled before the list was processed completely
+    pub interrupted: bool,
+}
+
+impl<'config, Stage, R: Input + Send, W: Write + Send> FilterController<'config, Stage, R, W> {
+    /// Hands the state shared among all stages over to the controller of the next stage
+    pub fn next_stage<NextStage, NR: Input + Send, NW: Write + Send>(
+        &mut self,
+    ) -> FilterController<'config, NextStage, NR, NW> {
+        FilterController {
+            stage: PhantomData,
+            config: self.config,
+            cached_lists: self.cached_lists.take(),
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: self.manifest.take(),
+            report: self.report.clone(),
+            observers: self.observers.clone(),
+            is_processing: self.is_processing.clone(),
+        }
+    }
+}
+
 /// `process` is the main data processing function. It reads chunks from the source
 /// applies a transformation function and writes the data to the output
 ///
 /// * `filter_lists`: a list of FilterListIO to be processed
-/// * `fn_transform`: the function to apply to every chunk the FilterListIO's reader returns
+/// * `fn_transform`: the function to apply to every chunk the FilterListIO's reader
+///   returns, along with the chunk's context
+/// * `regex_options`: the configured regex limits handed to the transform function
+/// * `error_code`: the code failures to read or transform a chunk are reported with
+/// * `parallel`: the maximum number of lists processed at once, all at once if None
 /// * `command_rx`: a channel receiver listening for commands
 /// * `message_tx`: a channel sender for messaging purpose
 pub async fn process<SRC, DST, FN, RES>(
     filter_lists: &mut Vec<FilterListIO<SRC, DST>>,
     fn_transform: &'static FN,
+    regex_options: &RegexOptions,
+    error_code: ErrorCode,
+    parallel: Option<usize>,
     is_processing: Arc<AtomicBool>,
-) -> Vec<JoinHandle<()>>
+) -> Vec<JoinHandle<ProcessResult>>
 where
     SRC: Input + Send + 'static,
-    FN: Fn(Arc<FilterList>, Option<Vec<u8>>) -> RES + Send + Sync + 'static,
+    FN: Fn(ChunkContext, Vec<u8>) -> RES + Send + Sync + 'static,
     DST: Write + Send + 'static,
-    RES: Future<Output = anyhow::Result<Option<Vec<u8>>>> + Send + Sync + 'static,
+    RES: Future<Output = anyhow::Result<Transformed>> + Send + Sync + 'static,
 {
-    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+    let mut handles: Vec<JoinHandle<ProcessResult>> = Vec::new();
+    let slots = parallel.map(|n| Arc::new(Semaphore::new(n.max(1))));
     for FilterListIO {
         reader,
         writer,
@@ -79,33 +215,56 @@ where
         let filter_list = Arc::new(filter_list.clone());
         let list = Arc::clone(&filter_list);
 
+        // the lists are started in their order as soon as a slot is free
+        let slot = match &slots {
+            Some(s) => Some(s.clone().acquire_owned().await.unwrap()),
+            None => None,
+        };
         let is_proc = Arc::clone(&is_processing);
+        let regex_options = regex_options.clone();
         let handle = tokio::spawn(async move {
-            let mut chunks_matched = 0;
-            let mut chunks_skipped = 0;
+            let _slot = slot;
+            let mut result = ProcessResult {
+                id: list.id.clone(),
+                ..Default::default()
+            };
+            let mut context = ChunkContext::new(list.clone(), regex_options);
             loop {
                 if !is_proc.load(Ordering::SeqCst) {
                     debug!("quitting task: {}", list.id);
-                    return;
+                    result.interrupted = true;
+                    return result;
                 }
+                context.lines_matched = result.lines_matched;
+                context.lines_skipped = result.lines_skipped;
+                context.line = result.lines_matched + result.lines_skipped + 1;
                 // stop task on quit message
-                let result = reader.lock().await.chunk().await;
-                match result {
-                    Ok(Some(chunk)) => match fn_transform(list.clone(), Some(chunk)).await {
+                let chunk = reader.lock().await.chunk().await;
+                match chunk {
+                    Ok(Some(chunk)) => match fn_transform(context.clone(), chunk).await {
                         // regex matched
-                        Ok(Some(chunk)) => {
-                            chunks_matched += 1;
+                        Ok(Transformed::Write(chunk)) => {
+                            result.lines_matched += 1;
                             if let Err(e) = writer.lock().await.write_all(&chunk) {
-                                error!("{}", e);
+                                let failure = IoFailure::classify(&e);
+                                result.error = Some(format!("{failure}: {e}"));
+                                result.code = Some(ErrorCode::from(failure));
+                                // there's no point in writing on to a full disk
+                                if failure.is_fatal() {
+                                    result.io_failure = Some(failure);
+                                    break;
+                                }
                             }
                         }
                         // regex did not match
-                        Ok(None) => {
-                            chunks_skipped += 1;
+                        Ok(Transformed::Skip(reason)) => {
+                            result.lines_skipped += 1;
+                            *result.skip_reasons.entry(reason).or_default() += 1;
                         }
                         // regex error
                         Err(e) => {
-                            error!("Error: {}", e);
+                            result.error = Some(format!("{e:#}"));
+                            result.code = Some(error_code);
                             break;
                         }
                     },
@@ -115,17 +274,20 @@ where
                     }
                     // reader error
                     Err(e) => {
-                        error!("Error: {}", e);
+                        result.error = Some(e.to_string());
+                        result.code = Some(error_code);
                         break;
                     }
                 }
             }
-            if chunks_matched == 0 {
-                warn!("No lines machted in list {}", list.id);
+            log_throttle::summarize(&list.id);
+            if result.lines_matched == 0 {
+                warn!("No lines matched in list {}", list.id);
             } else {
-                debug!("{}: {} lines matched", list.id, chunks_matched);
-                debug!("{}: {} lines skipped", list.id, chunks_skipped);
+                debug!("{}: {} lines matched", list.id, result.lines_matched);
+                debug!("{}: {} lines skipped", list.id, result.lines_skipped);
             }
+            result
         });
         handles.push(handle);
     }
@@ -161,6 +323,7 @@ mod tests {
             source: "".to_string(),
             tags: vec![],
             regex: "".to_string(),
+            ..Default::default()
         };
 
         // wrap the Filterlist in the FilterListIO object
@@ -172,7 +335,10 @@ mod tests {
         // process the data with a transform function just forwarding the data
         let handles = process(
             &mut vec![filter_list_io],
-            &|_, c| async { Ok(c) },
+            &|_, c| async { Ok(Transformed::Write(c)) },
+            &RegexOptions::default(),
+            ErrorCode::ExtractFailed,
+            Some(1),
             is_processing.clone(),
         )
         .await;
@@ -182,4 +348,46 @@ mod tests {
         // the data in the out put should be the same as the input data
         assert!(String::from_utf8_lossy(&o).starts_with(&input_data));
     }
+
+    #[tokio::test]
+    async fn test_process_context() {
+        let input = Arc::new(Mutex::new(CursorInput::new("# header\none\ntwo\nthree\n")));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let mut filter_list_io: FilterListIO<CursorInput, Cursor<Vec<u8>>> =
+            FilterListIO::new(FilterList {
+                id: "test".to_string(),
+                ..Default::default()
+            });
+        filter_list_io.reader = Some(input);
+        filter_list_io.writer = Some(output.clone());
+
+        // skips the header line and gives up after two entries
+        let handles = process(
+            &mut vec![filter_list_io],
+            &|context: ChunkContext, c| async move {
+                assert_eq!(context.list.id, "test");
+                if context.line == 1 {
+                    return Ok(Transformed::Skip(SkipReason::NoMatch));
+                }
+                if context.lines_matched == 2 {
+                    return Err(anyhow::anyhow!("stopped at line {}", context.line));
+                }
+                Ok(Transformed::Write(c))
+            },
+            &RegexOptions::default(),
+            ErrorCode::ExtractFailed,
+            None,
+            Arc::new(AtomicBool::new(true)),
+        )
+        .await;
+        let result = join_all(handles).await.pop().unwrap().unwrap();
+        let o = output.lock().await.clone().into_inner();
+        assert_eq!(String::from_utf8_lossy(&o), "one\ntwo\n");
+        assert_eq!(result.lines_matched, 2);
+        assert_eq!(
+            result.skip_reasons,
+            BTreeMap::from([(SkipReason::NoMatch, 1)])
+        );
+        assert_eq!(result.error.as_deref(), Some("stopped at line 4"));
+    }
 }
@@ -1,9 +1,15 @@
+use std::{collections::BTreeMap, fs};
+
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::input::file::Compression;
+use crate::{
+    dns::DnsConfig,
+    input::{ct_log::CtLogConfig, file::Compression},
+};
 
 /// FilterList contains the information needed to process a single filter list
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct FilterList {
     /// can be any string, must be unique among all filter lists
     pub id: String,
@@ -13,8 +19,119 @@ pub struct FilterList {
     pub compression: Option<Compression>,
     /// source is the path to where to get the list from (probably a URL)
     pub source: String,
+    /// the kind of source the list is downloaded from, the scheme of the source URL if
+    /// not set
+    pub source_type: Option<String>,
     /// tags describe the destinations where the processed URLs will end up
     pub tags: Vec<String>,
     /// regex to extract URL from a line
     pub regex: String,
+    /// tail a certificate transparency log found at `source` instead of downloading a list
+    pub ct_log: Option<CtLogConfig>,
+    /// path of a Lua script rewriting or dropping every extracted entry
+    pub transform: Option<String>,
+    /// shell command the downloaded list is piped through before extraction
+    pub transform_cmd: Option<String>,
+    /// seconds after which the transform command is killed
+    pub transform_cmd_timeout: Option<u64>,
+    /// additional HTTP headers sent when downloading the list
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// how often a failing download is repeated
+    pub retry: Option<RetryPolicy>,
+    /// seconds to wait for the server to answer the request including retries
+    pub request_timeout: Option<u64>,
+    /// accept invalid TLS certificates and host names when downloading the list
+    #[serde(default)]
+    pub insecure_tls: bool,
+    /// seconds the previous download may be used for at most if downloading fails
+    pub max_stale_age: Option<u64>,
+    /// lines longer than this number of bytes are skipped
+    pub max_line_length: Option<usize>,
+    /// lists of a higher priority are downloaded first, defaults to 0
+    pub priority: Option<i32>,
+    /// DNS servers and pinned addresses the source's host is resolved with, on top of
+    /// the global ones
+    pub dns: Option<DnsConfig>,
+}
+
+impl FilterList {
+    /// returns a hash of the settings determining what the extract stage produces from
+    /// the downloaded list. The contents of the transform script are part of the hash
+    /// so editing the script invalidates the extract as well.
+    pub fn settings_hash(&self) -> String {
+        let script = self
+            .transform
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok());
+        let mut settings = serde_json::json!({
+            "regex": self.regex,
+            "compression": self.compression,
+            "transform": self.transform,
+            "transform_script": script,
+            "transform_cmd": self.transform_cmd,
+        });
+        // only part of the hash if set so the extracts of earlier versions stay valid
+        if let Some(max_line_length) = self.max_line_length {
+            settings["max_line_length"] = max_line_length.into();
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(settings.to_string().as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// RetryPolicy determines how failed HTTP requests are repeated
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RetryPolicy {
+    /// the number of attempts including the first one
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    /// seconds to wait between two attempts
+    #[serde(default = "default_delay")]
+    pub delay: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: default_attempts(),
+            delay: default_delay(),
+        }
+    }
+}
+
+fn default_attempts() -> u32 {
+    1
+}
+
+fn default_delay() -> u64 {
+    5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_hash() {
+        let list = FilterList {
+            id: "list".to_string(),
+            regex: "^(.*)$".to_string(),
+            ..Default::default()
+        };
+        // settings not affecting the extract don't change the hash
+        let mut other = list.clone();
+        other.tags = vec!["malware".to_string()];
+        other
+            .headers
+            .insert("Accept".to_string(), "text/plain".to_string());
+        assert_eq!(list.settings_hash(), other.settings_hash());
+
+        other.compression = Some(Compression::Gz);
+        assert_ne!(list.settings_hash(), other.settings_hash());
+        other.compression = None;
+        other.regex = "^0.0.0.0 (.*)$".to_string();
+        assert_ne!(list.settings_hash(), other.settings_hash());
+    }
 }
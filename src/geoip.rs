@@ -0,0 +1,131 @@
+use std::{net::IpAddr, str::FromStr};
+
+#[cfg(feature = "geoip")]
+use anyhow::Context;
+#[cfg(feature = "geoip")]
+use maxminddb::{geoip2, Reader};
+use serde::{Deserialize, Serialize};
+
+use crate::entry::Entry;
+
+/// GeoIpConfig points to local MaxMind databases IP entries are looked up in
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GeoIpConfig {
+    /// path of a GeoIP2 or GeoLite2 country database
+    pub country_db: Option<String>,
+    /// path of a GeoLite2 ASN database
+    pub asn_db: Option<String>,
+    /// annotate the IP entries newly added to a category in the run report
+    #[serde(default)]
+    pub annotate: bool,
+}
+
+/// GeoIp looks up the country and autonomous system of IP addresses
+pub struct GeoIp {
+    #[cfg(feature = "geoip")]
+    country: Option<Reader<Vec<u8>>>,
+    #[cfg(feature = "geoip")]
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+#[cfg(not(feature = "geoip"))]
+impl GeoIp {
+    /// fails since the databases can't be read without the geoip feature
+    pub fn open(_config: &GeoIpConfig) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!(
+            "harvester was built without the geoip feature"
+        ))
+    }
+
+    pub fn has_countries(&self) -> bool {
+        false
+    }
+
+    pub fn country(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+
+    pub fn asn(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(feature = "geoip")]
+impl GeoIp {
+    /// opens the configured databases
+    ///
+    /// * `config`: the GeoIP configuration
+    pub fn open(config: &GeoIpConfig) -> anyhow::Result<Self> {
+        let open = |path: &Option<String>| -> anyhow::Result<Option<Reader<Vec<u8>>>> {
+            match path {
+                Some(p) => {
+                    Ok(Some(Reader::open_readfile(p).with_context(|| {
+                        format!("could not open GeoIP database {p}")
+                    })?))
+                }
+                None => Ok(None),
+            }
+        };
+        Ok(Self {
+            country: open(&config.country_db)?,
+            asn: open(&config.asn_db)?,
+        })
+    }
+
+    /// returns true if a country database was configured
+    pub fn has_countries(&self) -> bool {
+        self.country.is_some()
+    }
+
+    /// returns the ISO code of the country the address is located in
+    ///
+    /// * `ip`: the address to look up
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let country: geoip2::Country = self.country.as_ref()?.lookup(ip).ok()?;
+        country
+            .country
+            .and_then(|c| c.iso_code)
+            .map(|c| c.to_uppercase())
+    }
+
+    /// returns the number and organization of the autonomous system the address
+    /// belongs to
+    ///
+    /// * `ip`: the address to look up
+    pub fn asn(&self, ip: IpAddr) -> Option<String> {
+        let asn: geoip2::Asn = self.asn.as_ref()?.lookup(ip).ok()?;
+        let number = asn.autonomous_system_number?;
+        Some(match asn.autonomous_system_organization {
+            Some(org) => format!("AS{number} {org}"),
+            None => format!("AS{number}"),
+        })
+    }
+}
+
+impl GeoIp {
+    /// returns the country and autonomous system of an IP or network entry in a
+    /// human readable form or None if the entry is no IP or nothing is known about it
+    ///
+    /// * `entry`: a category entry
+    pub fn describe(&self, entry: &str) -> Option<String> {
+        let ip = entry_ip(entry)?;
+        let parts: Vec<String> = [self.country(ip), self.asn(ip)]
+            .into_iter()
+            .flatten()
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts.join(", "))
+    }
+}
+
+/// returns the address of an IP entry or the network address of a CIDR entry
+///
+/// * `entry`: a category entry
+pub fn entry_ip(entry: &str) -> Option<IpAddr> {
+    match Entry::from_str(entry) {
+        Ok(Entry::Ip(ip)) | Ok(Entry::Cidr(ip, _)) => Some(ip),
+        _ => None,
+    }
+}
@@ -0,0 +1,123 @@
+use std::{io::Write, process::Stdio, time::Duration};
+
+use anyhow::Context;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+
+use crate::input::Input;
+
+/// the time an external transform command may take if the list doesn't set a timeout
+pub const DEFAULT_COMMAND_TIMEOUT: u64 = 300;
+
+/// Pipes the content of an input through an external command and writes the command's
+/// output line by line to the given writer. The command is killed when it doesn't
+/// finish within the timeout.
+///
+/// * `command`: the shell command to be run
+/// * `input`: the data written to the command's stdin
+/// * `output`: the destination of the command's stdout
+/// * `timeout`: the time the command may take
+pub async fn pipe_through_command<I: Input + Send, W: Write>(
+    command: &str,
+    input: &mut I,
+    output: &mut W,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("could not run command {command}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+
+    // stdin and stdout are served concurrently so a command producing output before
+    // it has read all of its input can't block the pipeline
+    let feed = async move {
+        while let Some(chunk) = input.chunk().await? {
+            stdin.write_all(&chunk).await?;
+        }
+        // closing stdin signals the end of the input to the command
+        drop(stdin);
+        anyhow::Ok(())
+    };
+    let collect = async {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await? {
+            output.write_all(line.as_bytes())?;
+            output.write_all(b"\n")?;
+        }
+        anyhow::Ok(())
+    };
+    let piped = tokio::time::timeout(timeout, async {
+        let (fed, collected) = tokio::join!(feed, collect);
+        // a command may legitimately stop reading its input early
+        if let Err(e) = fed {
+            debug!("{}: {}", command, e);
+        }
+        collected?;
+        Ok(child.wait().await?)
+    })
+    .await;
+
+    match piped {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(anyhow::anyhow!("command {} failed: {}", command, status)),
+        Ok(Err(e)) => Err(e),
+        Err(_) => {
+            child.kill().await.ok();
+            Err(anyhow::anyhow!(
+                "command {} killed after {} seconds",
+                command,
+                timeout.as_secs()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::helper::cursor_input::CursorInput;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipe_through_command() {
+        let mut input = CursorInput::new("one.domain\nWWW.TWO.DOMAIN\n");
+        let mut output = vec![];
+        pipe_through_command(
+            "tr '[:upper:]' '[:lower:]'",
+            &mut input,
+            &mut output,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "one.domain\nwww.two.domain\n"
+        );
+
+        let mut input = CursorInput::new("");
+        let result = pipe_through_command(
+            "sleep 5",
+            &mut input,
+            &mut vec![],
+            Duration::from_millis(100),
+        )
+        .await;
+        assert!(result.unwrap_err().to_string().contains("killed"));
+
+        let mut input = CursorInput::new("");
+        assert!(
+            pipe_through_command("exit 3", &mut input, &mut vec![], Duration::from_secs(10))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use std::{collections::VecDeque, num::NonZeroU64};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use log::Level;
+use regex::Regex;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{input::Input, log_throttle};
+
+/// object identifier of the subject alternative name extension (2.5.29.17)
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+/// object identifier of the common name attribute (2.5.4.3)
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// context specific tag of the dNSName choice within a GeneralName
+const TAG_DNS_NAME: u8 = 0x82;
+
+/// CtLogConfig configures a filter list which tails a certificate transparency log
+/// instead of downloading a static list (experimental)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CtLogConfig {
+    /// only domains matching at least one of these regular expressions are kept
+    pub patterns: Vec<String>,
+    /// the number of most recently seen domains the rolling list retains
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    /// the number of log entries requested at once
+    #[serde(default = "default_batch_size")]
+    pub batch_size: NonZeroU64,
+    /// upper limit of log entries to be fetched in a single run
+    #[serde(default = "default_max_fetch")]
+    pub max_fetch: u64,
+}
+
+fn default_max_entries() -> usize {
+    10000
+}
+
+fn default_batch_size() -> NonZeroU64 {
+    NonZeroU64::new(256).unwrap()
+}
+
+fn default_max_fetch() -> u64 {
+    10000
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedTreeHead {
+    tree_size: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogEntries {
+    entries: Vec<LogEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogEntry {
+    leaf_input: String,
+}
+
+/// CtLogInput reads newly logged domains from a certificate transparency log
+/// using the RFC 6962 HTTP API
+#[derive(Debug)]
+pub struct CtLogInput {
+    /// base URL of the log, e.g. https://ct.googleapis.com/logs/us1/argon2024/
+    pub url: Url,
+    /// index of the next log entry to be fetched
+    pub position: Option<u64>,
+    config: CtLogConfig,
+    patterns: Vec<Regex>,
+    tree_size: Option<u64>,
+    fetched: u64,
+    buffer: VecDeque<String>,
+}
+
+impl CtLogInput {
+    /// Initialize a new CtLogInput
+    ///
+    /// * `url`: the log's base url
+    /// * `position`: the log index to continue from, starts at the log's head if None
+    /// * `config`: the list's certificate transparency settings
+    pub fn new(url: Url, position: Option<u64>, config: CtLogConfig) -> anyhow::Result<Self> {
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<Vec<Regex>, regex::Error>>()?;
+        Ok(Self {
+            url,
+            position,
+            config,
+            patterns,
+            tree_size: None,
+            fetched: 0,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    /// sends a GET request to the given log endpoint and parses the JSON response
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, endpoint: &str) -> anyhow::Result<T> {
+        let url = self
+            .url
+            .join(endpoint)
+            .with_context(|| format!("invalid log url {}", self.url))?;
+        let response = reqwest::get(url.clone()).await?;
+        let status_code = response.status();
+        if status_code != StatusCode::OK {
+            return Err(anyhow::anyhow!("status code {}: {}", status_code, url));
+        }
+        let body = response.text().await?;
+        let json = serde_json::from_str(&body).with_context(|| format!("{}", url))?;
+        Ok(json)
+    }
+
+    /// fetches the next batch of log entries and buffers the matching domains
+    async fn fetch_batch(&mut self) -> anyhow::Result<bool> {
+        let tree_size = match self.tree_size {
+            Some(s) => s,
+            None => {
+                let sth: SignedTreeHead = self.get_json("ct/v1/get-sth").await?;
+                self.tree_size = Some(sth.tree_size);
+                sth.tree_size
+            }
+        };
+        // a log which shrank was reset or replaced, it's tailed from its new head
+        if self.position.is_some_and(|p| p > tree_size) {
+            self.position = Some(tree_size);
+        }
+        // without a stored position the log is tailed starting from its current head
+        let start = *self.position.get_or_insert(tree_size);
+        if start >= tree_size || self.fetched >= self.config.max_fetch {
+            return Ok(false);
+        }
+        let end = (start + self.config.batch_size.get())
+            .min(tree_size)
+            .min(start + self.config.max_fetch - self.fetched)
+            - 1;
+        let batch: LogEntries = self
+            .get_json(&format!("ct/v1/get-entries?start={start}&end={end}"))
+            .await?;
+        if batch.entries.is_empty() {
+            return Ok(false);
+        }
+        // logs are allowed to return less entries than requested
+        let received = batch.entries.len() as u64;
+        self.position = Some(start + received);
+        self.fetched += received;
+        for entry in batch.entries {
+            let leaf = match base64::decode(&entry.leaf_input) {
+                Ok(l) => l,
+                Err(e) => {
+                    log_throttle::log(
+                        Level::Warn,
+                        self.url.as_str(),
+                        "invalid leaf input",
+                        format_args!("{}: invalid leaf input: {}", self.url, e),
+                    );
+                    continue;
+                }
+            };
+            for domain in domains_from_leaf(&leaf) {
+                if self.patterns.iter().any(|p| p.is_match(&domain)) {
+                    self.buffer.push_back(domain);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl Input for CtLogInput {
+    async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        while self.buffer.is_empty() {
+            if !self.fetch_batch().await? {
+                return Ok(None);
+            }
+        }
+        let domain = self.buffer.pop_front().unwrap();
+        Ok(Some(format!("{domain}\n").into_bytes()))
+    }
+
+    /// continue tailing from the current log head
+    async fn reset(&mut self) -> anyhow::Result<()> {
+        self.tree_size = None;
+        self.fetched = 0;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// the amount of entries still to be fetched
+    async fn len(&mut self) -> anyhow::Result<u64> {
+        let sth: SignedTreeHead = self.get_json("ct/v1/get-sth").await?;
+        Ok(sth
+            .tree_size
+            .saturating_sub(self.position.unwrap_or(sth.tree_size)))
+    }
+}
+
+/// extracts the certificate's domains from a MerkleTreeLeaf as defined in RFC 6962
+///
+/// * `leaf`: the decoded leaf_input of a log entry
+pub fn domains_from_leaf(leaf: &[u8]) -> Vec<String> {
+    // version (1), leaf type (1), timestamp (8), entry type (2)
+    if leaf.len() < 12 {
+        return vec![];
+    }
+    let der = match u16::from_be_bytes([leaf[10], leaf[11]]) {
+        // x509_entry: certificate with 24 bit length prefix
+        0 => length_prefixed(&leaf[12..]),
+        // precert_entry: issuer key hash (32) and tbs certificate with 24 bit length prefix
+        1 if leaf.len() > 44 => length_prefixed(&leaf[44..]),
+        _ => None,
+    };
+    let mut domains = vec![];
+    if let Some(der) = der {
+        collect_names(der, &mut domains);
+    }
+    domains
+}
+
+fn length_prefixed(data: &[u8]) -> Option<&[u8]> {
+    if data.len() < 3 {
+        return None;
+    }
+    let len = u32::from_be_bytes([0, data[0], data[1], data[2]]) as usize;
+    data.get(3..3 + len)
+}
+
+/// splits DER encoded data into its top level (tag, content) items
+fn der_items(mut data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut items = vec![];
+    while data.len() >= 2 {
+        let tag = data[0];
+        let (len, header_len) = match data[1] {
+            l if l < 0x80 => (l as usize, 2),
+            l => {
+                let num_bytes = (l & 0x7f) as usize;
+                if num_bytes == 0 || num_bytes > 4 || data.len() < 2 + num_bytes {
+                    break;
+                }
+                let len = data[2..2 + num_bytes]
+                    .iter()
+                    .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+                (len, 2 + num_bytes)
+            }
+        };
+        let content = match data.get(header_len..header_len + len) {
+            Some(c) => c,
+            None => break,
+        };
+        items.push((tag, content));
+        data = &data[header_len + len..];
+    }
+    items
+}
+
+/// recursively walks a DER structure and collects the common name and the
+/// subject alternative DNS names
+fn collect_names(der: &[u8], domains: &mut Vec<String>) {
+    let items = der_items(der);
+    for (i, (tag, content)) in items.iter().enumerate() {
+        let following = &items[i + 1..];
+        match tag {
+            // object identifier
+            0x06 if *content == OID_SUBJECT_ALT_NAME => {
+                // the extension value follows the optional critical flag
+                if let Some((_, value)) = following.iter().find(|(t, _)| *t == 0x04) {
+                    for (_, names) in der_items(value).iter().filter(|(t, _)| *t == 0x30) {
+                        for (_, name) in der_items(names).iter().filter(|(t, _)| *t == TAG_DNS_NAME)
+                        {
+                            push_domain(name, domains);
+                        }
+                    }
+                }
+            }
+            0x06 if *content == OID_COMMON_NAME => {
+                if let Some((0x0c | 0x13 | 0x16, name)) = following.first() {
+                    push_domain(name, domains);
+                }
+            }
+            // constructed types contain further items
+            t if t & 0x20 != 0 => collect_names(content, domains),
+            _ => {}
+        }
+    }
+}
+
+fn push_domain(name: &[u8], domains: &mut Vec<String>) {
+    let name = match std::str::from_utf8(name) {
+        Ok(n) => n.trim_start_matches("*.").to_lowercase(),
+        Err(_) => return,
+    };
+    if name.contains('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        && !domains.contains(&name)
+    {
+        domains.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::helper::list_server::ListServer;
+
+    use super::*;
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut v = vec![tag, content.len() as u8];
+        v.extend_from_slice(content);
+        v
+    }
+
+    #[test]
+    fn test_domains_from_leaf() {
+        // a subject with a common name and a subject alternative name extension
+        let cn = tlv(
+            0x30,
+            &[tlv(0x06, OID_COMMON_NAME), tlv(0x0c, b"Example.com")].concat(),
+        );
+        let san_names = tlv(
+            0x30,
+            &[
+                tlv(TAG_DNS_NAME, b"*.phish.example.net"),
+                tlv(TAG_DNS_NAME, b"example.com"),
+            ]
+            .concat(),
+        );
+        let san = tlv(
+            0x30,
+            &[tlv(0x06, OID_SUBJECT_ALT_NAME), tlv(0x04, &san_names)].concat(),
+        );
+        let cert = tlv(0x30, &[cn, san].concat());
+
+        let mut leaf = vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        leaf.extend_from_slice(&[0, 0, cert.len() as u8]);
+        leaf.extend_from_slice(&cert);
+
+        let got = domains_from_leaf(&leaf);
+        assert_eq!(got, vec!["example.com", "phish.example.net"]);
+    }
+
+    #[tokio::test]
+    async fn test_shrunk_log() {
+        let server = ListServer::start();
+        server.set_list("/ct/v1/get-sth", r#"{"tree_size": 10}"#);
+        let config: CtLogConfig = serde_json::from_str(r#"{"patterns": []}"#).unwrap();
+        let url = Url::parse(&server.url("/")).unwrap();
+        let mut input = CtLogInput::new(url, Some(100), config).unwrap();
+
+        // the stored position is beyond the head of the reset log
+        assert_eq!(input.len().await.unwrap(), 0);
+        assert_eq!(input.chunk().await.unwrap(), None);
+        assert_eq!(input.position, Some(10));
+    }
+
+    #[test]
+    fn test_batch_size_not_zero() {
+        let config: CtLogConfig = serde_json::from_str(r#"{"patterns": []}"#).unwrap();
+        assert_eq!(config.batch_size.get(), 256);
+        assert!(
+            serde_json::from_str::<CtLogConfig>(r#"{"patterns": [], "batch_size": 0}"#).is_err()
+        );
+    }
+}
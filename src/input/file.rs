@@ -8,7 +8,7 @@ use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tokio::{
     fs::{self, File},
-    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
 };
 use tokio_tar::{Archive, Entry};
 
@@ -19,11 +19,96 @@ pub enum Compression {
     TarGz(String),
 }
 
+/// lines longer than this number of bytes are skipped unless a list sets its own limit
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 8192;
+
 #[derive(Debug)]
 pub enum Handle {
     File(BufReader<File>),
-    Gz(GzipDecoder<BufReader<File>>),
-    TarGz(Entry<Archive<GzipDecoder<BufReader<File>>>>),
+    Gz(BufReader<GzipDecoder<BufReader<File>>>),
+    TarGz(BufReader<Entry<Archive<GzipDecoder<BufReader<File>>>>>),
+}
+
+/// the result of reading a single line
+enum Line {
+    /// a line including its newline character unless it's the last line of the data
+    Complete(Vec<u8>),
+    /// a line exceeding the maximum length, its bytes were discarded, contains the
+    /// number of bytes discarded
+    TooLong(usize),
+    /// the end of the data was reached
+    End,
+}
+
+/// UTF-8 byte order mark some editors put at the beginning of a file
+const BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// Removes the byte order mark from the first line and converts CRLF line endings
+/// so carriage returns don't end up in the extracted entries
+///
+/// * `line`: a line including its newline character
+/// * `first_line`: true if it's the first line of the data
+fn normalize_line(mut line: Vec<u8>, first_line: bool) -> Vec<u8> {
+    if first_line && line.starts_with(BOM) {
+        line.drain(..BOM.len());
+    }
+    let newline = line.ends_with(b"\n");
+    if newline {
+        line.pop();
+    }
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    if newline {
+        line.push(b'\n');
+    }
+    line
+}
+
+/// Reads bytes until the next newline character. Bytes of lines exceeding the
+/// maximum length are discarded as they are read so a single long line can't
+/// exhaust the memory.
+///
+/// * `reader`: the handle to read from
+/// * `max_len`: the maximum number of bytes of a line without its newline character
+async fn read_line_limited(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    max_len: usize,
+) -> std::io::Result<Line> {
+    let mut line = vec![];
+    let mut too_long = false;
+    let mut total = 0;
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(match (too_long, line.is_empty()) {
+                (true, _) => Line::TooLong(total),
+                (false, true) => Line::End,
+                (false, false) => Line::Complete(line),
+            });
+        }
+        let (consumed, complete) = match buf.iter().position(|b| *b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (buf.len(), false),
+        };
+        if !too_long {
+            line.extend_from_slice(&buf[..consumed]);
+            let content_len = line.len() - usize::from(complete);
+            if content_len > max_len {
+                too_long = true;
+                line = vec![];
+            }
+        }
+        reader.consume(consumed);
+        total += consumed;
+        if complete {
+            return Ok(if too_long {
+                Line::TooLong(total)
+            } else {
+                Line::Complete(line)
+            });
+        }
+    }
 }
 
 /// FileInput reads data from a File
@@ -35,6 +120,14 @@ pub struct FileInput {
     path: PathBuf,
     /// the file handle
     handle: Option<Handle>,
+    /// number of decompressed bytes returned so far
+    bytes_read: u64,
+    /// true once the end of the data was reached
+    exhausted: bool,
+    /// lines exceeding this number of bytes are skipped
+    max_line_length: usize,
+    /// number of lines skipped for exceeding the maximum length
+    lines_too_long: usize,
 }
 
 impl FileInput {
@@ -47,9 +140,21 @@ impl FileInput {
             compression,
             path,
             handle: None,
+            bytes_read: 0,
+            exhausted: false,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            lines_too_long: 0,
         }
     }
 
+    /// sets the number of bytes beyond which lines are skipped
+    ///
+    /// * `max_line_length`: the maximum length of a line in bytes
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
     /// initializes the file handle according to the specified compression format
     async fn init_handle(&mut self) -> anyhow::Result<()> {
         let f = File::open(self.path.clone()).await.with_context(|| {
@@ -65,7 +170,7 @@ impl FileInput {
         match &self.compression {
             Some(Compression::Gz) => {
                 let gz = GzipDecoder::new(BufReader::new(f));
-                self.handle = Some(Handle::Gz(gz));
+                self.handle = Some(Handle::Gz(BufReader::new(gz)));
             }
             Some(Compression::TarGz(wanted_path_str)) => {
                 let gz = GzipDecoder::new(BufReader::new(f));
@@ -78,7 +183,7 @@ impl FileInput {
                         && let Ok(path) = entry.path()
                         && path == path_wanted
                     {
-                        self.handle = Some(Handle::TarGz(entry));
+                        self.handle = Some(Handle::TarGz(BufReader::new(entry)));
                         break;
                     }
                 }
@@ -95,54 +200,45 @@ impl FileInput {
 #[async_trait]
 impl Input for FileInput {
     async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
-        /// inner function reading bytes until the next newline character
-        ///
-        /// * `archive`: the file handle to read from
-        /// * `vec_buf`: the target buffer containing the line
-        async fn read_bytes_till_newline(
-            archive: &mut (impl AsyncRead + Unpin),
-            mut vec_buf: Vec<u8>,
-        ) -> anyhow::Result<Option<Vec<u8>>> {
-            loop {
-                let mut byte_buf = Vec::with_capacity(1);
-                let n = archive.take(1).read_to_end(&mut byte_buf).await;
-                match n {
-                    Ok(n) if n > 0 => {
-                        if let Some(b) = byte_buf.last() && b == &10 {
-                                return Ok(Some(vec_buf));
-                            }
-                        vec_buf.extend(byte_buf);
-                        if vec_buf.len() >= vec_buf.capacity() {
-                            return Err(anyhow::anyhow!("Error reading chunk from file: line length exceeds buffer capacity"));
-                        }
-                    }
-                    Ok(n) if n > vec_buf.len() => {
-                        return Err(anyhow::anyhow!("Error reading chunk from file:  chunk exceedes maximum line length of {} bytes", vec_buf.len()));
-                    }
-                    Err(e) => return Err(anyhow::anyhow!("Error reading chunk from file: {}", e)),
-                    _ => return Ok(None),
-                }
-            }
-        }
-
-        // read buffer size for a single line
-        const BUF_SIZE: usize = 1024;
-
         if self.handle.is_none() {
             self.init_handle().await?;
         }
-        let mut str_buf = String::new();
-        let vec_buf = Vec::with_capacity(BUF_SIZE);
-        // handle can be safely unwrapped here since it's initialized at the beginning of the function
-        match self.handle.as_mut().unwrap() {
-            Handle::File(file) => match file.read_line(&mut str_buf).await {
-                Ok(n) if n > 0 => Ok(Some(str_buf.as_bytes().to_vec())),
-                Ok(0) => Ok(None),
-                Ok(_) => Ok(None),
-                Err(e) => Err(anyhow::anyhow!("Error reading line from file: {}", e)),
-            },
-            Handle::Gz(archive) => read_bytes_till_newline(archive, vec_buf).await,
-            Handle::TarGz(archive) => read_bytes_till_newline(archive, vec_buf).await,
+        loop {
+            // handle can be safely unwrapped here since it's initialized at the beginning of the function
+            let line = match self.handle.as_mut().unwrap() {
+                Handle::File(file) => read_line_limited(file, self.max_line_length).await,
+                Handle::Gz(archive) => read_line_limited(archive, self.max_line_length).await,
+                Handle::TarGz(archive) => read_line_limited(archive, self.max_line_length).await,
+            }
+            .map_err(|e| anyhow::anyhow!("Error reading line from file: {}", e))?;
+            match line {
+                Line::Complete(chunk) => {
+                    let first_line = self.bytes_read == 0;
+                    self.bytes_read += chunk.len() as u64;
+                    return Ok(Some(normalize_line(chunk, first_line)));
+                }
+                Line::TooLong(len) => {
+                    self.bytes_read += len as u64;
+                    self.lines_too_long += 1;
+                    debug!(
+                        "{}: skipped line longer than {} bytes",
+                        self.path.display(),
+                        self.max_line_length
+                    );
+                }
+                Line::End => {
+                    if self.lines_too_long > 0 && !self.exhausted {
+                        warn!(
+                            "{}: skipped {} lines longer than {} bytes",
+                            self.path.display(),
+                            self.lines_too_long,
+                            self.max_line_length
+                        );
+                    }
+                    self.exhausted = true;
+                    return Ok(None);
+                }
+            }
         }
     }
 
@@ -151,12 +247,20 @@ impl Input for FileInput {
         if self.handle.is_some() {
             self.handle.take();
         }
+        self.bytes_read = 0;
+        self.exhausted = false;
+        self.lines_too_long = 0;
         self.init_handle().await?;
         Ok(())
     }
 
-    /// get the file length from file metadata
+    /// get the file length from file metadata. The length of compressed data is only
+    /// known after it was read completely, before that the number of decompressed bytes
+    /// read so far or the compressed file size, whichever is larger, serves as estimate.
     async fn len(&mut self) -> anyhow::Result<u64> {
+        if self.compression.is_some() && self.exhausted {
+            return Ok(self.bytes_read);
+        }
         let content_len = fs::metadata(&self.path)
             .await
             .with_context(|| {
@@ -166,6 +270,88 @@ impl Input for FileInput {
                 )
             })?
             .len();
+        if self.compression.is_some() {
+            return Ok(content_len.max(self.bytes_read));
+        }
         Ok(content_len)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression as GzLevel};
+
+    use crate::tests::helper::cache_file_creator::CacheFileCreator;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_len_compressed() {
+        let cache = CacheFileCreator::new("test_len_compressed", "in", "out");
+        let path = Path::new(&cache.new_test_config().cache_dir).join("in/list.gz");
+        let data = "domain.one\n".repeat(1000);
+        let mut encoder = GzEncoder::new(std::fs::File::create(&path).unwrap(), GzLevel::best());
+        encoder.write_all(data.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut input = FileInput::new(path, Some(Compression::Gz));
+        let estimate = input.len().await.unwrap();
+        assert!(estimate > 0 && estimate < data.len() as u64);
+        while input.chunk().await.unwrap().is_some() {}
+        assert_eq!(input.len().await.unwrap(), data.len() as u64);
+        input.reset().await.unwrap();
+        assert_eq!(input.len().await.unwrap(), estimate);
+    }
+
+    #[tokio::test]
+    async fn test_bom_and_crlf() {
+        let cache = CacheFileCreator::new("test_bom_and_crlf", "in", "out");
+        let path = Path::new(&cache.new_test_config().cache_dir).join("in/list");
+        std::fs::write(
+            &path,
+            b"\xef\xbb\xbfdomain.one\r\ndomain.two\r\ndomain.three\r",
+        )
+        .unwrap();
+
+        let mut input = FileInput::new(path.clone(), None);
+        let mut lines = vec![];
+        while let Some(chunk) = input.chunk().await.unwrap() {
+            lines.push(String::from_utf8(chunk).unwrap());
+        }
+        assert_eq!(lines, vec!["domain.one\n", "domain.two\n", "domain.three"]);
+        assert_eq!(
+            input.len().await.unwrap(),
+            std::fs::metadata(&path).unwrap().len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skip_long_lines() {
+        let cache = CacheFileCreator::new("test_skip_long_lines", "in", "out");
+        let dir = Path::new(&cache.new_test_config().cache_dir).join("in");
+        let data = format!(
+            "domain.one\n{}\ndomain.two\n{}",
+            "x".repeat(100),
+            "y".repeat(100)
+        );
+        std::fs::write(dir.join("list"), &data).unwrap();
+        let mut encoder = GzEncoder::new(
+            std::fs::File::create(dir.join("list.gz")).unwrap(),
+            GzLevel::best(),
+        );
+        encoder.write_all(data.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        for (name, compression) in [("list", None), ("list.gz", Some(Compression::Gz))] {
+            let mut input = FileInput::new(dir.join(name), compression).with_max_line_length(50);
+            let mut lines = vec![];
+            while let Some(chunk) = input.chunk().await.unwrap() {
+                lines.push(String::from_utf8(chunk).unwrap());
+            }
+            assert_eq!(lines, vec!["domain.one\n", "domain.two\n"]);
+            assert_eq!(input.lines_too_long, 2);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+pub(crate) mod command;
+pub(crate) mod ct_log;
 pub(crate) mod file;
+pub(crate) mod source;
 pub(crate) mod url;
 
 use async_trait::async_trait;
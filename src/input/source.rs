@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Url;
+use tokio::{fs::File, io::AsyncReadExt};
+
+use crate::{
+    dns::DnsConfig,
+    filter_list::FilterList,
+    input::{url::UrlInput, Input},
+    manifest::HttpRecord,
+};
+
+use super::url::DEFAULT_CHUNK_SIZE;
+
+/// Source is the trait of the inputs lists are downloaded from. Besides the data a
+/// source may tell whether the list changed since the last download, the defaults
+/// suit sources without such a notion.
+#[async_trait]
+pub trait Source: Input + Send + Debug {
+    /// returns when the source asked to be asked again if it deferred the download
+    async fn retry_after(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// returns whether the list is unchanged since the download the validators were
+    /// taken from or None if the source can't tell
+    async fn is_not_modified(&mut self) -> anyhow::Result<Option<bool>> {
+        Ok(None)
+    }
+
+    /// returns the validators to be used by the next run's download
+    fn validators(&self) -> Option<HttpRecord> {
+        None
+    }
+}
+
+/// the input the download stage reads the lists from, whatever kind of source they are
+pub type SourceInput = Box<dyn Source>;
+
+#[async_trait]
+impl Input for SourceInput {
+    async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        self.as_mut().chunk().await
+    }
+
+    async fn reset(&mut self) -> anyhow::Result<()> {
+        self.as_mut().reset().await
+    }
+
+    async fn len(&mut self) -> anyhow::Result<u64> {
+        self.as_mut().len().await
+    }
+}
+
+/// SourceOptions are the run's settings a source is created with
+#[derive(Debug, Clone, Default)]
+pub struct SourceOptions {
+    /// the previous download's validators making the download conditional
+    pub validators: Option<HttpRecord>,
+    /// the number of bytes collected before they are written, the default if None
+    pub chunk_size: Option<usize>,
+    /// the DNS settings hosts are resolved with, the system's if None
+    pub dns: Option<DnsConfig>,
+}
+
+/// creates the source of a list
+pub type SourceFactory =
+    Arc<dyn Fn(&FilterList, SourceOptions) -> anyhow::Result<SourceInput> + Send + Sync>;
+
+/// the factories of the source kinds keyed by URL scheme or `source_type`
+static SOURCES: OnceLock<RwLock<HashMap<String, SourceFactory>>> = OnceLock::new();
+
+/// returns the registry, the built-in source kinds are registered on first use
+fn sources() -> &'static RwLock<HashMap<String, SourceFactory>> {
+    SOURCES.get_or_init(|| {
+        let mut sources: HashMap<String, SourceFactory> = HashMap::new();
+        sources.insert("http".to_string(), Arc::new(url_source));
+        sources.insert("https".to_string(), Arc::new(url_source));
+        sources.insert("file".to_string(), Arc::new(file_source));
+        RwLock::new(sources)
+    })
+}
+
+/// Registers the factory of a source kind, replacing the one registered before. Lists
+/// use it if their `source_type` or the scheme of their source is `kind`.
+///
+/// * `kind`: the URL scheme or `source_type` the factory is used for
+/// * `factory`: creates the source of a list
+// the extension point of code embedding the pipeline, the binary itself only uses the
+// built-in source kinds
+#[allow(dead_code)]
+pub fn register_source<F>(kind: &str, factory: F)
+where
+    F: Fn(&FilterList, SourceOptions) -> anyhow::Result<SourceInput> + Send + Sync + 'static,
+{
+    sources()
+        .write()
+        .unwrap()
+        .insert(kind.to_lowercase(), Arc::new(factory));
+}
+
+/// returns the kind of source a list is downloaded from, its `source_type` or else
+/// the scheme of its source URL
+///
+/// * `list`: the filter list
+pub fn source_kind(list: &FilterList) -> anyhow::Result<String> {
+    if let Some(source_type) = &list.source_type {
+        return Ok(source_type.to_lowercase());
+    }
+    let url =
+        Url::parse(&list.source).with_context(|| format!("invalid source {}", list.source))?;
+    Ok(url.scheme().to_string())
+}
+
+/// returns true if a factory is registered for the kind of source
+///
+/// * `kind`: the URL scheme or `source_type`
+pub fn is_registered(kind: &str) -> bool {
+    sources().read().unwrap().contains_key(kind)
+}
+
+/// Creates the source a list is downloaded from by the factory registered for its
+/// kind of source
+///
+/// * `list`: the filter list
+/// * `options`: the run's settings the source is created with
+pub fn create_source(list: &FilterList, options: SourceOptions) -> anyhow::Result<SourceInput> {
+    let kind = source_kind(list)?;
+    let factory = sources()
+        .read()
+        .unwrap()
+        .get(&kind)
+        .cloned()
+        .with_context(|| format!("unknown source type {kind}"))?;
+    factory(list, options)
+}
+
+/// creates the source of a list downloaded via HTTP
+///
+/// * `list`: the filter list
+/// * `options`: the run's settings the source is created with
+fn url_source(list: &FilterList, options: SourceOptions) -> anyhow::Result<SourceInput> {
+    let url = Url::parse(&list.source).with_context(|| format!("config file error: {:?}", list))?;
+    let mut input = UrlInput::new(url, &list.headers, list.retry.clone().unwrap_or_default())?;
+    if let Some(validators) = options.validators {
+        input.set_validators(validators);
+    }
+    if list.insecure_tls {
+        input.set_insecure_tls();
+    }
+    if let Some(chunk_size) = options.chunk_size {
+        input.set_chunk_size(chunk_size);
+    }
+    if let Some(dns) = options.dns {
+        input.set_dns(dns);
+    }
+    Ok(Box::new(input))
+}
+
+/// creates the source of a list copied from the local file system, the source is a
+/// `file://` URL or a path if the `source_type` is `file`
+///
+/// * `list`: the filter list
+/// * `options`: the run's settings the source is created with
+fn file_source(list: &FilterList, options: SourceOptions) -> anyhow::Result<SourceInput> {
+    let path = match Url::parse(&list.source) {
+        Ok(url) if url.scheme() == "file" => url
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("invalid file URL {}", list.source))?,
+        _ => PathBuf::from(&list.source),
+    };
+    Ok(Box::new(LocalInput {
+        path,
+        chunk_size: options.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1),
+        file: None,
+    }))
+}
+
+/// LocalInput copies a list from the local file system unchanged
+#[derive(Debug)]
+pub struct LocalInput {
+    path: PathBuf,
+    /// the number of bytes read at once
+    chunk_size: usize,
+    file: Option<File>,
+}
+
+#[async_trait]
+impl Input for LocalInput {
+    async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        if self.file.is_none() {
+            self.reset().await?;
+        }
+        let mut buffer = vec![0; self.chunk_size];
+        let read = self.file.as_mut().unwrap().read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.truncate(read);
+        Ok(Some(buffer))
+    }
+
+    async fn reset(&mut self) -> anyhow::Result<()> {
+        let file = File::open(&self.path)
+            .await
+            .with_context(|| format!("unable to open file {}", self.path.display()))?;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    async fn len(&mut self) -> anyhow::Result<u64> {
+        let metadata = tokio::fs::metadata(&self.path)
+            .await
+            .with_context(|| format!("unable to open file {}", self.path.display()))?;
+        Ok(metadata.len())
+    }
+}
+
+impl Source for LocalInput {}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::tests::helper::cache_file_creator::CacheFileCreator;
+
+    use super::*;
+
+    /// a source returning the list's source as its only line
+    #[derive(Debug)]
+    struct EchoInput(Option<String>);
+
+    #[async_trait]
+    impl Input for EchoInput {
+        async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(self.0.take().map(|s| format!("{s}\n").into_bytes()))
+        }
+
+        async fn reset(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn len(&mut self) -> anyhow::Result<u64> {
+            Err(anyhow::anyhow!("unknown"))
+        }
+    }
+
+    impl Source for EchoInput {}
+
+    #[tokio::test]
+    async fn test_source_registry() {
+        let list = |source: &str, source_type: Option<&str>| FilterList {
+            id: "test".to_string(),
+            source: source.to_string(),
+            source_type: source_type.map(String::from),
+            ..Default::default()
+        };
+        assert_eq!(
+            source_kind(&list("https://example.com/list", None)).unwrap(),
+            "https"
+        );
+        assert_eq!(source_kind(&list("repo/list", Some("Git"))).unwrap(), "git");
+        assert!(source_kind(&list("repo/list", None)).is_err());
+        assert!(create_source(&list("ftp://example.com/list", None), Default::default()).is_err());
+
+        register_source("echo", |list, _| {
+            Ok(Box::new(EchoInput(Some(list.source.clone()))))
+        });
+        assert!(is_registered("echo"));
+        let mut input =
+            create_source(&list("malicious.com", Some("echo")), Default::default()).unwrap();
+        assert_eq!(
+            input.chunk().await.unwrap(),
+            Some(b"malicious.com\n".to_vec())
+        );
+        assert_eq!(input.chunk().await.unwrap(), None);
+
+        let cache = CacheFileCreator::new("test_source_registry", "in", "out");
+        let config = cache.new_test_config();
+        fs::create_dir_all(&config.cache_dir).unwrap();
+        let path = PathBuf::from(&config.cache_dir).join("list");
+        fs::write(&path, "malicious.com\nunwanted.net\n").unwrap();
+        let options = SourceOptions {
+            chunk_size: Some(16),
+            ..Default::default()
+        };
+        let url = Url::from_file_path(fs::canonicalize(&path).unwrap()).unwrap();
+        let mut input = create_source(&list(url.as_str(), None), options).unwrap();
+        assert_eq!(input.len().await.unwrap(), 27);
+        assert_eq!(
+            input.chunk().await.unwrap(),
+            Some(b"malicious.com\nun".to_vec())
+        );
+        assert_eq!(input.chunk().await.unwrap(), Some(b"wanted.net\n".to_vec()));
+        assert_eq!(input.chunk().await.unwrap(), None);
+        assert_eq!(input.validators(), None);
+    }
+}
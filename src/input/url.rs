@@ -1,12 +1,39 @@
-use crate::input::Input;
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    dns::DnsConfig,
+    filter_list::RetryPolicy,
+    input::{source::Source, Input},
+    manifest::HttpRecord,
+};
 use anyhow::Context;
 use async_trait::async_trait;
-use reqwest::{header::CONTENT_LENGTH, Response, StatusCode, Url};
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+        LAST_MODIFIED, RETRY_AFTER,
+    },
+    Method, Response, StatusCode, Url,
+};
+use url::Host;
+
+/// number of bytes of the response body collected before they are handed on
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
 
 /// UrlInput downloads data from an Url
 #[derive(Debug)]
 pub struct UrlInput {
     pub url: Url,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+    /// validators of the previous download turning the request into a conditional one
+    validators: Option<HttpRecord>,
+    /// skip the verification of the server's certificate
+    insecure_tls: bool,
+    /// DNS servers and pinned addresses the host is resolved with
+    dns: Option<DnsConfig>,
+    /// the network chunks are collected up to this number of bytes
+    chunk_size: usize,
     response: Option<reqwest::Response>,
 }
 
@@ -14,68 +41,236 @@ impl UrlInput {
     /// Initialize a new UrlInput
     ///
     /// * `url`: url to download from
-    pub fn new(url: Url) -> Self {
-        Self {
+    /// * `headers`: additional HTTP headers sent with every request
+    /// * `retry`: how often failed requests are repeated
+    pub fn new(
+        url: Url,
+        headers: &BTreeMap<String, String>,
+        retry: RetryPolicy,
+    ) -> anyhow::Result<Self> {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid header name {name}"))?,
+                HeaderValue::from_str(value)
+                    .with_context(|| format!("invalid value of header {name}"))?,
+            );
+        }
+        Ok(Self {
             url,
+            headers: header_map,
+            retry,
+            validators: None,
+            insecure_tls: false,
+            dns: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
             response: None,
+        })
+    }
+
+    /// Makes the request conditional on the list having changed since the download
+    /// the validators were taken from
+    ///
+    /// * `validators`: ETag and Last-Modified of the previous download
+    pub fn set_validators(&mut self, validators: HttpRecord) {
+        self.validators = Some(validators);
+    }
+
+    /// Sets the number of bytes the network chunks are collected up to before they are
+    /// written, fewer bytes save memory, more bytes save writes
+    ///
+    /// * `chunk_size`: the number of bytes
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
+
+    /// Sends a request and repeats it according to the retry policy if the server
+    /// can't be reached or answers with a server error
+    ///
+    /// * `method`: the HTTP request method
+    /// * `headers`: the request headers
+    async fn send(&self, method: Method, headers: HeaderMap) -> anyhow::Result<Response> {
+        let mut builder = reqwest::Client::builder().danger_accept_invalid_certs(self.insecure_tls);
+        if let Some(dns) = &self.dns
+            && let Some(Host::Domain(host)) = self.url.host()
+        {
+            let port = self.url.port_or_known_default().unwrap_or_default();
+            let addrs = dns.resolve(host, port).await?;
+            if !addrs.is_empty() {
+                builder = builder.resolve_to_addrs(host, &addrs);
+            }
+        }
+        let cli = builder.build()?;
+        let mut attempt = 1;
+        loop {
+            let result = cli
+                .request(method.clone(), self.url.clone())
+                .headers(headers.clone())
+                .send()
+                .await;
+            let retry = match &result {
+                // the server tells when to come back, retrying earlier is pointless
+                Ok(r) if r.headers().contains_key(RETRY_AFTER) => false,
+                Ok(r) => r.status().is_server_error(),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if !retry || attempt >= self.retry.attempts {
+                return Ok(result?);
+            }
+            debug!(
+                "{} {}: attempt {} failed, retrying in {}s",
+                method, self.url, attempt, self.retry.delay
+            );
+            tokio::time::sleep(Duration::from_secs(self.retry.delay)).await;
+            attempt += 1;
         }
     }
 
-    /// perform a head request and return the response
-    pub async fn head_request(&self) -> anyhow::Result<Response> {
-        let cli = reqwest::Client::new();
-        let header = cli.head(self.url.clone()).send().await?;
-        let status_code = header.status();
-        if status_code != StatusCode::OK {
-            return Err(anyhow::anyhow!("status code {}: {}", status_code, self.url,))
-                .with_context(|| format!("{}", self.url));
+    /// Resolves the host with the given DNS servers and pinned addresses instead of
+    /// the system's resolver
+    ///
+    /// * `dns`: the DNS settings of the list
+    pub fn set_dns(&mut self, dns: DnsConfig) {
+        self.dns = Some(dns);
+    }
+
+    /// Accepts invalid, expired or revoked certificates and certificates issued for
+    /// other host names. Only meant for servers with known broken certificates.
+    pub fn set_insecure_tls(&mut self) {
+        self.insecure_tls = true;
+    }
+
+    /// Returns the response of the GET request, the request is only sent once per
+    /// input. The header section is used to decide whether the list changed, the body
+    /// is only read if it did.
+    async fn response(&mut self) -> anyhow::Result<&mut Response> {
+        if self.response.is_none() {
+            let mut headers = self.headers.clone();
+            if let Some(validators) = &self.validators {
+                if let Some(etag) = &validators.etag {
+                    headers.insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+                }
+                if let Some(last_modified) = &validators.last_modified {
+                    headers.insert(IF_MODIFIED_SINCE, HeaderValue::from_str(last_modified)?);
+                }
+            }
+            self.response = Some(self.send(Method::GET, headers).await?);
         }
-        Ok(header)
+        Ok(self.response.as_mut().unwrap())
+    }
+
+    /// Returns whether the server answered the conditional request with 304 Not
+    /// Modified or None if the request wasn't conditional
+    pub async fn is_not_modified(&mut self) -> anyhow::Result<Option<bool>> {
+        if self.validators.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.response().await?.status() == StatusCode::NOT_MODIFIED,
+        ))
+    }
+
+    /// Returns the Retry-After header if the server answered with 429 Too Many Requests
+    /// or 503 Service Unavailable and asked to come back later
+    pub async fn retry_after(&mut self) -> anyhow::Result<Option<String>> {
+        let response = self.response().await?;
+        if !matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        ) {
+            return Ok(None);
+        }
+        Ok(response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from))
+    }
+
+    /// returns the validators of the response to be used by the next run's request
+    pub fn validators(&self) -> Option<HttpRecord> {
+        let headers = self.response.as_ref()?.headers();
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(String::from)
+        };
+        let record = HttpRecord {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        if record == HttpRecord::default() {
+            return None;
+        }
+        Some(record)
+    }
+}
+
+#[async_trait]
+impl Source for UrlInput {
+    async fn retry_after(&mut self) -> anyhow::Result<Option<String>> {
+        UrlInput::retry_after(self).await
+    }
+
+    async fn is_not_modified(&mut self) -> anyhow::Result<Option<bool>> {
+        UrlInput::is_not_modified(self).await
+    }
+
+    fn validators(&self) -> Option<HttpRecord> {
+        UrlInput::validators(self)
     }
 }
 
 #[async_trait]
 impl Input for UrlInput {
     async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
-        if self.response.is_none() {
-            self.response = Some(reqwest::get(self.url.clone()).await?);
-        }
-
-        let status_code = self.response.as_ref().unwrap().status();
+        let url = self.url.clone();
+        let chunk_size = self.chunk_size;
+        let response = self.response().await?;
+        let status_code = response.status();
         if status_code != StatusCode::OK {
-            return Err(anyhow::anyhow!("status code {}: {}", status_code, self.url,))
-                .with_context(|| format!("{}", self.url));
+            return Err(anyhow::anyhow!("status code {}: {}", status_code, url))
+                .with_context(|| format!("{url}"));
         }
 
-        match self.response.as_mut().unwrap().chunk().await {
-            Ok(Some(r)) => {
-                let r = r.to_vec();
-                Ok(Some(r))
+        // the small chunks arriving from the network are collected so the writer is
+        // locked and called less often
+        let mut buffer = vec![];
+        loop {
+            match response.chunk().await {
+                Ok(Some(r)) => {
+                    buffer.extend_from_slice(&r);
+                    if buffer.len() >= chunk_size {
+                        return Ok(Some(buffer));
+                    }
+                }
+                Ok(None) if buffer.is_empty() => return Ok(None),
+                Ok(None) => return Ok(Some(buffer)),
+                Err(e) => return Err(anyhow::anyhow!(e)).with_context(|| format!("{url}")),
             }
-            Ok(None) => Ok(None),
-            Err(e) => Err(anyhow::anyhow!(e)).with_context(|| format!("{}", self.url)),
         }
     }
 
     /// download again to read request body from zero
     async fn reset(&mut self) -> anyhow::Result<()> {
-        if self.response.is_none() {
-            self.response = Some(reqwest::get(self.url.clone()).await?);
-        }
+        self.response().await?;
         Ok(())
     }
 
-    /// get the file length from file metadata
+    /// get the content length from the response's header section
     async fn len(&mut self) -> anyhow::Result<u64> {
-        let head = self.head_request().await?;
-        let header_content_len: String = head
+        let url = self.url.clone();
+        let response = self.response().await?;
+        let header_content_len: String = response
             .headers()
             .get(CONTENT_LENGTH)
             .with_context(|| {
-                debug!("Headers {}: {:#?}", self.url, head.headers());
+                debug!("Headers {}: {:#?}", url, response.headers());
                 format!(
                     "request to {} returned without header {}",
-                    self.url, CONTENT_LENGTH
+                    url, CONTENT_LENGTH
                 )
             })?
             .to_str()?
@@ -84,3 +279,129 @@ impl Input for UrlInput {
         Ok(content_length)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    /// Serves a single request, answers 304 if the request carries the ETag "v1" and
+    /// the list otherwise. Returns the url to request.
+    fn serve_once() -> Url {
+        serve(|request| {
+            if request.contains("if-none-match: \"v1\"") {
+                "HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\ncontent-length: 0\r\n\r\n"
+            } else {
+                "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: 8\r\n\r\nexample\n"
+            }
+        })
+    }
+
+    /// Serves a single request with the response returned for the lowercased request
+    ///
+    /// * `respond`: returns the raw response for the request
+    fn serve(respond: fn(&str) -> &'static str) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/list", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let len = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..len]).to_lowercase();
+            stream.write_all(respond(&request).as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_content_encoding() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/list", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let len = stream.read(&mut request).unwrap();
+            assert!(len > 0);
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(b"example\n").unwrap();
+            let body = encoder.finish().unwrap();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\ncontent-encoding: gzip\r\ncontent-length: {}\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+
+        let mut input = UrlInput::new(url, &BTreeMap::new(), RetryPolicy::default()).unwrap();
+        let mut body = vec![];
+        while let Some(chunk) = input.chunk().await.unwrap() {
+            body.extend(chunk);
+        }
+        assert_eq!(body, b"example\n");
+    }
+
+    #[tokio::test]
+    async fn test_conditional_request() {
+        let mut input =
+            UrlInput::new(serve_once(), &BTreeMap::new(), RetryPolicy::default()).unwrap();
+        assert_eq!(input.is_not_modified().await.unwrap(), None);
+        assert_eq!(input.len().await.unwrap(), 8);
+        assert_eq!(input.chunk().await.unwrap().unwrap(), b"example\n");
+        let validators = input.validators().unwrap();
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+
+        let mut input =
+            UrlInput::new(serve_once(), &BTreeMap::new(), RetryPolicy::default()).unwrap();
+        input.set_validators(validators);
+        assert_eq!(input.is_not_modified().await.unwrap(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_chunk_size() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/list", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let len = stream.read(&mut request).unwrap();
+            assert!(len > 0);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 16\r\n\r\n")
+                .unwrap();
+            // the body arrives in several network chunks
+            for part in ["exam", "ple\n", "exam", "ple\n"] {
+                stream.write_all(part.as_bytes()).unwrap();
+                stream.flush().unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let mut input = UrlInput::new(url, &BTreeMap::new(), RetryPolicy::default()).unwrap();
+        input.set_chunk_size(8);
+        assert_eq!(input.chunk().await.unwrap().unwrap(), b"example\n");
+        assert_eq!(input.chunk().await.unwrap().unwrap(), b"example\n");
+        assert_eq!(input.chunk().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let url = serve(|_| {
+            "HTTP/1.1 503 Service Unavailable\r\nretry-after: 120\r\ncontent-length: 0\r\n\r\n"
+        });
+        let retry = RetryPolicy {
+            attempts: 3,
+            delay: 0,
+        };
+        // the request isn't repeated since the server only serves a single request
+        let mut input = UrlInput::new(url, &BTreeMap::new(), retry).unwrap();
+        assert_eq!(input.retry_after().await.unwrap().as_deref(), Some("120"));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use flate2::{write::GzEncoder, Compression};
+
+/// AtomicFile writes to a temporary file next to the target path which only replaces
+/// the target file once it's committed. An incomplete write therefore retains the
+/// previous version of the file.
+#[derive(Debug)]
+pub struct AtomicFile {
+    file: Sink,
+    tmp_path: PathBuf,
+    path: PathBuf,
+}
+
+/// Sink is the temporary file written either directly or through a gzip encoder
+#[derive(Debug)]
+enum Sink {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl AtomicFile {
+    /// Creates the temporary file for the given target path
+    ///
+    /// * `path`: the path of the file to be replaced on commit
+    pub fn create(path: PathBuf) -> anyhow::Result<Self> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("invalid file name: {}", path.display()))?
+            .to_string_lossy();
+        let tmp_path = path.with_file_name(format!(".{file_name}.tmp"));
+        let file = File::create(&tmp_path)
+            .with_context(|| format!("could not write file {}", tmp_path.display()))?;
+        Ok(Self {
+            file: Sink::Plain(file),
+            tmp_path,
+            path,
+        })
+    }
+
+    /// Creates the temporary file for the given target path, everything written is
+    /// gzip compressed
+    ///
+    /// * `path`: the path of the file to be replaced on commit
+    pub fn create_gzip(path: PathBuf) -> anyhow::Result<Self> {
+        let mut file = Self::create(path)?;
+        if let Sink::Plain(f) = file.file {
+            file.file = Sink::Gzip(GzEncoder::new(f, Compression::best()));
+        }
+        Ok(file)
+    }
+
+    /// the path of the target file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// sets the permissions the target file will have
+    ///
+    /// * `permissions`: the permissions
+    #[cfg(feature = "self-update")]
+    pub fn set_permissions(&self, permissions: fs::Permissions) -> std::io::Result<()> {
+        match &self.file {
+            Sink::Plain(f) => f.set_permissions(permissions),
+            Sink::Gzip(e) => e.get_ref().set_permissions(permissions),
+        }
+    }
+
+    /// flushes the temporary file to disk and moves it to the target path
+    pub fn commit(&mut self) -> anyhow::Result<()> {
+        let file = match &mut self.file {
+            Sink::Plain(f) => {
+                f.flush()?;
+                f
+            }
+            // writes the rest of the compressed data and the gzip trailer
+            Sink::Gzip(e) => {
+                e.try_finish()?;
+                e.get_mut()
+            }
+        };
+        file.sync_all()?;
+        fs::rename(&self.tmp_path, &self.path)
+            .with_context(|| format!("could not replace file {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// removes the temporary file leaving the target file untouched
+    pub fn discard(&mut self) {
+        if let Err(e) = fs::remove_file(&self.tmp_path) {
+            debug!("{}: {}", self.tmp_path.display(), e);
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.file {
+            Sink::Plain(f) => f.write(buf),
+            Sink::Gzip(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.file {
+            Sink::Plain(f) => f.flush(),
+            Sink::Gzip(e) => e.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_atomic_file() {
+        let dir = PathBuf::from(crate::tests::helper::cache_file_creator::TEST_CACHE)
+            .join("test_atomic_file");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("list");
+        fs::write(&path, "previous\n").unwrap();
+
+        // a discarded write keeps the previous file
+        let mut file = AtomicFile::create(path.clone()).unwrap();
+        file.write_all(b"incomplete\n").unwrap();
+        file.discard();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous\n");
+
+        // a committed write replaces it
+        let mut file = AtomicFile::create(path.clone()).unwrap();
+        file.write_all(b"current\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous\n");
+        file.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "current\n");
+
+        let path = dir.join("list.gz");
+        let mut file = AtomicFile::create_gzip(path.clone()).unwrap();
+        file.write_all(b"compressed\n").unwrap();
+        file.commit().unwrap();
+        let mut contents = String::new();
+        flate2::read::GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "compressed\n");
+    }
+}
@@ -1,16 +1,30 @@
 use std::{
+    collections::HashSet,
     fs::{self, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
 use anyhow::Context;
 use futures::lock::Mutex;
 
-use crate::input::{file::FileInput, Input};
+use crate::{
+    input::{
+        file::{FileInput, DEFAULT_MAX_LINE_LENGTH},
+        Input,
+    },
+    output::OutputType,
+};
+
+use super::{
+    atomic_file::AtomicFile,
+    filter_list_io::FilterListIO,
+    storage::{ListReader, ListWriter},
+};
 
-use super::filter_list_io::FilterListIO;
+/// the output files of a category and the formats they're written in
+pub type FormatFiles = Vec<(OutputType, Arc<Mutex<AtomicFile>>)>;
 
 /// CategoryListIO contains a reader and a writer used to manipulate category wise
 /// assembled filter lists
@@ -20,6 +34,11 @@ pub struct CategoryListIO<R: Input + Send, W: Write + Send> {
     pub included_filter_lists: Vec<FilterListIO<R, W>>,
     pub reader: Option<Arc<Mutex<R>>>,
     pub writer: Option<Arc<Mutex<W>>>,
+    /// the writers of the output stage, one per output format and directory
+    pub format_writers: FormatFiles,
+    /// the entries of the category written on the last run, only read for the run report
+    /// and the audit log
+    pub previous_entries: Option<HashSet<String>>,
 }
 
 impl<R: Input + Send, W: Write + Send> CategoryListIO<R, W> {
@@ -32,46 +51,29 @@ impl<R: Input + Send, W: Write + Send> CategoryListIO<R, W> {
             included_filter_lists: vec![],
             reader: None,
             writer: None,
+            format_writers: vec![],
+            previous_entries: None,
         }
     }
 }
 
-impl<W: Write + Send> CategoryListIO<FileInput, W> {
+impl<R: ListReader, W: Write + Send> CategoryListIO<R, W> {
     /// Attaches a potentially existing input file to the reader attribute for inspection
     ///
     /// * `base_dir`: the base directory where the input file is being tried to read
     pub fn attach_existing_input_file(&mut self, base_dir: &Path) -> anyhow::Result<()> {
-        let mut contents =
-            fs::read_dir(base_dir).with_context(|| "input file directory does not exist")?;
-        let entry = contents
-            .find(|it| {
-                if let Ok(it) = it {
-                    return it.file_name().to_str().unwrap() == self.name;
-                }
-                false
-            })
-            .ok_or_else(|| anyhow::anyhow!("file not found: {}", self.name))??;
-        self.reader = Some(Arc::new(Mutex::new(FileInput::new(entry.path(), None))));
+        let input = R::open(&base_dir.join(&self.name), None, DEFAULT_MAX_LINE_LENGTH)?;
+        self.reader = Some(Arc::new(Mutex::new(input)));
         Ok(())
     }
 }
 
-impl CategoryListIO<FileInput, File> {
+impl<R: Input + Send, W: ListWriter> CategoryListIO<R, W> {
     /// Tries to read the potential output file for inspection
     ///
     /// * `base_dir`: the base directory where the output file is being tried to read
     pub fn attach_existing_file_writer(&mut self, base_dir: &Path) -> anyhow::Result<()> {
-        let out_path = base_dir;
-        let mut out_path = out_path.to_path_buf();
-        out_path.push(&self.name);
-        if !out_path.exists() {
-            return Err(anyhow::anyhow!(
-                "File {} not found",
-                out_path.as_os_str().to_str().unwrap()
-            ));
-        }
-        let out_file =
-            File::open(out_path).with_context(|| "could not open out file for reading")?;
+        let out_file = W::open(&base_dir.join(&self.name))?;
         self.writer = Some(Arc::new(Mutex::new(out_file)));
         Ok(())
     }
@@ -81,11 +83,43 @@ impl CategoryListIO<FileInput, File> {
     ///
     /// * `base_dir`: the base directory where the output file is being created
     pub fn attach_new_file_writer(&mut self, base_dir: &Path) -> anyhow::Result<()> {
-        let mut out_path = base_dir.to_path_buf();
-        fs::create_dir_all(&out_path).with_context(|| "could not create out directory")?;
-        out_path.push(&self.name);
-        let out_file = File::create(out_path).with_context(|| "could not write out file")?;
+        let out_file = W::create(&base_dir.join(&self.name))?;
         self.writer = Some(Arc::new(Mutex::new(out_file)));
         Ok(())
     }
 }
+
+impl CategoryListIO<FileInput, File> {
+    /// Creates an output file for every output format in the format's output directory.
+    /// A directory which is not writable is skipped so the remaining directories are
+    /// still updated. Returns the files of the skipped directories.
+    ///
+    /// * `output_files`: the output formats and the files they are written to
+    /// * `gzip`: compress the written files
+    pub fn attach_new_format_writers(
+        &mut self,
+        output_files: &[(OutputType, PathBuf)],
+        gzip: bool,
+    ) -> Vec<PathBuf> {
+        let mut skipped = vec![];
+        for (format, path) in output_files {
+            let base_dir = path.parent().unwrap_or(Path::new("."));
+            let writer = fs::create_dir_all(base_dir)
+                .with_context(|| format!("could not create out directory {}", base_dir.display()))
+                .and_then(|_| match gzip {
+                    true => AtomicFile::create_gzip(path.clone()),
+                    false => AtomicFile::create(path.clone()),
+                });
+            match writer {
+                Ok(w) => self
+                    .format_writers
+                    .push((format.clone(), Arc::new(Mutex::new(w)))),
+                Err(e) => {
+                    error!("{}: {:?}", self.name, e);
+                    skipped.push(path.clone());
+                }
+            }
+        }
+        skipped
+    }
+}
@@ -1,19 +1,21 @@
-use std::{fs, io::Write, path::Path, sync::Arc};
+use std::{fs::File, io::Write, path::Path, sync::Arc};
 
 use anyhow::Context;
 use futures::lock::Mutex;
-use reqwest::Url;
-use std::fs::File;
 
 use crate::{
+    dns::DnsConfig,
     filter_list::FilterList,
     input::{
-        file::{Compression, FileInput},
-        url::UrlInput,
+        file::{Compression, DEFAULT_MAX_LINE_LENGTH},
+        source::{create_source, SourceInput, SourceOptions},
         Input,
     },
+    manifest::HttpRecord,
 };
 
+use super::storage::{ListReader, ListWriter};
+
 /// FilterListIO is a wrapper type for FilterList objects which additionally
 /// contains input sources and output writers. The wrapper is necessary to
 /// keep the FilterList itself serializable.
@@ -44,73 +46,102 @@ impl<R: Input + Send, W: Write + Send> FilterListIO<R, W> {
     }
 }
 
-impl<W: Write + Send> FilterListIO<UrlInput, W> {
-    /// configures input to read from HTTP response
-    pub fn attach_url_reader(&mut self) -> anyhow::Result<()> {
-        let url = Url::parse(&self.filter_list.source)
+impl<W: Write + Send> FilterListIO<SourceInput, W> {
+    /// configures input to read from the source registered for the list's kind of source
+    ///
+    /// * `validators`: the previous download's validators making the request conditional
+    /// * `chunk_size`: the number of bytes collected before they are written, the
+    ///   default if None
+    /// * `dns`: the DNS settings the host is resolved with, the system's if None
+    pub fn attach_source_reader(
+        &mut self,
+        validators: Option<HttpRecord>,
+        chunk_size: Option<usize>,
+        dns: Option<DnsConfig>,
+    ) -> anyhow::Result<()> {
+        let options = SourceOptions {
+            validators,
+            chunk_size,
+            dns,
+        };
+        let input = create_source(&self.filter_list, options)
             .with_context(|| format!("config file error: {:?}", &self.filter_list))?;
-        let input = UrlInput::new(url);
         self.reader = Some(Arc::new(Mutex::new(input)));
         Ok(())
     }
+
+    /// returns the Retry-After header if the server deferred the download
+    pub async fn retry_after(&self) -> Option<String> {
+        let mut reader = self.reader.as_ref()?.lock().await;
+        match reader.retry_after().await {
+            Ok(retry_after) => retry_after,
+            Err(e) => {
+                debug!("{}: {}", self.filter_list.id, e);
+                None
+            }
+        }
+    }
+
+    /// returns the validators of the response the list is downloaded from
+    pub async fn validators(&self) -> Option<HttpRecord> {
+        self.reader.as_ref()?.lock().await.validators()
+    }
 }
 
-impl<W: Write + Send> FilterListIO<FileInput, W> {
-    /// Searches the file system in the given base directory for a file named after the list id. If the
-    /// file was found it's being opened for reading and the reader is attached to the FilterListIO or
-    /// otherwise returns an error.
+impl FilterListIO<SourceInput, File> {
+    /// Decides whether the list changed since the last download. The server answers
+    /// conditional requests itself, otherwise the content length is compared to the
+    /// size of the former download.
+    pub async fn is_unmodified(&mut self) -> anyhow::Result<bool> {
+        let not_modified = match self.reader.as_ref() {
+            Some(reader) => reader.lock().await.is_not_modified().await,
+            None => Ok(None),
+        };
+        match not_modified {
+            Ok(Some(not_modified)) => Ok(not_modified),
+            Ok(None) => self.is_cached().await,
+            Err(e) => {
+                warn!("{}", e);
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl<R: ListReader, W: Write + Send> FilterListIO<R, W> {
+    /// Looks for the list named after the list id in the given base directory. If the
+    /// list was found it's opened for reading and the reader is attached to the
+    /// FilterListIO or otherwise returns an error. Empty lists get no reader.
     ///
-    /// * `base_dir`: the file system path to be searched
+    /// * `base_dir`: the path to be searched
     /// * `compression`: file compression method to be expected
     pub fn attach_existing_input_file(
         &mut self,
         base_dir: &Path,
         compression: Option<Compression>,
     ) -> anyhow::Result<()> {
-        let mut contents =
-            fs::read_dir(base_dir).with_context(|| "input file directory does not exist")?;
-        let entry = contents
-            .find(|it| {
-                if let Ok(it) = it {
-                    return it.file_name().to_str().unwrap() == self.filter_list.id;
-                }
-                false
-            })
-            .ok_or_else(|| anyhow::anyhow!("file not found: {}", self.filter_list.id))??;
-        let path = entry.path();
-        let file_name = path.as_os_str().to_str().unwrap();
-        match entry.metadata() {
-            Ok(meta) => {
-                if meta.len() == 0 {
-                    debug!("File {} has zero length", file_name);
-                    return Ok(());
-                };
-            }
-            Err(_) => {
-                debug!("File {} has no length", file_name);
-                return Ok(());
-            }
-        };
-        self.reader = Some(Arc::new(Mutex::new(FileInput::new(
-            entry.path(),
-            compression,
-        ))));
+        let path = base_dir.join(&self.filter_list.id);
+        if R::stored_len(&path)? == 0 {
+            debug!("File {} has zero length", path.display());
+            return Ok(());
+        }
+        let max_line_length = self
+            .filter_list
+            .max_line_length
+            .unwrap_or(DEFAULT_MAX_LINE_LENGTH);
+        let input = R::open(&path, compression, max_line_length)?;
+        self.reader = Some(Arc::new(Mutex::new(input)));
         Ok(())
     }
 }
 
-impl<R: Input + Send> FilterListIO<R, File> {
+impl<R: Input + Send, W: ListWriter> FilterListIO<R, W> {
     /// returns the writer's content length
     pub async fn writer_len(&self) -> anyhow::Result<u64> {
         if self.writer.is_none() {
             return Err(anyhow::anyhow!("writer attribute is None"));
         }
-        let file = self.writer.as_ref().unwrap().lock().await;
-        let file_meta = file
-            .metadata()
-            .with_context(|| format!("file {file:?} has no metadata"))?;
-        let file_len = file_meta.len();
-        Ok(file_len)
+        self.writer.as_ref().unwrap().lock().await.written_len()
     }
 
     /// is_cached compares the reader's length to the writer's length
@@ -141,11 +172,7 @@ impl<R: Input + Send> FilterListIO<R, File> {
     ///
     /// * `base_dir`: the base directory where the output file is tried to read
     pub fn attach_existing_file_writer(&mut self, base_dir: &Path) -> anyhow::Result<()> {
-        let out_path = base_dir;
-        let mut out_path = out_path.to_path_buf();
-        out_path.push(&self.filter_list.id);
-        let out_file =
-            File::open(out_path).with_context(|| "could not open out file for reading")?;
+        let out_file = W::open(&base_dir.join(&self.filter_list.id))?;
         self.writer = Some(Arc::new(Mutex::new(out_file)));
         Ok(())
     }
@@ -155,10 +182,7 @@ impl<R: Input + Send> FilterListIO<R, File> {
     ///
     /// * `base_dir`: the base directory where the output file is being created
     pub fn attach_new_file_writer(&mut self, base_dir: &Path) -> anyhow::Result<()> {
-        let mut out_path = base_dir.to_path_buf();
-        fs::create_dir_all(&out_path).with_context(|| "could not create out directory")?;
-        out_path.push(&self.filter_list.id);
-        let out_file = File::create(out_path).with_context(|| "could not write out file")?;
+        let out_file = W::create(&base_dir.join(&self.filter_list.id))?;
         self.writer = Some(Arc::new(Mutex::new(out_file)));
         Ok(())
     }
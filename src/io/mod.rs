@@ -1,2 +1,76 @@
+pub mod atomic_file;
 pub mod category_list_io;
 pub mod filter_list_io;
+pub mod storage;
+
+use std::fmt::Display;
+
+/// IoFailure classifies write errors. Full disks and missing permissions can't be
+/// solved by continuing to write, so the affected list or category is aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IoFailure {
+    DiskFull,
+    PermissionDenied,
+    Other,
+}
+
+impl IoFailure {
+    /// determines the class of an I/O error
+    ///
+    /// * `error`: the error returned by a write operation
+    pub fn classify(error: &std::io::Error) -> Self {
+        match error.raw_os_error() {
+            Some(libc::ENOSPC) | Some(libc::EDQUOT) => return IoFailure::DiskFull,
+            Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => {
+                return IoFailure::PermissionDenied
+            }
+            _ => {}
+        }
+        match error.kind() {
+            std::io::ErrorKind::PermissionDenied => IoFailure::PermissionDenied,
+            _ => IoFailure::Other,
+        }
+    }
+
+    /// determines the class of the I/O error causing an error, if there is one
+    ///
+    /// * `error`: an error possibly caused by an I/O error
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<std::io::Error>())
+            .map(Self::classify)
+    }
+
+    /// returns true if writing has to be stopped
+    pub fn is_fatal(&self) -> bool {
+        *self != IoFailure::Other
+    }
+}
+
+impl Display for IoFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoFailure::DiskFull => write!(f, "disk full"),
+            IoFailure::PermissionDenied => write!(f, "permission denied"),
+            IoFailure::Other => write!(f, "I/O error"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_io_failure() {
+        let full = std::io::Error::from_raw_os_error(libc::ENOSPC);
+        assert_eq!(IoFailure::classify(&full), IoFailure::DiskFull);
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(IoFailure::classify(&denied), IoFailure::PermissionDenied);
+        let other = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert!(!IoFailure::classify(&other).is_fatal());
+        let wrapped = anyhow::Error::from(full).context("could not replace file");
+        assert_eq!(IoFailure::of(&wrapped), Some(IoFailure::DiskFull));
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    input::{
+        file::{Compression, FileInput},
+        Input,
+    },
+    manifest::count_lines,
+};
+
+/// ListReader opens the intermediate lists a stage reads. The lists are addressed by
+/// their path below the cache directory whether or not they are kept on the file
+/// system.
+pub trait ListReader: Input + Send + Sized + 'static {
+    /// Opens the list stored at the path. Fails if there is no such list.
+    ///
+    /// * `path`: the list's path
+    /// * `compression`: the compression of the stored list
+    /// * `max_line_length`: lines longer than this number of bytes are skipped
+    fn open(
+        path: &Path,
+        compression: Option<Compression>,
+        max_line_length: usize,
+    ) -> anyhow::Result<Self>;
+
+    /// returns the number of bytes stored for the list or fails if there is no such list
+    ///
+    /// * `path`: the list's path
+    fn stored_len(path: &Path) -> anyhow::Result<u64>;
+}
+
+/// ListWriter stores the intermediate lists a stage writes, addressed like the ones
+/// read by [`ListReader`]
+pub trait ListWriter: Write + Send + Sized + 'static {
+    /// creates the list at the path replacing a former version
+    ///
+    /// * `path`: the list's path
+    fn create(path: &Path) -> anyhow::Result<Self>;
+
+    /// opens the list written on an earlier run for inspection
+    ///
+    /// * `path`: the list's path
+    fn open(path: &Path) -> anyhow::Result<Self>;
+
+    /// returns the number of bytes of the list
+    fn written_len(&self) -> anyhow::Result<u64>;
+
+    /// returns the content of the list stored at the path
+    ///
+    /// * `path`: the list's path
+    fn read(path: &Path) -> anyhow::Result<String>;
+
+    /// returns the number of lines of the list stored at the path
+    ///
+    /// * `path`: the list's path
+    fn count_lines(path: &Path) -> anyhow::Result<usize>;
+
+    /// removes the list stored at the path if there is one
+    ///
+    /// * `path`: the list's path
+    fn remove(path: &Path);
+}
+
+impl ListReader for FileInput {
+    fn open(
+        path: &Path,
+        compression: Option<Compression>,
+        max_line_length: usize,
+    ) -> anyhow::Result<Self> {
+        Self::stored_len(path)?;
+        Ok(FileInput::new(path.to_path_buf(), compression).with_max_line_length(max_line_length))
+    }
+
+    fn stored_len(path: &Path) -> anyhow::Result<u64> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        if !dir.is_dir() {
+            return Err(anyhow::anyhow!("input file directory does not exist"));
+        }
+        let meta = fs::metadata(path).with_context(|| {
+            format!(
+                "file not found: {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            )
+        })?;
+        Ok(meta.len())
+    }
+}
+
+impl ListWriter for File {
+    fn create(path: &Path) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| "could not create out directory")?;
+        }
+        // the file may be a hard link to a blob shared with other lists which must not
+        // be truncated
+        fs::remove_file(path).ok();
+        File::create(path).with_context(|| "could not write out file")
+    }
+
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        File::open(path).with_context(|| "could not open out file for reading")
+    }
+
+    fn written_len(&self) -> anyhow::Result<u64> {
+        let meta = self
+            .metadata()
+            .with_context(|| format!("file {self:?} has no metadata"))?;
+        Ok(meta.len())
+    }
+
+    fn read(path: &Path) -> anyhow::Result<String> {
+        fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))
+    }
+
+    fn count_lines(path: &Path) -> anyhow::Result<usize> {
+        count_lines(path)
+    }
+
+    fn remove(path: &Path) {
+        fs::remove_file(path).ok();
+    }
+}
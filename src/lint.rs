@@ -0,0 +1,233 @@
+use std::{collections::HashMap, fmt::Display};
+
+use regex::RegexBuilder;
+
+use crate::{
+    config::{Config, ALL_CATEGORY},
+    dns,
+    filter_list::FilterList,
+    input::source::{is_registered, source_kind},
+    output::OutputType,
+};
+
+/// Severity distinguishes configuration errors from best practice suggestions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Finding is a single problem detected in the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// the id of the list the finding refers to
+    pub list: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(list: &FilterList, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            list: Some(list.id.clone()),
+            message,
+        }
+    }
+
+    fn warning(list: &FilterList, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            list: Some(list.id.clone()),
+            message,
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.list {
+            Some(id) => write!(f, "{severity}: {id}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// Checks the configuration for errors which would make a run fail or lose lists
+///
+/// * `config`: the configuration to be checked
+pub fn validate(config: &Config) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut ids: HashMap<&String, usize> = HashMap::new();
+    for list in config.lists.iter() {
+        *ids.entry(&list.id).or_default() += 1;
+        match source_kind(list) {
+            Ok(kind) if !is_registered(&kind) => {
+                findings.push(Finding::error(list, format!("unknown source type {kind}")));
+            }
+            Ok(_) => {}
+            Err(e) => findings.push(Finding::error(list, format!("{e:#}"))),
+        }
+        if config.all_category && list.tags.iter().any(|t| t == ALL_CATEGORY) {
+            findings.push(Finding::error(
+                list,
+                format!("tag {ALL_CATEGORY} is reserved for the built-in category"),
+            ));
+        }
+        let servers = config.dns(list).map(|d| d.servers).unwrap_or_default();
+        for e in servers.iter().filter_map(|s| dns::server_addr(s).err()) {
+            findings.push(Finding::error(list, format!("invalid DNS server: {e}")));
+        }
+        let regex = RegexBuilder::new(&list.regex)
+            .size_limit(config.regex_options.size_limit)
+            .dfa_size_limit(config.regex_options.dfa_size_limit)
+            .build();
+        match regex {
+            Ok(re) if re.captures_len() < 2 => findings.push(Finding::error(
+                list,
+                "regex has no capture group for the entry".to_string(),
+            )),
+            Ok(_) => {}
+            Err(e) => findings.push(Finding::error(list, format!("invalid regex: {e}"))),
+        }
+    }
+    for (id, _) in ids.into_iter().filter(|(_, count)| *count > 1) {
+        findings.push(Finding {
+            severity: Severity::Error,
+            list: Some(id.clone()),
+            message: "id is used by several lists".to_string(),
+        });
+    }
+    if config.output_dir.is_empty() || config.output_format.is_empty() {
+        findings.push(Finding {
+            severity: Severity::Error,
+            list: None,
+            message: "at least one output directory and format is required".to_string(),
+        });
+    }
+    // the formats whose settings may keep their adapters from being created
+    for format in [OutputType::Template, OutputType::Bloom] {
+        if config.output_formats().contains(&format)
+            && let Err(e) = format.get_adapter(config, "category")
+        {
+            findings.push(Finding {
+                severity: Severity::Error,
+                list: None,
+                message: format!("{e:#}"),
+            });
+        }
+    }
+    findings
+}
+
+/// Applies heuristic rules to the configuration and returns suggestions which
+/// don't keep harvester from running but likely lead to unexpected results
+///
+/// * `config`: the configuration to be checked
+pub fn lint(config: &Config) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut sources: HashMap<&String, &String> = HashMap::new();
+    for list in config.lists.iter() {
+        if list.tags.is_empty() {
+            findings.push(Finding::warning(
+                list,
+                "list has no tags and never reaches any output".to_string(),
+            ));
+        }
+        if list.ct_log.is_none() && list.source.ends_with(".gz") && list.compression.is_none() {
+            findings.push(Finding::warning(
+                list,
+                "source looks gzip compressed but no compression is configured".to_string(),
+            ));
+        }
+        if let Some(other) = sources.insert(&list.source, &list.id) {
+            findings.push(Finding::warning(
+                list,
+                format!("source is configured twice, see list {other}"),
+            ));
+        }
+        if !list.regex.starts_with('^') && !list.regex.ends_with('$') {
+            findings.push(Finding::warning(
+                list,
+                "regex is not anchored and may match in comments or the middle of lines"
+                    .to_string(),
+            ));
+        }
+        if list.insecure_tls {
+            findings.push(Finding::warning(
+                list,
+                "TLS certificate verification is disabled".to_string(),
+            ));
+        }
+        if list.regex.contains("(.*)") || list.regex.contains("(.+)") {
+            findings.push(Finding::warning(
+                list,
+                "regex captures anything, consider a more specific pattern like ([^\\s#]+)"
+                    .to_string(),
+            ));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::helper::cache_file_creator::CacheFileCreator;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_and_lint() {
+        let cache = CacheFileCreator::new("test_validate_and_lint", "in", "out");
+        let mut config = cache.new_test_config();
+        config.lists = vec![
+            FilterList {
+                id: "good".to_string(),
+                source: "https://lists.example/good.txt".to_string(),
+                tags: vec!["malware".to_string()],
+                regex: r"^0\.0\.0\.0 ([^\s#]+)".to_string(),
+                ..Default::default()
+            },
+            FilterList {
+                id: "bad".to_string(),
+                source: "https://lists.example/good.txt".to_string(),
+                regex: "(".to_string(),
+                ..Default::default()
+            },
+            FilterList {
+                id: "sloppy".to_string(),
+                source: "https://lists.example/sloppy.gz".to_string(),
+                tags: vec!["malware".to_string()],
+                regex: "(.*)".to_string(),
+                insecure_tls: true,
+                ..Default::default()
+            },
+        ];
+
+        let errors: Vec<String> = validate(&config).iter().map(|f| f.to_string()).collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("error: bad: invalid regex"));
+
+        let warnings: Vec<String> = lint(&config).iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "warning: bad: list has no tags and never reaches any output",
+                "warning: bad: source is configured twice, see list good",
+                "warning: bad: regex is not anchored and may match in comments or the middle of lines",
+                "warning: sloppy: source looks gzip compressed but no compression is configured",
+                "warning: sloppy: regex is not anchored and may match in comments or the middle of lines",
+                "warning: sloppy: TLS certificate verification is disabled",
+                "warning: sloppy: regex captures anything, consider a more specific pattern like ([^\\s#]+)",
+            ]
+        );
+
+        config.lists[0].source_type = Some("git".to_string());
+        let errors: Vec<String> = validate(&config).iter().map(|f| f.to_string()).collect();
+        assert_eq!(errors[0], "error: good: unknown source type git");
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    fmt::Arguments,
+    sync::{Mutex, OnceLock},
+};
+
+use log::Level;
+
+/// the number of messages of a kind logged per source before they are only counted
+pub const LOGGED_PER_KIND: usize = 5;
+
+/// the level and number of the messages per source and kind
+type Messages = HashMap<(String, &'static str), (Level, usize)>;
+
+static MESSAGES: OnceLock<Mutex<Messages>> = OnceLock::new();
+
+/// Counts a message of a kind and returns true if it is to be logged, i.e. fewer than
+/// [`LOGGED_PER_KIND`] messages of the kind were logged for the source before
+///
+/// * `level`: the level the message is logged at
+/// * `source`: the list or log the message is about
+/// * `kind`: the kind of the message, e.g. `invalid entry`
+fn admit(level: Level, source: &str, kind: &'static str) -> bool {
+    let mut messages = MESSAGES.get_or_init(Default::default).lock().unwrap();
+    let counted = messages
+        .entry((source.to_string(), kind))
+        .or_insert((level, 0));
+    counted.1 += 1;
+    counted.1 <= LOGGED_PER_KIND
+}
+
+/// Logs a message repeated per line of a source, e.g. about an invalid line. Only the
+/// first messages of a kind are logged per source, the rest is summed up by
+/// [`summarize`].
+///
+/// * `level`: the level the message is logged at
+/// * `source`: the list or log the message is about
+/// * `kind`: the kind of the message, e.g. `invalid entry`
+/// * `message`: the message
+pub fn log(level: Level, source: &str, kind: &'static str, message: Arguments) {
+    if admit(level, source, kind) {
+        log!(level, "{}", message);
+    }
+}
+
+/// Returns the number of messages of every kind which weren't logged for a source and
+/// forgets the source's messages
+///
+/// * `source`: the list or log the messages were about
+pub fn take_suppressed(source: &str) -> Vec<(&'static str, Level, usize)> {
+    let mut messages = MESSAGES.get_or_init(Default::default).lock().unwrap();
+    let keys: Vec<(String, &'static str)> = messages
+        .keys()
+        .filter(|(s, _)| s == source)
+        .cloned()
+        .collect();
+    let mut suppressed: Vec<(&'static str, Level, usize)> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (level, count) = messages.remove(&key)?;
+            (count > LOGGED_PER_KIND).then(|| (key.1, level, count - LOGGED_PER_KIND))
+        })
+        .collect();
+    suppressed.sort();
+    suppressed
+}
+
+/// logs how many messages of every kind weren't logged for a source
+///
+/// * `source`: the list or log the messages were about
+pub fn summarize(source: &str) {
+    for (kind, level, count) in take_suppressed(source) {
+        log!(
+            level,
+            "{}: {} more {} messages suppressed",
+            source,
+            count,
+            kind
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        for line in 0..LOGGED_PER_KIND + 3 {
+            log(
+                Level::Debug,
+                "test_throttle",
+                "invalid entry",
+                format_args!("line {line}"),
+            );
+        }
+        log(
+            Level::Warn,
+            "test_throttle",
+            "invalid leaf",
+            format_args!("leaf"),
+        );
+        assert!(!admit(Level::Debug, "test_throttle", "invalid entry"));
+        assert!(admit(Level::Debug, "another source", "invalid entry"));
+        assert_eq!(
+            take_suppressed("test_throttle"),
+            vec![("invalid entry", Level::Debug, 4)]
+        );
+        // the counts start over
+        assert!(take_suppressed("test_throttle").is_empty());
+        assert!(admit(Level::Debug, "test_throttle", "invalid entry"));
+        take_suppressed("test_throttle");
+        take_suppressed("another source");
+    }
+}
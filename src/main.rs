@@ -1,28 +1,70 @@
 #![feature(let_chains)]
+mod archive;
+mod audit;
+mod cache;
+mod cache_backend;
 mod config;
+mod crowdsec;
+mod dns;
+mod entry;
+mod error_code;
 mod filter_controller;
 mod filter_list;
+mod geoip;
 mod input;
 mod io;
+mod lint;
 mod log_level;
+mod log_throttle;
+mod manifest;
+mod messages;
+mod migration;
+mod normalize;
+mod observer;
 mod output;
+mod pipeline;
+mod rdap;
+mod report;
+mod review;
+mod run_state;
+mod sandbox;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod shutdown;
+mod sqlite;
 mod stages;
+mod stats;
+mod syslog;
+mod telemetry;
 mod tests;
+mod theme;
+mod transform;
+mod tuning;
+mod verify;
 
 use std::{
-    path::Path,
+    io::Write,
+    net::IpAddr,
+    path::{Path, PathBuf},
     process::exit,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    sync::{Arc, Mutex},
 };
 
-use clap::Parser;
-use colored::*;
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
 use env_logger::Env;
-use filter_controller::FilterController;
+use error_code::ErrorCode;
 use log_level::LogLevel;
+use manifest::Manifest;
+use messages::Message;
+use migration::BlocklistManager;
+use observer::LogObserver;
+use output::bloom::BloomFilter;
+use pipeline::{CancellationToken, Pipeline};
+use report::RunReport;
+use sandbox::SandboxMode;
+use shutdown::{Shutdown, ShutdownPolicy};
+use theme::ColorChoice;
+use tuning::Tuning;
 
 use crate::config::Config;
 
@@ -30,8 +72,14 @@ use crate::config::Config;
 pub const DOWNLOAD_PATH: &str = "download";
 /// Sub path for extracted lists
 pub const EXTRACT_PATH: &str = "extract";
+/// Sub path for lists piped through their transform command
+pub const TRANSFORM_PATH: &str = "transform";
 /// Sub path for the assembled categorized lists
 pub const CATEGORIZE_PATH: &str = "categorize";
+/// Sub path for the lists the entries of each category were taken from
+pub const SOURCES_PATH: &str = "sources";
+/// Sub path for the previous versions of the categories awaiting review
+pub const REVIEW_PATH: &str = "review";
 
 #[macro_use]
 extern crate log;
@@ -39,104 +87,673 @@ extern crate log;
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
+    /// the configuration file, required by all commands but self-update, import and lookup
     #[arg(short, long)]
-    config: String,
-    #[arg(value_enum, short, long, default_value = "warn")]
+    config: Option<String>,
+    #[arg(value_enum, short, long, default_value = "warn", global = true)]
     log_level: LogLevel,
+    /// color the terminal output, auto colors terminals unless NO_COLOR is set
+    #[arg(value_enum, long, default_value = "auto", global = true)]
+    color: ColorChoice,
+    /// don't download anything and process the lists found in the cache directory
+    #[arg(long)]
+    offline: bool,
+    /// continue an interrupted run, the lists it already downloaded are not requested again
+    #[arg(long)]
+    resume: bool,
+    /// what an interrupted run completes before it exits, overrides the configured
+    /// policy, a second Ctrl-C always stops immediately
+    #[arg(value_enum, long)]
+    shutdown: Option<ShutdownPolicy>,
+    /// ask for confirmation before the changes of a category are written to the outputs
+    #[arg(long)]
+    review: bool,
+    /// approve changes of fewer added and removed entries without asking
+    #[arg(long, requires = "review")]
+    auto_approve_below: Option<usize>,
+    /// restrict file system access to the configured directories and network access
+    /// to HTTPS while processing lists, strict fails where the kernel doesn't support it
+    #[arg(value_enum, long, default_value = "off")]
+    sandbox: SandboxMode,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the configuration file without processing any lists
+    Validate {
+        /// additionally apply best practice rules
+        #[arg(long)]
+        lint: bool,
+    },
+    /// Check the integrity of the downloaded lists
+    Verify {
+        /// additionally trace a sample of every category's entries back to the lines
+        /// of the downloaded lists they were extracted from
+        #[arg(long)]
+        deep: bool,
+        /// the number of entries traced per category
+        #[arg(long, default_value_t = 20)]
+        sample: usize,
+    },
+    /// Print how the lists' regexes match the cached downloads
+    Stats {
+        /// print the details of this list only
+        #[arg(long)]
+        list: Option<String>,
+        /// the number of unmatched line patterns and captured entries printed
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Move the download cache between machines
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Replace this binary by the latest release after verifying its signature
+    SelfUpdate,
+    /// Print a configuration equivalent to the list subscriptions of another tool
+    Import {
+        /// the tool the configuration belongs to
+        #[arg(value_enum, long)]
+        from: BlocklistManager,
+        /// file system path or url of the tool's configuration
+        source: String,
+    },
+    /// Print the list subscriptions of another tool for the served output files
+    ExportConfig {
+        /// the tool subscribing to the output files
+        #[arg(value_enum, long)]
+        to: BlocklistManager,
+        /// the url the output directory is served at
+        #[arg(long)]
+        base_url: String,
+    },
+    /// Look up domains or addresses in a filter written by the Bloom output format
+    Lookup {
+        /// the Bloom filter file
+        file: PathBuf,
+        /// the queried domains or addresses
+        #[arg(required = true)]
+        entries: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Bundle the downloaded lists and the manifest into a .tar.zst file
+    Export { file: PathBuf },
+    /// Unpack a bundle created by export into the cache directory
+    Import { file: PathBuf },
+    /// Check the downloaded lists against their content hashes
+    Verify,
+}
+
+fn main() -> anyhow::Result<()> {
     // setup command line interface
     let args = Args::parse();
 
+    // every log line names the run it was written by
+    let run_id = uuid::Uuid::new_v4().to_string();
+
     // initialize logging
     let env = Env::default()
         .filter_or("HV_LOG_LEVEL", &args.log_level)
-        .write_style_or("HV_LOG_STYLE", "auto");
+        .write_style_or("HV_LOG_STYLE", args.color.log_style());
+    args.color.apply();
 
     let mut builder = env_logger::Builder::from_env(env);
-    builder.format_timestamp(None).format_target(false).init();
+    let short_id = run_id[..8].to_string();
+    builder
+        .format(move |buf, record| {
+            writeln!(
+                buf,
+                "[{} {}] {}",
+                buf.default_styled_level(record.level()),
+                short_id,
+                record.args()
+            )
+        })
+        .init();
 
-    // is_processing determines if the program was interrupted or is still running
-    let is_processing = Arc::new(AtomicBool::new(true));
-    let is_proc = Arc::clone(&is_processing);
+    // the sandbox only restricts threads started after it was applied, it has to be in
+    // place before the runtime starts its workers
+    let processes_lists = matches!(
+        args.command,
+        None | Some(Command::Verify { .. }) | Some(Command::Stats { .. })
+    );
+    if args.sandbox == SandboxMode::Strict
+        && processes_lists
+        && let Some(config_path) = &args.config
+    {
+        let result = Config::load(Path::new(config_path))
+            .and_then(|c| sandbox::apply(&c, Path::new(config_path)));
+        if let Err(e) = result {
+            error!("could not apply the sandbox: {:?}", e);
+            exit(1);
+        }
+        info!("the sandbox is applied");
+    }
 
-    // handle ctrl_c
-    tokio::spawn(async move {
-        tokio::signal::ctrl_c().await.unwrap();
-        info!("{}", "gracefully shutting down ...".yellow());
-        is_proc.store(false, Ordering::SeqCst);
-    });
+    tokio::runtime::Runtime::new()?.block_on(run(args, run_id))
+}
+
+/// processes the lists or runs the given command
+///
+/// * `args`: the command line arguments
+/// * `run_id`: the unique id of the run
+async fn run(args: Args, run_id: String) -> anyhow::Result<()> {
+    match &args.command {
+        Some(Command::SelfUpdate) => self_update().await,
+        Some(Command::Import { from, source }) => import(*from, source).await,
+        Some(Command::Lookup { file, entries }) => lookup(file, entries),
+        _ => {}
+    }
 
     // crate configuration
-    let mut config = match Config::load(Path::new(&args.config)) {
+    let config_path = match &args.config {
+        Some(c) => c,
+        None => Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the argument '--config <CONFIG>' is required",
+            )
+            .exit(),
+    };
+    let mut config = match Config::load(Path::new(config_path)) {
         Err(e) => {
-            error!("{}: {:?}", &args.config, e);
+            error!("{}: {:?}", config_path, e);
             exit(1);
         }
         Ok(c) => c,
     };
 
-    // the lists are going through a process of four stages
-    let mut download_controller = FilterController::new(&config, is_processing.clone());
+    config.offline = args.offline;
+    config.resume = args.resume;
+    if let Some(policy) = args.shutdown {
+        config.shutdown = policy;
+    }
+    config.review = args.review;
+    config.tuning = Tuning::detect();
+    config.run_id = run_id;
+    config.palette.clone().apply();
 
-    // start the processing chain by downloading the filter lists
-    info!("{}", "Downalading lists ...".yellow());
-    let mut extract_controller = match download_controller.run(DOWNLOAD_PATH).await {
-        Ok(c) => c,
-        Err(e) => {
+    match &args.command {
+        Some(Command::Validate { lint }) => validate(&config, *lint),
+        Some(Command::Verify { deep, sample }) => {
+            let mut result = verify_cache(Path::new(&config.cache_dir));
+            if *deep {
+                result = verify_deep(&config, *sample).await.and(result);
+            }
+            if let Err(e) = result {
+                error!("{:?}", e);
+                exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Stats { list, top }) => {
+            if let Err(e) = print_stats(&config, list.as_deref(), *top).await {
+                error!("{:?}", e);
+                exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::ExportConfig { to, base_url }) => {
+            match migration::export_config(&config, *to, base_url) {
+                Ok(c) => print!("{c}"),
+                Err(e) => {
+                    error!("{:?}", e);
+                    exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Cache { action }) => {
+            let cache_dir = Path::new(&config.cache_dir);
+            let result = match action {
+                CacheAction::Export { file } => cache::export(cache_dir, file).await,
+                CacheAction::Import { file } => cache::import(cache_dir, file).await,
+                CacheAction::Verify => verify_cache(cache_dir),
+            };
+            if let Err(e) = result {
+                error!("{:?}", e);
+                exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::SelfUpdate)
+        | Some(Command::Import { .. })
+        | Some(Command::Lookup { .. })
+        | None => {}
+    }
+
+    // the token is cancelled if the program was interrupted
+    let cancellation = CancellationToken::default();
+
+    // handle ctrl_c, a repeated interruption stops the run immediately
+    let shutdown = Shutdown::new(config.shutdown, cancellation.clone());
+    let handler = shutdown.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::signal::ctrl_c().await.unwrap();
+            if handler.interrupt() {
+                info!("{}", theme::banner(Message::ShuttingDown.text()));
+                break;
+            }
+            info!("{}", theme::banner(Message::FinishingRun.text()));
+        }
+    });
+
+    // a stateless container starts with the cache kept by the backend on the last run
+    let cache_backend = match config.cache_backend.as_ref().map(cache_backend::open) {
+        Some(Ok(backend)) => Some(backend),
+        Some(Err(e)) => {
             error!("{:?}", e);
             exit(1);
         }
+        None => None,
     };
-
-    // the second stage extracts the URLs from the downloaded lists which come in heterogeneous formats
-    if is_processing.load(Ordering::SeqCst) {
-        info!("{}", "Extracting domains ...".yellow());
-    }
-    let mut categorize_controller = match extract_controller.run(DOWNLOAD_PATH, EXTRACT_PATH).await
+    if let Some(backend) = &cache_backend
+        && !Path::new(&config.cache_dir).join(DOWNLOAD_PATH).is_dir()
     {
-        Ok(c) => c,
+        match cache_backend::restore(backend.as_ref(), Path::new(&config.cache_dir)).await {
+            Ok(true) => info!("restored the cache from the cache backend"),
+            Ok(false) => debug!("the cache backend doesn't keep a cache yet"),
+            Err(e) => warn!(
+                "could not restore the cache from the cache backend: {:?}",
+                e
+            ),
+        }
+    }
+
+    // a cache written by an incompatible version is rebuilt before anything reads it
+    match cache::check_compatibility(Path::new(&config.cache_dir)) {
+        Ok(true) => config.cached_config = None,
+        Ok(false) => {}
         Err(e) => {
             error!("{:?}", e);
             exit(1);
         }
-    };
+    }
+
+    // the lists are going through a process of four stages
+    let mut pipeline = Pipeline::builder(&config)
+        .cancellation(cancellation.clone())
+        .network_cancellation(shutdown.network())
+        .observer(Arc::new(LogObserver))
+        .observer(Arc::new(shutdown.clone()))
+        .build();
+    let report = pipeline.report();
+
+    // start the processing chain by downloading the filter lists
+    info!("{}", theme::banner(Message::Downloading.text()));
+    if let Err(e) = pipeline.download().await {
+        abort(&config, &report, e);
+    }
+
+    // the second stage extracts the URLs from the downloaded lists which come in heterogeneous formats
+    if !cancellation.is_cancelled() {
+        info!("{}", theme::banner(Message::Extracting.text()));
+    }
+    if let Err(e) = pipeline.extract().await {
+        abort(&config, &report, e);
+    }
 
     // the third stage assembles the URLs into lists corresponding to the tags set in the configuration file
-    if is_processing.load(Ordering::SeqCst) {
-        info!("{}", "Categorizing domains ...".yellow());
+    if !cancellation.is_cancelled() {
+        info!("{}", theme::banner(Message::Categorizing.text()));
+    }
+    if let Err(e) = pipeline.categorize().await {
+        abort(&config, &report, e);
+    }
+
+    // rejected changes are discarded before anything is written
+    if args.review && !cancellation.is_cancelled() {
+        let stdin = std::io::stdin();
+        let approve = |name: &str, category: &report::CategoryReport| {
+            review::confirm(
+                name,
+                category,
+                args.auto_approve_below,
+                &mut stdin.lock(),
+                &mut std::io::stderr(),
+            )
+        };
+        match pipeline.review(approve) {
+            Ok(rejected) if !rejected.is_empty() => {
+                warn!("keeping the previous version of {}", rejected.join(", "))
+            }
+            Ok(_) => {}
+            Err(e) => abort(&config, &report, e),
+        }
     }
-    let mut output_controller = match categorize_controller
-        .run(EXTRACT_PATH, CATEGORIZE_PATH)
-        .await
+
+    // the fourth stage finally transforms the category lists into the desired output format
+    if !cancellation.is_cancelled() {
+        info!("{}", theme::banner(Message::CreatingOutput.text()));
+    }
+    if let Err(e) = pipeline.output().await {
+        abort(&config, &report, e);
+    }
+
+    if !cancellation.is_cancelled() {
+        write_sqlite(&config, &report);
+        write_blocky_fragment(&config, &report);
+    }
+
+    if let Err(e) = pipeline.save_manifest() {
+        error!("Error writing manifest to cache directory: {}", e);
+    }
+
+    if let Err(e) = config.save_to_cache() {
+        error!(
+            "Error writing last configuration file to cache directory: {}",
+            e
+        );
+    }
+
+    if let Some(backend) = &cache_backend
+        && let Err(e) = cache_backend::persist(backend.as_ref(), Path::new(&config.cache_dir)).await
     {
-        Ok(c) => c,
+        error!("Error handing the cache to the cache backend: {:?}", e);
+    }
+
+    if !config.offline && !shutdown.is_offline() {
+        push_crowdsec(&config, &report).await;
+        annotate_report(&config, &report).await;
+        telemetry::send(&config).await;
+    }
+    send_syslog(&config, &report);
+    write_report(&config, &report, None);
+
+    // running out of disk space or permissions needs attention, the affected files
+    // were kept in their previous version
+    let io_failures = report.lock().unwrap().io_failures();
+    if !io_failures.is_empty() {
+        for (failure, names) in io_failures {
+            error!("{}: kept previous version of {}", failure, names.join(", "));
+        }
+        exit(1);
+    }
+
+    Ok(())
+}
+
+/// replaces the binary by the latest release and exits
+#[cfg(feature = "self-update")]
+async fn self_update() -> ! {
+    match self_update::self_update().await {
+        Ok(Some(version)) => println!("updated harvester to version {version}"),
+        Ok(None) => println!("harvester {} is up to date", env!("CARGO_PKG_VERSION")),
         Err(e) => {
             error!("{:?}", e);
             exit(1);
         }
-    };
+    }
+    exit(0);
+}
 
-    // the fourth stage finally transforms the category lists into the desired output format
-    if is_processing.load(Ordering::SeqCst) {
-        info!("{}", "Creating output files ...".yellow());
+/// fails since the binary was built without the self-update feature
+#[cfg(not(feature = "self-update"))]
+async fn self_update() -> ! {
+    error!("harvester was built without the self-update feature");
+    exit(1);
+}
+
+/// prints the configuration generated from another tool's configuration and exits
+///
+/// * `format`: the tool the configuration belongs to
+/// * `source`: file system path or url of the configuration
+async fn import(format: BlocklistManager, source: &str) -> ! {
+    let config = match migration::read_source(source).await {
+        Ok(c) => migration::generate_config(format, &c),
+        Err(e) => Err(e),
+    };
+    match config.and_then(|c| Ok(serde_json::to_string_pretty(&c)?)) {
+        Ok(c) => println!("{c}"),
+        Err(e) => {
+            error!("{}: {:?}", source, e);
+            exit(1);
+        }
     }
-    match output_controller.run(CATEGORIZE_PATH).await {
-        Ok(c) => c,
+    exit(0);
+}
+
+/// prints whether the entries are in a Bloom filter and exits
+///
+/// * `file`: the Bloom filter file
+/// * `entries`: the queried domains or addresses
+fn lookup(file: &Path, entries: &[String]) -> ! {
+    let filter = match BloomFilter::read(file) {
+        Ok(f) => f,
         Err(e) => {
             error!("{:?}", e);
             exit(1);
         }
     };
+    debug!("{} holds {} entries", file.display(), filter.len());
+    for entry in entries {
+        // addresses are matched as they are, domains by their wildcards as well
+        let listed = match entry.parse::<IpAddr>() {
+            Ok(_) => filter.contains(entry),
+            Err(_) => filter.matches_domain(entry),
+        };
+        match listed {
+            true => println!("{entry}: probably listed"),
+            false => println!("{entry}: not listed"),
+        }
+    }
+    exit(0);
+}
 
-    if let Err(e) = config.save_to_cache() {
-        error!(
-            "Error writing last configuration file to cache directory: {}",
-            e
-        );
+/// prints the problems found in the configuration and exits, with an error code
+/// if the configuration is invalid
+///
+/// * `config`: the configuration to be checked
+/// * `with_lint`: also print the best practice suggestions
+fn validate(config: &Config, with_lint: bool) -> ! {
+    let mut findings = lint::validate(config);
+    if with_lint {
+        findings.extend(lint::lint(config));
+    }
+    for finding in findings.iter() {
+        println!("{finding}");
+    }
+    if findings.iter().any(|f| f.severity == lint::Severity::Error) {
+        exit(1);
     }
+    exit(0);
+}
+
+/// prints the problems found in the cache directory, fails if there are any
+///
+/// * `cache_dir`: the cache directory to be checked
+fn verify_cache(cache_dir: &Path) -> anyhow::Result<()> {
+    let manifest = Manifest::load(cache_dir)?;
+    let problems = cache::verify(cache_dir, &manifest)?;
+    for problem in problems.iter() {
+        println!("{problem}");
+    }
+    if !problems.is_empty() {
+        return Err(anyhow::anyhow!("{} problems found", problems.len()));
+    }
+    Ok(())
+}
+
+/// prints the match rates of the lists or the details of a single list
+///
+/// * `config`: the configuration the lists are defined in
+/// * `list`: the id of the single list
+/// * `top`: the number of unmatched line patterns and captured entries printed
+async fn print_stats(config: &Config, list: Option<&str>, top: usize) -> anyhow::Result<()> {
+    for result in stats::stats(config, list, top).await? {
+        match (result, list) {
+            (Ok(s), Some(_)) => print!("{s}"),
+            (Ok(s), None) => println!("{}", s.summary()),
+            (Err(e), Some(_)) => return Err(e),
+            (Err(e), None) => println!("{e:#}"),
+        }
+    }
+    Ok(())
+}
 
+/// prints the entries of the categories which can't be traced back to the downloaded
+/// lists, fails if there are any
+///
+/// * `config`: the configuration the categories are defined in
+/// * `sample`: the number of entries traced per category
+async fn verify_deep(config: &Config, sample: usize) -> anyhow::Result<()> {
+    let problems = verify::verify_deep(config, sample).await?;
+    for problem in problems.iter() {
+        println!("{problem}");
+    }
+    if !problems.is_empty() {
+        return Err(anyhow::anyhow!("{} entries not traced", problems.len()));
+    }
     Ok(())
 }
+
+/// reports the error a stage failed with and exits
+///
+/// * `config`: the configuration containing the report settings
+/// * `report`: the report collected during the run
+/// * `e`: the error which aborted the run
+fn abort(config: &Config, report: &Arc<Mutex<RunReport>>, e: anyhow::Error) -> ! {
+    error!("{} {:?}", ErrorCode::RunAborted, e);
+    write_report(config, report, Some(e));
+    exit(1);
+}
+
+/// pushes the addresses of the configured categories to CrowdSec
+///
+/// * `config`: the configuration containing the CrowdSec settings
+/// * `report`: the report collected during the run
+async fn push_crowdsec(config: &Config, report: &Arc<Mutex<RunReport>>) {
+    let crowdsec = match &config.crowdsec {
+        Some(c) => c,
+        None => return,
+    };
+    match crowdsec::push(crowdsec, Path::new(&config.cache_dir), &config.run_id).await {
+        Ok(pushed) => info!("pushed {} addresses to CrowdSec", pushed),
+        Err(e) => {
+            error!("{} {:?}", ErrorCode::PushFailed, e);
+            report
+                .lock()
+                .unwrap()
+                .failures
+                .push(format!("{} {:?}", ErrorCode::PushFailed, e));
+        }
+    }
+}
+
+/// sends a summary of every category to the log daemon if configured
+///
+/// * `config`: the configuration containing the log daemon's settings
+/// * `report`: the report collected during the run
+fn send_syslog(config: &Config, report: &Arc<Mutex<RunReport>>) {
+    let syslog = match &config.syslog {
+        Some(s) => s,
+        None => return,
+    };
+    let mut report = report.lock().unwrap();
+    match syslog::send(syslog, &report, &config.run_id) {
+        Ok(sent) => info!("sent {} category summaries to {:?}", sent, syslog.target),
+        Err(e) => {
+            error!("{} {:?}", ErrorCode::PushFailed, e);
+            report
+                .failures
+                .push(format!("{} {:?}", ErrorCode::PushFailed, e));
+        }
+    }
+}
+
+/// writes the categories to the SQLite database if configured
+///
+/// * `config`: the configuration containing the database settings
+/// * `report`: the report collected during the run
+fn write_sqlite(config: &Config, report: &Arc<Mutex<RunReport>>) {
+    let sqlite = match &config.sqlite {
+        Some(s) => s,
+        None => return,
+    };
+    match sqlite::write(config, sqlite) {
+        Ok(written) => info!("wrote {} entries to {}", written, sqlite.path),
+        Err(e) => {
+            error!("{} {:?}", ErrorCode::OutputFailed, e);
+            report
+                .lock()
+                .unwrap()
+                .failures
+                .push(format!("{} {:?}", ErrorCode::OutputFailed, e));
+        }
+    }
+}
+
+/// writes the Blocky configuration fragment referencing the written lists if configured
+///
+/// * `config`: the configuration containing the Blocky settings
+/// * `report`: the report collected during the run
+fn write_blocky_fragment(config: &Config, report: &Arc<Mutex<RunReport>>) {
+    let path = match config.blocky.as_ref().and_then(|b| b.references.as_ref()) {
+        Some(p) => p,
+        None => return,
+    };
+    match output::blocky::write_fragment(config, path) {
+        Ok(written) => info!("referenced {} categories in {}", written, path),
+        Err(e) => {
+            error!("{} {:?}", ErrorCode::OutputFailed, e);
+            report
+                .lock()
+                .unwrap()
+                .failures
+                .push(format!("{} {:?}", ErrorCode::OutputFailed, e));
+        }
+    }
+}
+
+/// adds the registration data of the newly added entries to the run report if configured
+///
+/// * `config`: the configuration containing the report settings
+/// * `report`: the report collected during the run
+async fn annotate_report(config: &Config, report: &Arc<Mutex<RunReport>>) {
+    let rdap = match config.report.as_ref().and_then(|r| r.rdap.as_ref()) {
+        Some(r) => r,
+        None => return,
+    };
+    let domains = report.lock().unwrap().added_entries();
+    match rdap::lookup(rdap, Path::new(&config.cache_dir), &domains).await {
+        Ok(infos) => {
+            let mut report = report.lock().unwrap();
+            for (domain, info) in infos {
+                report.annotations.insert(domain, info.to_string());
+            }
+        }
+        Err(e) => warn!("RDAP lookup failed: {:?}", e),
+    }
+}
+
+/// writes the run report if configured
+///
+/// * `config`: the configuration containing the report settings
+/// * `report`: the report collected during the run
+/// * `failure`: the error which aborted the run if any
+fn write_report(config: &Config, report: &Arc<Mutex<RunReport>>, failure: Option<anyhow::Error>) {
+    let report_config = match &config.report {
+        Some(r) => r,
+        None => return,
+    };
+    let mut report = report.lock().unwrap();
+    if let Some(e) = failure {
+        report
+            .failures
+            .push(format!("{} {:?}", ErrorCode::RunAborted, e));
+    }
+    match report.write(report_config) {
+        Ok(()) if report.has_failures() => {
+            warn!("run finished with failures, see {}", report_config.path)
+        }
+        Ok(()) => {}
+        Err(e) => error!("Error writing run report: {:?}", e),
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::io::atomic_file::AtomicFile;
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// the version of the manifest's schema, manifests of older versions are migrated on load
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// the number of runs whose match rates are kept per list
+const MATCH_RATE_HISTORY: usize = 10;
+
+/// timestamps before 2024-01-01 are taken for a clock which wasn't set yet, e.g. on
+/// routers without real time clock right after boot
+const CLOCK_FLOOR: i64 = 1_704_067_200;
+
+/// Manifest keeps track of state which has to survive between program runs and
+/// is stored next to the cached lists in the cache directory
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Manifest {
+    /// the schema version, manifests without version are version 0
+    #[serde(default)]
+    pub version: u32,
+    /// index of the next entry to fetch per certificate transparency log list
+    #[serde(default)]
+    pub ct_log_positions: HashMap<String, u64>,
+    /// hash of the content addressed blob storing the downloaded list per list id
+    #[serde(default)]
+    pub blobs: HashMap<String, String>,
+    /// what the extract stage produced per list id on the last run
+    #[serde(default)]
+    pub extracts: HashMap<String, ExtractRecord>,
+    /// validators of the response the downloaded list was taken from per list id
+    #[serde(default)]
+    pub http: HashMap<String, HttpRecord>,
+    /// unix timestamp of when the downloaded list was last known to be current per list id
+    #[serde(default)]
+    pub fetched: HashMap<String, i64>,
+    /// seconds since the downloaded list was last known to be current per list id,
+    /// accumulated run by run so jumps of the clock don't distort them
+    #[serde(default)]
+    pub ages: HashMap<String, u64>,
+    /// unix timestamp of the last run, the ages advance by the time passed since
+    #[serde(default)]
+    pub clock: Option<i64>,
+    /// the version of harvester which wrote the manifest
+    #[serde(default)]
+    pub harvester_version: Option<String>,
+    /// the id of the run which wrote the manifest
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// categories whose changes were rejected in review, they are assembled again on
+    /// the next run
+    #[serde(default)]
+    pub rejected: BTreeSet<String>,
+    /// output files which couldn't be replaced on the last run, their categories are
+    /// written again on the next run even if they didn't change
+    #[serde(default)]
+    pub failed_outputs: BTreeSet<String>,
+    /// the share of lines the extract stage matched on the last runs per list id,
+    /// the latest last
+    #[serde(default)]
+    pub match_rates: HashMap<String, Vec<f64>>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            ct_log_positions: HashMap::new(),
+            blobs: HashMap::new(),
+            extracts: HashMap::new(),
+            http: HashMap::new(),
+            fetched: HashMap::new(),
+            ages: HashMap::new(),
+            clock: None,
+            harvester_version: None,
+            run_id: None,
+            rejected: BTreeSet::new(),
+            failed_outputs: BTreeSet::new(),
+            match_rates: HashMap::new(),
+        }
+    }
+}
+
+/// ExtractRecord describes a list's extract file and how it was created
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ExtractRecord {
+    /// the number of lines written to the extract file
+    pub lines: usize,
+    /// hash of the list settings the lines were extracted with
+    pub settings: String,
+}
+
+/// HttpRecord keeps the validators of a download so the next run can ask the server
+/// whether the list changed in the same request which downloads it otherwise
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpRecord {
+    /// the response's ETag header
+    pub etag: Option<String>,
+    /// the response's Last-Modified header
+    pub last_modified: Option<String>,
+}
+
+impl Manifest {
+    /// Loads the manifest from the cache directory. A missing manifest results in an
+    /// empty one since there is no state on the very first run.
+    ///
+    /// * `cache_dir`: the directory the manifest is stored in
+    pub fn load(cache_dir: &Path) -> anyhow::Result<Self> {
+        let path = manifest_path(cache_dir);
+        if !path.exists() {
+            debug!("no manifest found");
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path).with_context(|| "error reading manifest")?;
+        let value: Value = serde_json::from_str(&contents).with_context(|| "invalid manifest")?;
+        let manifest =
+            serde_json::from_value(migrate(value)?).with_context(|| "invalid manifest")?;
+        Ok(manifest)
+    }
+
+    /// records an output file which kept its previous version so the next run writes
+    /// it again
+    ///
+    /// * `path`: the output file
+    pub fn output_failed(&mut self, path: &Path) {
+        self.failed_outputs
+            .insert(path.to_string_lossy().to_string());
+    }
+
+    /// forgets about an output file which failed on an earlier run once it's written
+    ///
+    /// * `path`: the output file
+    pub fn output_written(&mut self, path: &Path) {
+        self.failed_outputs.remove(path.to_string_lossy().as_ref());
+    }
+
+    /// returns whether an output file kept its previous version on the last run
+    ///
+    /// * `path`: the output file
+    pub fn is_output_failed(&self, path: &Path) -> bool {
+        self.failed_outputs
+            .contains(path.to_string_lossy().as_ref())
+    }
+
+    /// Advances the ages of the downloaded lists by the time passed since the last run.
+    /// Time isn't counted if the clock went backwards or was set in between, so the
+    /// ages never decrease and a clock set after boot doesn't age all lists at once.
+    ///
+    /// * `now`: the current unix timestamp
+    pub fn tick(&mut self, now: i64) {
+        let elapsed = match self.clock {
+            Some(last) if now >= last && (last < CLOCK_FLOOR) == (now < CLOCK_FLOOR) => {
+                (now - last) as u64
+            }
+            Some(last) => {
+                warn!(
+                    "the clock jumped by {} seconds since the last run, the time in between isn't counted",
+                    now - last
+                );
+                0
+            }
+            None => 0,
+        };
+        for age in self.ages.values_mut() {
+            *age = age.saturating_add(elapsed);
+        }
+        // manifests of older versions only know when the lists were fetched
+        for (id, fetched) in self.fetched.iter() {
+            if !self.ages.contains_key(id) && *fetched >= CLOCK_FLOOR && now >= *fetched {
+                self.ages.insert(id.clone(), (now - fetched) as u64);
+            }
+        }
+        self.clock = Some(now);
+    }
+
+    /// records that the downloaded list is current
+    ///
+    /// * `id`: the list's id
+    /// * `now`: the current unix timestamp
+    pub fn mark_current(&mut self, id: &str, now: i64) {
+        self.fetched.insert(id.to_string(), now);
+        self.ages.insert(id.to_string(), 0);
+    }
+
+    /// Records the match rate of a list's extraction. Returns the average rate of the
+    /// previous runs if the rate fell by more than the given share of it, which
+    /// usually means the list's format changed.
+    ///
+    /// * `id`: the list's id
+    /// * `rate`: the share of the list's lines which were matched
+    /// * `drop`: the share of the average rate which may be lost
+    pub fn record_match_rate(&mut self, id: &str, rate: f64, drop: f64) -> Option<f64> {
+        let rates = self.match_rates.entry(id.to_string()).or_default();
+        let average = match rates.len() {
+            0 => None,
+            n => Some(rates.iter().sum::<f64>() / n as f64),
+        };
+        rates.push(rate);
+        if rates.len() > MATCH_RATE_HISTORY {
+            rates.remove(0);
+        }
+        average.filter(|a| rate < a * (1.0 - drop))
+    }
+
+    /// Writes the manifest to the cache directory. The previous manifest is only
+    /// replaced once the new one was written completely, so an interrupted run can't
+    /// leave a corrupted manifest behind.
+    ///
+    /// * `cache_dir`: the directory the manifest is stored in
+    pub fn save(&self, cache_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(cache_dir).with_context(|| "could not create cache directory")?;
+        let mut manifest_file = AtomicFile::create(manifest_path(cache_dir))?;
+        let mut manifest = serde_json::to_value(self)?;
+        manifest["harvester_version"] = env!("CARGO_PKG_VERSION").into();
+        let manifest_str = manifest.to_string();
+        if let Err(e) = manifest_file.write_all(manifest_str.as_bytes()) {
+            manifest_file.discard();
+            return Err(e).with_context(|| "could not write manifest");
+        }
+        if let Err(e) = manifest_file.commit() {
+            manifest_file.discard();
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Upgrades a manifest written by an older version to the current schema
+///
+/// * `value`: the manifest as read from the cache directory
+fn migrate(mut value: Value) -> anyhow::Result<Value> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > MANIFEST_VERSION as u64 {
+        return Err(anyhow::anyhow!(
+            "manifest version {} is not supported, the latest known version is {}",
+            version,
+            MANIFEST_VERSION
+        ));
+    }
+    let manifest = value
+        .as_object_mut()
+        .with_context(|| "manifest is not an object")?;
+    if version < 1 {
+        // extract records used to hold the regex instead of the hash of all list
+        // settings, without the hash the extracts can't be validated
+        manifest.remove("extracts");
+    }
+    manifest.insert("version".to_string(), MANIFEST_VERSION.into());
+    Ok(value)
+}
+
+/// counts the lines of a file
+///
+/// * `path`: the file to be read
+pub fn count_lines(path: &Path) -> anyhow::Result<usize> {
+    let contents = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+    Ok(contents.iter().filter(|b| **b == b'\n').count())
+}
+
+fn manifest_path(cache_dir: &Path) -> PathBuf {
+    let mut path = cache_dir.to_path_buf();
+    path.push(MANIFEST_FILE_NAME);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::helper::cache_file_creator::CacheFileCreator;
+
+    use super::*;
+
+    #[test]
+    fn test_manifest_migration() {
+        let cache = CacheFileCreator::new("test_manifest_migration", "in", "out");
+        let cache_dir = PathBuf::from(cache.new_test_config().cache_dir);
+        let legacy = r#"{
+            "ct_log_positions": {"ct": 42},
+            "blobs": {"list": "abc"},
+            "extracts": {"list": {"lines": 2, "regex": "(.*)"}}
+        }"#;
+        fs::write(manifest_path(&cache_dir), legacy).unwrap();
+
+        let manifest = Manifest::load(&cache_dir).unwrap();
+        assert_eq!(manifest.version, MANIFEST_VERSION);
+        assert_eq!(manifest.ct_log_positions["ct"], 42);
+        assert_eq!(manifest.blobs["list"], "abc");
+        assert!(manifest.extracts.is_empty());
+
+        manifest.save(&cache_dir).unwrap();
+        assert!(!cache_dir
+            .join(format!(".{MANIFEST_FILE_NAME}.tmp"))
+            .exists());
+        let saved = Manifest::load(&cache_dir).unwrap();
+        assert_eq!(saved.blobs, manifest.blobs);
+
+        fs::write(manifest_path(&cache_dir), r#"{"version": 99}"#).unwrap();
+        assert!(Manifest::load(&cache_dir).is_err());
+    }
+
+    #[test]
+    fn test_record_match_rate() {
+        let mut manifest = Manifest::default();
+        assert_eq!(manifest.record_match_rate("list", 0.75, 0.5), None);
+        assert_eq!(manifest.record_match_rate("list", 0.625, 0.5), None);
+        assert_eq!(manifest.record_match_rate("list", 0.125, 0.5), Some(0.6875));
+        for _ in 0..MATCH_RATE_HISTORY {
+            manifest.record_match_rate("list", 0.125, 0.5);
+        }
+        // the drop becomes the new normal once the history forgot the old rates
+        assert_eq!(manifest.match_rates["list"].len(), MATCH_RATE_HISTORY);
+        assert_eq!(manifest.record_match_rate("list", 0.125, 0.5), None);
+    }
+
+    #[test]
+    fn test_tick_clock_skew() {
+        let now = 1_750_000_000;
+        let mut manifest = Manifest::default();
+        manifest.fetched.insert("legacy".to_string(), now - 100);
+        manifest.fetched.insert("unset".to_string(), 3600);
+        manifest.tick(now);
+        assert_eq!(manifest.ages["legacy"], 100);
+        assert!(!manifest.ages.contains_key("unset"));
+
+        manifest.mark_current("one", now);
+        manifest.tick(now + 60);
+        assert_eq!(manifest.ages["one"], 60);
+        assert_eq!(manifest.ages["legacy"], 160);
+
+        // the clock went backwards, e.g. a router booted without real time clock
+        manifest.tick(120);
+        assert_eq!(manifest.ages["one"], 60);
+        // runs with the unset clock are counted
+        manifest.mark_current("two", 120);
+        manifest.tick(180);
+        assert_eq!(manifest.ages["one"], 120);
+        assert_eq!(manifest.ages["two"], 60);
+        // setting the clock doesn't age the lists by decades
+        manifest.tick(now + 300);
+        assert_eq!(manifest.ages["one"], 120);
+        assert_eq!(manifest.ages["two"], 60);
+        manifest.tick(now + 330);
+        assert_eq!(manifest.ages["two"], 90);
+    }
+}
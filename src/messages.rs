@@ -0,0 +1,100 @@
+use std::{env, sync::OnceLock};
+
+/// the language of the messages, detected once
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Language is a language the messages are translated to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    /// returns the language of a locale like de_DE.UTF-8, English if there is no
+    /// translation to the locale's language
+    ///
+    /// * `locale`: the locale
+    pub fn from_locale(locale: &str) -> Self {
+        match locale.split(['_', '.', '-']).next() {
+            Some("de") => Language::German,
+            _ => Language::English,
+        }
+    }
+
+    /// returns the language of the locale set in the environment, LC_ALL taking
+    /// precedence over LC_MESSAGES and LANG as usual
+    pub fn detect() -> Self {
+        *LANGUAGE.get_or_init(|| {
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .filter_map(|v| env::var(v).ok())
+                .find(|l| !l.is_empty())
+                .map(|l| Self::from_locale(&l))
+                .unwrap_or(Language::English)
+        })
+    }
+}
+
+/// Message is a text shown to the user on the terminal
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Message {
+    ShuttingDown,
+    FinishingRun,
+    Downloading,
+    Extracting,
+    Categorizing,
+    CreatingOutput,
+}
+
+impl Message {
+    /// returns the message in the language of the user's locale
+    pub fn text(self) -> &'static str {
+        self.translate(Language::detect())
+    }
+
+    /// returns the message in the given language
+    ///
+    /// * `language`: the language of the message
+    pub fn translate(self, language: Language) -> &'static str {
+        match (self, language) {
+            (Message::ShuttingDown, Language::English) => "gracefully shutting down ...",
+            (Message::ShuttingDown, Language::German) => "wird kontrolliert beendet ...",
+            (Message::FinishingRun, Language::English) => {
+                "finishing the run, press Ctrl-C again to stop immediately ..."
+            }
+            (Message::FinishingRun, Language::German) => {
+                "Lauf wird abgeschlossen, erneut Strg-C drücken zum sofortigen Beenden ..."
+            }
+            (Message::Downloading, Language::English) => "Downloading lists ...",
+            (Message::Downloading, Language::German) => "Lade Listen herunter ...",
+            (Message::Extracting, Language::English) => "Extracting domains ...",
+            (Message::Extracting, Language::German) => "Extrahiere Domains ...",
+            (Message::Categorizing, Language::English) => "Categorizing domains ...",
+            (Message::Categorizing, Language::German) => "Kategorisiere Domains ...",
+            (Message::CreatingOutput, Language::English) => "Creating output files ...",
+            (Message::CreatingOutput, Language::German) => "Erstelle Ausgabedateien ...",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(Language::from_locale("de_DE.UTF-8"), Language::German);
+        assert_eq!(Language::from_locale("de"), Language::German);
+        assert_eq!(Language::from_locale("C.UTF-8"), Language::English);
+        assert_eq!(Language::from_locale("fr_FR"), Language::English);
+        assert_eq!(
+            Message::Downloading.translate(Language::English),
+            "Downloading lists ..."
+        );
+        assert_eq!(
+            Message::Downloading.translate(Language::German),
+            "Lade Listen herunter ..."
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use std::{collections::HashSet, fs, time::Duration};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    config::{Config, ALLOW_CATEGORY},
+    output::OutputType,
+};
+
+/// regex extracting the domain of a hosts file line
+const HOSTS_REGEX: &str = r"^(?:0\.0\.0\.0|127\.0\.0\.1|::1?)\s+([^\s#]+)";
+/// regex extracting the domain of a blocking rule in Adblock Plus syntax
+const ADBLOCK_REGEX: &str = r"^\|\|([^\^/$]+)\^";
+/// regex extracting the domain of a list with one domain per line
+const DOMAINS_REGEX: &str = r"^([^\s#!]+)$";
+
+/// keywords in the name or url of a list suggesting a tag
+const TAG_KEYWORDS: &[(&str, &[&str])] = &[
+    (
+        "advertising",
+        &["ad", "ads", "adware", "advert", "adservers", "easylist"],
+    ),
+    (
+        "tracking",
+        &[
+            "track",
+            "tracking",
+            "tracker",
+            "privacy",
+            "telemetry",
+            "spy",
+        ],
+    ),
+    (
+        "malware",
+        &["malware", "malicious", "ransomware", "threat", "virus"],
+    ),
+    ("phishing", &["phishing", "phish", "scam", "fraud"]),
+    ("adult", &["porn", "adult", "nsfw"]),
+    ("gambling", &["gambling", "casino"]),
+    ("social", &["social", "facebook"]),
+    (
+        "crypto",
+        &["crypto", "coinminer", "cryptojacking", "mining"],
+    ),
+];
+
+/// BlocklistManager names another tool whose list subscriptions harvester converts
+/// from and to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum BlocklistManager {
+    /// adlists.list or the addresses of the adlist table in gravity.db, one per line
+    Pihole,
+    /// AdGuardHome.yaml
+    Adguardhome,
+    /// the hBlock sources.list, one url per line
+    Hblock,
+}
+
+/// ImportedList is a list subscription found in another tool's configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImportedList {
+    name: Option<String>,
+    url: String,
+    /// the list contains domains which must never be blocked
+    allow: bool,
+}
+
+/// the parts of AdGuardHome.yaml describing the list subscriptions
+#[derive(Debug, Deserialize, Serialize)]
+struct AdGuardHomeConfig {
+    #[serde(default)]
+    filters: Vec<AdGuardHomeFilter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    whitelist_filters: Vec<AdGuardHomeFilter>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct AdGuardHomeFilter {
+    #[serde(default)]
+    enabled: bool,
+    url: String,
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+}
+
+/// reads the configuration to be imported from a file or a http(s) url
+///
+/// * `source`: file system path or url of the configuration
+pub async fn read_source(source: &str) -> anyhow::Result<String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        let cli = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        let response = cli.get(source).send().await?.error_for_status()?;
+        return Ok(response.text().await?);
+    }
+    fs::read_to_string(source).with_context(|| format!("could not read {source}"))
+}
+
+/// returns the list subscriptions of another tool's configuration, disabled lists
+/// are left out
+///
+/// * `format`: the tool the configuration belongs to
+/// * `contents`: the configuration
+fn parse(format: BlocklistManager, contents: &str) -> anyhow::Result<Vec<ImportedList>> {
+    match format {
+        BlocklistManager::Pihole | BlocklistManager::Hblock => Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| ImportedList {
+                name: None,
+                url: l.to_string(),
+                allow: false,
+            })
+            .collect()),
+        BlocklistManager::Adguardhome => {
+            let config: AdGuardHomeConfig =
+                serde_yaml::from_str(contents).with_context(|| "invalid AdGuardHome.yaml")?;
+            let lists = |filters: Vec<AdGuardHomeFilter>, allow: bool| {
+                filters
+                    .into_iter()
+                    .filter(|f| f.enabled)
+                    .map(move |f| ImportedList {
+                        name: f.name,
+                        url: f.url,
+                        allow,
+                    })
+            };
+            Ok(lists(config.filters, false)
+                .chain(lists(config.whitelist_filters, true))
+                .collect())
+        }
+    }
+}
+
+/// returns the tags suggested by the keywords found in the name and url of a list
+///
+/// * `list`: the imported list
+fn suggest_tags(list: &ImportedList) -> Vec<String> {
+    if list.allow {
+        return vec![ALLOW_CATEGORY.to_string()];
+    }
+    let text = format!("{} {}", list.name.as_deref().unwrap_or_default(), list.url);
+    let words: HashSet<String> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(String::from)
+        .collect();
+    TAG_KEYWORDS
+        .iter()
+        .filter(|(_, keywords)| keywords.iter().any(|k| words.contains(*k)))
+        .map(|(tag, _)| tag.to_string())
+        .collect()
+}
+
+/// returns the regex suggested for a list, guessed from its url and the syntax the
+/// tool expects by default
+///
+/// * `format`: the tool the list was imported from
+/// * `list`: the imported list
+fn suggest_regex(format: BlocklistManager, list: &ImportedList) -> &'static str {
+    let url = list.url.to_lowercase();
+    if url.contains("hosts") {
+        HOSTS_REGEX
+    } else if format == BlocklistManager::Adguardhome || url.contains("adblock") {
+        ADBLOCK_REGEX
+    } else if format == BlocklistManager::Hblock {
+        HOSTS_REGEX
+    } else {
+        DOMAINS_REGEX
+    }
+}
+
+/// returns an id for a list derived from its name or the last part of its url which
+/// is not taken yet
+///
+/// * `list`: the imported list
+/// * `taken`: the ids of the lists imported before
+fn list_id(list: &ImportedList, taken: &mut HashSet<String>) -> String {
+    let base = list
+        .name
+        .clone()
+        .or_else(|| {
+            url::Url::parse(&list.url).ok().map(|u| {
+                let file = u.path_segments().and_then(|mut s| s.next_back());
+                match file {
+                    Some(f) if !f.is_empty() => format!("{} {}", u.host_str().unwrap_or(""), f),
+                    _ => u.host_str().unwrap_or("list").to_string(),
+                }
+            })
+        })
+        .unwrap_or_else(|| list.url.clone());
+    let mut id = base
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if id.is_empty() {
+        id = "list".to_string();
+    }
+    let mut unique = id.clone();
+    let mut n = 2;
+    while !taken.insert(unique.clone()) {
+        unique = format!("{id}-{n}");
+        n += 1;
+    }
+    unique
+}
+
+/// Generates a harvester configuration from another tool's configuration. Every list
+/// subscription becomes a list with tags and a regex suggested by its name and url,
+/// which should be reviewed before the first run.
+///
+/// * `format`: the tool the configuration belongs to
+/// * `contents`: the configuration
+pub fn generate_config(format: BlocklistManager, contents: &str) -> anyhow::Result<Value> {
+    let mut taken = HashSet::new();
+    let lists: Vec<Value> = parse(format, contents)?
+        .iter()
+        .map(|l| {
+            let mut list = json!({
+                "id": list_id(l, &mut taken),
+                "source": l.url,
+                "tags": suggest_tags(l),
+                "regex": suggest_regex(format, l),
+            });
+            if let Some(name) = &l.name {
+                list["comment"] = name.clone().into();
+            }
+            list
+        })
+        .collect();
+    let output_format = match format {
+        BlocklistManager::Pihole => "Domains",
+        BlocklistManager::Adguardhome => "Adblock",
+        BlocklistManager::Hblock => "Hostsfile",
+    };
+    Ok(json!({
+        "cache_dir": "./cache",
+        "output_dir": "./result",
+        "output_format": output_format,
+        "default_tags": ["uncategorized"],
+        "lists": lists,
+    }))
+}
+
+/// returns the output formats the tool is able to read in the order of preference
+///
+/// * `manager`: the tool subscribing to the outputs
+fn readable_formats(manager: BlocklistManager) -> &'static [OutputType] {
+    match manager {
+        BlocklistManager::Pihole => &[
+            OutputType::Domains,
+            OutputType::Hostsfile,
+            OutputType::Adblock,
+        ],
+        BlocklistManager::Adguardhome => &[
+            OutputType::Adblock,
+            OutputType::Domains,
+            OutputType::Hostsfile,
+        ],
+        BlocklistManager::Hblock => &[OutputType::Hostsfile, OutputType::Domains],
+    }
+}
+
+/// Generates the list subscriptions of another tool pointing at the categories a
+/// web server serves from harvester's output directory. The first configured output
+/// format the tool is able to read is subscribed to.
+///
+/// * `config`: the configuration whose outputs are subscribed to
+/// * `manager`: the tool subscribing to the outputs
+/// * `base_url`: the url the output directory is served at
+pub fn export_config(
+    config: &Config,
+    manager: BlocklistManager,
+    base_url: &str,
+) -> anyhow::Result<String> {
+    let readable = readable_formats(manager);
+    let formats = config.output_formats();
+    let format = formats
+        .iter()
+        .find(|f| readable.contains(f))
+        .with_context(|| {
+            format!(
+                "none of the output formats can be read by {:?}, configure one of {}",
+                manager,
+                readable
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+    // with several output formats each format is written to its own subdirectory
+    let mut base_url = base_url.trim_end_matches('/').to_string();
+    if formats.len() > 1 {
+        base_url = format!("{}/{}", base_url, format.dir_name());
+    }
+    // categories with formats of their own may not be written in the subscribed one
+    let mut categories: Vec<String> = config
+        .get_tags()
+        .into_iter()
+        .filter(|t| t != ALLOW_CATEGORY && config.category_formats(t).contains(format))
+        .collect();
+    categories.sort();
+    let urls = categories.iter().map(|c| {
+        (
+            c,
+            format!("{base_url}/{}", config.output_file_name(format, c)),
+        )
+    });
+
+    match manager {
+        BlocklistManager::Pihole | BlocklistManager::Hblock => {
+            Ok(urls.map(|(_, url)| url + "\n").collect())
+        }
+        BlocklistManager::Adguardhome => {
+            let filters = urls
+                .zip(1..)
+                .map(|((category, url), id)| AdGuardHomeFilter {
+                    enabled: true,
+                    url,
+                    name: Some(format!("harvester {category}")),
+                    id: Some(id),
+                })
+                .collect();
+            Ok(serde_yaml::to_string(&AdGuardHomeConfig {
+                filters,
+                whitelist_filters: vec![],
+            })?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator};
+
+    use super::*;
+
+    #[test]
+    fn test_generate_config() {
+        let adguardhome = r#"
+filters:
+  - enabled: true
+    url: https://adguardteam.github.io/HostlistsRegistry/assets/filter_1.txt
+    name: AdGuard DNS filter
+    id: 1
+  - enabled: false
+    url: https://example.org/disabled.txt
+    name: Disabled
+    id: 2
+  - enabled: true
+    url: https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts
+    name: Malware hosts
+    id: 3
+whitelist_filters:
+  - enabled: true
+    url: https://example.org/allow.txt
+    name: Allowed
+    id: 4
+"#;
+        let config = generate_config(BlocklistManager::Adguardhome, adguardhome).unwrap();
+        assert_eq!(config["output_format"], "Adblock");
+        let lists = config["lists"].as_array().unwrap();
+        assert_eq!(lists.len(), 3);
+        assert_eq!(lists[0]["id"], "adguard-dns-filter");
+        assert_eq!(lists[0]["regex"], ADBLOCK_REGEX);
+        assert_eq!(lists[1]["tags"], json!(["malware"]));
+        assert_eq!(lists[1]["regex"], HOSTS_REGEX);
+        assert_eq!(lists[2]["tags"], json!([ALLOW_CATEGORY]));
+
+        let pihole =
+            "# adlists\nhttps://example.org/ads/list.txt\n\nhttps://example.org/ads/list.txt\n";
+        let config = generate_config(BlocklistManager::Pihole, pihole).unwrap();
+        let lists = config["lists"].as_array().unwrap();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0]["id"], "example-org-list-txt");
+        assert_eq!(lists[1]["id"], "example-org-list-txt-2");
+        assert_eq!(lists[0]["tags"], json!(["advertising"]));
+        assert_eq!(lists[0]["regex"], DOMAINS_REGEX);
+
+        serde_json::from_value::<Config>(config).unwrap();
+
+        assert!(generate_config(BlocklistManager::Adguardhome, "filters: 1").is_err());
+    }
+
+    #[test]
+    fn test_export_config() {
+        let cache = CacheFileCreator::new("test_export_config", "in", "out");
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "one".to_string(),
+            tags: vec!["tracking".to_string(), "ads".to_string()],
+            ..Default::default()
+        }];
+        config.output_format = vec![OutputType::Lua, OutputType::Hostsfile];
+
+        let adguardhome =
+            export_config(&config, BlocklistManager::Adguardhome, "https://lists.lan/").unwrap();
+        assert_eq!(
+            adguardhome,
+            "filters:\n\
+             - enabled: true\n  url: https://lists.lan/hostsfile/ads\n  name: harvester ads\n  id: 1\n\
+             - enabled: true\n  url: https://lists.lan/hostsfile/tracking\n  name: harvester tracking\n  id: 2\n"
+        );
+        let imported = generate_config(BlocklistManager::Adguardhome, &adguardhome).unwrap();
+        assert_eq!(imported["lists"].as_array().unwrap().len(), 2);
+
+        config.output_format = vec![OutputType::Domains];
+        assert_eq!(
+            export_config(&config, BlocklistManager::Pihole, "https://lists.lan").unwrap(),
+            "https://lists.lan/ads\nhttps://lists.lan/tracking\n"
+        );
+        config.output_format = vec![OutputType::Lua];
+        assert!(export_config(&config, BlocklistManager::Hblock, "https://lists.lan").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use unicode_script::{Script, UnicodeScript};
+
+/// IdnPolicy determines how internationalized domain names end up in a category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum IdnPolicy {
+    /// convert IDNs to their punycode form
+    #[default]
+    Punycode,
+    /// emit the punycode form and additionally the Unicode form
+    Both,
+    /// like Punycode but drop domains with labels mixing several scripts
+    RejectMixedScript,
+}
+
+/// Normalizes a single extracted entry and returns the entries which make it into
+/// the category. Depending on the IDN policy this can be none, one or two entries.
+///
+/// * `entry`: a line extracted from a filter list
+/// * `idn_policy`: how to treat internationalized domain names
+pub fn normalize(entry: &str, idn_policy: IdnPolicy) -> Vec<String> {
+    let entry = fold_case(entry.trim());
+    let entry = entry.as_str();
+    if entry.is_empty() {
+        return vec![];
+    }
+    if !is_idn(entry) {
+        return vec![entry.to_string()];
+    }
+    // wildcards are normalized without their prefix
+    if let Some(domain) = entry.strip_prefix("*.") {
+        return normalize(domain, idn_policy)
+            .into_iter()
+            .map(|d| format!("*.{d}"))
+            .collect();
+    }
+
+    let ascii = match idna::domain_to_ascii(entry) {
+        Ok(a) => a,
+        Err(e) => {
+            warn!("invalid internationalized domain {}: {:?}", entry, e);
+            return vec![];
+        }
+    };
+    let (unicode, _) = idna::domain_to_unicode(&ascii);
+    if idn_policy == IdnPolicy::RejectMixedScript && is_mixed_script(&unicode) {
+        warn!("rejected mixed script domain {} ({})", unicode, ascii);
+        return vec![];
+    }
+    if idn_policy == IdnPolicy::Both && unicode != ascii {
+        return vec![ascii, unicode];
+    }
+    vec![ascii]
+}
+
+/// Lowercases the parts of an entry which are case insensitive so entries differing
+/// only by case are deduplicated. The path and query of URLs are kept as they are.
+///
+/// * `entry`: a line extracted from a filter list
+fn fold_case(entry: &str) -> String {
+    match entry.split_once("://") {
+        Some((scheme, rest)) => {
+            let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+            let (authority, path) = rest.split_at(authority_end);
+            format!(
+                "{}://{}{}",
+                scheme.to_lowercase(),
+                authority.to_lowercase(),
+                path
+            )
+        }
+        None => entry.to_lowercase(),
+    }
+}
+
+/// checks whether an entry contains non ASCII characters or punycode labels
+fn is_idn(entry: &str) -> bool {
+    !entry.is_ascii() || entry.split('.').any(|label| label.starts_with("xn--"))
+}
+
+/// checks if any label of a domain mixes characters of different scripts
+/// (e.g. latin and cyrillic) which is a strong hint for a homograph attack
+pub fn is_mixed_script(domain: &str) -> bool {
+    domain.split('.').any(|label| {
+        let mut label_script: Option<Script> = None;
+        for script in label.chars().map(|c| c.script()) {
+            let script = match script {
+                // digits, hyphens and combining marks belong to every script
+                Script::Common | Script::Inherited | Script::Unknown => continue,
+                // east asian writing systems legitimately combine these scripts
+                Script::Hiragana | Script::Katakana | Script::Hangul | Script::Bopomofo => {
+                    Script::Han
+                }
+                s => s,
+            };
+            match label_script {
+                Some(s) if s != script => return true,
+                _ => label_script = Some(script),
+            }
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_idn() {
+        // ascii domains are left untouched
+        assert_eq!(
+            normalize(" domain.one\n", IdnPolicy::Both),
+            vec!["domain.one"]
+        );
+        // unicode domains are converted to punycode
+        assert_eq!(
+            normalize("bücher.example", IdnPolicy::Punycode),
+            vec!["xn--bcher-kva.example"]
+        );
+        // the unicode form is added on demand
+        assert_eq!(
+            normalize("xn--bcher-kva.example", IdnPolicy::Both),
+            vec!["xn--bcher-kva.example", "bücher.example"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_case() {
+        assert_eq!(
+            normalize("Example.COM", IdnPolicy::Punycode),
+            vec!["example.com"]
+        );
+        assert_eq!(
+            normalize("*.Example.com", IdnPolicy::Punycode),
+            vec!["*.example.com"]
+        );
+        assert_eq!(
+            normalize("2001:DB8::1", IdnPolicy::Punycode),
+            vec!["2001:db8::1"]
+        );
+        // the path of a URL is case sensitive
+        assert_eq!(
+            normalize("HTTPS://Example.com/Path?Q=1", IdnPolicy::Punycode),
+            vec!["https://example.com/Path?Q=1"]
+        );
+        // upper case IDNs and punycode end up in the same form as lower case ones
+        for idn in ["BÜCHER.example", "Bücher.Example", "XN--BCHER-KVA.example"] {
+            assert_eq!(
+                normalize(idn, IdnPolicy::Both),
+                vec!["xn--bcher-kva.example", "bücher.example"]
+            );
+        }
+        // the dotted capital I has no single character lower case form
+        assert_eq!(
+            normalize("İstanbul.example", IdnPolicy::Punycode),
+            normalize("i\u{307}stanbul.example", IdnPolicy::Punycode)
+        );
+    }
+
+    #[test]
+    fn test_normalize_mixed_script() {
+        // the first "a" is a cyrillic character
+        let homograph = "p\u{0430}ypal.com";
+        assert!(is_mixed_script(homograph));
+        assert!(normalize(homograph, IdnPolicy::RejectMixedScript).is_empty());
+        assert_eq!(normalize(homograph, IdnPolicy::Punycode).len(), 1);
+        // a single non latin script is fine
+        assert!(!is_mixed_script("пример.рф"));
+        assert_eq!(
+            normalize("пример.рф", IdnPolicy::RejectMixedScript),
+            vec!["xn--e1afmkfd.xn--p1ai"]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{error_code::ErrorCode, report::Status};
+
+/// PipelineStage names the stage an event occurred in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStage {
+    Download,
+    Extract,
+    Categorize,
+    Output,
+}
+
+impl Display for PipelineStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineStage::Download => write!(f, "download"),
+            PipelineStage::Extract => write!(f, "extract"),
+            PipelineStage::Categorize => write!(f, "categorize"),
+            PipelineStage::Output => write!(f, "output"),
+        }
+    }
+}
+
+/// Plan tells upfront what a stage is going to do with the configured lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// lists to be processed
+    pub updated: usize,
+    /// lists taken over from the last run
+    pub unchanged: usize,
+    /// lists whose previous version is used since the server asked to retry later
+    pub deferred: usize,
+    /// lists whose previous version is used since the server didn't answer
+    pub stale: usize,
+    /// lists which can't be processed at all
+    pub failed: usize,
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} updated, {} unchanged, {} deferred, {} stale, {} failed",
+            self.updated, self.unchanged, self.deferred, self.stale, self.failed
+        )
+    }
+}
+
+/// Observer is notified about the progress of a pipeline. Lists are filter lists in
+/// the download and extract stage and categories in the categorize and output stage.
+/// All methods do nothing by default so implementations only handle the events they
+/// are interested in.
+pub trait Observer: Send + Sync {
+    /// the stage decided which lists are going to be processed
+    fn on_plan(&self, _stage: PipelineStage, _plan: &Plan) {}
+
+    /// a list is about to be processed
+    fn on_list_start(&self, _stage: PipelineStage, _name: &str) {}
+
+    /// a list was processed or taken over from the last run
+    fn on_list_done(&self, _stage: PipelineStage, _name: &str, _status: Status) {}
+
+    /// all lists of a stage were processed
+    fn on_stage_done(&self, _stage: PipelineStage) {}
+
+    /// a list failed, `name` is None if the failure doesn't concern a single list
+    fn on_error(&self, _stage: PipelineStage, _name: Option<&str>, _code: ErrorCode, _error: &str) {
+    }
+}
+
+/// Observers passes events on to all registered observers
+#[derive(Clone, Default)]
+pub struct Observers(Vec<Arc<dyn Observer>>);
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+impl Observers {
+    /// registers an observer
+    ///
+    /// * `observer`: the observer to be notified
+    pub fn push(&mut self, observer: Arc<dyn Observer>) {
+        self.0.push(observer);
+    }
+
+    pub fn plan(&self, stage: PipelineStage, plan: &Plan) {
+        self.0.iter().for_each(|o| o.on_plan(stage, plan));
+    }
+
+    pub fn list_start(&self, stage: PipelineStage, name: &str) {
+        self.0.iter().for_each(|o| o.on_list_start(stage, name));
+    }
+
+    pub fn list_done(&self, stage: PipelineStage, name: &str, status: Status) {
+        self.0
+            .iter()
+            .for_each(|o| o.on_list_done(stage, name, status));
+    }
+
+    pub fn stage_done(&self, stage: PipelineStage) {
+        self.0.iter().for_each(|o| o.on_stage_done(stage));
+    }
+
+    pub fn error(&self, stage: PipelineStage, name: Option<&str>, code: ErrorCode, error: &str) {
+        self.0
+            .iter()
+            .for_each(|o| o.on_error(stage, name, code, error));
+    }
+}
+
+/// LogObserver writes the pipeline's events to the log
+#[derive(Debug, Default)]
+pub struct LogObserver;
+
+impl Observer for LogObserver {
+    fn on_plan(&self, stage: PipelineStage, plan: &Plan) {
+        info!("{}: {}", stage, plan);
+    }
+
+    fn on_list_start(&self, _stage: PipelineStage, name: &str) {
+        info!("Updated: {}", name);
+    }
+
+    fn on_list_done(&self, stage: PipelineStage, name: &str, status: Status) {
+        match status {
+            Status::Unchanged => info!("Unchanged: {}", name),
+            Status::Deferred => warn!("Deferred: {} - using the previous download", name),
+            Status::Stale => warn!("Stale: {} - using the previous download", name),
+            _ => debug!("{}: {} done", stage, name),
+        }
+    }
+
+    fn on_stage_done(&self, stage: PipelineStage) {
+        debug!("{} stage done", stage);
+    }
+
+    fn on_error(&self, _stage: PipelineStage, name: Option<&str>, code: ErrorCode, error: &str) {
+        match name {
+            Some(name) => error!("{} {} - {}", code, name, error),
+            None => error!("{} {}", code, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// records the events as strings
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Observer for Recorder {
+        fn on_plan(&self, stage: PipelineStage, plan: &Plan) {
+            self.0.lock().unwrap().push(format!("{stage} {plan}"));
+        }
+
+        fn on_list_done(&self, stage: PipelineStage, name: &str, status: Status) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{stage} {name} {status:?}"));
+        }
+
+        fn on_error(&self, stage: PipelineStage, name: Option<&str>, code: ErrorCode, _: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{stage} {} {code}", name.unwrap_or_default()));
+        }
+    }
+
+    #[test]
+    fn test_observers() {
+        let recorder = Arc::new(Recorder::default());
+        let mut observers = Observers::default();
+        observers.push(recorder.clone());
+        observers.push(Arc::new(LogObserver));
+
+        observers.list_start(PipelineStage::Download, "one");
+        observers.list_done(PipelineStage::Download, "one", Status::Unchanged);
+        observers.error(
+            PipelineStage::Extract,
+            Some("two"),
+            ErrorCode::RegexFailed,
+            "invalid regex",
+        );
+        observers.stage_done(PipelineStage::Extract);
+        observers.plan(
+            PipelineStage::Download,
+            &Plan {
+                updated: 12,
+                unchanged: 185,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "download one Unchanged",
+                "extract two HV2002",
+                "download 12 updated, 185 unchanged, 0 deferred, 0 stale, 0 failed"
+            ]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// AdblockAdapter translates a category into a filter list in Adblock Plus syntax as
+/// understood by Adblock Plus, uBlock Origin and AdGuard
+pub struct AdblockAdapter {
+    pub category: String,
+}
+
+impl OutputAdapter for AdblockAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some(format!(
+            "[Adblock Plus 2.0]\n! Title: harvester {}\n! Expires: 1 day\n",
+            self.category
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            // a domain rule matches the subdomains as well
+            Entry::Domain(domain) | Entry::Wildcard(domain) => {
+                Ok(format!("||{domain}^$important\n"))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Adblock Plus filters can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_adblock_adapter() {
+        let input_data = "malicious.com\n*.unwanted.net\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = AdblockAdapter {
+            category: "malware".to_string(),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        assert_eq!(
+            String::from_utf8_lossy(&o),
+            "[Adblock Plus 2.0]\n! Title: harvester malware\n! Expires: 1 day\n\
+             ||malicious.com^$important\n||unwanted.net^$important\n"
+        );
+        assert_eq!(report.written, 2);
+        assert_eq!(report.dropped[&EntryKind::Ip], 1);
+    }
+}
@@ -0,0 +1,232 @@
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Config, ALLOW_CATEGORY},
+    entry::{Entry, EntryKind},
+    io::atomic_file::AtomicFile,
+};
+
+use super::{OutputAdapter, OutputType};
+
+/// BlockyConfig configures the files written by the Blocky output format
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct BlockyConfig {
+    /// the fragment referencing the written lists is written to this path, the
+    /// categories are written as plain lists then instead of inline fragments
+    pub references: Option<String>,
+}
+
+/// BlockyAdapter translates a category into a fragment of the Blocky configuration
+/// listing the domains inline or into a plain list to be referenced by the fragment
+/// written by [`write_fragment`]
+pub struct BlockyAdapter {
+    pub category: String,
+    /// write the domains inline into a configuration fragment
+    pub inline: bool,
+}
+
+impl BlockyAdapter {
+    /// creates the adapter for a category
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> Self {
+        Self {
+            category: category.to_string(),
+            inline: config
+                .blocky
+                .as_ref()
+                .is_none_or(|b| b.references.is_none()),
+        }
+    }
+}
+
+impl OutputAdapter for BlockyAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard]
+    }
+
+    fn header(&self) -> Option<String> {
+        if !self.inline {
+            return None;
+        }
+        Some(format!(
+            "blocking:\n  blackLists:\n    {}:\n      - |\n",
+            serde_json::to_string(&self.category).ok()?
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let indent = if self.inline { "        " } else { "" };
+        match entry {
+            Entry::Domain(_) | Entry::Wildcard(_) => Ok(format!("{indent}{entry}\n")),
+            _ => Err(anyhow::anyhow!(
+                "Blocky lists can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        // Blocky skips comments in inline lists as well
+        Some("#")
+    }
+}
+
+/// the part of Blocky's configuration written by [`write_fragment`]
+#[derive(Debug, Serialize)]
+struct Fragment {
+    blocking: Blocking,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Blocking {
+    black_lists: BTreeMap<String, Vec<String>>,
+    client_groups_block: BTreeMap<String, Vec<String>>,
+}
+
+/// Writes a fragment of Blocky's configuration referencing the lists written in the
+/// Blocky output format and blocking all categories for the default client group.
+/// Returns the number of referenced categories.
+///
+/// * `config`: the configuration the categories are defined in
+/// * `path`: the file the fragment is written to
+pub fn write_fragment(config: &Config, path: &str) -> anyhow::Result<usize> {
+    let mut categories = config.get_tags();
+    categories.retain(|t| {
+        t != ALLOW_CATEGORY && config.category_formats(t).contains(&OutputType::Blocky)
+    });
+    categories.sort();
+    let dirs: Vec<_> = config
+        .output_paths()
+        .into_iter()
+        .filter(|(format, _)| *format == OutputType::Blocky)
+        .map(|(_, dir)| fs::canonicalize(&dir).unwrap_or(dir))
+        .collect();
+    let black_lists: BTreeMap<String, Vec<String>> = categories
+        .iter()
+        .map(|category| {
+            let files = dirs
+                .iter()
+                .map(|dir| {
+                    dir.join(config.output_file_name(&OutputType::Blocky, category))
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect();
+            (category.clone(), files)
+        })
+        .collect();
+    let fragment = Fragment {
+        blocking: Blocking {
+            black_lists,
+            client_groups_block: BTreeMap::from([("default".to_string(), categories.clone())]),
+        },
+    };
+    let contents = serde_yaml::to_string(&fragment)?;
+
+    let path = Path::new(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| "could not create fragment directory")?;
+    }
+    let mut file = AtomicFile::create(path.to_path_buf())?;
+    if let Err(e) = file.write_all(contents.as_bytes()) {
+        file.discard();
+        return Err(e).with_context(|| format!("could not write {}", path.display()));
+    }
+    if let Err(e) = file.commit() {
+        file.discard();
+        return Err(e);
+    }
+    Ok(categories.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        filter_list::FilterList,
+        output::write_outputs,
+        tests::helper::{cache_file_creator::CacheFileCreator, cursor_input::CursorInput},
+    };
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    /// writes the input with the adapter and returns the written file
+    async fn write(adapter: BlockyAdapter, input_data: &str) -> String {
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+        write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await;
+        let o = output.lock().await.clone().into_inner();
+        String::from_utf8_lossy(&o).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_blocky_adapter() {
+        let adapter = |inline| BlockyAdapter {
+            category: "malware".to_string(),
+            inline,
+        };
+        let input_data = "malicious.com\n*.unwanted.net\n10.0.0.1\n";
+        let expect = "blocking:
+  blackLists:
+    \"malware\":
+      - |
+        malicious.com
+        *.unwanted.net
+";
+        assert_eq!(write(adapter(true), input_data).await, expect);
+        assert_eq!(
+            write(adapter(false), input_data).await,
+            "malicious.com\n*.unwanted.net\n"
+        );
+    }
+
+    #[test]
+    fn test_write_fragment() {
+        let cache = CacheFileCreator::new("test_write_fragment", "in", "out");
+        let mut config = cache.new_test_config();
+        config.output_dir = vec!["/srv/harvester".to_string()];
+        config.output_format = vec![OutputType::Blocky];
+        let list = |tag: &str| FilterList {
+            id: tag.to_string(),
+            tags: vec![tag.to_string()],
+            ..Default::default()
+        };
+        config.lists = vec![list("phishing"), list("malware"), list(ALLOW_CATEGORY)];
+
+        let path = Path::new(&config.cache_dir).join("blocky.yml");
+        let written = write_fragment(&config, &path.to_string_lossy()).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "blocking:
+  blackLists:
+    malware:
+    - /srv/harvester/malware
+    phishing:
+    - /srv/harvester/phishing
+  clientGroupsBlock:
+    default:
+    - malware
+    - phishing
+"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::{f64::consts::LN_2, path::Path, sync::Mutex};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::OutputAdapter;
+
+/// the first bytes of every Bloom filter file
+const MAGIC: &[u8; 4] = b"HVBF";
+/// the version of the file format
+const VERSION: u8 = 1;
+/// the number of bytes preceding the bits of the filter
+const HEADER_LEN: usize = 24;
+/// the most hash functions a filter uses, more don't pay off for any sensible rate
+const MAX_HASHES: u8 = 32;
+
+/// BloomConfig configures the filters written by the Bloom output format
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BloomConfig {
+    /// the share of the entries not in a category which are taken for entries of it
+    #[serde(default = "default_false_positive_rate")]
+    pub false_positive_rate: f64,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            false_positive_rate: default_false_positive_rate(),
+        }
+    }
+}
+
+fn default_false_positive_rate() -> f64 {
+    0.001
+}
+
+/// returns the two hashes the positions of an entry's bits are derived from
+///
+/// * `entry`: the entry as written in the category list
+fn hashes(entry: &str) -> (u64, u64) {
+    let digest = Sha256::digest(entry.as_bytes());
+    let h1 = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap());
+    (h1, h2)
+}
+
+/// returns the position of the `i`th bit of an entry in a filter of `bits` bits
+fn position((h1, h2): (u64, u64), i: u8, bits: u64) -> u64 {
+    h1.wrapping_add((i as u64).wrapping_mul(h2)) % bits
+}
+
+/// BloomFilter is a set of entries which takes a small share of the entries not in
+/// it for members but never misses a member. Its file starts with a header of 24
+/// bytes, the numbers in little endian:
+///
+/// | bytes | content                                   |
+/// |-------|-------------------------------------------|
+/// | 0-3   | `HVBF`                                    |
+/// | 4     | format version, 1                         |
+/// | 5     | number of hash functions `k`              |
+/// | 6-7   | reserved, 0                               |
+/// | 8-15  | number of bits `m`                        |
+/// | 16-23 | number of entries                         |
+///
+/// followed by the `m` bits, bit `i` is bit `i % 8` of byte `i / 8`. The bits of an
+/// entry are `(h1 + i * h2) % m` for `i` in `0..k`, `h1` and `h2` being the first
+/// two little endian 64 bit numbers of the entry's SHA-256 digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    hash_count: u8,
+    bit_count: u64,
+    entries: u64,
+    bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for a number of entries, more entries raise the
+    /// false positive rate.
+    ///
+    /// * `entries`: the number of entries the filter is going to hold
+    /// * `false_positive_rate`: the share of non-members taken for members
+    pub fn new(entries: usize, false_positive_rate: f64) -> Self {
+        let n = entries.max(1) as f64;
+        let bit_count = (-n * false_positive_rate.ln() / (LN_2 * LN_2)).ceil() as u64;
+        let bit_count = bit_count.max(8);
+        let hash_count = (bit_count as f64 / n * LN_2).round() as u8;
+        Self {
+            hash_count: hash_count.clamp(1, MAX_HASHES),
+            bit_count,
+            entries: 0,
+            bits: vec![0; bit_count.div_ceil(8) as usize],
+        }
+    }
+
+    /// Reads a filter written by the Bloom output format. Fails if the file is no
+    /// such filter.
+    ///
+    /// * `path`: the filter's file
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+        Self::from_bytes(&data).with_context(|| format!("{} is no Bloom filter", path.display()))
+    }
+
+    /// returns the filter serialized in the file format
+    ///
+    /// * `data`: the content of a filter's file
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < HEADER_LEN || &data[..4] != MAGIC {
+            return Err(anyhow::anyhow!("missing header"));
+        }
+        if data[4] != VERSION {
+            return Err(anyhow::anyhow!("unsupported version {}", data[4]));
+        }
+        let hash_count = data[5];
+        let bit_count = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        let entries = u64::from_le_bytes(data[16..24].try_into().unwrap());
+        let bits = data[HEADER_LEN..].to_vec();
+        if hash_count == 0 || bit_count == 0 || bits.len() as u64 != bit_count.div_ceil(8) {
+            return Err(anyhow::anyhow!("inconsistent header"));
+        }
+        Ok(Self {
+            hash_count,
+            bit_count,
+            entries,
+            bits,
+        })
+    }
+
+    /// returns the filter in the file format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_LEN + self.bits.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&[VERSION, self.hash_count, 0, 0]);
+        data.extend_from_slice(&self.bit_count.to_le_bytes());
+        data.extend_from_slice(&self.entries.to_le_bytes());
+        data.extend_from_slice(&self.bits);
+        data
+    }
+
+    /// returns the number of entries added to the filter
+    pub fn len(&self) -> u64 {
+        self.entries
+    }
+
+    /// adds an entry by its hashes
+    ///
+    /// * `hashes`: the entry's hashes
+    fn insert_hashes(&mut self, hashes: (u64, u64)) {
+        for i in 0..self.hash_count {
+            let bit = position(hashes, i, self.bit_count);
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        self.entries += 1;
+    }
+
+    /// returns true if the entry is probably in the filter, false if it's certainly
+    /// not
+    ///
+    /// * `entry`: the entry as written in the category list, e.g. `malicious.com`,
+    ///   `*.unwanted.net` or `10.0.0.1`
+    pub fn contains(&self, entry: &str) -> bool {
+        let hashes = hashes(entry);
+        (0..self.hash_count).all(|i| {
+            let bit = position(hashes, i, self.bit_count);
+            self.bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+        })
+    }
+
+    /// Returns true if a query for the domain is probably blocked, i.e. the filter
+    /// contains the domain itself or a wildcard of the domain or one of its parent
+    /// domains.
+    ///
+    /// * `domain`: the queried domain
+    pub fn matches_domain(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        if self.contains(&domain) {
+            return true;
+        }
+        let mut suffix = domain.as_str();
+        loop {
+            if self.contains(&format!("*.{suffix}")) {
+                return true;
+            }
+            match suffix.split_once('.') {
+                Some((_, parent)) => suffix = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// BloomAdapter translates a category into a Bloom filter, for resolvers on devices
+/// which can't hold the category's entries as strings. The filter is written once
+/// all entries are known, as it's sized by their number.
+pub struct BloomAdapter {
+    false_positive_rate: f64,
+    /// the hashes of the entries written so far
+    hashes: Mutex<Vec<(u64, u64)>>,
+}
+
+impl BloomAdapter {
+    /// creates the adapter, fails if the configured false positive rate is out of range
+    ///
+    /// * `config`: the configuration the category is defined in
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let rate = config.bloom.clone().unwrap_or_default().false_positive_rate;
+        if !(rate > 0.0 && rate < 1.0) {
+            return Err(anyhow::anyhow!(
+                "bloom: false_positive_rate must be between 0 and 1, got {rate}"
+            ));
+        }
+        Ok(Self {
+            false_positive_rate: rate,
+            hashes: Mutex::new(vec![]),
+        })
+    }
+}
+
+impl OutputAdapter for BloomAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard, EntryKind::Ip]
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(_) | Entry::Wildcard(_) | Entry::Ip(_) => {
+                self.hashes.lock().unwrap().push(hashes(&entry.to_string()));
+                Ok(String::new())
+            }
+            _ => Err(anyhow::anyhow!(
+                "Bloom filters can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn footer_bytes(&self) -> Option<Vec<u8>> {
+        let hashes = self.hashes.lock().unwrap();
+        let mut filter = BloomFilter::new(hashes.len(), self.false_positive_rate);
+        hashes.iter().for_each(|h| filter.insert_hashes(*h));
+        Some(filter.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        output::write_outputs,
+        tests::helper::{cache_file_creator::CacheFileCreator, cursor_input::CursorInput},
+    };
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        assert_eq!(filter.hash_count, 7);
+        assert_eq!(filter.bit_count, 9586);
+        for i in 0..1000 {
+            filter.insert_hashes(hashes(&format!("domain{i}.one")));
+        }
+        assert!((0..1000).all(|i| filter.contains(&format!("domain{i}.one"))));
+        let false_positives = (0..10000)
+            .filter(|i| filter.contains(&format!("domain{i}.two")))
+            .count();
+        assert!(false_positives < 200, "{false_positives} false positives");
+
+        let data = filter.to_bytes();
+        assert_eq!(&data[..8], b"HVBF\x01\x07\x00\x00");
+        assert_eq!(BloomFilter::from_bytes(&data).unwrap(), filter);
+        assert!(BloomFilter::from_bytes(&data[..data.len() - 1]).is_err());
+        assert!(BloomFilter::from_bytes(b"malicious.com\n").is_err());
+
+        let empty = BloomFilter::new(0, 0.01);
+        assert!(!empty.contains("malicious.com"));
+    }
+
+    #[tokio::test]
+    async fn test_bloom_adapter() {
+        let cache = CacheFileCreator::new("test_bloom_adapter", "in", "out");
+        let mut config = cache.new_test_config();
+        let input_data =
+            "malicious.com\n*.unwanted.net\nhttps://phishing.org/login\n10.0.0.1\n10.0.0.0/8\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let report = write_outputs(
+            vec![(
+                Box::new(BloomAdapter::new(&config).unwrap()),
+                output.clone(),
+            )],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        assert_eq!(report.written, 4);
+        assert_eq!(report.dropped[&EntryKind::Cidr], 1);
+
+        let o = output.lock().await.clone().into_inner();
+        let filter = BloomFilter::from_bytes(&o).unwrap();
+        assert_eq!(filter.len(), 4);
+        assert!(filter.contains("10.0.0.1"));
+        assert!(filter.matches_domain("malicious.com."));
+        assert!(filter.matches_domain("unwanted.net"));
+        assert!(filter.matches_domain("ads.unwanted.net"));
+        assert!(filter.matches_domain("phishing.org"));
+        assert!(!filter.matches_domain("ads.malicious.com"));
+
+        config.bloom = Some(BloomConfig {
+            false_positive_rate: 1.0,
+        });
+        assert!(BloomAdapter::new(&config).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::{OutputAdapter, OutputType};
+
+/// CoreDnsConfig configures the files written by the CoreDns output format
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CoreDnsConfig {
+    /// the address the category's domains resolve to
+    #[serde(default = "default_sink_ip")]
+    pub sink_ip: IpAddr,
+    /// the address the domains resolve to for AAAA queries, no IPv6 entries are
+    /// written if not set
+    pub sink_ipv6: Option<IpAddr>,
+    /// start the file with a comment showing the Corefile block loading it
+    #[serde(default)]
+    pub header: bool,
+}
+
+fn default_sink_ip() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+}
+
+impl Default for CoreDnsConfig {
+    fn default() -> Self {
+        Self {
+            sink_ip: default_sink_ip(),
+            sink_ipv6: None,
+            header: false,
+        }
+    }
+}
+
+/// CoreDnsAdapter translates a category into a hosts file for the `hosts` plugin of
+/// CoreDNS, written to `<category>.hosts`
+pub struct CoreDnsAdapter {
+    pub category: String,
+    /// the file's path as referenced by the header, no header is written if not set
+    pub path: Option<String>,
+    pub sink_ip: IpAddr,
+    pub sink_ipv6: Option<IpAddr>,
+}
+
+impl CoreDnsAdapter {
+    /// creates the adapter for a category
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> Self {
+        let coredns = config.coredns.clone().unwrap_or_default();
+        let path = match coredns.header {
+            true => config
+                .output_paths()
+                .into_iter()
+                .find(|(format, _)| *format == OutputType::CoreDns)
+                .map(|(format, dir)| {
+                    dir.join(config.output_file_name(&format, category))
+                        .to_string_lossy()
+                        .to_string()
+                }),
+            false => None,
+        };
+        Self {
+            category: category.to_string(),
+            path,
+            sink_ip: coredns.sink_ip,
+            sink_ipv6: coredns.sink_ipv6,
+        }
+    }
+}
+
+impl OutputAdapter for CoreDnsAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain]
+    }
+
+    fn header(&self) -> Option<String> {
+        let path = self.path.as_ref()?;
+        Some(format!(
+            "# harvester category {}, loaded in the Corefile's server block with\n\
+             # hosts {} {{\n\
+             #     fallthrough\n\
+             # }}\n",
+            self.category, path
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(domain) => Ok(match self.sink_ipv6 {
+                Some(ipv6) => format!("{} {domain}\n{ipv6} {domain}\n", self.sink_ip),
+                None => format!("{} {domain}\n", self.sink_ip),
+            }),
+            _ => Err(anyhow::anyhow!(
+                "CoreDNS hosts files can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        Some("#")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_coredns_adapter() {
+        let input_data = "malicious.com\nhttps://unwanted.net/path\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = CoreDnsAdapter {
+            category: "malware".to_string(),
+            path: Some("/etc/coredns/malware.hosts".to_string()),
+            sink_ip: "10.53.0.1".parse().unwrap(),
+            sink_ipv6: Some("::".parse().unwrap()),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "# harvester category malware, loaded in the Corefile's server block with
+# hosts /etc/coredns/malware.hosts {
+#     fallthrough
+# }
+10.53.0.1 malicious.com
+:: malicious.com
+10.53.0.1 unwanted.net
+:: unwanted.net
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 2);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::{siem_lookup::field, sources::EntrySources, OutputAdapter};
+
+/// CsvAdapter translates a category into a CSV file naming the lists every entry
+/// was taken from and the tags of these lists, for auditing where an entry came from
+pub struct CsvAdapter {
+    pub category: String,
+    /// the lists the entries were taken from
+    pub sources: EntrySources,
+    /// the tags of every list by list id
+    pub tags: HashMap<String, Vec<String>>,
+}
+
+impl CsvAdapter {
+    /// creates the adapter for a category and loads the sources of its entries
+    /// recorded by the categorize stage
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> Self {
+        Self {
+            category: category.to_string(),
+            sources: EntrySources::load(config, category),
+            tags: config
+                .lists
+                .iter()
+                .map(|l| (l.id.clone(), l.tags.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl OutputAdapter for CsvAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+            EntryKind::Url,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some("entry,type,lists,tags,category\n".to_string())
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let lists = self.sources.lists(entry);
+        let tags: BTreeSet<&String> = lists
+            .iter()
+            .filter_map(|id| self.tags.get(id))
+            .flatten()
+            .collect();
+        Ok(format!(
+            "{},{},{},{},{}\n",
+            field(&entry.to_string()),
+            entry.kind().to_string().to_lowercase(),
+            field(&lists.join(";")),
+            field(
+                &tags
+                    .into_iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(";")
+            ),
+            field(&self.category)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_csv_adapter() {
+        let input_data = "domain.one\n10.0.0.0/8\nhttps://domain.two/a,b\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = CsvAdapter {
+            category: "malware".to_string(),
+            sources: EntrySources {
+                by_entry: HashMap::from([(
+                    "domain.one".to_string(),
+                    vec!["one".to_string(), "two".to_string()],
+                )]),
+                fallback: vec!["two".to_string()],
+            },
+            tags: HashMap::from([
+                (
+                    "one".to_string(),
+                    vec!["malware".to_string(), "phishing".to_string()],
+                ),
+                ("two".to_string(), vec!["malware".to_string()]),
+            ]),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "entry,type,lists,tags,category
+domain.one,domain,one;two,malware;phishing,malware
+10.0.0.0/8,cidr,two,malware,malware
+\"https://domain.two/a,b\",url,two,malware,malware
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 3);
+    }
+}
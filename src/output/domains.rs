@@ -0,0 +1,56 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// DomainsAdapter writes one domain per line without any decoration
+pub struct DomainsAdapter;
+
+impl OutputAdapter for DomainsAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain]
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(domain) => Ok(format!("{domain}\n")),
+            _ => Err(anyhow::anyhow!(
+                "domain list can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_domains_adapter() {
+        let input_data = "domain.one\nhttps://domain.two/path\n*.domain.three\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let report = write_outputs(
+            vec![(Box::new(DomainsAdapter), output.clone())],
+            "test".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        assert_eq!(String::from_utf8_lossy(&o), "domain.one\ndomain.two\n");
+        assert_eq!(report.written, 2);
+    }
+}
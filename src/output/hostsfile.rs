@@ -1,62 +1,77 @@
-use std::{
-    io::Write,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-};
+use std::net::{IpAddr, Ipv4Addr};
 
-use futures::lock::Mutex;
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
 
-use crate::input::Input;
+use super::OutputAdapter;
 
-/// hostsfile_adapter translates the extracted URLs int a hosts file format
+/// HostsfileAdapter translates the extracted URLs into a hosts file format
 /// as found in /etc/hosts
-///
-/// * `reader`: data source that implements the Input trait
-/// * `writer`: data sink that implements std::io::Write
-/// * `cmd_rx`: channel listening for commands
-/// * `msg_tx`: channel for messaging
-pub async fn hostsfile_adapter(
-    reader: Arc<Mutex<dyn Input + Send>>,
-    writer: Arc<Mutex<dyn Write + Send>>,
-    is_processing: Arc<AtomicBool>,
-) {
-    loop {
-        if !is_processing.load(Ordering::SeqCst) {
-            return;
+pub struct HostsfileAdapter {
+    /// the address the domains resolve to
+    pub sink_ip: IpAddr,
+    /// the address of a second line per domain, for platforms only answering AAAA
+    /// queries from IPv6 entries
+    pub sink_ipv6: Option<IpAddr>,
+}
+
+impl HostsfileAdapter {
+    /// creates the adapter with the sink addresses of the configuration
+    ///
+    /// * `config`: the configuration the category is defined in
+    pub fn new(config: &Config) -> Self {
+        Self {
+            sink_ip: config.hosts_ip.unwrap_or(Ipv4Addr::UNSPECIFIED.into()),
+            sink_ipv6: config.hosts_ipv6,
+        }
+    }
+}
+
+impl Default for HostsfileAdapter {
+    fn default() -> Self {
+        Self {
+            sink_ip: Ipv4Addr::UNSPECIFIED.into(),
+            sink_ipv6: None,
         }
-        match reader.lock().await.chunk().await {
-            Ok(Some(chunk)) => {
-                let str_chunk = match String::from_utf8(chunk) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        warn!("{}", e);
-                        continue;
-                    }
-                };
-                let chunk = format!("0.0.0.0 {}\n", str_chunk.trim_end());
-                if let Err(e) = writer.lock().await.write_all(chunk.as_bytes()) {
-                    error!("{}", e);
-                }
-            }
-            Ok(None) => {
-                break;
-            }
-            Err(e) => {
-                error!("{}", e);
-                break;
-            }
+    }
+}
+
+impl OutputAdapter for HostsfileAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain]
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(domain) => Ok(match self.sink_ipv6 {
+                Some(ipv6) => format!("{} {domain}\n{ipv6} {domain}\n", self.sink_ip),
+                None => format!("{} {domain}\n", self.sink_ip),
+            }),
+            _ => Err(anyhow::anyhow!(
+                "hosts file can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
         }
     }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        Some("#")
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::helper::cursor_input::CursorInput;
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
 
     use super::*;
-    use std::io::Cursor;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
 
     #[tokio::test]
     async fn test_hostfile_adapter() {
@@ -67,10 +82,65 @@ mod tests {
         let output = Arc::new(Mutex::new(Cursor::new(vec![0, 32])));
         let is_processing = Arc::new(AtomicBool::new(true));
 
-        hostsfile_adapter(input, output.clone(), is_processing).await;
+        let report = write_outputs(
+            vec![(Box::new(HostsfileAdapter::default()), output.clone())],
+            "test".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
         let o = output.lock().await.clone().into_inner();
         let expect = "0.0.0.0 domain.one\n0.0.0.0 domain.two\n";
         let got = String::from_utf8_lossy(&o);
         assert_eq!(got, expect);
+        assert_eq!(report.written, 2);
+    }
+
+    #[test]
+    fn test_hostfile_adapter_sink_ips() {
+        let mut config = crate::tests::helper::cache_file_creator::CacheFileCreator::new(
+            "test_hostfile_adapter_sink_ips",
+            "in",
+            "out",
+        )
+        .new_test_config();
+        config.hosts_ip = Some("127.0.0.1".parse().unwrap());
+        config.hosts_ipv6 = Some("::".parse().unwrap());
+        let domain: Entry = "domain.one".parse().unwrap();
+        assert_eq!(
+            HostsfileAdapter::new(&config).entry(&domain).unwrap(),
+            "127.0.0.1 domain.one\n:: domain.one\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hostfile_adapter_conversion() {
+        let input_data = "http://domain.one/path\n*.domain.two\n10.0.0.1\ndomain.three\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let report = write_outputs(
+            vec![(Box::new(HostsfileAdapter::default()), output.clone())],
+            "test".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        // the URL's host is blocked, wildcards and IPs can't be represented
+        let expect = "0.0.0.0 domain.one\n0.0.0.0 domain.three\n";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 2);
+        assert_eq!(
+            report.converted.get(&(EntryKind::Url, EntryKind::Domain)),
+            Some(&1)
+        );
+        assert_eq!(report.dropped.get(&EntryKind::Wildcard), Some(&1));
+        assert_eq!(report.dropped.get(&EntryKind::Ip), Some(&1));
     }
 }
@@ -0,0 +1,97 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// IpsetAdapter translates a category into the input of `ipset restore`. A hash:net
+/// set holds the addresses of one family only, so the IPv6 entries go into a second
+/// set named after the category with a `-v6` suffix.
+pub struct IpsetAdapter {
+    pub category: String,
+}
+
+impl IpsetAdapter {
+    /// returns the name of the set an entry belongs to
+    ///
+    /// * `ipv4`: true if the entry is an IPv4 address or network
+    fn set_name(&self, ipv4: bool) -> String {
+        if ipv4 {
+            self.category.clone()
+        } else {
+            format!("{}-v6", self.category)
+        }
+    }
+}
+
+impl OutputAdapter for IpsetAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Ip, EntryKind::Cidr]
+    }
+
+    fn header(&self) -> Option<String> {
+        // -exist allows restoring into the sets of a previous run
+        Some(format!(
+            "create {} hash:net family inet -exist\ncreate {} hash:net family inet6 -exist\n",
+            self.set_name(true),
+            self.set_name(false)
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Ip(ip) | Entry::Cidr(ip, _) => Ok(format!(
+                "add {} {} -exist\n",
+                self.set_name(ip.is_ipv4()),
+                entry
+            )),
+            _ => Err(anyhow::anyhow!(
+                "ipset can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_ipset_adapter() {
+        let input_data = "10.0.0.1\n192.168.0.0/16\n2001:db8::/32\nmalicious.com\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = IpsetAdapter {
+            category: "malware".to_string(),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        assert_eq!(
+            String::from_utf8_lossy(&o),
+            "create malware hash:net family inet -exist\n\
+             create malware-v6 hash:net family inet6 -exist\n\
+             add malware 10.0.0.1 -exist\n\
+             add malware 192.168.0.0/16 -exist\n\
+             add malware-v6 2001:db8::/32 -exist\n"
+        );
+        assert_eq!(report.written, 3);
+        assert_eq!(report.dropped[&EntryKind::Domain], 1);
+    }
+}
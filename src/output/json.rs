@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::OutputAdapter;
+
+/// JsonConfig configures the documents written by the Json output format
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct JsonConfig {
+    /// wrap the entries in an object along with the category's name, the time of
+    /// generation, the run's id and the number of entries
+    #[serde(default)]
+    pub metadata: bool,
+}
+
+/// JsonAdapter translates a category into a JSON array of its entries or an object
+/// containing the array and the category's metadata
+pub struct JsonAdapter {
+    pub category: String,
+    pub metadata: bool,
+    /// id of the run writing the document
+    pub run_id: String,
+    /// number of entries written so far
+    pub count: AtomicUsize,
+}
+
+impl JsonAdapter {
+    /// creates the adapter for a category
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> Self {
+        Self {
+            category: category.to_string(),
+            metadata: config.json.as_ref().is_some_and(|j| j.metadata),
+            run_id: config.run_id.clone(),
+            count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl OutputAdapter for JsonAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+            EntryKind::Url,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        if !self.metadata {
+            return Some("[".to_string());
+        }
+        Some(format!(
+            "{{\"category\":{},\"generated\":\"{}\",\"run\":{},\"entries\":[",
+            serde_json::to_string(&self.category).ok()?,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            serde_json::to_string(&self.run_id).ok()?
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let separator = match self.count.fetch_add(1, Ordering::SeqCst) {
+            0 => "",
+            _ => ",",
+        };
+        Ok(format!(
+            "{}\n  {}",
+            separator,
+            serde_json::to_string(&entry.to_string())?
+        ))
+    }
+
+    fn footer(&self) -> Option<String> {
+        let count = self.count.load(Ordering::SeqCst);
+        let newline = if count > 0 { "\n" } else { "" };
+        if !self.metadata {
+            return Some(format!("{newline}]\n"));
+        }
+        Some(format!("{newline}],\"count\":{count}}}\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use serde_json::Value;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    /// writes the input with the adapter and returns the written document
+    async fn write(adapter: JsonAdapter, input_data: &str) -> String {
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+        write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await;
+        let o = output.lock().await.clone().into_inner();
+        String::from_utf8_lossy(&o).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_json_adapter() {
+        let adapter = |metadata| JsonAdapter {
+            category: "malware".to_string(),
+            metadata,
+            run_id: "3f2a1b2c-0000-4000-8000-000000000000".to_string(),
+            count: AtomicUsize::new(0),
+        };
+        let input_data = "malicious.com\n10.0.0.0/8\nhttps://unwanted.net/\"x\"\n";
+        assert_eq!(
+            write(adapter(false), input_data).await,
+            "[\n  \"malicious.com\",\n  \"10.0.0.0/8\",\n  \"https://unwanted.net/%22x%22\"\n]\n"
+        );
+        assert_eq!(write(adapter(false), "").await, "[]\n");
+
+        let document: Value =
+            serde_json::from_str(&write(adapter(true), input_data).await).unwrap();
+        assert_eq!(document["category"], "malware");
+        assert_eq!(document["count"], 3);
+        assert_eq!(document["run"], "3f2a1b2c-0000-4000-8000-000000000000");
+        assert_eq!(document["entries"][0], "malicious.com");
+        assert!(document["generated"].as_str().unwrap().ends_with('Z'));
+        let empty: Value = serde_json::from_str(&write(adapter(true), "").await).unwrap();
+        assert_eq!(empty["entries"], serde_json::json!([]));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// KnotResolverAdapter translates a category into a Knot Resolver policy denying
+/// the category's domains and all of their subdomains. The file is loaded into the
+/// resolver's configuration with `dofile`.
+pub struct KnotResolverAdapter;
+
+impl OutputAdapter for KnotResolverAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some("policy.add(policy.suffix(policy.DENY, policy.todnames({\n".to_string())
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            // suffix rules always match the subdomains
+            Entry::Domain(domain) | Entry::Wildcard(domain) => Ok(format!("  '{domain}',\n")),
+            _ => Err(anyhow::anyhow!(
+                "Knot Resolver policies can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn footer(&self) -> Option<String> {
+        Some("})))\n".to_string())
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        Some("--")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_knot_resolver_adapter() {
+        let input_data = "malicious.com\n*.unwanted.net\nhttps://phishing.org/login\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let report = write_outputs(
+            vec![(Box::new(KnotResolverAdapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "policy.add(policy.suffix(policy.DENY, policy.todnames({
+  'malicious.com',
+  'unwanted.net',
+  'phishing.org',
+})))
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 3);
+        assert_eq!(report.dropped[&EntryKind::Ip], 1);
+    }
+}
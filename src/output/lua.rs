@@ -1,74 +1,48 @@
-use std::{
-    io::Write,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-};
+use crate::entry::{Entry, EntryKind};
 
-use futures::lock::Mutex;
+use super::OutputAdapter;
 
-use crate::input::Input;
+/// LuaAdapter translates the extracted URLs into a lua module format
+pub struct LuaAdapter;
 
-/// lua_adapter translates the extracted URLs int a lua module format
-///
-/// * `reader`: data source that implements the Input trait
-/// * `writer`: data sink that implements std::io::Write
-/// * `cmd_rx`: channel listening for commands
-/// * `msg_tx`: channel for messaging
-pub async fn lua_adapter(
-    reader: Arc<Mutex<dyn Input + Send>>,
-    writer: Arc<Mutex<dyn Write + Send>>,
-    is_processing: Arc<AtomicBool>,
-) {
-    let mut worte_header = false;
-    loop {
-        if !is_processing.load(Ordering::SeqCst) {
-            return;
-        }
-        // write header line
-        if !worte_header {
-            if let Err(e) = writer.lock().await.write_all("return {\n".as_bytes()) {
-                error!("{}", e);
-            }
-            worte_header = true;
-        }
+impl OutputAdapter for LuaAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+            EntryKind::Url,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some("return {\n".to_string())
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        Ok(format!("  \"{entry}\",\n"))
+    }
+
+    fn footer(&self) -> Option<String> {
+        Some("}".to_string())
+    }
 
-        match reader.lock().await.chunk().await {
-            Ok(Some(chunk)) => {
-                let str_chunk = match String::from_utf8(chunk) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        warn!("{}", e);
-                        continue;
-                    }
-                };
-                let chunk = format!("  \"{}\",\n", str_chunk.trim_end());
-                if let Err(e) = writer.lock().await.write_all(chunk.as_bytes()) {
-                    error!("{}", e);
-                }
-            }
-            Ok(None) => {
-                // write footer line
-                if let Err(e) = writer.lock().await.write_all("}".as_bytes()) {
-                    error!("{}", e);
-                }
-                break;
-            }
-            Err(e) => {
-                error!("{}", e);
-                break;
-            }
-        }
+    fn comment_prefix(&self) -> Option<&'static str> {
+        Some("--")
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::helper::cursor_input::CursorInput;
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
 
     use super::*;
-    use std::io::Cursor;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
 
     #[tokio::test]
     async fn test_luafile_adapter() {
@@ -79,7 +53,15 @@ mod tests {
         let output = Arc::new(Mutex::new(Cursor::new(vec![0, 32])));
         let is_processing = Arc::new(AtomicBool::new(true));
 
-        lua_adapter(input, output.clone(), is_processing).await;
+        write_outputs(
+            vec![(Box::new(LuaAdapter), output.clone())],
+            "test".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
         let o = output.lock().await.clone().into_inner();
         let expect = "return {\n  \"domain.one\",\n  \"domain.two\",\n}";
         let got = String::from_utf8_lossy(&o);
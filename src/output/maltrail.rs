@@ -0,0 +1,89 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// MaltrailAdapter translates a category into a Maltrail trails file. Maltrail takes
+/// the trails' description from the file name which is the category's name.
+pub struct MaltrailAdapter {
+    pub category: String,
+}
+
+impl OutputAdapter for MaltrailAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Url,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some(format!("# harvester category {}\n\n", self.category))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            // Maltrail matches the subdomains of domain trails anyway
+            Entry::Domain(domain) | Entry::Wildcard(domain) => Ok(format!("{domain}\n")),
+            Entry::Ip(ip) => Ok(format!("{ip}\n")),
+            // URL trails are written without their scheme
+            Entry::Url(url) => {
+                let url = url.as_str();
+                Ok(format!(
+                    "{}\n",
+                    url.split_once("://").map_or(url, |(_, u)| u)
+                ))
+            }
+            Entry::Cidr(_, _) => Err(anyhow::anyhow!(
+                "Maltrail trails can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_maltrail_adapter() {
+        let input_data =
+            "domain.one\n*.domain.two\n10.0.0.0/8\n10.0.0.1\nhttp://domain.three/x.exe\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = MaltrailAdapter {
+            category: "malware".to_string(),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "# harvester category malware
+
+domain.one
+domain.two
+10.0.0.1
+domain.three/x.exe
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.dropped.get(&EntryKind::Cidr), Some(&1));
+    }
+}
@@ -1,38 +1,753 @@
 use std::{
-    fs::File,
-    pin::Pin,
-    sync::{atomic::AtomicBool, Arc},
+    collections::BTreeMap,
+    fmt::Display,
+    io::Write,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use futures::{lock::Mutex, Future};
+use chrono::{SecondsFormat, Utc};
+use futures::lock::Mutex;
 use serde::{Deserialize, Serialize};
 
-use crate::input::file::FileInput;
+use crate::{
+    config::{Config, ALLOW_CATEGORY, ALL_CATEGORY},
+    entry::{Entry, EntryKind},
+    error_code::ErrorCode,
+    input::Input,
+    io::IoFailure,
+};
 
-use self::{hostsfile::hostsfile_adapter, lua::lua_adapter};
+use self::{
+    adblock::AdblockAdapter,
+    blocky::BlockyAdapter,
+    bloom::BloomAdapter,
+    coredns::CoreDnsAdapter,
+    csv::CsvAdapter,
+    domains::DomainsAdapter,
+    hostsfile::HostsfileAdapter,
+    ipset::IpsetAdapter,
+    json::JsonAdapter,
+    knot::KnotResolverAdapter,
+    lua::LuaAdapter,
+    maltrail::MaltrailAdapter,
+    powerdns::PowerDnsAdapter,
+    routeros::RouterOsAdapter,
+    siem_lookup::SiemLookupAdapter,
+    sigma::SigmaAdapter,
+    smartdns::SmartDnsAdapter,
+    suricata::{SuricataAdapter, SuricataDatasetAdapter},
+    template::TemplateAdapter,
+    wildcard::WildcardDomains,
+    zeek::ZeekAdapter,
+};
 
+mod adblock;
+pub mod blocky;
+pub mod bloom;
+pub mod coredns;
+mod csv;
+mod domains;
 mod hostsfile;
+mod ipset;
+pub mod json;
+mod knot;
 mod lua;
+mod maltrail;
+mod powerdns;
+mod routeros;
+mod siem_lookup;
+mod sigma;
+mod smartdns;
+pub mod sources;
+pub mod suricata;
+pub mod template;
+pub mod wildcard;
+pub mod zeek;
 
 /// OutputType represents a result format for the created block lists
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum OutputType {
     /// Lua module format
     Lua,
     /// Hostsfile format as found in /etc/hosts
     Hostsfile,
+    /// one domain per line
+    Domains,
+    /// Sigma rule matching DNS queries for the category's domains
+    Sigma,
+    /// CSV lookup file for SIEMs consuming CEF or LEEF events
+    SiemLookup,
+    /// Suricata rules, one per entry
+    Suricata,
+    /// Suricata dataset matching DNS queries
+    SuricataDataset,
+    /// Zeek Intelligence Framework file
+    Zeek,
+    /// Maltrail trails file
+    Maltrail,
+    /// filter list in Adblock Plus syntax for browser blockers
+    Adblock,
+    /// sets of IP addresses and networks in the format of `ipset restore`
+    Ipset,
+    /// JSON array of the entries, optionally wrapped in an object with metadata
+    Json,
+    /// CSV file naming the lists and tags every entry was taken from
+    Csv,
+    /// MikroTik RouterOS script adding static DNS entries and address list entries
+    RouterOs,
+    /// Knot Resolver policy denying the category's domains and their subdomains
+    KnotResolver,
+    /// hosts file for the `hosts` plugin of CoreDNS
+    CoreDns,
+    /// PowerDNS Recursor Lua script blocking the category's domains in `preresolve`
+    PowerDns,
+    /// fragment of the Blocky configuration or list referenced by it
+    Blocky,
+    /// smartdns `address` rules answering the queries for the category's domains with SOA
+    SmartDns,
+    /// any format defined by the configured Handlebars templates
+    Template,
+    /// binary Bloom filter of the category's entries for memory constrained resolvers
+    Bloom,
 }
 
 impl OutputType {
-    pub fn get_adapter<'a>(
-        &self,
-        reader: Arc<Mutex<FileInput>>,
-        writer: Arc<Mutex<File>>,
-        is_processing: Arc<AtomicBool>,
-    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+    /// the name of the directory the format's files are written to if several
+    /// output formats are configured
+    pub fn dir_name(&self) -> String {
+        self.to_string().to_lowercase()
+    }
+
+    /// returns the name of the file a category is written to in this format
+    ///
+    /// * `category`: the name of the category
+    pub fn file_name(&self, category: &str) -> String {
         match self {
-            OutputType::Lua => Box::pin(lua_adapter(reader, writer, is_processing)),
-            OutputType::Hostsfile => Box::pin(hostsfile_adapter(reader, writer, is_processing)),
+            // matches the file names the zone blocks of a Corefile refer to
+            OutputType::CoreDns => format!("{category}.hosts"),
+            OutputType::Bloom => format!("{category}.bloom"),
+            _ => category.to_string(),
+        }
+    }
+
+    /// returns true if the format names the lists the entries were taken from, which
+    /// the categorize stage records for it
+    pub fn needs_sources(&self) -> bool {
+        matches!(self, OutputType::Zeek | OutputType::Csv)
+    }
+
+    /// returns the adapter translating the entries of a category into this output
+    /// format, fails if the format's settings are broken
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the name of the category being written
+    pub fn get_adapter(
+        &self,
+        config: &Config,
+        category: &str,
+    ) -> anyhow::Result<Box<dyn OutputAdapter>> {
+        let adapter: Box<dyn OutputAdapter> = match self {
+            OutputType::Lua => Box::new(LuaAdapter),
+            OutputType::Hostsfile => Box::new(HostsfileAdapter::new(config)),
+            OutputType::Domains => Box::new(DomainsAdapter),
+            OutputType::Sigma => Box::new(SigmaAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::SiemLookup => Box::new(SiemLookupAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::Suricata => Box::new(SuricataAdapter::new(config, category)),
+            OutputType::SuricataDataset => Box::new(SuricataDatasetAdapter),
+            OutputType::Zeek => Box::new(ZeekAdapter::new(config, category)),
+            OutputType::Maltrail => Box::new(MaltrailAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::Adblock => Box::new(AdblockAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::Ipset => Box::new(IpsetAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::Json => Box::new(JsonAdapter::new(config, category)),
+            OutputType::Csv => Box::new(CsvAdapter::new(config, category)),
+            OutputType::RouterOs => Box::new(RouterOsAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::KnotResolver => Box::new(KnotResolverAdapter),
+            OutputType::CoreDns => Box::new(CoreDnsAdapter::new(config, category)),
+            OutputType::PowerDns => Box::new(PowerDnsAdapter {
+                category: category.to_string(),
+            }),
+            OutputType::Blocky => Box::new(BlockyAdapter::new(config, category)),
+            OutputType::SmartDns => Box::new(SmartDnsAdapter),
+            OutputType::Template => Box::new(TemplateAdapter::new(config, category)?),
+            OutputType::Bloom => Box::new(BloomAdapter::new(config)?),
+        };
+        let adapter: Box<dyn OutputAdapter> =
+            if config.wildcard.as_ref().is_some_and(|w| w.applies_to(self))
+                && adapter.supported_kinds().contains(&EntryKind::Wildcard)
+            {
+                Box::new(WildcardDomains(adapter))
+            } else {
+                adapter
+            };
+        let adapter: Box<dyn OutputAdapter> = if config.category_options(category).annotate_idn
+            && adapter.comment_prefix().is_some()
+        {
+            Box::new(IdnAnnotation(adapter))
+        } else {
+            adapter
+        };
+        if !config.run_id.is_empty() && adapter.comment_prefix().is_some() {
+            return Ok(Box::new(RunAnnotation {
+                adapter,
+                run_id: config.run_id.clone(),
+            }));
+        }
+        Ok(adapter)
+    }
+}
+
+impl Display for OutputType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// OutputAdapter is the trait all output formats must implement
+pub trait OutputAdapter: Send + Sync {
+    /// the entry kinds the output format is able to represent
+    fn supported_kinds(&self) -> &'static [EntryKind];
+
+    /// text written before the first entry
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// translates a single entry into a line of the output format or returns an
+    /// error if the format is not able to represent the entry. Only entries of the
+    /// supported kinds are passed to this function.
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String>;
+
+    /// text written after the last entry
+    fn footer(&self) -> Option<String> {
+        None
+    }
+
+    /// data written after the last entry, the footer unless the format is binary
+    fn footer_bytes(&self) -> Option<Vec<u8>> {
+        self.footer().map(String::into_bytes)
+    }
+
+    /// the marker starting a comment at the end of a line if the format supports
+    /// comments
+    fn comment_prefix(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// IdnAnnotation wraps the adapter of a format supporting comments and appends the
+/// Unicode form to every line of an entry in punycode
+pub struct IdnAnnotation(pub Box<dyn OutputAdapter>);
+
+impl OutputAdapter for IdnAnnotation {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        self.0.supported_kinds()
+    }
+
+    fn header(&self) -> Option<String> {
+        self.0.header()
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let line = self.0.entry(entry)?;
+        let domain = match entry {
+            Entry::Domain(d) | Entry::Wildcard(d) => d,
+            _ => return Ok(line),
+        };
+        let prefix = match self.0.comment_prefix() {
+            Some(p) if domain.split('.').any(|l| l.starts_with("xn--")) => p,
+            _ => return Ok(line),
+        };
+        let (unicode, _) = idna::domain_to_unicode(domain);
+        if unicode == *domain {
+            return Ok(line);
+        }
+        Ok(match line.strip_suffix('\n') {
+            Some(l) => format!("{l} {prefix} {unicode}\n"),
+            None => format!("{line} {prefix} {unicode}"),
+        })
+    }
+
+    fn footer(&self) -> Option<String> {
+        self.0.footer()
+    }
+
+    fn footer_bytes(&self) -> Option<Vec<u8>> {
+        self.0.footer_bytes()
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        self.0.comment_prefix()
+    }
+}
+
+/// RunAnnotation wraps the adapter of a format supporting comments and names the run
+/// which wrote the output in a comment preceding the adapter's header
+pub struct RunAnnotation {
+    pub adapter: Box<dyn OutputAdapter>,
+    pub run_id: String,
+}
+
+impl OutputAdapter for RunAnnotation {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        self.adapter.supported_kinds()
+    }
+
+    fn header(&self) -> Option<String> {
+        let prefix = self.adapter.comment_prefix()?;
+        let header = self.adapter.header().unwrap_or_default();
+        Some(format!("{prefix} harvester run {}\n{header}", self.run_id))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        self.adapter.entry(entry)
+    }
+
+    fn footer(&self) -> Option<String> {
+        self.adapter.footer()
+    }
+
+    fn footer_bytes(&self) -> Option<Vec<u8>> {
+        self.adapter.footer_bytes()
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        self.adapter.comment_prefix()
+    }
+}
+
+/// CategoryMetadata describes a category in the banner of its output files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryMetadata {
+    pub category: String,
+    /// when the files were generated, RFC 3339 in UTC
+    pub generated: String,
+    /// the ids and sources of the lists the category is assembled from
+    pub lists: Vec<(String, String)>,
+    /// the number of entries of the category
+    pub entries: usize,
+}
+
+impl CategoryMetadata {
+    /// describes a category written now
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    /// * `entries`: the number of entries of the category
+    pub fn new(config: &Config, category: &str, entries: usize) -> Self {
+        let lists = config
+            .lists
+            .iter()
+            .filter(|l| match category {
+                // the all category merges every category but the allow category
+                ALL_CATEGORY => l.tags.iter().any(|t| t != ALLOW_CATEGORY),
+                _ => l.tags.iter().any(|t| t == category),
+            })
+            .map(|l| (l.id.clone(), l.source.clone()))
+            .collect();
+        Self {
+            category: category.to_string(),
+            generated: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            lists,
+            entries,
         }
     }
 }
+
+/// MetadataBanner wraps the adapter of a format supporting comments and describes the
+/// category in comments preceding the adapter's header
+pub struct MetadataBanner {
+    pub adapter: Box<dyn OutputAdapter>,
+    pub metadata: CategoryMetadata,
+}
+
+impl MetadataBanner {
+    /// Wraps the adapter if its format supports comments and returns it unchanged
+    /// otherwise
+    ///
+    /// * `adapter`: the adapter of the category
+    /// * `metadata`: the description of the category
+    pub fn wrap(
+        adapter: Box<dyn OutputAdapter>,
+        metadata: CategoryMetadata,
+    ) -> Box<dyn OutputAdapter> {
+        match adapter.comment_prefix() {
+            Some(_) => Box::new(Self { adapter, metadata }),
+            None => adapter,
+        }
+    }
+}
+
+impl OutputAdapter for MetadataBanner {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        self.adapter.supported_kinds()
+    }
+
+    fn header(&self) -> Option<String> {
+        let prefix = self.adapter.comment_prefix()?;
+        let m = &self.metadata;
+        let mut banner = format!(
+            "{prefix} category: {}\n{prefix} generated: {} by harvester {}\n{prefix} entries: {}\n",
+            m.category,
+            m.generated,
+            env!("CARGO_PKG_VERSION"),
+            m.entries
+        );
+        for (id, source) in m.lists.iter() {
+            banner.push_str(&format!("{prefix} list: {id} {source}\n"));
+        }
+        Some(banner + &self.adapter.header().unwrap_or_default())
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        self.adapter.entry(entry)
+    }
+
+    fn footer(&self) -> Option<String> {
+        self.adapter.footer()
+    }
+
+    fn footer_bytes(&self) -> Option<Vec<u8>> {
+        self.adapter.footer_bytes()
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        self.adapter.comment_prefix()
+    }
+}
+
+/// ConversionReport summarizes how the entries of a category were translated into
+/// the output format
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConversionReport {
+    /// number of entries written
+    pub written: usize,
+    /// number of entries converted into another entry kind, by original and target kind
+    pub converted: BTreeMap<(EntryKind, EntryKind), usize>,
+    /// number of entries the output format can't represent, by kind
+    pub dropped: BTreeMap<EntryKind, usize>,
+    /// number of lines which could not be read as entries
+    pub invalid: usize,
+    /// number of entries which failed to be written
+    pub write_errors: usize,
+    /// a failure which stopped the output from being written, the output is incomplete
+    pub io_failure: Option<IoFailure>,
+}
+
+impl ConversionReport {
+    /// records a failed write, writing stops on failures which can't be recovered from
+    ///
+    /// * `category`: the name of the category
+    /// * `e`: the error returned by the writer
+    fn write_failed(&mut self, category: &str, e: &std::io::Error) {
+        self.write_errors += 1;
+        let failure = IoFailure::classify(e);
+        let code = ErrorCode::from(failure);
+        if failure.is_fatal() {
+            error!(
+                "{} {}: {} - aborting output: {}",
+                code, category, failure, e
+            );
+            self.io_failure = Some(failure);
+        } else {
+            error!("{} {}: {}", code, category, e);
+        }
+    }
+
+    /// returns true if the output must not replace the previous version
+    pub fn is_aborted(&self) -> bool {
+        self.io_failure.is_some()
+    }
+
+    /// logs the report for a category
+    ///
+    /// * `category`: the name of the category
+    /// * `format`: the output format the category was written in
+    pub fn log(&self, category: &str, format: &OutputType) {
+        debug!("{}: {} entries written", category, self.written);
+        for ((from, to), count) in &self.converted {
+            info!(
+                "{}: converted {} {} entries to {} for {} output",
+                category, count, from, to, format
+            );
+        }
+        for (kind, count) in &self.dropped {
+            warn!(
+                "{}: dropped {} {} entries which can't be represented in {} output",
+                category, count, kind, format
+            );
+        }
+        if self.invalid > 0 {
+            warn!("{}: skipped {} invalid entries", category, self.invalid);
+        }
+    }
+}
+
+/// Converts an entry into a kind supported by the output format if it's not
+/// supported as it is. Returns None if there is no lossless conversion, e.g.
+/// wildcards can't be expanded into the explicit list of subdomains.
+///
+/// * `entry`: the entry to be written
+/// * `supported`: the entry kinds the output format supports
+pub fn convert(entry: Entry, supported: &[EntryKind]) -> Option<Entry> {
+    if supported.contains(&entry.kind()) {
+        return Some(entry);
+    }
+    let converted = match entry {
+        // the host is the only part of a URL a DNS based block list is able to block
+        Entry::Url(url) => {
+            let host = url.host_str()?;
+            match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+                Ok(ip) => Entry::Ip(ip),
+                Err(_) => Entry::Domain(host.to_string()),
+            }
+        }
+        e => e,
+    };
+    let converted = match converted {
+        Entry::Ip(ip) if !supported.contains(&EntryKind::Ip) => {
+            Entry::Cidr(ip, if ip.is_ipv4() { 32 } else { 128 })
+        }
+        Entry::Cidr(ip, 32) if ip.is_ipv4() => Entry::Ip(ip),
+        Entry::Cidr(ip, 128) if ip.is_ipv6() => Entry::Ip(ip),
+        e => e,
+    };
+    if supported.contains(&converted.kind()) {
+        Some(converted)
+    } else {
+        None
+    }
+}
+
+/// an output format's adapter and the data sink it writes to
+pub type FormatWriter = (Box<dyn OutputAdapter>, Arc<Mutex<dyn Write + Send>>);
+
+/// write_outputs reads the entries of a category list once and tees them to several
+/// output formats. Returns a conversion report per output format.
+///
+/// * `outputs`: the output formats and the data sinks they write to
+/// * `category`: the name of the category being written
+/// * `reader`: data source that implements the Input trait
+/// * `is_processing`: false if the program was interrupted
+pub async fn write_outputs(
+    outputs: Vec<FormatWriter>,
+    category: String,
+    reader: Arc<Mutex<dyn Input + Send>>,
+    is_processing: Arc<AtomicBool>,
+) -> Vec<ConversionReport> {
+    let mut reports = vec![ConversionReport::default(); outputs.len()];
+    for ((adapter, writer), report) in outputs.iter().zip(reports.iter_mut()) {
+        if let Some(header) = adapter.header()
+            && let Err(e) = writer.lock().await.write_all(header.as_bytes())
+        {
+            report.write_failed(&category, &e);
+        }
+    }
+    loop {
+        if !is_processing.load(Ordering::SeqCst) {
+            return reports;
+        }
+        match reader.lock().await.chunk().await {
+            Ok(Some(chunk)) => {
+                let str_chunk = match String::from_utf8(chunk) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        warn!("{}", e);
+                        continue;
+                    }
+                };
+                let entry: Entry = match str_chunk.parse() {
+                    Ok(e) => e,
+                    Err(e) => {
+                        debug!("{}: {}", category, e);
+                        reports.iter_mut().for_each(|r| r.invalid += 1);
+                        continue;
+                    }
+                };
+                for ((adapter, writer), report) in outputs.iter().zip(reports.iter_mut()) {
+                    if report.is_aborted() {
+                        continue;
+                    }
+                    let kind = entry.kind();
+                    let entry = match convert(entry.clone(), adapter.supported_kinds()) {
+                        Some(e) => e,
+                        None => {
+                            *report.dropped.entry(kind).or_default() += 1;
+                            continue;
+                        }
+                    };
+                    if entry.kind() != kind {
+                        *report.converted.entry((kind, entry.kind())).or_default() += 1;
+                    }
+                    let line = match adapter.entry(&entry) {
+                        Ok(l) => l,
+                        Err(e) => {
+                            warn!("{}: {}", category, e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = writer.lock().await.write_all(line.as_bytes()) {
+                        report.write_failed(&category, &e);
+                        continue;
+                    }
+                    report.written += 1;
+                }
+            }
+            Ok(None) => {
+                for ((adapter, writer), report) in outputs.iter().zip(reports.iter_mut()) {
+                    if !report.is_aborted()
+                        && let Some(footer) = adapter.footer_bytes()
+                        && let Err(e) = writer.lock().await.write_all(&footer)
+                    {
+                        report.write_failed(&category, &e);
+                    }
+                }
+                break;
+            }
+            Err(e) => {
+                error!("{} {}: {}", ErrorCode::OutputFailed, category, e);
+                reports.iter_mut().for_each(|r| r.write_errors += 1);
+                break;
+            }
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let domain_only = &[EntryKind::Domain];
+        let url: Entry = "https://domain.one/path".parse().unwrap();
+        assert_eq!(
+            convert(url, domain_only),
+            Some(Entry::Domain("domain.one".to_string()))
+        );
+        let wildcard: Entry = "*.domain.one".parse().unwrap();
+        assert_eq!(convert(wildcard, domain_only), None);
+
+        let cidr_only = &[EntryKind::Cidr];
+        let ip: Entry = "10.0.0.1".parse().unwrap();
+        assert_eq!(convert(ip, cidr_only), "10.0.0.1/32".parse().ok());
+        let ip_only = &[EntryKind::Ip];
+        let url: Entry = "http://[::1]/".parse().unwrap();
+        assert_eq!(convert(url, ip_only), "::1".parse().ok());
+        let cidr: Entry = "10.0.0.0/8".parse().unwrap();
+        assert_eq!(convert(cidr, ip_only), None);
+    }
+
+    #[test]
+    fn test_idn_annotation() {
+        let hostsfile = IdnAnnotation(Box::new(HostsfileAdapter::default()));
+        let idn: Entry = "xn--bcher-kva.example".parse().unwrap();
+        assert_eq!(
+            hostsfile.entry(&idn).unwrap(),
+            "0.0.0.0 xn--bcher-kva.example # bücher.example\n"
+        );
+        let ascii: Entry = "domain.one".parse().unwrap();
+        assert_eq!(hostsfile.entry(&ascii).unwrap(), "0.0.0.0 domain.one\n");
+        let lua = IdnAnnotation(Box::new(LuaAdapter));
+        let wildcard: Entry = "*.xn--bcher-kva.example".parse().unwrap();
+        assert_eq!(
+            lua.entry(&wildcard).unwrap(),
+            "  \"*.xn--bcher-kva.example\", -- bücher.example\n"
+        );
+    }
+
+    #[test]
+    fn test_wildcard_domains() {
+        let mut config = crate::tests::helper::cache_file_creator::CacheFileCreator::new(
+            "test_wildcard_domains",
+            "in",
+            "out",
+        )
+        .new_test_config();
+        config.output_format = vec![OutputType::Blocky, OutputType::Hostsfile];
+        config.wildcard = Some(wildcard::WildcardConfig {
+            formats: vec![OutputType::Blocky, OutputType::Hostsfile],
+            collapse: false,
+        });
+        let domain: Entry = "domain.one".parse().unwrap();
+        let blocky = OutputType::Blocky.get_adapter(&config, "malware").unwrap();
+        assert_eq!(blocky.entry(&domain).unwrap(), "        *.domain.one\n");
+        // hosts files can't represent wildcards
+        let hostsfile = OutputType::Hostsfile
+            .get_adapter(&config, "malware")
+            .unwrap();
+        assert_eq!(hostsfile.entry(&domain).unwrap(), "0.0.0.0 domain.one\n");
+        let wildcard = config.wildcard.as_ref().unwrap();
+        assert!(!wildcard.covers_subdomains(&config));
+        config.output_format = vec![OutputType::Blocky];
+        assert!(config.wildcard.as_ref().unwrap().covers_subdomains(&config));
+    }
+
+    #[test]
+    fn test_run_annotation() {
+        let run_id = "3f2a1b2c-0000-4000-8000-000000000000".to_string();
+        let hostsfile = RunAnnotation {
+            adapter: Box::new(HostsfileAdapter::default()),
+            run_id: run_id.clone(),
+        };
+        assert_eq!(
+            hostsfile.header().unwrap(),
+            format!("# harvester run {run_id}\n")
+        );
+        let lua = RunAnnotation {
+            adapter: Box::new(LuaAdapter),
+            run_id: run_id.clone(),
+        };
+        assert_eq!(
+            lua.header().unwrap(),
+            format!(
+                "-- harvester run {run_id}\n{}",
+                LuaAdapter.header().unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_metadata_banner() {
+        let metadata = CategoryMetadata {
+            category: "malware".to_string(),
+            generated: "2024-05-01T00:00:00Z".to_string(),
+            lists: vec![("one".to_string(), "https://lists.example/one".to_string())],
+            entries: 2,
+        };
+        let hostsfile = MetadataBanner::wrap(
+            Box::new(RunAnnotation {
+                adapter: Box::new(HostsfileAdapter::default()),
+                run_id: "run-1".to_string(),
+            }),
+            metadata.clone(),
+        );
+        assert_eq!(
+            hostsfile.header().unwrap(),
+            format!(
+                "# category: malware\n# generated: 2024-05-01T00:00:00Z by harvester {}\n\
+                 # entries: 2\n# list: one https://lists.example/one\n# harvester run run-1\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        // formats without comments are left alone
+        let domains = MetadataBanner::wrap(Box::new(DomainsAdapter), metadata);
+        assert_eq!(domains.header(), None);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// PowerDnsAdapter translates a category into a PowerDNS Recursor Lua script to be
+/// loaded with `lua-dns-script`. The script's `preresolve` hook answers the queries
+/// for the category's domains and all of their subdomains with NXDOMAIN.
+pub struct PowerDnsAdapter {
+    pub category: String,
+}
+
+impl OutputAdapter for PowerDnsAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some(format!(
+            "-- harvester category {}\nblocked = newDS()\nblocked:add({{\n",
+            self.category
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            // domain sets always match the subdomains
+            Entry::Domain(domain) | Entry::Wildcard(domain) => Ok(format!("  \"{domain}\",\n")),
+            _ => Err(anyhow::anyhow!(
+                "PowerDNS Lua scripts can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn footer(&self) -> Option<String> {
+        Some(
+            "})
+
+function preresolve(dq)
+  if blocked:check(dq.qname) then
+    dq.rcode = pdns.NXDOMAIN
+    return true
+  end
+  return false
+end
+"
+            .to_string(),
+        )
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        Some("--")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_powerdns_adapter() {
+        let input_data = "malicious.com\n*.unwanted.net\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = PowerDnsAdapter {
+            category: "malware".to_string(),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = r#"-- harvester category malware
+blocked = newDS()
+blocked:add({
+  "malicious.com",
+  "unwanted.net",
+})
+
+function preresolve(dq)
+  if blocked:check(dq.qname) then
+    dq.rcode = pdns.NXDOMAIN
+    return true
+  end
+  return false
+end
+"#;
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 2);
+    }
+}
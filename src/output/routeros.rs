@@ -0,0 +1,124 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// RouterOsAdapter translates a category into a MikroTik RouterOS script which is
+/// imported with `/import`. Domains become static DNS entries answered with
+/// NXDOMAIN, addresses become entries of a firewall address list named after the
+/// category. The script removes the entries of the previous import first.
+pub struct RouterOsAdapter {
+    pub category: String,
+}
+
+impl RouterOsAdapter {
+    /// returns the category quoted as string of the RouterOS scripting language
+    ///
+    /// * `prefix`: text preceding the category in the string
+    fn quoted(&self, prefix: &str) -> String {
+        let escaped = format!("{prefix}{}", self.category)
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$");
+        format!("\"{escaped}\"")
+    }
+}
+
+impl OutputAdapter for RouterOsAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        let comment = self.quoted("harvester ");
+        let list = self.quoted("");
+        Some(format!(
+            "# harvester category {}\n\
+             /ip dns static remove [find comment={comment}]\n\
+             /ip firewall address-list remove [find list={list}]\n\
+             /ipv6 firewall address-list remove [find list={list}]\n",
+            self.category
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let comment = self.quoted("harvester ");
+        match entry {
+            Entry::Domain(domain) => Ok(format!(
+                "/ip dns static add name={domain} type=NXDOMAIN comment={comment}\n"
+            )),
+            Entry::Wildcard(domain) => Ok(format!(
+                "/ip dns static add name={domain} match-subdomain=yes type=NXDOMAIN comment={comment}\n"
+            )),
+            Entry::Ip(ip) | Entry::Cidr(ip, _) => {
+                let family = if ip.is_ipv4() { "ip" } else { "ipv6" };
+                Ok(format!(
+                    "/{} firewall address-list add list={} address={} comment={}\n",
+                    family,
+                    self.quoted(""),
+                    entry,
+                    comment
+                ))
+            }
+            Entry::Url(_) => Err(anyhow::anyhow!(
+                "RouterOS scripts can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_routeros_adapter() {
+        let input_data = "malicious.com\n*.unwanted.net\n10.0.0.0/8\n2001:db8::1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = RouterOsAdapter {
+            category: "malware".to_string(),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = r#"# harvester category malware
+/ip dns static remove [find comment="harvester malware"]
+/ip firewall address-list remove [find list="malware"]
+/ipv6 firewall address-list remove [find list="malware"]
+/ip dns static add name=malicious.com type=NXDOMAIN comment="harvester malware"
+/ip dns static add name=unwanted.net match-subdomain=yes type=NXDOMAIN comment="harvester malware"
+/ip firewall address-list add list="malware" address=10.0.0.0/8 comment="harvester malware"
+/ipv6 firewall address-list add list="malware" address=2001:db8::1 comment="harvester malware"
+"#;
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 4);
+
+        let adapter = RouterOsAdapter {
+            category: "a\"$b".to_string(),
+        };
+        assert_eq!(adapter.quoted(""), r#""a\"\$b""#);
+    }
+}
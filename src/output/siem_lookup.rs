@@ -0,0 +1,93 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// SiemLookupAdapter translates a category into a CSV lookup file which can be
+/// imported as ArcSight active list or QRadar reference set and matched against the
+/// host and address fields of CEF and LEEF events
+pub struct SiemLookupAdapter {
+    pub category: String,
+}
+
+/// quotes a CSV field if it contains a separator, quote or line break
+pub(super) fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl OutputAdapter for SiemLookupAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+            EntryKind::Url,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some("indicator,type,cef_field,leef_field,category\n".to_string())
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let (kind, cef_field, leef_field) = match entry {
+            Entry::Domain(_) => ("domain", "dhost", "dstHostName"),
+            Entry::Wildcard(_) => ("wildcard", "dhost", "dstHostName"),
+            Entry::Ip(_) => ("ip", "dst", "dst"),
+            Entry::Cidr(_, _) => ("cidr", "dst", "dst"),
+            Entry::Url(_) => ("url", "request", "url"),
+        };
+        Ok(format!(
+            "{},{},{},{},{}\n",
+            field(&entry.to_string()),
+            kind,
+            cef_field,
+            leef_field,
+            field(&self.category)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_siem_lookup_adapter() {
+        let input_data = "domain.one\n10.0.0.0/8\nhttp://domain.two/a,b\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = SiemLookupAdapter {
+            category: "malware".to_string(),
+        };
+        write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "indicator,type,cef_field,leef_field,category
+domain.one,domain,dhost,dstHostName,malware
+10.0.0.0/8,cidr,dst,dst,malware
+\"http://domain.two/a,b\",url,request,url,malware
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// SigmaAdapter translates a category into a Sigma rule matching DNS queries for the
+/// category's domains, ready to be converted into the query language of a SIEM
+pub struct SigmaAdapter {
+    pub category: String,
+}
+
+/// quotes a value as YAML single quoted string
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+impl OutputAdapter for SigmaAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some(format!(
+            "title: {}\n\
+             status: experimental\n\
+             description: {}\n\
+             logsource:\n    \
+                 category: dns\n\
+             detection:\n    \
+                 selection:\n",
+            quote(&format!("Harvester category {}", self.category)),
+            quote(&format!(
+                "DNS query for a domain of the harvester category {}",
+                self.category
+            )),
+        ))
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(domain) => Ok(format!("        - query: {}\n", quote(domain))),
+            // a wildcard matches the domain itself and all of its subdomains
+            Entry::Wildcard(domain) => Ok(format!(
+                "        - query: {}\n        - query|endswith: {}\n",
+                quote(domain),
+                quote(&format!(".{domain}"))
+            )),
+            _ => Err(anyhow::anyhow!(
+                "Sigma rule can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn footer(&self) -> Option<String> {
+        Some(format!(
+            "    condition: selection\ntags:\n    - {}\nlevel: high\n",
+            quote(&format!("harvester.{}", self.category))
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_sigma_adapter() {
+        let input_data = "*.domain.one\ndomain.two\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = SigmaAdapter {
+            category: "malware".to_string(),
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "title: 'Harvester category malware'
+status: experimental
+description: 'DNS query for a domain of the harvester category malware'
+logsource:
+    category: dns
+detection:
+    selection:
+        - query: 'domain.one'
+        - query|endswith: '.domain.one'
+        - query: 'domain.two'
+    condition: selection
+tags:
+    - 'harvester.malware'
+level: high
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 2);
+        assert_eq!(report.dropped.get(&EntryKind::Ip), Some(&1));
+    }
+}
@@ -0,0 +1,68 @@
+use crate::entry::{Entry, EntryKind};
+
+use super::OutputAdapter;
+
+/// SmartDnsAdapter translates a category into `address` rules of smartdns answering
+/// the queries for the category's domains and their subdomains with SOA. The file is
+/// included into the smartdns configuration with `conf-file`.
+pub struct SmartDnsAdapter;
+
+impl OutputAdapter for SmartDnsAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain, EntryKind::Wildcard]
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            // address rules always match the subdomains
+            Entry::Domain(domain) | Entry::Wildcard(domain) => Ok(format!("address /{domain}/#\n")),
+            _ => Err(anyhow::anyhow!(
+                "smartdns address rules can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        Some("#")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_smartdns_adapter() {
+        let input_data = "malicious.com\n*.unwanted.net\nhttps://phishing.org/login\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let report = write_outputs(
+            vec![(Box::new(SmartDnsAdapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "address /malicious.com/#
+address /unwanted.net/#
+address /phishing.org/#
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 3);
+        assert_eq!(report.dropped[&EntryKind::Ip], 1);
+    }
+}
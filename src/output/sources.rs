@@ -0,0 +1,65 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use crate::{config::Config, entry::Entry, SOURCES_PATH};
+
+/// EntrySources names the lists the entries of a category were taken from
+#[derive(Debug, Clone, Default)]
+pub struct EntrySources {
+    /// the ids of the lists each entry was taken from
+    pub by_entry: HashMap<String, Vec<String>>,
+    /// the ids of all lists included in the category, used for unknown entries
+    pub fallback: Vec<String>,
+}
+
+impl EntrySources {
+    /// loads the sources of a category's entries recorded by the categorize stage
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn load(config: &Config, category: &str) -> Self {
+        let path = Path::new(&config.cache_dir)
+            .join(SOURCES_PATH)
+            .join(category);
+        let by_entry = fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let fallback = config
+            .lists
+            .iter()
+            .filter(|l| l.tags.iter().any(|t| t == category))
+            .map(|l| l.id.clone())
+            .collect();
+        Self { by_entry, fallback }
+    }
+
+    /// returns the ids of the lists an entry was taken from
+    ///
+    /// * `entry`: an entry of the category
+    pub fn lists(&self, entry: &Entry) -> &[String] {
+        self.by_entry
+            .get(&entry.to_string())
+            .unwrap_or(&self.fallback)
+    }
+}
+
+/// Writes the ids of the lists each entry of a category was taken from so the
+/// output stage can name the source of every entry
+///
+/// * `cache_dir`: the cache directory
+/// * `category`: the category's name
+/// * `sources`: the list ids by entry
+pub fn write_sources(
+    cache_dir: &Path,
+    category: &str,
+    sources: &BTreeMap<&String, &Vec<String>>,
+) -> anyhow::Result<()> {
+    let dir = cache_dir.join(SOURCES_PATH);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(category), serde_json::to_string(sources)?)?;
+    Ok(())
+}
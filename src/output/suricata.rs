@@ -0,0 +1,247 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::OutputAdapter;
+
+/// SuricataConfig configures the rules generated for the Suricata output format
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SuricataConfig {
+    /// the first SID of the first category
+    #[serde(default = "default_sid_start")]
+    pub sid_start: u64,
+    /// number of SIDs reserved for every category
+    #[serde(default = "default_sid_range")]
+    pub sid_range: u64,
+    /// classtype of the generated rules
+    #[serde(default = "default_classtype")]
+    pub classtype: String,
+}
+
+fn default_sid_start() -> u64 {
+    9_000_000
+}
+
+fn default_sid_range() -> u64 {
+    100_000
+}
+
+fn default_classtype() -> String {
+    "bad-unknown".to_string()
+}
+
+impl Default for SuricataConfig {
+    fn default() -> Self {
+        Self {
+            sid_start: default_sid_start(),
+            sid_range: default_sid_range(),
+            classtype: default_classtype(),
+        }
+    }
+}
+
+impl SuricataConfig {
+    /// returns the first and the last SID reserved for a category. The range is
+    /// either set in the category's options or derived from the category's position
+    /// among all categories in alphabetical order.
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn sid_range(&self, config: &Config, category: &str) -> (u64, u64) {
+        let start = match config.category_options(category).sid_start {
+            Some(s) => s,
+            None => {
+                let mut tags = config.get_tags();
+                tags.sort();
+                let index = tags.iter().position(|t| t == category).unwrap_or_default();
+                self.sid_start + index as u64 * self.sid_range
+            }
+        };
+        (start, start + self.sid_range - 1)
+    }
+}
+
+/// SuricataAdapter translates a category into Suricata rules, one rule per entry
+pub struct SuricataAdapter {
+    pub category: String,
+    pub classtype: String,
+    /// the next SID to be assigned
+    pub next_sid: AtomicU64,
+    /// the last SID reserved for the category
+    pub last_sid: u64,
+}
+
+impl SuricataAdapter {
+    /// creates the adapter for a category
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> Self {
+        let suricata = config.suricata.clone().unwrap_or_default();
+        let (first, last) = suricata.sid_range(config, category);
+        Self {
+            category: category.to_string(),
+            classtype: suricata.classtype,
+            next_sid: AtomicU64::new(first),
+            last_sid: last,
+        }
+    }
+}
+
+impl OutputAdapter for SuricataAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Wildcard,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+        ]
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let sid = self.next_sid.fetch_add(1, Ordering::SeqCst);
+        if sid > self.last_sid {
+            return Err(anyhow::anyhow!(
+                "SID range exhausted, no rule written for {}",
+                entry
+            ));
+        }
+        let (header, options) = match entry {
+            Entry::Domain(domain) => (
+                "dns any any -> any any".to_string(),
+                format!("dns.query; content:\"{domain}\"; nocase; startswith; endswith; "),
+            ),
+            // the leading dot makes the rule match the domain itself as well
+            Entry::Wildcard(domain) => (
+                "dns any any -> any any".to_string(),
+                format!("dns.query; dotprefix; content:\".{domain}\"; nocase; endswith; "),
+            ),
+            Entry::Ip(_) | Entry::Cidr(_, _) => {
+                (format!("ip any any -> {entry} any"), String::new())
+            }
+            Entry::Url(_) => {
+                return Err(anyhow::anyhow!(
+                    "Suricata rules can't represent {} entries: {}",
+                    entry.kind(),
+                    entry
+                ))
+            }
+        };
+        Ok(format!(
+            "alert {} (msg:\"harvester {} {}\"; {}classtype:{}; sid:{}; rev:1;)\n",
+            header, self.category, entry, options, self.classtype, sid
+        ))
+    }
+}
+
+/// SuricataDatasetAdapter translates a category into a Suricata dataset of the type
+/// string which can be matched against DNS queries with the `dataset` keyword
+pub struct SuricataDatasetAdapter;
+
+impl OutputAdapter for SuricataDatasetAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[EntryKind::Domain]
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(domain) => Ok(format!("{}\n", base64::encode(domain))),
+            _ => Err(anyhow::anyhow!(
+                "Suricata dataset can't represent {} entries: {}",
+                entry.kind(),
+                entry
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        output::write_outputs,
+        tests::helper::{cache_file_creator::CacheFileCreator, cursor_input::CursorInput},
+    };
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_suricata_adapter() {
+        let input_data = "domain.one\n*.domain.two\n10.0.0.0/8\ndomain.three\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = SuricataAdapter {
+            category: "malware".to_string(),
+            classtype: "bad-unknown".to_string(),
+            next_sid: AtomicU64::new(100),
+            last_sid: 102,
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = r#"alert dns any any -> any any (msg:"harvester malware domain.one"; dns.query; content:"domain.one"; nocase; startswith; endswith; classtype:bad-unknown; sid:100; rev:1;)
+alert dns any any -> any any (msg:"harvester malware *.domain.two"; dns.query; dotprefix; content:".domain.two"; nocase; endswith; classtype:bad-unknown; sid:101; rev:1;)
+alert ip any any -> 10.0.0.0/8 any (msg:"harvester malware 10.0.0.0/8"; classtype:bad-unknown; sid:102; rev:1;)
+"#;
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        // the last entry exceeds the SID range
+        assert_eq!(report.written, 3);
+    }
+
+    #[tokio::test]
+    async fn test_suricata_dataset_adapter() {
+        let input = Arc::new(Mutex::new(CursorInput::new("domain.one\n*.domain.two\n")));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        write_outputs(
+            vec![(Box::new(SuricataDatasetAdapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        assert_eq!(String::from_utf8_lossy(&o), "ZG9tYWluLm9uZQ==\n");
+    }
+
+    #[test]
+    fn test_sid_range() {
+        let mut config = CacheFileCreator::new("test_sid_range", "in", "out").new_test_config();
+        config.lists = serde_json::from_value(serde_json::json!([
+            { "id": "one", "source": "one.txt", "tags": ["phishing", "ads"], "regex": "(.*)" }
+        ]))
+        .unwrap();
+        let suricata = SuricataConfig::default();
+        assert_eq!(suricata.sid_range(&config, "ads"), (9_000_000, 9_099_999));
+        assert_eq!(
+            suricata.sid_range(&config, "phishing"),
+            (9_100_000, 9_199_999)
+        );
+        config.categories.insert(
+            "phishing".to_string(),
+            serde_json::from_value(serde_json::json!({ "sid_start": 5000 })).unwrap(),
+        );
+        assert_eq!(suricata.sid_range(&config, "phishing"), (5000, 104_999));
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    fs,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::Context;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::OutputAdapter;
+
+/// names the templates are registered under
+const HEADER: &str = "header";
+const ENTRY: &str = "entry";
+const FOOTER: &str = "footer";
+
+/// the distinct sets of configured entry kinds, adapters declare their kinds statically
+/// so every set is leaked once
+static KINDS: OnceLock<Mutex<Vec<&'static [EntryKind]>>> = OnceLock::new();
+
+/// TemplateConfig configures the Template output format, a format defined by
+/// Handlebars templates
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TemplateConfig {
+    /// template file rendered once at the start of every category
+    pub header: Option<String>,
+    /// template file rendered for every entry
+    pub entry: String,
+    /// template file rendered once at the end of every category
+    pub footer: Option<String>,
+    /// the entry kinds the format is able to represent, other entries are converted
+    /// or dropped
+    #[serde(default = "default_kinds")]
+    pub kinds: Vec<EntryKind>,
+}
+
+fn default_kinds() -> Vec<EntryKind> {
+    vec![EntryKind::Domain]
+}
+
+/// returns the static copy of a set of entry kinds
+///
+/// * `kinds`: the configured entry kinds
+fn intern_kinds(kinds: &[EntryKind]) -> &'static [EntryKind] {
+    let mut sets = KINDS.get_or_init(Default::default).lock().unwrap();
+    if let Some(set) = sets.iter().find(|s| **s == kinds) {
+        return set;
+    }
+    let set: &'static [EntryKind] = Box::leak(kinds.to_vec().into_boxed_slice());
+    sets.push(set);
+    set
+}
+
+/// TemplateAdapter renders a category through the configured templates. The header
+/// and footer see the variables `category` and `run_id`, the entry template sees
+/// `category`, `entry`, `kind` and `value`, the entry without the `*.` of wildcards.
+pub struct TemplateAdapter {
+    category: String,
+    registry: Handlebars<'static>,
+    kinds: &'static [EntryKind],
+    header: Option<String>,
+    footer: Option<String>,
+}
+
+impl TemplateAdapter {
+    /// Creates the adapter for a category. Fails if the templates can't be read or
+    /// rendered.
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> anyhow::Result<Self> {
+        let template = config
+            .template
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the Template output format requires template"))?;
+        let mut registry = Handlebars::new();
+        // the rendered formats aren't HTML
+        registry.register_escape_fn(handlebars::no_escape);
+        // misspelled variables fail instead of rendering as empty strings
+        registry.set_strict_mode(true);
+        let files = [
+            (HEADER, template.header.as_ref()),
+            (ENTRY, Some(&template.entry)),
+            (FOOTER, template.footer.as_ref()),
+        ];
+        for (name, path) in files {
+            if let Some(path) = path {
+                let source = fs::read_to_string(path)
+                    .with_context(|| format!("could not read template {path}"))?;
+                registry
+                    .register_template_string(name, source)
+                    .with_context(|| format!("invalid template {path}"))?;
+            }
+        }
+
+        let data = json!({ "category": category, "run_id": config.run_id });
+        let render = |name: &str| -> anyhow::Result<Option<String>> {
+            match registry.has_template(name) {
+                true => Ok(Some(registry.render(name, &data)?)),
+                false => Ok(None),
+            }
+        };
+        let adapter = Self {
+            category: category.to_string(),
+            header: render(HEADER)?,
+            footer: render(FOOTER)?,
+            kinds: intern_kinds(&template.kinds),
+            registry,
+        };
+        // the entry template is checked before any category is written
+        adapter.entry(&Entry::Domain("example.com".to_string()))?;
+        Ok(adapter)
+    }
+}
+
+impl OutputAdapter for TemplateAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        self.kinds
+    }
+
+    fn header(&self) -> Option<String> {
+        self.header.clone()
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let value = match entry {
+            Entry::Wildcard(domain) => domain.clone(),
+            _ => entry.to_string(),
+        };
+        let data = json!({
+            "category": self.category,
+            "entry": entry.to_string(),
+            "kind": entry.kind().to_string().to_lowercase(),
+            "value": value,
+        });
+        self.registry
+            .render(ENTRY, &data)
+            .with_context(|| format!("could not render {entry}"))
+    }
+
+    fn footer(&self) -> Option<String> {
+        self.footer.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        output::write_outputs,
+        tests::helper::{cache_file_creator::CacheFileCreator, cursor_input::CursorInput},
+    };
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_template_adapter() {
+        let cache = CacheFileCreator::new("test_template_adapter", "in", "out");
+        let mut config = cache.new_test_config();
+        config.run_id = "run-1".to_string();
+        let dir = Path::new(&config.cache_dir);
+        fs::create_dir_all(dir).unwrap();
+        let file = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        config.template = Some(TemplateConfig {
+            header: Some(file("header.hbs", "server:\n# {{category}} {{run_id}}\n")),
+            entry: file(
+                "entry.hbs",
+                "local-zone: \"{{value}}\" {{#if (eq kind \"wildcard\")}}always_nxdomain{{else}}static{{/if}}\n",
+            ),
+            footer: None,
+            kinds: vec![EntryKind::Domain, EntryKind::Wildcard],
+        });
+
+        let input_data = "malicious.com\n*.unwanted.net\n10.0.0.1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let adapter = TemplateAdapter::new(&config, "malware").unwrap();
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            Arc::new(AtomicBool::new(true)),
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "server:
+# malware run-1
+local-zone: \"malicious.com\" static
+local-zone: \"unwanted.net\" always_nxdomain
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.written, 2);
+        assert_eq!(report.dropped[&EntryKind::Ip], 1);
+
+        // unknown variables are reported before anything is written
+        config.template.as_mut().unwrap().entry = file("typo.hbs", "{{domain}}\n");
+        assert!(TemplateAdapter::new(&config, "malware").is_err());
+    }
+}
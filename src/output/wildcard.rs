@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::{OutputAdapter, OutputType};
+
+/// WildcardConfig writes the domains of the categories as wildcards matching their
+/// subdomains as well, for resolvers supporting wildcard blocking
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct WildcardConfig {
+    /// the formats the domains are written as wildcards in, all formats if empty
+    #[serde(default)]
+    pub formats: Vec<OutputType>,
+    /// remove the entries from the categories which are covered by a wildcard
+    #[serde(default)]
+    pub collapse: bool,
+}
+
+impl WildcardConfig {
+    /// returns true if the domains are written as wildcards in the format
+    ///
+    /// * `format`: the output format
+    pub fn applies_to(&self, format: &OutputType) -> bool {
+        self.formats.is_empty() || self.formats.contains(format)
+    }
+
+    /// returns true if all configured output formats write the domains as wildcards,
+    /// a domain covers its subdomains in every output then
+    ///
+    /// * `config`: the configuration the output formats are taken from
+    pub fn covers_subdomains(&self, config: &Config) -> bool {
+        config.output_formats().iter().all(|format| {
+            self.applies_to(format)
+                && format
+                    .get_adapter(config, "")
+                    .is_ok_and(|a| a.supported_kinds().contains(&EntryKind::Wildcard))
+        })
+    }
+}
+
+/// WildcardDomains wraps the adapter of a format supporting wildcards and writes the
+/// domains in the format's wildcard syntax
+pub struct WildcardDomains(pub Box<dyn OutputAdapter>);
+
+impl OutputAdapter for WildcardDomains {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        self.0.supported_kinds()
+    }
+
+    fn header(&self) -> Option<String> {
+        self.0.header()
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        match entry {
+            Entry::Domain(domain) => self.0.entry(&Entry::Wildcard(domain.clone())),
+            e => self.0.entry(e),
+        }
+    }
+
+    fn footer(&self) -> Option<String> {
+        self.0.footer()
+    }
+
+    fn footer_bytes(&self) -> Option<Vec<u8>> {
+        self.0.footer_bytes()
+    }
+
+    fn comment_prefix(&self) -> Option<&'static str> {
+        self.0.comment_prefix()
+    }
+}
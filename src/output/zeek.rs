@@ -0,0 +1,125 @@
+use crate::{
+    config::Config,
+    entry::{Entry, EntryKind},
+};
+
+use super::{sources::EntrySources, OutputAdapter};
+
+/// ZeekAdapter translates a category into a file of Zeek's Intelligence Framework
+pub struct ZeekAdapter {
+    pub category: String,
+    /// the lists the entries were taken from
+    pub sources: EntrySources,
+}
+
+impl ZeekAdapter {
+    /// creates the adapter for a category and loads the sources of its entries
+    /// recorded by the categorize stage
+    ///
+    /// * `config`: the configuration the category is defined in
+    /// * `category`: the category's name
+    pub fn new(config: &Config, category: &str) -> Self {
+        Self {
+            category: category.to_string(),
+            sources: EntrySources::load(config, category),
+        }
+    }
+}
+
+/// replaces the characters separating the fields and set elements of an intel file
+fn sanitize(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+impl OutputAdapter for ZeekAdapter {
+    fn supported_kinds(&self) -> &'static [EntryKind] {
+        &[
+            EntryKind::Domain,
+            EntryKind::Ip,
+            EntryKind::Cidr,
+            EntryKind::Url,
+        ]
+    }
+
+    fn header(&self) -> Option<String> {
+        Some("#fields\tindicator\tindicator_type\tmeta.source\tmeta.desc\n".to_string())
+    }
+
+    fn entry(&self, entry: &Entry) -> anyhow::Result<String> {
+        let (indicator, indicator_type) = match entry {
+            Entry::Domain(domain) => (domain.clone(), "Intel::DOMAIN"),
+            Entry::Ip(ip) => (ip.to_string(), "Intel::ADDR"),
+            Entry::Cidr(_, _) => (entry.to_string(), "Intel::SUBNET"),
+            // Zeek matches URLs without their scheme
+            Entry::Url(url) => {
+                let url = url.as_str();
+                let without_scheme = url.split_once("://").map_or(url, |(_, u)| u);
+                (without_scheme.to_string(), "Intel::URL")
+            }
+            Entry::Wildcard(_) => {
+                return Err(anyhow::anyhow!(
+                    "Zeek intel can't represent {} entries: {}",
+                    entry.kind(),
+                    entry
+                ))
+            }
+        };
+        let source = self.sources.lists(entry).join(",");
+        Ok(format!(
+            "{}\t{}\t{}\tharvester category {}\n",
+            sanitize(&indicator),
+            indicator_type,
+            sanitize(&source),
+            sanitize(&self.category)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{output::write_outputs, tests::helper::cursor_input::CursorInput};
+
+    use super::*;
+    use futures::lock::Mutex;
+    use std::{
+        collections::HashMap,
+        io::Cursor,
+        sync::{atomic::AtomicBool, Arc},
+    };
+
+    #[tokio::test]
+    async fn test_zeek_adapter() {
+        let input_data = "domain.one\n*.domain.two\n10.0.0.0/8\nhttps://domain.three/path?q=1\n";
+        let input = Arc::new(Mutex::new(CursorInput::new(input_data)));
+        let output = Arc::new(Mutex::new(Cursor::new(vec![])));
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let adapter = ZeekAdapter {
+            category: "malware".to_string(),
+            sources: EntrySources {
+                by_entry: HashMap::from([(
+                    "domain.one".to_string(),
+                    vec!["one".to_string(), "two".to_string()],
+                )]),
+                fallback: vec!["one".to_string(), "two".to_string(), "three".to_string()],
+            },
+        };
+        let report = write_outputs(
+            vec![(Box::new(adapter), output.clone())],
+            "malware".to_string(),
+            input,
+            is_processing,
+        )
+        .await
+        .pop()
+        .unwrap();
+        let o = output.lock().await.clone().into_inner();
+        let expect = "#fields\tindicator\tindicator_type\tmeta.source\tmeta.desc
+domain.one\tIntel::DOMAIN\tone,two\tharvester category malware
+10.0.0.0/8\tIntel::SUBNET\tone,two,three\tharvester category malware
+domain.three/path?q=1\tIntel::URL\tone,two,three\tharvester category malware
+";
+        assert_eq!(String::from_utf8_lossy(&o), expect);
+        assert_eq!(report.dropped.get(&EntryKind::Wildcard), Some(&1));
+    }
+}
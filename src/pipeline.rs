@@ -0,0 +1,460 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::Context;
+use chrono::Utc;
+
+use crate::{
+    config::Config,
+    error_code::ErrorCode,
+    filter_controller::{
+        FilterController, StageCategorize, StageDownload, StageExtract, StageOutput,
+    },
+    input::{file::FileInput, source::SourceInput},
+    manifest::Manifest,
+    observer::{Observer, Observers, PipelineStage},
+    report::{CategoryReport, RunReport, Status},
+    run_state::{RunState, RunStateObserver},
+    CATEGORIZE_PATH, DOWNLOAD_PATH, EXTRACT_PATH, REVIEW_PATH,
+};
+
+/// CancellationToken stops a pipeline gracefully. Stages check the token between
+/// lists and chunks and return early once it was cancelled.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    is_processing: Arc<AtomicBool>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self {
+            is_processing: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+impl CancellationToken {
+    /// cancels the pipeline the token belongs to
+    pub fn cancel(&self) {
+        self.is_processing.store(false, Ordering::SeqCst);
+    }
+
+    /// returns true if the pipeline was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        !self.is_processing.load(Ordering::SeqCst)
+    }
+}
+
+/// the controller of the stage to be run next
+enum NextStage<'config> {
+    Download(FilterController<'config, StageDownload, SourceInput, File>),
+    Extract(FilterController<'config, StageExtract, FileInput, File>),
+    Categorize(FilterController<'config, StageCategorize, FileInput, File>),
+    Output(FilterController<'config, StageOutput, FileInput, File>),
+    Finished,
+}
+
+impl NextStage<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            NextStage::Download(_) => "download",
+            NextStage::Extract(_) => "extract",
+            NextStage::Categorize(_) => "categorize",
+            NextStage::Output(_) => "output",
+            NextStage::Finished => "none",
+        }
+    }
+}
+
+/// PipelineBuilder configures a Pipeline
+pub struct PipelineBuilder<'config> {
+    config: &'config Config,
+    cancellation: CancellationToken,
+    network: Option<CancellationToken>,
+    observers: Observers,
+}
+
+impl<'config> PipelineBuilder<'config> {
+    /// sets the token the pipeline can be cancelled with
+    ///
+    /// * `cancellation`: the cancellation token
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Sets the token the downloads can be cancelled with while the following stages
+    /// process the previous versions of the lists not downloaded. The downloads are
+    /// cancelled with the pipeline's token by default.
+    ///
+    /// * `network`: the cancellation token of the downloads
+    pub fn network_cancellation(mut self, network: CancellationToken) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// registers an observer notified about the pipeline's progress
+    ///
+    /// * `observer`: the observer
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Creates the pipeline. The state of the last run is loaded from the manifest in
+    /// the cache directory, a missing or broken manifest starts from scratch. Unless
+    /// the configuration asks to resume, the state of an interrupted run is discarded.
+    pub fn build(mut self) -> Pipeline<'config> {
+        if !self.config.resume
+            && let Err(e) = RunState::remove(Path::new(&self.config.cache_dir))
+        {
+            warn!("{:?}", e);
+        }
+        self.observers
+            .push(Arc::new(RunStateObserver::new(self.config)));
+        let mut manifest = match Manifest::load(Path::new(&self.config.cache_dir)) {
+            Err(e) => {
+                warn!("{:?}", e);
+                Manifest::default()
+            }
+            Ok(m) => m,
+        };
+        manifest.tick(Utc::now().timestamp());
+        if !self.config.run_id.is_empty() {
+            manifest.run_id = Some(self.config.run_id.clone());
+        }
+        let network = self.network.unwrap_or_else(|| self.cancellation.clone());
+        let mut controller = FilterController::new(self.config, manifest, network.is_processing);
+        controller.observers = self.observers.clone();
+        controller.report.lock().unwrap().run_id = self.config.run_id.clone();
+        Pipeline {
+            config: self.config,
+            cancellation: self.cancellation,
+            observers: self.observers,
+            report: controller.report.clone(),
+            next: NextStage::Download(controller),
+            manifest: None,
+        }
+    }
+}
+
+/// Pipeline runs the four stages download, extract, categorize and output in this
+/// order. It hides the controllers of the single stages and hands the state over
+/// from one stage to the next.
+pub struct Pipeline<'config> {
+    config: &'config Config,
+    cancellation: CancellationToken,
+    observers: Observers,
+    next: NextStage<'config>,
+    report: Arc<Mutex<RunReport>>,
+    /// the manifest is available once all stages ran
+    manifest: Option<Manifest>,
+}
+
+impl<'config> Pipeline<'config> {
+    /// returns a builder for a pipeline processing the given configuration
+    ///
+    /// * `config`: the configuration to be processed
+    pub fn builder(config: &'config Config) -> PipelineBuilder<'config> {
+        PipelineBuilder {
+            config,
+            cancellation: CancellationToken::default(),
+            network: None,
+            observers: Observers::default(),
+        }
+    }
+
+    /// the report collecting what happened in the stages run so far
+    pub fn report(&self) -> Arc<Mutex<RunReport>> {
+        self.report.clone()
+    }
+
+    /// stores the manifest as left by the stages run so far, so an interrupted run can
+    /// be resumed with their results
+    fn checkpoint(&self) {
+        let manifest = match &self.next {
+            NextStage::Extract(c) => c.manifest.as_ref(),
+            NextStage::Categorize(c) => c.manifest.as_ref(),
+            _ => None,
+        };
+        if let Some(m) = manifest
+            && let Err(e) = m.save(Path::new(&self.config.cache_dir))
+        {
+            warn!("{:?}", e);
+        }
+    }
+
+    fn out_of_order(&self, stage: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "can't run the {} stage, the next stage is {}",
+            stage,
+            self.next.name()
+        )
+    }
+
+    /// downloads the filter lists
+    pub async fn download(&mut self) -> anyhow::Result<()> {
+        let mut controller = match std::mem::replace(&mut self.next, NextStage::Finished) {
+            NextStage::Download(c) => c,
+            other => {
+                self.next = other;
+                return Err(self.out_of_order("download"));
+            }
+        };
+        let mut next = controller.run(DOWNLOAD_PATH).await?;
+        // the following stages go on once the downloads were cancelled
+        next.is_processing = self.cancellation.is_processing.clone();
+        self.next = NextStage::Extract(next);
+        self.checkpoint();
+        self.observers.stage_done(PipelineStage::Download);
+        Ok(())
+    }
+
+    /// extracts the entries from the downloaded lists
+    pub async fn extract(&mut self) -> anyhow::Result<()> {
+        let mut controller = match std::mem::replace(&mut self.next, NextStage::Finished) {
+            NextStage::Extract(c) => c,
+            other => {
+                self.next = other;
+                return Err(self.out_of_order("extract"));
+            }
+        };
+        self.next = NextStage::Categorize(controller.run(DOWNLOAD_PATH, EXTRACT_PATH).await?);
+        self.checkpoint();
+        self.observers.stage_done(PipelineStage::Extract);
+        Ok(())
+    }
+
+    /// assembles the categories from the extracted entries
+    pub async fn categorize(&mut self) -> anyhow::Result<()> {
+        let mut controller = match std::mem::replace(&mut self.next, NextStage::Finished) {
+            NextStage::Categorize(c) => c,
+            other => {
+                self.next = other;
+                return Err(self.out_of_order("categorize"));
+            }
+        };
+        self.next = NextStage::Output(controller.run(EXTRACT_PATH, CATEGORIZE_PATH).await?);
+        self.observers.stage_done(PipelineStage::Categorize);
+        Ok(())
+    }
+
+    /// writes the categories in the configured output formats
+    pub async fn output(&mut self) -> anyhow::Result<()> {
+        let mut controller = match std::mem::replace(&mut self.next, NextStage::Finished) {
+            NextStage::Output(c) => c,
+            other => {
+                self.next = other;
+                return Err(self.out_of_order("output"));
+            }
+        };
+        controller.run(CATEGORIZE_PATH).await?;
+        self.manifest = controller.manifest.take();
+        self.observers.stage_done(PipelineStage::Output);
+        Ok(())
+    }
+
+    /// Lets the changes of every updated category be approved before the output stage
+    /// writes them. Rejected categories get their previous version back, keep their
+    /// previous outputs and are assembled again on the next run. Returns the rejected
+    /// categories.
+    ///
+    /// * `approve`: decides whether the changes of a category are written
+    pub fn review(
+        &mut self,
+        mut approve: impl FnMut(&str, &CategoryReport) -> bool,
+    ) -> anyhow::Result<Vec<String>> {
+        let controller = match &mut self.next {
+            NextStage::Output(c) => c,
+            _ => return Err(self.out_of_order("review")),
+        };
+        let changed: Vec<(String, CategoryReport)> = self
+            .report
+            .lock()
+            .unwrap()
+            .categories
+            .iter()
+            .filter(|(_, c)| c.status == Status::Updated && c.added + c.removed > 0)
+            .map(|(name, c)| (name.clone(), c.clone()))
+            .collect();
+        let cache_dir = Path::new(&self.config.cache_dir);
+        let mut rejected = vec![];
+        for (name, category) in changed {
+            let previous = cache_dir.join(REVIEW_PATH).join(&name);
+            if approve(&name, &category) {
+                fs::remove_file(&previous).ok();
+                continue;
+            }
+            fs::rename(&previous, cache_dir.join(CATEGORIZE_PATH).join(&name))
+                .with_context(|| format!("could not restore the previous version of {name}"))?;
+            self.report.lock().unwrap().category_failed(
+                &name,
+                ErrorCode::ReviewRejected,
+                "the changes were rejected, keeping the previous version".to_string(),
+            );
+            if let Some(m) = controller.manifest.as_mut() {
+                m.rejected.insert(name.clone());
+            }
+            rejected.push(name);
+        }
+        Ok(rejected)
+    }
+
+    /// writes the state to be kept for the next run to the cache directory. The state
+    /// needed to resume the run is removed unless the pipeline was cancelled.
+    pub fn save_manifest(&self) -> anyhow::Result<()> {
+        let cache_dir = Path::new(&self.config.cache_dir);
+        match &self.manifest {
+            Some(m) => m.save(cache_dir)?,
+            None => return Err(anyhow::anyhow!("the pipeline didn't finish")),
+        }
+        if !self.cancellation.is_cancelled() {
+            RunState::remove(cache_dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::CACHED_CONF_FILE_NAME,
+        filter_list::FilterList,
+        output::OutputType,
+        tests::helper::{
+            cache_file_creator::{CacheFileCreator, TEST_CACHE},
+            list_server::ListServer,
+        },
+    };
+
+    use super::*;
+
+    /// runs all four stages and stores the state for the next run like the program
+    /// does, returns the run's report
+    ///
+    /// * `config`: the configuration to be processed
+    async fn run(config: &mut Config) -> RunReport {
+        let report = {
+            let mut pipeline = Pipeline::builder(config).build();
+            pipeline.download().await.unwrap();
+            pipeline.extract().await.unwrap();
+            pipeline.categorize().await.unwrap();
+            pipeline.output().await.unwrap();
+            pipeline.save_manifest().unwrap();
+            pipeline.report().lock().unwrap().clone()
+        };
+        config.save_to_cache().unwrap();
+        report
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_end_to_end() {
+        fs::remove_dir_all(Path::new(TEST_CACHE).join("test_pipeline_end_to_end")).ok();
+        let cache = CacheFileCreator::new("test_pipeline_end_to_end", "in", "out");
+        let server = ListServer::start();
+        server.set_list("/kept", "# kept\n0.0.0.0 kept.com\n");
+        server.set_list("/changed", "0.0.0.0 changed-v1.com\n");
+        server.set_list("/removed", "0.0.0.0 removed.com\n");
+        let list = |id: &str, tags: &[&str]| FilterList {
+            id: id.to_string(),
+            source: server.url(&format!("/{id}")),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            regex: r"^0\.0\.0\.0 (.*)".to_string(),
+            ..Default::default()
+        };
+        let mut config = cache.new_test_config();
+        config.output_format = vec![OutputType::Domains];
+        config.lists = vec![
+            list("kept", &["malware"]),
+            list("changed", &["malware", "phishing"]),
+            list("removed", &["phishing"]),
+        ];
+        let output_dir = Path::new(&config.output_dir[0]).to_path_buf();
+        let output = |category: &str| {
+            let contents = fs::read_to_string(output_dir.join(category)).unwrap();
+            let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+            lines.sort();
+            lines
+        };
+
+        let report = run(&mut config).await;
+        for id in ["kept", "changed", "removed"] {
+            assert_eq!(report.lists[id].status, Status::Updated, "{id}");
+        }
+        assert_eq!(output("malware"), vec!["changed-v1.com", "kept.com"]);
+        assert_eq!(output("phishing"), vec!["changed-v1.com", "removed.com"]);
+        let manifest = Manifest::load(Path::new(&config.cache_dir)).unwrap();
+        for id in ["kept", "changed", "removed"] {
+            assert!(manifest.blobs.contains_key(id), "{id}");
+            assert_eq!(manifest.extracts[id].lines, 1, "{id}");
+        }
+
+        // the second run finds one list unchanged, one updated and one removed
+        server.set_list("/changed", "0.0.0.0 changed-v2.com\n");
+        let mut config = cache.new_test_config();
+        config.output_format = vec![OutputType::Domains];
+        config.lists = vec![
+            list("kept", &["malware"]),
+            list("changed", &["malware", "phishing"]),
+        ];
+        let cached = Path::new(&config.cache_dir).join(CACHED_CONF_FILE_NAME);
+        config.cached_config = Some(Box::new(Config::load(&cached).unwrap()));
+
+        let report = run(&mut config).await;
+        assert_eq!(report.lists["kept"].status, Status::Unchanged);
+        assert_eq!(report.lists["changed"].status, Status::Updated);
+        assert!(!report.lists.contains_key("removed"));
+        assert_eq!(output("malware"), vec!["changed-v2.com", "kept.com"]);
+        assert_eq!(output("phishing"), vec!["changed-v2.com"]);
+        let manifest = Manifest::load(Path::new(&config.cache_dir)).unwrap();
+        assert_eq!(manifest.extracts["kept"].lines, 1);
+        assert_eq!(manifest.extracts["changed"].lines, 1);
+        // the next run asks the server conditionally
+        assert!(manifest.http.contains_key("kept"));
+        assert!(manifest.http.contains_key("changed"));
+
+        // nothing changed on the third run, the outputs are kept
+        let cached = Path::new(&config.cache_dir).join(CACHED_CONF_FILE_NAME);
+        config.cached_config = Some(Box::new(Config::load(&cached).unwrap()));
+        let report = run(&mut config).await;
+        assert_eq!(report.lists["kept"].status, Status::Unchanged);
+        assert_eq!(report.lists["changed"].status, Status::Unchanged);
+        assert_eq!(output("malware"), vec!["changed-v2.com", "kept.com"]);
+        assert_eq!(output("phishing"), vec!["changed-v2.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_stage_order() {
+        let cache = CacheFileCreator::new("test_pipeline_stage_order", "in", "out");
+        let config = cache.new_test_config();
+        let cancellation = CancellationToken::default();
+        let mut pipeline = Pipeline::builder(&config)
+            .cancellation(cancellation.clone())
+            .build();
+
+        let e = pipeline.extract().await.unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "can't run the extract stage, the next stage is download"
+        );
+        assert!(pipeline.save_manifest().is_err());
+
+        pipeline.download().await.unwrap();
+        pipeline.extract().await.unwrap();
+        pipeline.categorize().await.unwrap();
+        cancellation.cancel();
+        assert!(cancellation.is_cancelled());
+        pipeline.output().await.unwrap();
+        assert!(pipeline.download().await.is_err());
+        pipeline.save_manifest().unwrap();
+        // the cancelled run can be resumed
+        let cache_dir = Path::new(&config.cache_dir);
+        assert!(RunState::load(cache_dir).unwrap().is_some());
+    }
+}
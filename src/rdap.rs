@@ -0,0 +1,225 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    fs,
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use reqwest::header::ACCEPT;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// file in the cache directory the lookup results are kept in
+pub const RDAP_CACHE_FILE_NAME: &str = "rdap.json";
+
+/// RdapConfig configures the registration data looked up for the domains newly added
+/// to the categories
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RdapConfig {
+    /// base url of the RDAP service the domain is appended to
+    #[serde(default = "default_endpoint")]
+    pub endpoint: String,
+    /// maximum number of lookups sent per run
+    #[serde(default = "default_max_lookups")]
+    pub max_lookups: usize,
+    /// milliseconds to wait between two lookups
+    #[serde(default = "default_delay")]
+    pub delay: u64,
+    /// days a lookup result is reused
+    #[serde(default = "default_cache_days")]
+    pub cache_days: u64,
+}
+
+fn default_endpoint() -> String {
+    "https://rdap.org/domain/".to_string()
+}
+
+fn default_max_lookups() -> usize {
+    50
+}
+
+fn default_delay() -> u64 {
+    1000
+}
+
+fn default_cache_days() -> u64 {
+    30
+}
+
+/// RdapInfo is the registration data of a domain relevant for triage
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RdapInfo {
+    pub registrar: Option<String>,
+    /// date of the registration in the format YYYY-MM-DD
+    pub created: Option<String>,
+}
+
+impl Display for RdapInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if let Some(registrar) = &self.registrar {
+            parts.push(format!("registrar: {registrar}"));
+        }
+        if let Some(created) = &self.created {
+            parts.push(format!("created: {created}"));
+        }
+        if parts.is_empty() {
+            return write!(f, "no registration data");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl RdapInfo {
+    /// Extracts the registrar's name and the registration date from an RDAP domain
+    /// response
+    ///
+    /// * `response`: the RDAP response's json document
+    pub fn parse(response: &Value) -> Self {
+        let created = response["events"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|e| e["eventAction"] == "registration")
+            .and_then(|e| e["eventDate"].as_str())
+            .map(|d| d.chars().take(10).collect());
+        let registrar = response["entities"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|e| {
+                e["roles"]
+                    .as_array()
+                    .is_some_and(|r| r.iter().any(|r| r == "registrar"))
+            })
+            .and_then(|e| e["vcardArray"][1].as_array())
+            .and_then(|props| props.iter().find(|p| p[0] == "fn"))
+            .and_then(|p| p[3].as_str())
+            .map(String::from);
+        Self { registrar, created }
+    }
+}
+
+/// a lookup result along with the time it was fetched
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct CachedInfo {
+    info: RdapInfo,
+    /// unix timestamp of the lookup
+    fetched: i64,
+}
+
+/// requests the registration data of a single domain
+///
+/// * `cli`: the HTTP client
+/// * `url`: the RDAP url of the domain
+async fn fetch(cli: &reqwest::Client, url: &str) -> anyhow::Result<RdapInfo> {
+    let response = cli
+        .get(url)
+        .header(ACCEPT, "application/rdap+json")
+        .send()
+        .await?
+        .error_for_status()?;
+    let document: Value = serde_json::from_str(&response.text().await?)
+        .with_context(|| format!("invalid RDAP response from {url}"))?;
+    Ok(RdapInfo::parse(&document))
+}
+
+/// Looks up the registration data of the given domains. Results of earlier runs are
+/// taken from the cache directory, new lookups are limited and spaced according to
+/// the configuration. Domains whose lookup failed are left out.
+///
+/// * `config`: the RDAP configuration
+/// * `cache_dir`: the directory the lookup results are cached in
+/// * `domains`: the domains to be looked up
+pub async fn lookup(
+    config: &RdapConfig,
+    cache_dir: &Path,
+    domains: &[String],
+) -> anyhow::Result<BTreeMap<String, RdapInfo>> {
+    let cache_path = cache_dir.join(RDAP_CACHE_FILE_NAME);
+    let mut cache: HashMap<String, CachedInfo> = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default();
+    let now = Utc::now().timestamp();
+    let max_age = config.cache_days as i64 * 24 * 60 * 60;
+    cache.retain(|_, c| now - c.fetched <= max_age);
+
+    let cli = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+    let mut results = BTreeMap::new();
+    let mut lookups = 0;
+    for domain in domains {
+        if let Some(cached) = cache.get(domain) {
+            results.insert(domain.clone(), cached.info.clone());
+            continue;
+        }
+        if lookups >= config.max_lookups {
+            debug!("RDAP lookup limit reached, skipping {}", domain);
+            continue;
+        }
+        if lookups > 0 {
+            tokio::time::sleep(Duration::from_millis(config.delay)).await;
+        }
+        lookups += 1;
+        let url = format!("{}{}", config.endpoint, domain);
+        let info = match fetch(&cli, &url).await {
+            Ok(i) => i,
+            Err(e) => {
+                debug!("{:?}", e);
+                continue;
+            }
+        };
+        cache.insert(
+            domain.clone(),
+            CachedInfo {
+                info: info.clone(),
+                fetched: now,
+            },
+        );
+        results.insert(domain.clone(), info);
+    }
+
+    fs::write(&cache_path, serde_json::to_string(&cache)?)
+        .with_context(|| "could not write RDAP cache")?;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rdap_response() {
+        let response = serde_json::json!({
+            "ldhName": "example.com",
+            "events": [
+                { "eventAction": "expiration", "eventDate": "2025-08-13T04:00:00Z" },
+                { "eventAction": "registration", "eventDate": "1995-08-14T04:00:00Z" }
+            ],
+            "entities": [
+                { "roles": ["technical"], "vcardArray": ["vcard", [["fn", {}, "text", "Tech"]]] },
+                {
+                    "roles": ["registrar"],
+                    "vcardArray": ["vcard", [
+                        ["version", {}, "text", "4.0"],
+                        ["fn", {}, "text", "Example Registrar, Inc."]
+                    ]]
+                }
+            ]
+        });
+        let info = RdapInfo::parse(&response);
+        assert_eq!(
+            info.to_string(),
+            "registrar: Example Registrar, Inc., created: 1995-08-14"
+        );
+        assert_eq!(
+            RdapInfo::parse(&serde_json::json!({})).to_string(),
+            "no registration data"
+        );
+    }
+}
@@ -0,0 +1,601 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error_code::ErrorCode, filter_controller::SkipReason, io::IoFailure, rdap::RdapConfig,
+};
+
+/// maximum number of added or removed entries listed per category
+const DIFF_SAMPLE_SIZE: usize = 10;
+
+/// ReportFormat is the file format of the run report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+/// ReportConfig configures the optional report written after each run
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReportConfig {
+    /// file format of the report
+    pub format: ReportFormat,
+    /// the file the report is written to
+    pub path: String,
+    /// look up the registration data of the newly added entries
+    pub rdap: Option<RdapConfig>,
+}
+
+/// Status is the result of processing a list or category in a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    #[default]
+    Updated,
+    Unchanged,
+    Failed,
+    /// the server asked to retry later, the previous download was used
+    Deferred,
+    /// the download failed, the previous download was used
+    Stale,
+}
+
+/// ListReport collects the information about a single filter list
+#[derive(Debug, Clone, Default)]
+pub struct ListReport {
+    pub status: Status,
+    /// number of lines the extract stage matched
+    pub lines_extracted: Option<usize>,
+    /// number of lines the extract stage skipped by the reason they were skipped for
+    pub skip_reasons: BTreeMap<SkipReason, usize>,
+    pub error: Option<String>,
+    /// classifies the error
+    pub code: Option<ErrorCode>,
+    /// set if writing the list's files was aborted
+    pub io_failure: Option<IoFailure>,
+    /// when the server asked to retry a deferred download
+    pub retry_after: Option<String>,
+    /// the list was downloaded without verifying the server's certificate
+    pub insecure_tls: bool,
+    /// the average match rate of the previous runs and the current one if the rate
+    /// dropped sharply, probably because the list's format changed
+    pub match_rate_drop: Option<(f64, f64)>,
+}
+
+/// CategoryReport collects the information about a single category
+#[derive(Debug, Clone, Default)]
+pub struct CategoryReport {
+    pub status: Status,
+    pub entries: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub added_sample: Vec<String>,
+    pub removed_sample: Vec<String>,
+    /// set if writing the category's files was aborted
+    pub io_failure: Option<IoFailure>,
+}
+
+/// RunReport collects what happened during a program run
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    pub started: DateTime<Local>,
+    /// the unique id of the run
+    pub run_id: String,
+    pub lists: BTreeMap<String, ListReport>,
+    pub categories: BTreeMap<String, CategoryReport>,
+    /// errors not related to a single list, prefixed with their error code
+    pub failures: Vec<String>,
+    /// notes shown next to added entries, e.g. their registration data
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Default for RunReport {
+    fn default() -> Self {
+        Self {
+            started: Local::now(),
+            run_id: String::new(),
+            lists: BTreeMap::new(),
+            categories: BTreeMap::new(),
+            failures: vec![],
+            annotations: BTreeMap::new(),
+        }
+    }
+}
+
+/// a section of the report consisting of a heading, an optional table and list items
+struct Section {
+    heading: String,
+    table: Vec<Vec<String>>,
+    items: Vec<String>,
+}
+
+impl RunReport {
+    /// records the status of a filter list
+    ///
+    /// * `id`: the filter list's id
+    /// * `status`: the status of the list in the current stage
+    pub fn list_status(&mut self, id: &str, status: Status) {
+        let list = self.lists.entry(id.to_string()).or_default();
+        // a failure or fallback in an earlier stage must not be overwritten
+        if !matches!(
+            list.status,
+            Status::Failed | Status::Deferred | Status::Stale
+        ) {
+            list.status = status;
+        }
+    }
+
+    /// records a failure of a filter list
+    ///
+    /// * `id`: the filter list's id
+    /// * `code`: the class of the error
+    /// * `error`: the error message
+    pub fn list_failed(&mut self, id: &str, code: ErrorCode, error: String) {
+        let list = self.lists.entry(id.to_string()).or_default();
+        list.status = Status::Failed;
+        list.error = Some(error);
+        list.code = Some(code);
+    }
+
+    /// records a list which is downloaded without verifying the server's certificate
+    ///
+    /// * `id`: the filter list's id
+    pub fn list_insecure_tls(&mut self, id: &str) {
+        self.lists.entry(id.to_string()).or_default().insecure_tls = true;
+    }
+
+    /// records a list whose download the server deferred
+    ///
+    /// * `id`: the filter list's id
+    /// * `retry_after`: the server's Retry-After header
+    pub fn list_deferred(&mut self, id: &str, retry_after: String) {
+        self.list_status(id, Status::Deferred);
+        self.lists.get_mut(id).unwrap().retry_after = Some(retry_after);
+    }
+
+    /// records a list whose download failed and whose previous download is used instead
+    ///
+    /// * `id`: the filter list's id
+    /// * `code`: the class of the error
+    /// * `error`: the error message
+    pub fn list_stale(&mut self, id: &str, code: ErrorCode, error: String) {
+        self.list_status(id, Status::Stale);
+        let list = self.lists.get_mut(id).unwrap();
+        list.error = Some(error);
+        list.code = Some(code);
+    }
+
+    /// records a list whose files could not be written, e.g. because the disk is full
+    ///
+    /// * `id`: the filter list's id
+    /// * `failure`: the class of the I/O error
+    /// * `error`: the error message
+    pub fn list_io_failed(&mut self, id: &str, failure: IoFailure, error: String) {
+        self.list_failed(id, failure.into(), error);
+        self.lists.get_mut(id).unwrap().io_failure = Some(failure);
+    }
+
+    /// records a list whose match rate dropped sharply
+    ///
+    /// * `id`: the filter list's id
+    /// * `previous`: the average match rate of the previous runs
+    /// * `rate`: the match rate of this run
+    pub fn list_match_rate_dropped(&mut self, id: &str, previous: f64, rate: f64) {
+        self.lists
+            .entry(id.to_string())
+            .or_default()
+            .match_rate_drop = Some((previous, rate));
+    }
+
+    /// records the entries of a rebuilt category and the difference to the last run
+    ///
+    /// * `name`: the category's name
+    /// * `entries`: the new entries
+    /// * `previous`: the entries of the last run
+    pub fn category_updated<'a>(
+        &mut self,
+        name: &str,
+        entries: impl Iterator<Item = &'a String>,
+        previous: &HashSet<String>,
+    ) {
+        let mut category = CategoryReport::default();
+        let mut current = HashSet::new();
+        for entry in entries {
+            category.entries += 1;
+            if !previous.contains(entry) {
+                category.added += 1;
+                if category.added_sample.len() < DIFF_SAMPLE_SIZE {
+                    category.added_sample.push(entry.clone());
+                }
+            }
+            current.insert(entry);
+        }
+        for entry in previous.iter().filter(|e| !current.contains(e)) {
+            category.removed += 1;
+            if category.removed_sample.len() < DIFF_SAMPLE_SIZE {
+                category.removed_sample.push(entry.clone());
+            }
+        }
+        category.removed_sample.sort();
+        self.categories.insert(name.to_string(), category);
+    }
+
+    /// records a category which was taken over from the last run
+    ///
+    /// * `name`: the category's name
+    pub fn category_unchanged(&mut self, name: &str) {
+        self.categories.insert(
+            name.to_string(),
+            CategoryReport {
+                status: Status::Unchanged,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// records a failure of a category
+    ///
+    /// * `name`: the category's name
+    /// * `code`: the class of the error
+    /// * `error`: the error message
+    pub fn category_failed(&mut self, name: &str, code: ErrorCode, error: String) {
+        self.categories.entry(name.to_string()).or_default().status = Status::Failed;
+        self.failures.push(format!("{code} {name}: {error}"));
+    }
+
+    /// records a category whose files could not be written, e.g. because the disk is full
+    ///
+    /// * `name`: the category's name
+    /// * `failure`: the class of the I/O error
+    /// * `error`: the error message
+    pub fn category_io_failed(&mut self, name: &str, failure: IoFailure, error: String) {
+        self.category_failed(name, failure.into(), error);
+        self.categories.get_mut(name).unwrap().io_failure = Some(failure);
+    }
+
+    /// returns the lists and categories affected by each class of I/O failure
+    pub fn io_failures(&self) -> BTreeMap<IoFailure, Vec<String>> {
+        let mut failures: BTreeMap<IoFailure, Vec<String>> = BTreeMap::new();
+        let lists = self.lists.iter().map(|(id, l)| (id, l.io_failure));
+        let categories = self.categories.iter().map(|(name, c)| (name, c.io_failure));
+        for (name, failure) in lists.chain(categories) {
+            if let Some(failure) = failure {
+                failures.entry(failure).or_default().push(name.clone());
+            }
+        }
+        failures
+    }
+
+    /// returns the sampled entries added to any category
+    pub fn added_entries(&self) -> Vec<String> {
+        let mut entries: Vec<String> = self
+            .categories
+            .values()
+            .flat_map(|c| c.added_sample.iter().cloned())
+            .collect();
+        entries.sort();
+        entries.dedup();
+        entries
+    }
+
+    /// returns true if anything failed during the run
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty() || self.lists.values().any(|l| l.status == Status::Failed)
+    }
+
+    /// writes the report in the configured format
+    ///
+    /// * `config`: the report configuration
+    pub fn write(&self, config: &ReportConfig) -> anyhow::Result<()> {
+        let contents = match config.format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Html => self.to_html(),
+        };
+        let path = Path::new(&config.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| "could not create report directory")?;
+        }
+        fs::write(path, contents)
+            .with_context(|| format!("could not write report {}", config.path))?;
+        Ok(())
+    }
+
+    fn count(statuses: impl Iterator<Item = Status>) -> Vec<String> {
+        let mut counts = [0; 5];
+        for status in statuses {
+            counts[status as usize] += 1;
+        }
+        counts.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![];
+
+        let mut summary = vec![vec![
+            "".to_string(),
+            "Updated".to_string(),
+            "Unchanged".to_string(),
+            "Failed".to_string(),
+            "Deferred".to_string(),
+            "Stale".to_string(),
+        ]];
+        summary.push(
+            [
+                vec!["Lists".to_string()],
+                Self::count(self.lists.values().map(|l| l.status)),
+            ]
+            .concat(),
+        );
+        summary.push(
+            [
+                vec!["Categories".to_string()],
+                Self::count(self.categories.values().map(|c| c.status)),
+            ]
+            .concat(),
+        );
+        let mut items: Vec<String> = self
+            .io_failures()
+            .into_iter()
+            .map(|(failure, names)| format!("{failure}: {}", names.join(", ")))
+            .collect();
+        items.extend(self.lists.iter().filter_map(|(id, l)| {
+            l.retry_after
+                .as_ref()
+                .map(|r| format!("deferred: {id} (retry after {r})"))
+        }));
+        items.extend(
+            self.lists
+                .iter()
+                .filter(|(_, l)| l.status == Status::Stale)
+                .map(|(id, _)| format!("stale: {id}")),
+        );
+        items.extend(
+            self.lists
+                .iter()
+                .filter(|(_, l)| l.insecure_tls)
+                .map(|(id, _)| format!("insecure TLS: {id}")),
+        );
+        items.extend(self.lists.iter().filter_map(|(id, l)| {
+            l.match_rate_drop.map(|(previous, rate)| {
+                format!(
+                    "format changed?: {} (match rate {:.1}% instead of {:.1}%)",
+                    id,
+                    rate * 100.0,
+                    previous * 100.0
+                )
+            })
+        }));
+        sections.push(Section {
+            heading: "Summary".to_string(),
+            table: summary,
+            items,
+        });
+
+        let mut categories = vec![vec![
+            "Category".to_string(),
+            "Status".to_string(),
+            "Entries".to_string(),
+            "Added".to_string(),
+            "Removed".to_string(),
+        ]];
+        for (name, c) in self.categories.iter() {
+            categories.push(vec![
+                name.clone(),
+                format!("{:?}", c.status),
+                c.entries.to_string(),
+                c.added.to_string(),
+                c.removed.to_string(),
+            ]);
+        }
+        sections.push(Section {
+            heading: "Categories".to_string(),
+            table: categories,
+            items: vec![],
+        });
+
+        for (name, c) in self.categories.iter() {
+            if c.added_sample.is_empty() && c.removed_sample.is_empty() {
+                continue;
+            }
+            let mut items: Vec<String> = c
+                .added_sample
+                .iter()
+                .map(|e| match self.annotations.get(e) {
+                    Some(note) => format!("+ {e} ({note})"),
+                    None => format!("+ {e}"),
+                })
+                .collect();
+            if c.added > c.added_sample.len() {
+                items.push(format!("+ … {} more", c.added - c.added_sample.len()));
+            }
+            items.extend(c.removed_sample.iter().map(|e| format!("- {e}")));
+            if c.removed > c.removed_sample.len() {
+                items.push(format!("- … {} more", c.removed - c.removed_sample.len()));
+            }
+            sections.push(Section {
+                heading: format!("Changes in {name}"),
+                table: vec![],
+                items,
+            });
+        }
+
+        let mut lists = vec![vec![
+            "List".to_string(),
+            "Status".to_string(),
+            "Lines extracted".to_string(),
+            "Lines skipped".to_string(),
+        ]];
+        for (id, l) in self.lists.iter() {
+            let skipped: Vec<String> = l
+                .skip_reasons
+                .iter()
+                .map(|(reason, n)| format!("{n} {reason}"))
+                .collect();
+            lists.push(vec![
+                id.clone(),
+                format!("{:?}", l.status),
+                l.lines_extracted.map(|n| n.to_string()).unwrap_or_default(),
+                skipped.join(", "),
+            ]);
+        }
+        sections.push(Section {
+            heading: "Lists".to_string(),
+            table: lists,
+            items: vec![],
+        });
+
+        let mut failures: Vec<String> = self
+            .lists
+            .iter()
+            .filter_map(|(id, l)| match (&l.code, &l.error) {
+                (Some(code), Some(e)) => Some(format!("{code} {id}: {e}")),
+                (None, Some(e)) => Some(format!("{id}: {e}")),
+                _ => None,
+            })
+            .collect();
+        failures.extend(self.failures.iter().cloned());
+        if !failures.is_empty() {
+            sections.push(Section {
+                heading: "Failures".to_string(),
+                table: vec![],
+                items: failures,
+            });
+        }
+        sections
+    }
+
+    /// renders the report as Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "# Harvester run report\n\nStarted: {}\n",
+            self.started.to_rfc2822()
+        );
+        if !self.run_id.is_empty() {
+            md.push_str(&format!("Run: `{}`\n", self.run_id));
+        }
+        for section in self.sections() {
+            md.push_str(&format!("\n## {}\n\n", section.heading));
+            for (i, row) in section.table.iter().enumerate() {
+                md.push_str(&format!("| {} |\n", row.join(" | ")));
+                if i == 0 {
+                    md.push_str(&format!("|{}\n", " --- |".repeat(row.len())));
+                }
+            }
+            for item in section.items.iter() {
+                md.push_str(&format!("- `{item}`\n"));
+            }
+        }
+        md
+    }
+
+    /// renders the report as a simple HTML page
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Harvester run report</title></head>\n<body>\n<h1>Harvester run report</h1>\n<p>Started: {}</p>\n",
+            self.started.to_rfc2822()
+        );
+        if !self.run_id.is_empty() {
+            html.push_str(&format!("<p>Run: {}</p>\n", escape_html(&self.run_id)));
+        }
+        for section in self.sections() {
+            html.push_str(&format!("<h2>{}</h2>\n", escape_html(&section.heading)));
+            if !section.table.is_empty() {
+                html.push_str("<table>\n");
+                for (i, row) in section.table.iter().enumerate() {
+                    let cell = if i == 0 { "th" } else { "td" };
+                    let cells: String = row
+                        .iter()
+                        .map(|c| format!("<{cell}>{}</{cell}>", escape_html(c)))
+                        .collect();
+                    html.push_str(&format!("<tr>{cells}</tr>\n"));
+                }
+                html.push_str("</table>\n");
+            }
+            if !section.items.is_empty() {
+                html.push_str("<ul>\n");
+                for item in section.items.iter() {
+                    html.push_str(&format!("<li><code>{}</code></li>\n", escape_html(item)));
+                }
+                html.push_str("</ul>\n");
+            }
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_markdown() {
+        let mut report = RunReport::default();
+        report.list_status("one", Status::Updated);
+        report.list_status("two", Status::Unchanged);
+        report.list_failed(
+            "three",
+            ErrorCode::DownloadFailed,
+            "status code 404".to_string(),
+        );
+        report.list_deferred("four", "120".to_string());
+        report.list_insecure_tls("one");
+        report.list_match_rate_dropped("one", 0.9, 0.05);
+        report.lists.get_mut("one").unwrap().skip_reasons =
+            BTreeMap::from([(SkipReason::NoMatch, 12), (SkipReason::InvalidEntry, 1)]);
+        report.list_stale(
+            "five",
+            ErrorCode::DownloadFailed,
+            "status code 500".to_string(),
+        );
+        let previous = HashSet::from(["old.domain".to_string(), "kept.domain".to_string()]);
+        let entries = ["kept.domain".to_string(), "new.domain".to_string()];
+        report.category_updated("malware", entries.iter(), &previous);
+        report.annotations.insert(
+            "new.domain".to_string(),
+            "registrar: Example Registrar".to_string(),
+        );
+        report.category_io_failed("phishing", IoFailure::DiskFull, "disk full".to_string());
+
+        report.run_id = "3f2a1b2c-0000-4000-8000-000000000000".to_string();
+
+        let md = report.to_markdown();
+        assert!(md.contains("Run: `3f2a1b2c-0000-4000-8000-000000000000`\n"));
+        assert!(md.contains("| Lists | 1 | 1 | 1 | 1 | 1 |\n"));
+        assert!(md.contains("| malware | Updated | 2 | 1 | 1 |\n"));
+        assert!(md.contains("| one | Updated |  | 12 no match, 1 invalid entry |\n"));
+        assert!(md.contains("- `+ new.domain (registrar: Example Registrar)`\n- `- old.domain`\n"));
+        assert_eq!(report.added_entries(), vec!["new.domain"]);
+        assert!(md.contains("- `HV1001 three: status code 404`\n"));
+        assert!(md.contains("- `HV5001 phishing: disk full`\n"));
+        assert!(md.contains("- `disk full: phishing`\n"));
+        assert!(md.contains("- `deferred: four (retry after 120)`\n"));
+        assert!(md.contains("- `stale: five`\n"));
+        assert!(md.contains("- `insecure TLS: one`\n"));
+        assert!(md.contains("- `format changed?: one (match rate 5.0% instead of 90.0%)`\n"));
+        assert!(md.contains("- `HV1001 five: status code 500`\n"));
+        assert_eq!(report.io_failures().len(), 1);
+        assert!(report.has_failures());
+    }
+
+    #[test]
+    fn test_report_html_escaped() {
+        let mut report = RunReport::default();
+        report.list_failed("one", ErrorCode::ExtractFailed, "<script>".to_string());
+        let html = report.to_html();
+        assert!(html.contains("<li><code>HV2001 one: &lt;script&gt;</code></li>"));
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{BufRead, Write};
+
+use crate::report::CategoryReport;
+
+/// Asks whether the changes of a category are written to the outputs. Changes
+/// smaller than the threshold are approved without asking, a missing answer
+/// rejects them.
+///
+/// * `name`: the category's name
+/// * `category`: the category's changes
+/// * `auto_approve_below`: the number of added and removed entries approved without asking
+/// * `input`: the answers are read from here
+/// * `out`: the changes and the question are written here
+pub fn confirm(
+    name: &str,
+    category: &CategoryReport,
+    auto_approve_below: Option<usize>,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> bool {
+    let changes = category.added + category.removed;
+    if auto_approve_below.is_some_and(|n| changes < n) {
+        return true;
+    }
+    let mut text = format!(
+        "\n{}: {} added, {} removed\n",
+        name, category.added, category.removed
+    );
+    for (sign, sample, total) in [
+        ('+', &category.added_sample, category.added),
+        ('-', &category.removed_sample, category.removed),
+    ] {
+        for entry in sample.iter() {
+            text.push_str(&format!("  {sign} {entry}\n"));
+        }
+        if total > sample.len() {
+            text.push_str(&format!("  {sign} and {} more\n", total - sample.len()));
+        }
+    }
+    text.push_str(&format!("write the changes of {name}? [y/N] "));
+    if out.write_all(text.as_bytes()).and(out.flush()).is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::report::Status;
+
+    use super::*;
+
+    #[test]
+    fn test_confirm() {
+        let category = CategoryReport {
+            status: Status::Updated,
+            entries: 10,
+            added: 3,
+            removed: 1,
+            added_sample: vec!["new.domain".to_string(), "other.domain".to_string()],
+            removed_sample: vec!["old.domain".to_string()],
+            ..Default::default()
+        };
+        let ask = |answer: &str, threshold| {
+            let mut out = vec![];
+            let approved = confirm(
+                "malware",
+                &category,
+                threshold,
+                &mut Cursor::new(answer.as_bytes()),
+                &mut out,
+            );
+            (approved, String::from_utf8(out).unwrap())
+        };
+
+        let (approved, out) = ask("y\n", None);
+        assert!(approved);
+        assert_eq!(
+            out,
+            "\nmalware: 3 added, 1 removed\n  + new.domain\n  + other.domain\n  + and 1 more\n  \
+             - old.domain\nwrite the changes of malware? [y/N] "
+        );
+        assert!(ask("Yes\n", Some(4)).0);
+        assert!(!ask("\n", None).0);
+        assert!(!ask("", None).0);
+        assert!(!ask("n\n", Some(4)).0);
+        assert_eq!(ask("", Some(5)), (true, String::new()));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    io::atomic_file::AtomicFile,
+    observer::{Observer, PipelineStage},
+    report::Status,
+};
+
+/// file in the cache directory the state of an unfinished run is kept in
+pub const RUN_STATE_FILE_NAME: &str = "run_state.json";
+
+/// RunState records the progress of a run which didn't finish, so it can be resumed
+/// by the next run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RunState {
+    /// source of each list the download stage completed, per list id
+    pub downloaded: BTreeMap<String, String>,
+}
+
+impl RunState {
+    /// Loads the state of the interrupted run from the cache directory, None if the
+    /// last run finished
+    ///
+    /// * `cache_dir`: the directory the state is stored in
+    pub fn load(cache_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = cache_dir.join(RUN_STATE_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path).with_context(|| "error reading run state")?;
+        let state = serde_json::from_str(&contents).with_context(|| "invalid run state")?;
+        Ok(Some(state))
+    }
+
+    /// Writes the state to the cache directory, replacing the former state only once
+    /// the new one was written completely
+    ///
+    /// * `cache_dir`: the directory the state is stored in
+    pub fn save(&self, cache_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(cache_dir).with_context(|| "could not create cache directory")?;
+        let mut file = AtomicFile::create(cache_dir.join(RUN_STATE_FILE_NAME))?;
+        if let Err(e) = file.write_all(serde_json::to_string(self)?.as_bytes()) {
+            file.discard();
+            return Err(e).with_context(|| "could not write run state");
+        }
+        file.commit()
+    }
+
+    /// Removes the state from the cache directory, there is nothing to resume
+    ///
+    /// * `cache_dir`: the directory the state is stored in
+    pub fn remove(cache_dir: &Path) -> anyhow::Result<()> {
+        let path = cache_dir.join(RUN_STATE_FILE_NAME);
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| "could not remove run state")?;
+        }
+        Ok(())
+    }
+}
+
+/// RunStateObserver keeps the run state in the cache directory up to date. The state
+/// is written once a stage is done, after the pipeline stored the manifest the stage's
+/// results depend on.
+pub struct RunStateObserver {
+    cache_dir: PathBuf,
+    /// source of every configured list per list id
+    sources: HashMap<String, String>,
+    state: Mutex<RunState>,
+}
+
+impl RunStateObserver {
+    /// creates an observer starting with an empty state
+    ///
+    /// * `config`: the configuration being processed
+    pub fn new(config: &Config) -> Self {
+        Self {
+            cache_dir: PathBuf::from(&config.cache_dir),
+            sources: config
+                .lists
+                .iter()
+                .map(|l| (l.id.clone(), l.source.clone()))
+                .collect(),
+            state: Default::default(),
+        }
+    }
+}
+
+impl Observer for RunStateObserver {
+    fn on_list_done(&self, stage: PipelineStage, name: &str, _status: Status) {
+        if stage != PipelineStage::Download {
+            return;
+        }
+        if let Some(source) = self.sources.get(name) {
+            self.state
+                .lock()
+                .unwrap()
+                .downloaded
+                .insert(name.to_string(), source.clone());
+        }
+    }
+
+    fn on_stage_done(&self, stage: PipelineStage) {
+        if stage != PipelineStage::Download {
+            return;
+        }
+        if let Err(e) = self.state.lock().unwrap().save(&self.cache_dir) {
+            warn!("{:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator};
+
+    use super::*;
+
+    #[test]
+    fn test_run_state_observer() {
+        let cache = CacheFileCreator::new("test_run_state_observer", "in", "out");
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "one".to_string(),
+            source: "https://list.one".to_string(),
+            ..Default::default()
+        }];
+        let cache_dir = Path::new(&config.cache_dir);
+        RunState::remove(cache_dir).unwrap();
+        assert_eq!(RunState::load(cache_dir).unwrap(), None);
+
+        let observer = RunStateObserver::new(&config);
+        observer.on_list_done(PipelineStage::Download, "one", Status::Updated);
+        observer.on_list_done(PipelineStage::Download, "unknown", Status::Updated);
+        observer.on_list_done(PipelineStage::Extract, "one", Status::Updated);
+        assert_eq!(RunState::load(cache_dir).unwrap(), None);
+        observer.on_stage_done(PipelineStage::Download);
+        assert_eq!(
+            RunState::load(cache_dir).unwrap().unwrap().downloaded,
+            BTreeMap::from([("one".to_string(), "https://list.one".to_string())])
+        );
+
+        RunState::remove(cache_dir).unwrap();
+        assert_eq!(RunState::load(cache_dir).unwrap(), None);
+    }
+}
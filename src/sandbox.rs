@@ -0,0 +1,456 @@
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::config::Config;
+
+/// SandboxMode defines how far harvester restricts itself before processing lists
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SandboxMode {
+    /// restrict the file system to the configured directories and the network to HTTPS
+    Strict,
+    Off,
+}
+
+/// system directories which are readable and executable inside the sandbox, they
+/// contain the shared libraries, certificates, the resolver configuration and the
+/// programs called by transform commands
+const SYSTEM_PATHS: &[&str] = &[
+    "/bin", "/sbin", "/usr", "/lib", "/lib32", "/lib64", "/etc", "/proc", "/sys", "/dev",
+];
+
+/// devices which are writable inside the sandbox
+const DEVICES: &[&str] = &["/dev/null"];
+
+/// the only port connections may be opened to inside the sandbox
+const HTTPS_PORT: u64 = 443;
+
+/// Paths lists the locations the sandboxed process may access
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Paths {
+    /// files and directories which are only read
+    pub read: Vec<PathBuf>,
+    /// directories in which files are created, written and removed
+    pub write: Vec<PathBuf>,
+}
+
+impl Paths {
+    /// collects the paths a run with the configuration needs to access
+    ///
+    /// * `config`: the configuration of the run
+    /// * `config_path`: the file the configuration was loaded from
+    pub fn new(config: &Config, config_path: &Path) -> Self {
+        let parent = |file: &str| match Path::new(file).parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut read: Vec<PathBuf> = config
+            .lists
+            .iter()
+            .filter_map(|l| l.transform.as_ref())
+            .chain(
+                config
+                    .categories
+                    .values()
+                    .filter_map(|c| c.transform.as_ref()),
+            )
+            .map(PathBuf::from)
+            .collect();
+        read.push(config_path.to_path_buf());
+        if let Some(template) = &config.template {
+            read.extend(
+                [&template.header, &template.footer]
+                    .into_iter()
+                    .flatten()
+                    .chain([&template.entry])
+                    .map(PathBuf::from),
+            );
+        }
+        if let Some(geoip) = &config.geoip {
+            read.extend(
+                [&geoip.country_db, &geoip.asn_db]
+                    .into_iter()
+                    .flatten()
+                    .map(PathBuf::from),
+            );
+        }
+
+        let mut write = vec![PathBuf::from(&config.cache_dir)];
+        write.extend(config.output_dir.iter().map(PathBuf::from));
+        write.extend(config.archive.iter().map(|a| PathBuf::from(&a.path)));
+        let files = [
+            config.report.as_ref().map(|r| &r.path),
+            config.sqlite.as_ref().map(|s| &s.path),
+            config.audit_log.as_ref(),
+            config.blocky.as_ref().and_then(|b| b.references.as_ref()),
+        ];
+        write.extend(files.into_iter().flatten().map(|f| parent(f)));
+
+        read.sort();
+        read.dedup();
+        write.sort();
+        write.dedup();
+        Self { read, write }
+    }
+}
+
+/// Restricts the process to the paths needed by the configured run and its network
+/// access to HTTPS. The restrictions only cover threads started afterwards, they
+/// have to be applied before the async runtime starts its workers. Fails if the
+/// platform or the kernel doesn't support the sandbox.
+///
+/// * `config`: the configuration of the run
+/// * `config_path`: the file the configuration was loaded from
+pub fn apply(config: &Config, config_path: &Path) -> anyhow::Result<()> {
+    let paths = Paths::new(config, config_path);
+    for dir in paths.write.iter() {
+        std::fs::create_dir_all(dir)?;
+    }
+    imp::apply(&paths)
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod imp {
+    use super::Paths;
+
+    pub fn apply(_paths: &Paths) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "the sandbox is only supported on Linux on x86_64 and aarch64"
+        ))
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod imp {
+    use std::{
+        fs::{File, OpenOptions},
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::fs::OpenOptionsExt,
+        },
+        path::Path,
+    };
+
+    use anyhow::Context;
+    use libc::{c_long, sock_filter, sock_fprog};
+
+    use super::{Paths, DEVICES, HTTPS_PORT, SYSTEM_PATHS};
+
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+    const LANDLOCK_RULE_NET_PORT: u32 = 2;
+
+    const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    const ACCESS_FS_REFER: u64 = 1 << 13;
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+    const ACCESS_FS_IOCTL_DEV: u64 = 1 << 15;
+    /// the access rights of the first landlock ABI
+    const ACCESS_FS_V1: u64 = (1 << 13) - 1;
+    /// the access rights which apply to files as opposed to directories
+    const ACCESS_FS_FILE: u64 = ACCESS_FS_EXECUTE
+        | ACCESS_FS_WRITE_FILE
+        | ACCESS_FS_READ_FILE
+        | ACCESS_FS_TRUNCATE
+        | ACCESS_FS_IOCTL_DEV;
+    const ACCESS_NET_BIND_TCP: u64 = 1 << 0;
+    const ACCESS_NET_CONNECT_TCP: u64 = 1 << 1;
+
+    /// the first landlock ABI able to restrict TCP connections, Linux 6.7
+    const ABI_NET: i64 = 4;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// system calls harvester never needs, they fail with EPERM inside the sandbox
+    const DENIED_SYSCALLS: &[c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_userfaultfd,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_reboot,
+        libc::SYS_acct,
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+    ];
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+        handled_access_net: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    #[repr(C)]
+    struct NetPortAttr {
+        allowed_access: u64,
+        port: u64,
+    }
+
+    pub fn apply(paths: &Paths) -> anyhow::Result<()> {
+        // SAFETY: querying the ABI version doesn't take any attributes
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| "the kernel doesn't support landlock or it is disabled");
+        }
+        if abi < ABI_NET {
+            return Err(anyhow::anyhow!(
+                "landlock ABI {abi} can't restrict network access, Linux 6.7 or later is required"
+            ));
+        }
+
+        let mut handled_fs = ACCESS_FS_V1 | ACCESS_FS_REFER | ACCESS_FS_TRUNCATE;
+        if abi >= 5 {
+            handled_fs |= ACCESS_FS_IOCTL_DEV;
+        }
+        let attr = RulesetAttr {
+            handled_access_fs: handled_fs,
+            handled_access_net: ACCESS_NET_BIND_TCP | ACCESS_NET_CONNECT_TCP,
+        };
+        // SAFETY: the attributes outlive the call and their size is passed along
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| "could not create the landlock ruleset");
+        }
+        // SAFETY: the kernel returned a new file descriptor owned by nobody else
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        let read = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+        for path in SYSTEM_PATHS.iter().map(Path::new) {
+            if path.exists() {
+                allow_path(&ruleset, path, read & handled_fs)?;
+            }
+        }
+        for path in paths.read.iter().filter(|p| p.exists()) {
+            allow_path(&ruleset, path, ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR)?;
+        }
+        for path in DEVICES.iter().map(Path::new) {
+            allow_path(
+                &ruleset,
+                path,
+                ACCESS_FS_READ_FILE | ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE,
+            )?;
+        }
+        for path in paths.write.iter() {
+            allow_path(&ruleset, path, handled_fs & !ACCESS_FS_EXECUTE)?;
+        }
+        allow_port(&ruleset, HTTPS_PORT)?;
+
+        // SAFETY: prctl doesn't take any pointers for this option
+        if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| "could not drop the ability to gain privileges");
+        }
+        // SAFETY: the ruleset is a valid landlock file descriptor
+        if unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| "could not enforce the landlock ruleset");
+        }
+        deny_syscalls()
+    }
+
+    /// allows access beneath a path, the rights not applying to files are dropped
+    /// if the path is a file
+    fn allow_path(ruleset: &OwnedFd, path: &Path, mut access: u64) -> anyhow::Result<()> {
+        let file: File = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(path)
+            .with_context(|| format!("could not open {} for the sandbox", path.display()))?;
+        if !file.metadata()?.is_dir() {
+            access &= ACCESS_FS_FILE;
+        }
+        let attr = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: file.as_raw_fd(),
+        };
+        add_rule(ruleset, LANDLOCK_RULE_PATH_BENEATH, &attr as *const _ as _)
+            .with_context(|| format!("could not allow access to {}", path.display()))
+    }
+
+    /// allows TCP connections to a port
+    fn allow_port(ruleset: &OwnedFd, port: u64) -> anyhow::Result<()> {
+        let attr = NetPortAttr {
+            allowed_access: ACCESS_NET_CONNECT_TCP,
+            port,
+        };
+        add_rule(ruleset, LANDLOCK_RULE_NET_PORT, &attr as *const _ as _)
+            .with_context(|| format!("could not allow connections to port {port}"))
+    }
+
+    fn add_rule(ruleset: &OwnedFd, rule_type: u32, attr: *const u8) -> std::io::Result<()> {
+        // SAFETY: the attributes match the rule type and outlive the call
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                rule_type,
+                attr,
+                0,
+            )
+        };
+        match result {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    /// installs a seccomp filter failing the denied system calls with EPERM and
+    /// killing the process on system calls of another architecture
+    fn deny_syscalls() -> anyhow::Result<()> {
+        let stmt = |code: u32, k: u32| sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        let jump = |k: u32, jt: u8, jf: u8| sock_filter {
+            code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        };
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let ret = libc::BPF_RET | libc::BPF_K;
+
+        // offsets of the architecture and the system call number in seccomp_data
+        let mut filter = vec![
+            stmt(load, 4),
+            jump(AUDIT_ARCH, 1, 0),
+            stmt(ret, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(load, 0),
+        ];
+        for nr in DENIED_SYSCALLS {
+            filter.push(jump(*nr as u32, 0, 1));
+            filter.push(stmt(ret, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+        }
+        filter.push(stmt(ret, libc::SECCOMP_RET_ALLOW));
+
+        let program = sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        // SAFETY: the program outlives the call, the kernel copies it
+        let result = unsafe {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const sock_fprog,
+            )
+        };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| "could not install the seccomp filter");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        archive::ArchiveConfig,
+        config::CategoryOptions,
+        filter_list::FilterList,
+        report::{ReportConfig, ReportFormat},
+        tests::helper::cache_file_creator::CacheFileCreator,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_sandbox_paths() {
+        let cache = CacheFileCreator::new("test_sandbox_paths", "in", "out");
+        let mut config = cache.new_test_config();
+        config.cache_dir = "/var/cache/harvester".to_string();
+        config.output_dir = vec!["/srv/lists".to_string(), "/srv/lists".to_string()];
+        config.lists = vec![FilterList {
+            transform: Some("/etc/harvester/rewrite.lua".to_string()),
+            ..Default::default()
+        }];
+        config.categories.insert(
+            "malware".to_string(),
+            CategoryOptions {
+                transform: Some("/etc/harvester/malware.lua".to_string()),
+                ..Default::default()
+            },
+        );
+        config.archive = Some(ArchiveConfig {
+            path: "/var/lib/harvester/archive".to_string(),
+            retention_days: None,
+        });
+        config.report = Some(ReportConfig {
+            format: ReportFormat::Html,
+            path: "report.html".to_string(),
+            rdap: None,
+        });
+        config.audit_log = Some("/var/log/harvester/audit.log".to_string());
+
+        assert_eq!(
+            Paths::new(&config, Path::new("/etc/harvester/config.json")),
+            Paths {
+                read: vec![
+                    PathBuf::from("/etc/harvester/config.json"),
+                    PathBuf::from("/etc/harvester/malware.lua"),
+                    PathBuf::from("/etc/harvester/rewrite.lua"),
+                ],
+                write: vec![
+                    PathBuf::from("/srv/lists"),
+                    PathBuf::from("/var/cache/harvester"),
+                    PathBuf::from("/var/lib/harvester/archive"),
+                    PathBuf::from("/var/log/harvester"),
+                    PathBuf::from("."),
+                ],
+            }
+        );
+    }
+}
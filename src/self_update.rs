@@ -0,0 +1,184 @@
+use std::{env, fs, io::Write, path::Path, time::Duration};
+
+use anyhow::Context;
+use minisign_verify::{PublicKey, Signature};
+use reqwest::header::ACCEPT;
+use semver::Version;
+use serde::Deserialize;
+
+use crate::io::atomic_file::AtomicFile;
+
+/// the GitHub API endpoint describing the latest release
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/bob3000/harvester/releases/latest";
+
+/// minisign public key the release binaries are signed with, embedded at build time
+const RELEASE_KEY: Option<&str> = option_env!("HARVESTER_RELEASE_KEY");
+
+/// the parts of a GitHub release needed to update the binary
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// returns the name of the release asset containing the binary for this platform,
+/// its signature is expected in an asset of the same name ending with .minisig
+pub fn asset_name() -> String {
+    format!("harvester-{}-{}", env::consts::ARCH, env::consts::OS)
+}
+
+/// returns the version of a release if it's newer than the running version
+///
+/// * `tag`: the release's tag, optionally starting with a v
+/// * `current`: the running version
+fn newer_version(tag: &str, current: &str) -> anyhow::Result<Option<Version>> {
+    let release = Version::parse(tag.trim_start_matches('v'))
+        .with_context(|| format!("invalid release version {tag}"))?;
+    let current = Version::parse(current)?;
+    Ok((release > current).then_some(release))
+}
+
+/// checks the minisign signature of a downloaded binary
+///
+/// * `public_key`: the base64 encoded minisign public key
+/// * `binary`: the downloaded binary
+/// * `signature`: the contents of the binary's .minisig file
+fn verify(public_key: &str, binary: &[u8], signature: &str) -> anyhow::Result<()> {
+    let key = PublicKey::from_base64(public_key)
+        .map_err(|e| anyhow::anyhow!("invalid release key: {e}"))?;
+    let signature =
+        Signature::decode(signature).map_err(|e| anyhow::anyhow!("invalid signature: {e}"))?;
+    key.verify(binary, &signature, false)
+        .map_err(|e| anyhow::anyhow!("signature verification failed: {e}"))
+}
+
+/// Replaces the executable by the new binary keeping its permissions. The executable
+/// is only replaced once the new binary was written completely.
+///
+/// * `exe`: path of the executable
+/// * `binary`: the new binary
+fn replace(exe: &Path, binary: &[u8]) -> anyhow::Result<()> {
+    let permissions = fs::metadata(exe)
+        .with_context(|| format!("could not read {}", exe.display()))?
+        .permissions();
+    let mut file = AtomicFile::create(exe.to_path_buf())?;
+    if let Err(e) = file
+        .write_all(binary)
+        .and_then(|_| file.set_permissions(permissions))
+    {
+        file.discard();
+        return Err(e).with_context(|| format!("could not write {}", exe.display()));
+    }
+    if let Err(e) = file.commit() {
+        file.discard();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// requests a url and returns the response's body
+///
+/// * `cli`: the HTTP client
+/// * `url`: the url to request
+async fn fetch(cli: &reqwest::Client, url: &str) -> anyhow::Result<bytes::Bytes> {
+    Ok(cli
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?)
+}
+
+/// Looks up the latest release on GitHub and replaces the running executable by its
+/// binary after verifying the binary's signature. Returns the new version or None if
+/// the running version is current.
+pub async fn self_update() -> anyhow::Result<Option<Version>> {
+    let key = RELEASE_KEY.with_context(|| {
+        "this build doesn't contain the key to verify releases, update it the way it was installed"
+    })?;
+    let cli = reqwest::Client::builder()
+        .user_agent(concat!("harvester/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(300))
+        .build()?;
+    let response = cli
+        .get(LATEST_RELEASE_URL)
+        .header(ACCEPT, "application/vnd.github+json")
+        .send()
+        .await?
+        .error_for_status()?;
+    let release: Release = serde_json::from_str(&response.text().await?)
+        .with_context(|| "invalid response from the GitHub releases API")?;
+    let version = match newer_version(&release.tag_name, env!("CARGO_PKG_VERSION"))? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let name = asset_name();
+    let url = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.browser_download_url.clone())
+            .with_context(|| format!("release {} has no asset {}", release.tag_name, name))
+    };
+    let binary = fetch(&cli, &url(&name)?).await?;
+    let signature = fetch(&cli, &url(&format!("{name}.minisig"))?).await?;
+    verify(key, &binary, &String::from_utf8_lossy(&signature))?;
+
+    let exe = env::current_exe()?.canonicalize()?;
+    replace(&exe, &binary)?;
+    Ok(Some(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::tests::helper::cache_file_creator::TEST_CACHE;
+
+    use super::*;
+
+    const KEY: &str = "RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBAgMEBQYHCNTYwQskLfcpt+sz4qCi0Ctl65QbnI7jbvzWELPpsnIYoiqvY9eKCDo87i8c6sup0BGNL0PFN5CK/z333DFfxgs=
+trusted comment: timestamp:1760000000\tfile:harvester-x86_64-linux
+0wAn61Eqfq6qWo3to70zO3jZnaGjj1Vj3MoVP8dHV/4QXMDEVxQLG5My6Vis64JM22GVyT08MslSIkArg2rPBQ==
+";
+
+    #[test]
+    fn test_verify_release() {
+        verify(KEY, b"harvester binary\n", SIGNATURE).unwrap();
+        assert!(verify(KEY, b"tampered binary\n", SIGNATURE).is_err());
+
+        assert_eq!(
+            newer_version("v1.2.0", "1.1.9").unwrap(),
+            Some(Version::new(1, 2, 0))
+        );
+        assert_eq!(newer_version("1.1.9", "1.1.9").unwrap(), None);
+        assert!(newer_version("latest", "1.1.9").is_err());
+    }
+
+    #[test]
+    fn test_replace_binary() {
+        let dir = Path::new(TEST_CACHE).join("test_replace_binary");
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("harvester");
+        fs::write(&exe, "old").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+
+        replace(&exe, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "new");
+        assert_eq!(
+            fs::metadata(&exe).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+    }
+}
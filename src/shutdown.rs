@@ -0,0 +1,135 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    observer::{Observer, PipelineStage},
+    pipeline::CancellationToken,
+};
+
+/// ShutdownPolicy defines what an interrupted run completes before it exits. A second
+/// interruption always stops the run immediately.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShutdownPolicy {
+    /// stop all stages at once, the run can be continued with --resume
+    #[default]
+    Immediate,
+    /// complete the running stage and skip the following ones
+    FinishCurrentStage,
+    /// stop downloading and complete the run with the previous versions of the lists
+    /// not downloaded yet
+    FinishRunWithoutNetwork,
+}
+
+/// Shutdown applies the shutdown policy to the interruptions of a run
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    policy: ShutdownPolicy,
+    /// cancels all stages
+    cancellation: CancellationToken,
+    /// cancels the downloads only
+    network: CancellationToken,
+    /// set once the run was interrupted
+    requested: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    /// creates the handler of the interruptions of a run
+    ///
+    /// * `policy`: what the run completes after the first interruption
+    /// * `cancellation`: the token the pipeline is cancelled with
+    pub fn new(policy: ShutdownPolicy, cancellation: CancellationToken) -> Self {
+        Self {
+            policy,
+            cancellation,
+            network: CancellationToken::default(),
+            requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// the token the downloads are cancelled with
+    pub fn network(&self) -> CancellationToken {
+        self.network.clone()
+    }
+
+    /// Handles an interruption of the run according to the policy. Returns true if the
+    /// run stops immediately.
+    pub fn interrupt(&self) -> bool {
+        let repeated = self.requested.swap(true, Ordering::SeqCst);
+        if repeated || self.policy == ShutdownPolicy::Immediate {
+            self.network.cancel();
+            self.cancellation.cancel();
+            return true;
+        }
+        if self.policy == ShutdownPolicy::FinishRunWithoutNetwork {
+            self.network.cancel();
+        }
+        false
+    }
+
+    /// returns true if the run was interrupted with a policy finishing it without
+    /// accessing the network
+    pub fn is_offline(&self) -> bool {
+        self.policy != ShutdownPolicy::Immediate && self.requested.load(Ordering::SeqCst)
+    }
+}
+
+impl Observer for Shutdown {
+    fn on_stage_done(&self, stage: PipelineStage) {
+        // the output stage is the last one, nothing is left to be skipped
+        if self.policy == ShutdownPolicy::FinishCurrentStage
+            && stage != PipelineStage::Output
+            && self.requested.load(Ordering::SeqCst)
+            && !self.cancellation.is_cancelled()
+        {
+            info!(
+                "the {} stage is complete, skipping the following stages",
+                stage
+            );
+            self.cancellation.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutdown_policy() {
+        let shutdown = |policy| {
+            let cancellation = CancellationToken::default();
+            (Shutdown::new(policy, cancellation.clone()), cancellation)
+        };
+
+        let (immediate, cancellation) = shutdown(ShutdownPolicy::Immediate);
+        assert!(immediate.interrupt());
+        assert!(cancellation.is_cancelled());
+        assert!(immediate.network().is_cancelled());
+        assert!(!immediate.is_offline());
+
+        let (finish_stage, cancellation) = shutdown(ShutdownPolicy::FinishCurrentStage);
+        finish_stage.on_stage_done(PipelineStage::Download);
+        assert!(!cancellation.is_cancelled());
+        assert!(!finish_stage.interrupt());
+        assert!(!cancellation.is_cancelled());
+        assert!(!finish_stage.network().is_cancelled());
+        assert!(finish_stage.is_offline());
+        finish_stage.on_stage_done(PipelineStage::Extract);
+        assert!(cancellation.is_cancelled());
+
+        let (without_network, cancellation) = shutdown(ShutdownPolicy::FinishRunWithoutNetwork);
+        assert!(!without_network.interrupt());
+        assert!(without_network.network().is_cancelled());
+        without_network.on_stage_done(PipelineStage::Download);
+        assert!(!cancellation.is_cancelled());
+        // the second interruption escalates
+        assert!(without_network.interrupt());
+        assert!(cancellation.is_cancelled());
+    }
+}
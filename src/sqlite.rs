@@ -0,0 +1,168 @@
+#[cfg(feature = "sqlite")]
+use std::{fs, path::Path, str::FromStr};
+
+#[cfg(feature = "sqlite")]
+use anyhow::Context;
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+#[cfg(feature = "sqlite")]
+use crate::{config::ALLOW_CATEGORY, entry::Entry, output::sources::EntrySources, CATEGORIZE_PATH};
+
+/// SqliteConfig points to the SQLite database all categories are written to
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SqliteConfig {
+    /// path of the database file, it's replaced after every run
+    pub path: String,
+}
+
+/// the tables of the database, the entries name the lists they were taken from
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+CREATE TABLE lists (id TEXT PRIMARY KEY, source TEXT NOT NULL, comment TEXT);
+CREATE TABLE tags (list_id TEXT NOT NULL REFERENCES lists(id), tag TEXT NOT NULL, PRIMARY KEY (list_id, tag));
+CREATE TABLE entries (category TEXT NOT NULL, entry TEXT NOT NULL, kind TEXT NOT NULL, PRIMARY KEY (category, entry));
+CREATE TABLE entry_lists (category TEXT NOT NULL, entry TEXT NOT NULL, list_id TEXT NOT NULL REFERENCES lists(id));
+CREATE INDEX entries_entry ON entries(entry);
+CREATE INDEX entry_lists_entry ON entry_lists(entry);
+";
+
+/// Writes the categories, the lists and their tags into a new SQLite database which
+/// replaces the previous one once it's complete. Returns the number of entries
+/// written.
+///
+/// * `config`: the configuration the categories are defined in
+/// * `sqlite`: the database settings
+#[cfg(feature = "sqlite")]
+pub fn write(config: &Config, sqlite: &SqliteConfig) -> anyhow::Result<usize> {
+    let path = Path::new(&sqlite.path);
+    let tmp_path = path.with_extension("tmp");
+    if tmp_path.exists() {
+        fs::remove_file(&tmp_path)?;
+    }
+    let mut conn = Connection::open(&tmp_path)
+        .with_context(|| format!("could not create {}", tmp_path.display()))?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    let mut written = 0;
+    {
+        let mut insert_list =
+            tx.prepare("INSERT OR IGNORE INTO lists (id, source, comment) VALUES (?1, ?2, ?3)")?;
+        let mut insert_tag =
+            tx.prepare("INSERT OR IGNORE INTO tags (list_id, tag) VALUES (?1, ?2)")?;
+        for list in config.lists.iter() {
+            insert_list.execute(params![list.id, list.source, list.comment])?;
+            for tag in list.tags.iter() {
+                insert_tag.execute(params![list.id, tag])?;
+            }
+        }
+
+        let mut insert_entry = tx
+            .prepare("INSERT OR IGNORE INTO entries (category, entry, kind) VALUES (?1, ?2, ?3)")?;
+        let mut insert_source =
+            tx.prepare("INSERT INTO entry_lists (category, entry, list_id) VALUES (?1, ?2, ?3)")?;
+        let cache_dir = Path::new(&config.cache_dir);
+        for category in config.get_tags().iter().filter(|t| *t != ALLOW_CATEGORY) {
+            let contents = fs::read_to_string(cache_dir.join(CATEGORIZE_PATH).join(category))
+                .with_context(|| format!("could not read category {category}"))?;
+            let sources = EntrySources::load(config, category);
+            for entry in contents.lines().filter_map(|l| Entry::from_str(l).ok()) {
+                let name = entry.to_string();
+                let kind = entry.kind().to_string().to_lowercase();
+                insert_entry.execute(params![category, name, kind])?;
+                for list_id in sources.lists(&entry) {
+                    insert_source.execute(params![category, name, list_id])?;
+                }
+                written += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    conn.close().map_err(|(_, e)| e)?;
+    fs::rename(&tmp_path, path).with_context(|| format!("could not replace {}", path.display()))?;
+    Ok(written)
+}
+
+/// fails since the database can't be written without the sqlite feature
+#[cfg(not(feature = "sqlite"))]
+pub fn write(_config: &Config, _sqlite: &SqliteConfig) -> anyhow::Result<usize> {
+    Err(anyhow::anyhow!(
+        "harvester was built without the sqlite feature"
+    ))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use crate::{filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator};
+
+    use super::*;
+
+    #[test]
+    fn test_write_sqlite() {
+        let cache = CacheFileCreator::new("test_write_sqlite", "in", "out");
+        let mut config = cache.new_test_config();
+        let list = |id: &str, tags: &[&str]| FilterList {
+            id: id.to_string(),
+            source: format!("https://{id}.example/list"),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        config.lists = vec![
+            list("one", &["malware", "phishing"]),
+            list("two", &["malware"]),
+        ];
+        let cache_dir = Path::new(&config.cache_dir);
+        fs::create_dir_all(cache_dir.join(CATEGORIZE_PATH)).unwrap();
+        fs::write(
+            cache_dir.join(CATEGORIZE_PATH).join("malware"),
+            "malicious.com\n10.0.0.0/8\n",
+        )
+        .unwrap();
+        fs::write(
+            cache_dir.join(CATEGORIZE_PATH).join("phishing"),
+            "malicious.com\n",
+        )
+        .unwrap();
+
+        let path = cache_dir.join("harvester.db");
+        let sqlite = SqliteConfig {
+            path: path.to_string_lossy().to_string(),
+        };
+        assert_eq!(write(&config, &sqlite).unwrap(), 3);
+        // the previous database is replaced
+        assert_eq!(write(&config, &sqlite).unwrap(), 3);
+
+        let conn = Connection::open(&path).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT e.category, s.list_id FROM entries e JOIN entry_lists s
+                 ON s.category = e.category AND s.entry = e.entry
+                 WHERE e.entry = ?1 ORDER BY e.category, s.list_id",
+            )
+            .unwrap();
+        let why: Vec<(String, String)> = stmt
+            .query_map(["malicious.com"], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            why,
+            vec![
+                ("malware".to_string(), "one".to_string()),
+                ("malware".to_string(), "two".to_string()),
+                ("phishing".to_string(), "one".to_string()),
+            ]
+        );
+        let kind: String = conn
+            .query_row(
+                "SELECT kind FROM entries WHERE entry = '10.0.0.0/8'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(kind, "cidr");
+    }
+}
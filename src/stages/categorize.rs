@@ -1,8 +1,6 @@
 use std::{
-    collections::{BTreeSet, HashSet},
-    fs::{self, File},
-    io::Write,
-    marker::PhantomData,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
     path::{Path, PathBuf},
     str::FromStr,
     sync::atomic::Ordering,
@@ -13,14 +11,145 @@ use futures::future::join_all;
 use tokio::task::JoinHandle;
 
 use crate::{
+    audit::{append, changes, AuditRecord},
+    config::{Config, EntryOrder, TOP_SITES_ID},
+    entry::Entry,
+    error_code::ErrorCode,
     filter_controller::{FilterController, StageCategorize, StageOutput},
-    input::{file::FileInput, Input},
-    io::{category_list_io::CategoryListIO, filter_list_io::FilterListIO},
+    geoip::{entry_ip, GeoIp},
+    input::file::DEFAULT_MAX_LINE_LENGTH,
+    io::{
+        category_list_io::CategoryListIO,
+        filter_list_io::FilterListIO,
+        storage::{ListReader, ListWriter},
+        IoFailure,
+    },
+    normalize::normalize,
+    observer::PipelineStage,
+    output::sources::write_sources,
+    report::Status,
+    transform::get_transform,
+    REVIEW_PATH,
 };
 
+/// returns the ids of the lists included in a category along with the hashes of
+/// their settings
+///
+/// * `config`: the configuration the category is defined in
+/// * `tag`: the category's tag
+fn included_settings(config: &Config, tag: &str) -> BTreeMap<String, String> {
+    config
+        .lists
+        .iter()
+        .filter(|list| list.tags.iter().any(|t| t == tag))
+        .map(|list| (list.id.clone(), list.settings_hash()))
+        .collect()
+}
+
+/// returns the entries of a category in the requested order
+///
+/// * `entries`: the deduplicated entries
+/// * `insertion`: the entries in the order they were read in, only needed for
+///   insertion order
+/// * `order`: the requested order
+fn order_entries(
+    entries: BTreeSet<String>,
+    insertion: Vec<String>,
+    order: EntryOrder,
+) -> Vec<String> {
+    match order {
+        EntryOrder::Lexicographic => entries.into_iter().collect(),
+        EntryOrder::Grouped => {
+            let mut entries: Vec<String> = entries.into_iter().collect();
+            entries.sort_by_cached_key(|e| e.rsplit('.').map(String::from).collect::<Vec<_>>());
+            entries
+        }
+        EntryOrder::Insertion => insertion
+            .into_iter()
+            .filter(|e| entries.contains(e))
+            .collect(),
+    }
+}
+
+/// returns whether the categories are collapsed and whether plain domains cover their
+/// subdomains while collapsing
+///
+/// * `config`: the configuration the categories are defined in
+fn collapse_settings(config: &Config) -> Option<bool> {
+    config
+        .wildcard
+        .as_ref()
+        .filter(|w| w.collapse)
+        .map(|w| w.covers_subdomains(config))
+}
+
+/// returns the settings of the top sites list determining what is subtracted from a
+/// category, None if the top sites aren't subtracted from the category
+///
+/// * `config`: the configuration the category is defined in
+/// * `tag`: the category's tag
+fn top_sites_settings(config: &Config, tag: &str) -> Option<String> {
+    config
+        .top_sites
+        .as_ref()
+        .filter(|t| t.subtract_from.iter().any(|s| s == tag))
+        .map(|t| {
+            serde_json::json!({
+                "source": t.source,
+                "compression": t.compression,
+                "rank_cutoff": t.rank_cutoff,
+            })
+            .to_string()
+        })
+}
+
+/// removes the entries covered by a wildcard of the same category, the wildcard
+/// matches the domain itself and all of its subdomains. Returns the number of
+/// removed entries.
+///
+/// * `entries`: the entries of the category
+/// * `domains_are_wildcards`: plain domains are written as wildcards and cover their
+///   subdomains as well
+fn collapse_subdomains(entries: &mut BTreeSet<String>, domains_are_wildcards: bool) -> usize {
+    let mut wildcards: HashSet<String> = HashSet::new();
+    let mut domains: HashSet<String> = HashSet::new();
+    for entry in entries.iter() {
+        match entry.parse::<Entry>() {
+            Ok(Entry::Wildcard(d)) => {
+                wildcards.insert(d);
+            }
+            Ok(Entry::Domain(d)) if domains_are_wildcards => {
+                domains.insert(d);
+            }
+            _ => {}
+        }
+    }
+    let len_before = entries.len();
+    entries.retain(|entry| {
+        let (domain, is_wildcard) = match entry.parse::<Entry>() {
+            Ok(Entry::Wildcard(d)) => (d, true),
+            Ok(Entry::Domain(d)) => (d, false),
+            _ => return true,
+        };
+        // a domain is covered by its own wildcard
+        if !is_wildcard && wildcards.contains(&domain) {
+            return false;
+        }
+        let mut parent = domain.as_str();
+        while let Some((_, p)) = parent.split_once('.') {
+            if wildcards.contains(p) || domains.contains(p) {
+                return false;
+            }
+            parent = p;
+        }
+        true
+    });
+    len_before - entries.len()
+}
+
 /// This stage assembles the category lists from the data extracted in the previous stage
 /// A category corresponds to a tag on a list.
-impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
+impl<'config, R: ListReader, W: ListWriter> FilterController<'config, StageCategorize, R, W> {
     /// runs the categorize stage and return controller for the output stage
     ///
     /// * `extract_base_path`: The source path containing the URL lists
@@ -29,22 +158,15 @@ impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
         &mut self,
         extract_base_path: &str,
         categorize_base_path: &str,
-    ) -> anyhow::Result<FilterController<StageOutput, FileInput, File>> {
+    ) -> anyhow::Result<FilterController<'config, StageOutput, R, W>> {
         let mut extract_path = PathBuf::from_str(&self.config.cache_dir)?;
         extract_path.push(extract_base_path);
         let mut categorize_path = PathBuf::from_str(&self.config.cache_dir)?;
         categorize_path.push(categorize_base_path);
 
         self.prepare_categorize(&extract_path, &categorize_path)?;
-        self.categorize(categorize_path).await?;
-        let output_controller = FilterController::<StageOutput, FileInput, File> {
-            stage: PhantomData,
-            config: self.config,
-            cached_lists: self.cached_lists.take(),
-            filter_lists: vec![],
-            category_lists: vec![],
-            is_processing: self.is_processing.clone(),
-        };
+        self.categorize(&extract_path, categorize_path).await?;
+        let output_controller = self.next_stage::<StageOutput, R, W>();
         Ok(output_controller)
     }
 
@@ -57,6 +179,15 @@ impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
         extract_path: &Path,
         categorize_path: &Path,
     ) -> anyhow::Result<()> {
+        // lists whose files are incomplete because they couldn't be written
+        let io_failed: HashSet<String> = self
+            .report
+            .lock()
+            .unwrap()
+            .io_failures()
+            .into_values()
+            .flatten()
+            .collect();
         // prepare category lists for writing
         self.config
             .get_tags()
@@ -66,42 +197,114 @@ impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
                 let included_lists = self.config.lists_with_tag(tag);
 
                 // include all ids into the category which have the currently processed tag attached
-                let include_ids: HashSet<String> = self
+                let mut include_ids: HashSet<String> = self
                     .config
                     .lists_with_tag(tag)
                     .iter()
                     .map(|list| list.id.clone())
                     .collect();
+                // categories the top sites are subtracted from depend on the top sites list
+                if let Some(top_sites) = &self.config.top_sites
+                    && top_sites.subtract_from.contains(tag)
+                {
+                    include_ids.insert(TOP_SITES_ID.to_string());
+                }
 
-               // calculate the difference between included lists an cached lists
+                // calculate the difference between included lists an cached lists
                 let difference: HashSet<&String> = include_ids
                     .difference(self.cached_lists.as_ref().unwrap())
                     .collect();
 
-                // if the cached_config lists vec and the current config lists vec have the same
-                // length no list has been removed since the last run
+                // if the cached config includes the same lists with the same settings in
+                // the category no list has been swapped, removed or edited since the last run
                 if let Some(cached_config) = &self.config.cached_config
-                    && self.config.lists_with_tag(tag).len() == cached_config.lists_with_tag(tag).len()
+                    && included_settings(self.config, tag) == included_settings(cached_config, tag)
+                    && self.config.category_options(tag) == cached_config.category_options(tag)
+                    // collapsing the subdomains changes the entries as well
+                    && collapse_settings(self.config) == collapse_settings(cached_config)
+                    // as does subtracting other top sites
+                    && top_sites_settings(self.config, tag) == top_sites_settings(cached_config, tag)
                     // if there is no difference between cached lists and included lists there is no need for action
                     && difference.is_empty()
+                    // the changes rejected in review are proposed again
+                    && !self.manifest.as_ref().is_some_and(|m| m.rejected.contains(tag))
                     // check if there was actually a file written on the last run
                     && category_list.attach_existing_file_writer(categorize_path).is_ok()
                 {
                     self.cached_lists.as_mut().unwrap().insert(tag.clone());
                     category_list.writer = None;
-                    info!("Unchanged: {}", tag.to_string());
+                    self.observers
+                        .list_done(PipelineStage::Categorize, tag, Status::Unchanged);
+                    self.report.lock().unwrap().category_unchanged(tag);
                     return Ok(());
                 }
 
-                category_list.attach_new_file_writer(categorize_path)?;
-                category_list.included_filter_lists = included_lists.into_iter().filter_map(|flist| {
-                    let mut flist_io = FilterListIO::new(flist.to_owned());
-                    if let Err(e) = flist_io.attach_existing_input_file(extract_path, None) {
-                        error!("Error: {} - {}", flist_io.filter_list.id, e);
-                        return None;
+                // keep the last version of the category if an included list is incomplete
+                if let Some(id) = include_ids.iter().find(|id| io_failed.contains(*id)) {
+                    let code = ErrorCode::ListIncomplete;
+                    let error = format!("keeping previous version, list {id} is incomplete");
+                    self.observers
+                        .error(PipelineStage::Categorize, Some(tag), code, &error);
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .category_failed(tag, code, error);
+                    return Ok(());
+                }
+
+                // keep the last version of the category if its transform script is broken
+                if let Some(path) = &self.config.category_options(tag).transform
+                    && let Err(e) = get_transform(path)
+                {
+                    let code = ErrorCode::CategoryTransformFailed;
+                    self.observers.error(
+                        PipelineStage::Categorize,
+                        Some(tag),
+                        code,
+                        &e.to_string(),
+                    );
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .category_failed(tag, code, e.to_string());
+                    return Ok(());
+                }
+
+                if let Some(m) = self.manifest.as_mut() {
+                    m.rejected.remove(tag);
+                }
+
+                // the report and the audit log compare against the last run so it has to be
+                // read before truncating
+                if self.config.report.is_some()
+                    || self.config.audit_log.is_some()
+                    || self.config.review
+                {
+                    let previous = W::read(&categorize_path.join(tag)).unwrap_or_default();
+                    // the previous version is restored if the changes are rejected in review
+                    if self.config.review {
+                        let review_path = Path::new(&self.config.cache_dir).join(REVIEW_PATH);
+                        fs::create_dir_all(&review_path)
+                            .with_context(|| "could not create review directory")?;
+                        fs::write(review_path.join(tag), &previous).with_context(|| {
+                            format!("could not keep the previous version of {tag}")
+                        })?;
                     }
-                    Some(flist_io)
-                }).collect();
+                    category_list.previous_entries =
+                        Some(previous.lines().map(String::from).collect());
+                }
+                category_list.attach_new_file_writer(categorize_path)?;
+                category_list.included_filter_lists = included_lists
+                    .into_iter()
+                    .filter_map(|flist| {
+                        let mut flist_io = FilterListIO::new(flist.to_owned());
+                        if let Err(e) = flist_io.attach_existing_input_file(extract_path, None) {
+                            error!("Error: {} - {}", flist_io.filter_list.id, e);
+                            return None;
+                        }
+                        Some(flist_io)
+                    })
+                    .collect();
 
                 self.category_lists.push(category_list);
                 Ok(())
@@ -109,13 +312,93 @@ impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
         Ok(())
     }
 
+    /// Reads the popular domains up to the configured rank cutoff from the extracted top
+    /// sites list
+    ///
+    /// * `extract_path`: The directory where the extracted data from the previous stage was stored
+    async fn load_top_sites(&self, extract_path: &Path) -> anyhow::Result<HashSet<String>> {
+        let mut top_sites = HashSet::new();
+        let rank_cutoff = match &self.config.top_sites {
+            Some(t) => t.rank_cutoff,
+            None => return Ok(top_sites),
+        };
+        let mut reader = R::open(
+            &extract_path.join(TOP_SITES_ID),
+            None,
+            DEFAULT_MAX_LINE_LENGTH,
+        )?;
+        while top_sites.len() < rank_cutoff
+            && let Some(chunk) = reader.chunk().await?
+        {
+            let domain = String::from_utf8_lossy(&chunk).trim().to_string();
+            if !domain.is_empty() {
+                top_sites.insert(domain);
+            }
+        }
+        Ok(top_sites)
+    }
+
     /// assembles the category lists from the extracted URLs according to the existing tags
     /// in the configuration file
     ///
+    /// * `extract_path`: The directory where the extracted data from the previous stage was stored
     /// * `categorize_path`: the file system path where the resulting lists are stored
-    async fn categorize(&mut self, categorize_path: PathBuf) -> anyhow::Result<()> {
-        fs::create_dir_all(&categorize_path).with_context(|| "could not create out directory")?;
-        let mut handles: Vec<JoinHandle<()>> = vec![];
+    async fn categorize(
+        &mut self,
+        extract_path: &Path,
+        categorize_path: PathBuf,
+    ) -> anyhow::Result<()> {
+        let mut handles: Vec<JoinHandle<(String, std::io::Result<()>)>> = vec![];
+        // the audit records are only written for categories which were written completely
+        let mut audit: HashMap<String, Vec<AuditRecord>> = HashMap::new();
+        // categories exceeding the safety limit, the run fails once all others are written
+        let mut oversized: Vec<String> = vec![];
+        // the sources of the entries are needed by the audit log, some output formats
+        // and the SQLite database
+        let sources_output = self.config.sqlite.is_some()
+            || self
+                .config
+                .output_formats()
+                .iter()
+                .any(|f| f.needs_sources());
+        let collect_sources = self.config.audit_log.is_some() || sources_output;
+
+        // popular domains are removed from categories prone to false positives
+        let subtract_from = match &self.config.top_sites {
+            Some(t) => t.subtract_from.clone(),
+            None => vec![],
+        };
+        let top_sites = if self
+            .category_lists
+            .iter()
+            .any(|l| subtract_from.contains(&l.name))
+        {
+            match self.load_top_sites(extract_path).await {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("top sites not available: {}", e);
+                    HashSet::new()
+                }
+            }
+        } else {
+            HashSet::new()
+        };
+
+        // the GeoIP databases are only opened if any category makes use of them
+        let annotate = self.config.geoip.as_ref().is_some_and(|g| g.annotate);
+        let geoip = match &self.config.geoip {
+            Some(g)
+                if annotate
+                    || self
+                        .category_lists
+                        .iter()
+                        .any(|l| self.config.category_options(&l.name).filters_countries()) =>
+            {
+                Some(GeoIp::open(g)?)
+            }
+            _ => None,
+        };
+
         for category_list in self.category_lists.iter_mut() {
             if !self.is_processing.load(Ordering::SeqCst) {
                 return Ok(());
@@ -123,8 +406,28 @@ impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
 
             // QUESTION: is there a better data structure to enable concurrent access?
             let mut tree_set: BTreeSet<String> = BTreeSet::new();
+            // the lists each entry was taken from, only collected if needed
+            let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+            // the order the entries were read in, only collected for insertion order
+            let mut insertion: Vec<String> = vec![];
 
-            info!("Updated: {}", category_list.name);
+            self.observers
+                .list_start(PipelineStage::Categorize, &category_list.name);
+            let options = self.config.category_options(&category_list.name);
+            let transform = match &options.transform {
+                Some(path) => Some(get_transform(path)?),
+                None => None,
+            };
+            let country_filter = match &geoip {
+                Some(g) if options.filters_countries() && g.has_countries() => Some(g),
+                _ => None,
+            };
+            if options.filters_countries() && country_filter.is_none() {
+                warn!(
+                    "{}: country filters are ignored without a GeoIP country database",
+                    category_list.name
+                );
+            }
 
             // read lines from the included list and insert them into a tree set to remove duplicates
             for filter_list_io in category_list.included_filter_lists.iter_mut() {
@@ -138,37 +441,193 @@ impl<'config> FilterController<'config, StageCategorize, FileInput, File> {
                         continue;
                     }
                 };
+                let mut line_count = 0;
                 while let Ok(Some(chunk)) = flist.lock().await.chunk().await {
+                    // the top sites list is ranked so the cutoff is a line limit
+                    if filter_list_io.filter_list.id == TOP_SITES_ID
+                        && let Some(t) = &self.config.top_sites
+                        && line_count >= t.rank_cutoff
+                    {
+                        break;
+                    }
+                    line_count += 1;
                     // insert the URLs into a BTreeSet to deduplicate and sort the data
                     let str_chunk = match String::from_utf8(chunk) {
-                        Ok(s) => s.trim().to_string(),
+                        Ok(s) => s,
                         Err(e) => {
                             warn!("{}", e);
                             continue;
                         }
                     };
-                    if str_chunk.is_empty() {
-                        continue;
+                    for entry in normalize(&str_chunk, options.idn) {
+                        let entry = match &transform {
+                            Some(t) => match t.apply(&entry, &category_list.name) {
+                                Ok(Some(e)) => e,
+                                Ok(None) => continue,
+                                Err(e) => {
+                                    warn!("{}", e);
+                                    continue;
+                                }
+                            },
+                            None => entry,
+                        };
+                        if let Some(g) = country_filter
+                            && let Some(ip) = entry_ip(&entry)
+                            && !options.allows_country(g.country(ip).as_deref())
+                        {
+                            continue;
+                        }
+                        if collect_sources {
+                            let ids = sources.entry(entry.clone()).or_default();
+                            if !ids.contains(&filter_list_io.filter_list.id) {
+                                ids.push(filter_list_io.filter_list.id.clone());
+                            }
+                        }
+                        if options.order == EntryOrder::Insertion && !tree_set.contains(&entry) {
+                            insertion.push(entry.clone());
+                        }
+                        tree_set.insert(entry);
                     }
-                    tree_set.insert(str_chunk);
                 }
             }
 
+            if subtract_from.contains(&category_list.name) {
+                let len_before = tree_set.len();
+                tree_set.retain(|domain| !top_sites.contains(domain));
+                debug!(
+                    "{}: removed {} top sites",
+                    category_list.name,
+                    len_before - tree_set.len()
+                );
+            }
+
+            if let Some(domains_are_wildcards) = collapse_settings(self.config) {
+                let removed = collapse_subdomains(&mut tree_set, domains_are_wildcards);
+                debug!(
+                    "{}: removed {} entries covered by wildcards",
+                    category_list.name, removed
+                );
+            }
+
+            if let Some(max) = self.config.absolute_max_entries
+                && tree_set.len() > max
+            {
+                let code = ErrorCode::TooManyEntries;
+                let error = format!(
+                    "{} entries exceed absolute_max_entries of {}, check the regexes and compression settings of the included lists",
+                    tree_set.len(),
+                    max
+                );
+                self.observers.error(
+                    PipelineStage::Categorize,
+                    Some(&category_list.name),
+                    code,
+                    &error,
+                );
+                self.report
+                    .lock()
+                    .unwrap()
+                    .category_failed(&category_list.name, code, error);
+                // the truncated file must not be taken for the category on the next run
+                category_list.writer = None;
+                W::remove(&categorize_path.join(&category_list.name));
+                oversized.push(category_list.name.clone());
+                continue;
+            }
+
+            if let Some(previous) = &category_list.previous_entries {
+                let mut report = self.report.lock().unwrap();
+                report.category_updated(&category_list.name, tree_set.iter(), previous);
+                if annotate && let Some(g) = &geoip {
+                    let added = report.categories[&category_list.name].added_sample.clone();
+                    for entry in added {
+                        if let Some(location) = g.describe(&entry) {
+                            report.annotations.insert(entry, location);
+                        }
+                    }
+                }
+                if self.config.audit_log.is_some() {
+                    let records = changes(
+                        &report.started.to_rfc3339(),
+                        &category_list.name,
+                        &tree_set,
+                        previous,
+                        &sources,
+                    );
+                    audit.insert(category_list.name.clone(), records);
+                }
+            }
+
+            if sources_output {
+                let entry_sources: BTreeMap<&String, &Vec<String>> = tree_set
+                    .iter()
+                    .filter_map(|e| sources.get(e).map(|ids| (e, ids)))
+                    .collect();
+                if let Err(e) = write_sources(
+                    Path::new(&self.config.cache_dir),
+                    &category_list.name,
+                    &entry_sources,
+                ) {
+                    warn!(
+                        "{}: could not write the sources of the entries: {}",
+                        category_list.name, e
+                    );
+                }
+            }
+
+            let entries = order_entries(tree_set, insertion, options.order);
             let writer = category_list.writer.take().unwrap();
+            let name = category_list.name.clone();
             let handle = tokio::spawn(async move {
-                for mut line in tree_set {
+                for mut line in entries {
                     if !line.ends_with('\n') {
                         line.push('\n');
                     }
                     if let Err(e) = writer.lock().await.write_all(line.as_bytes()) {
-                        error!("{:?}", e);
-                        break;
+                        return (name, Err(e));
                     }
                 }
+                (name, Ok(()))
             });
             handles.push(handle);
         }
-        join_all(handles).await;
+        // the outputs of categories which couldn't be written completely are skipped
+        let results = join_all(handles).await;
+        let mut report = self.report.lock().unwrap();
+        for (name, result) in results.into_iter().flatten() {
+            let e = match result {
+                Ok(()) => {
+                    if let Some(path) = &self.config.audit_log
+                        && let Some(records) = audit.get(&name)
+                        && let Err(e) = append(Path::new(path), records)
+                    {
+                        warn!("{:?}", e);
+                    }
+                    self.observers
+                        .list_done(PipelineStage::Categorize, &name, Status::Updated);
+                    continue;
+                }
+                Err(e) => e,
+            };
+            let failure = IoFailure::classify(&e);
+            let error = format!("{failure}: {e}");
+            self.observers.error(
+                PipelineStage::Categorize,
+                Some(&name),
+                failure.into(),
+                &error,
+            );
+            // an incomplete file must not be taken for the category on the next run
+            W::remove(&categorize_path.join(&name));
+            report.category_io_failed(&name, failure, error);
+        }
+        if !oversized.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} {} exceeded absolute_max_entries, refusing to write the outputs",
+                ErrorCode::TooManyEntries,
+                oversized.join(", ")
+            ));
+        }
         Ok(())
     }
 }
@@ -178,16 +637,97 @@ mod tests {
 
     use std::{
         collections::HashMap,
+        fs::File,
+        marker::PhantomData,
         sync::{atomic::AtomicBool, Arc},
     };
 
     use crate::{
-        filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator,
-        CATEGORIZE_PATH, EXTRACT_PATH,
+        config::{TopSites, ALLOW_CATEGORY},
+        filter_controller::StageExtract,
+        filter_list::FilterList,
+        input::file::FileInput,
+        manifest::Manifest,
+        tests::helper::{
+            cache_file_creator::CacheFileCreator,
+            memory_storage::{self, MemoryInput, MemoryList},
+        },
+        CATEGORIZE_PATH, DOWNLOAD_PATH, EXTRACT_PATH,
     };
 
     use super::*;
 
+    #[test]
+    fn test_order_entries() {
+        let read = [
+            "b.example.org",
+            "example.com",
+            "a.example.org",
+            "www.example.com",
+        ];
+        let insertion: Vec<String> = read.iter().map(|e| e.to_string()).collect();
+        let entries: BTreeSet<String> = insertion.iter().cloned().collect();
+        assert_eq!(
+            order_entries(entries.clone(), vec![], EntryOrder::Lexicographic),
+            vec![
+                "a.example.org",
+                "b.example.org",
+                "example.com",
+                "www.example.com"
+            ]
+        );
+        assert_eq!(
+            order_entries(entries.clone(), vec![], EntryOrder::Grouped),
+            vec![
+                "example.com",
+                "www.example.com",
+                "a.example.org",
+                "b.example.org"
+            ]
+        );
+        // entries removed after reading, e.g. top sites, are left out
+        let mut without_first = entries;
+        without_first.remove("b.example.org");
+        assert_eq!(
+            order_entries(without_first, insertion, EntryOrder::Insertion),
+            vec!["example.com", "a.example.org", "www.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_collapse_subdomains() {
+        let entries: BTreeSet<String> = [
+            "*.example.com",
+            "example.com",
+            "ads.example.com",
+            "*.cdn.example.com",
+            "example.org",
+            "www.example.org",
+            "10.0.0.1",
+        ]
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+
+        let mut collapsed = entries.clone();
+        assert_eq!(collapse_subdomains(&mut collapsed, false), 3);
+        let collapsed: Vec<&str> = collapsed.iter().map(|e| e.as_str()).collect();
+        assert_eq!(
+            collapsed,
+            vec![
+                "*.example.com",
+                "10.0.0.1",
+                "example.org",
+                "www.example.org"
+            ]
+        );
+
+        // written as wildcards the domains cover their subdomains as well
+        let mut collapsed = entries;
+        assert_eq!(collapse_subdomains(&mut collapsed, true), 4);
+        assert!(!collapsed.contains("www.example.org"));
+    }
+
     #[tokio::test]
     async fn test_categorize_successful() {
         // prepare folder structure
@@ -203,6 +743,7 @@ mod tests {
                 source: "".to_string(),
                 tags: vec!["advertising".to_string()],
                 regex: r"(.*)".to_string(),
+                ..Default::default()
             },
             FilterList {
                 id: "malware".to_string(),
@@ -211,6 +752,7 @@ mod tests {
                 source: "".to_string(),
                 tags: vec!["malware".to_string()],
                 regex: r"(.*)".to_string(),
+                ..Default::default()
             },
             FilterList {
                 id: "advertising_malware".to_string(),
@@ -219,6 +761,7 @@ mod tests {
                 source: "".to_string(),
                 tags: vec!["malware".to_string(), "advertising".to_string()],
                 regex: r"(.*)".to_string(),
+                ..Default::default()
             },
         ];
         // the contents of each filter list
@@ -237,6 +780,9 @@ mod tests {
             config: &config,
             filter_lists: vec![],
             category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
             is_processing: Arc::new(AtomicBool::new(true)),
         };
         if let Err(e) = categorize_controller
@@ -271,4 +817,259 @@ mod tests {
             assert_eq!(want, &got);
         }
     }
+
+    #[tokio::test]
+    async fn test_categorize_swapped_list() {
+        let cache = CacheFileCreator::new(
+            "test_categorize_swapped_list",
+            EXTRACT_PATH,
+            CATEGORIZE_PATH,
+        );
+        let mut config = cache.new_test_config();
+        let list = |id: &str| FilterList {
+            id: id.to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        };
+        let mut cached_config = config.clone();
+        cached_config.lists = vec![list("old")];
+        config.lists = vec![list("new")];
+        config.cached_config = Some(Box::new(cached_config));
+        cache.write_input("old", "old.domain\n");
+        cache.write_input("new", "new.domain\n");
+        // the category written on the last run
+        fs::write(
+            Path::new(&config.cache_dir)
+                .join(CATEGORIZE_PATH)
+                .join("malware"),
+            "old.domain\n",
+        )
+        .unwrap();
+
+        // both lists are unchanged since the last run and have the same tag
+        let mut categorize_controller = FilterController::<StageCategorize, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::from(["old".to_string(), "new".to_string()])),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        categorize_controller
+            .run(&cache.inpath, &cache.outpath)
+            .await
+            .unwrap();
+        assert_eq!(cache.read_result("malware").unwrap(), "new.domain\n");
+    }
+
+    #[tokio::test]
+    async fn test_categorize_top_sites() {
+        let cache =
+            CacheFileCreator::new("test_categorize_top_sites", EXTRACT_PATH, CATEGORIZE_PATH);
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "phishing".to_string(),
+            tags: vec!["phishing".to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        }];
+        config.top_sites = Some(TopSites {
+            source: "".to_string(),
+            compression: None,
+            rank_cutoff: 1,
+            subtract_from: vec!["phishing".to_string()],
+        });
+        config.add_builtin_lists();
+        cache.write_input("phishing", "google.com\nexample.org\nbad.domain\n");
+        // the extracted top sites are ordered by rank
+        cache.write_input(TOP_SITES_ID, "google.com\nexample.org\n");
+
+        let mut categorize_controller = FilterController::<StageCategorize, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::new()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        if let Err(e) = categorize_controller
+            .run(&cache.inpath, &cache.outpath)
+            .await
+        {
+            error!("{}", e);
+        }
+
+        // only the top ranked domain is removed from the phishing category
+        let got = cache.read_result("phishing").unwrap();
+        assert_eq!("bad.domain\nexample.org\n", got);
+        let got = cache.read_result(ALLOW_CATEGORY).unwrap();
+        assert_eq!("google.com\n", got);
+    }
+
+    #[tokio::test]
+    async fn test_categorize_top_sites_changed() {
+        let cache = CacheFileCreator::new(
+            "test_categorize_top_sites_changed",
+            EXTRACT_PATH,
+            CATEGORIZE_PATH,
+        );
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "phishing".to_string(),
+            tags: vec!["phishing".to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        }];
+        config.top_sites = Some(TopSites {
+            source: "".to_string(),
+            compression: None,
+            rank_cutoff: 1,
+            subtract_from: vec!["phishing".to_string()],
+        });
+        config.add_builtin_lists();
+        let cached_config = config.clone();
+        config.top_sites.as_mut().unwrap().rank_cutoff = 2;
+        config.cached_config = Some(Box::new(cached_config));
+        cache.write_input("phishing", "google.com\nexample.org\nbad.domain\n");
+        cache.write_input(TOP_SITES_ID, "google.com\nexample.org\n");
+        // the category written on the last run with the former cutoff
+        fs::write(
+            Path::new(&config.cache_dir)
+                .join(CATEGORIZE_PATH)
+                .join("phishing"),
+            "bad.domain\nexample.org\n",
+        )
+        .unwrap();
+
+        // the lists are unchanged since the last run
+        let mut categorize_controller = FilterController::<StageCategorize, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::from([
+                "phishing".to_string(),
+                TOP_SITES_ID.to_string(),
+            ])),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        categorize_controller
+            .run(&cache.inpath, &cache.outpath)
+            .await
+            .unwrap();
+        assert_eq!(cache.read_result("phishing").unwrap(), "bad.domain\n");
+    }
+
+    #[tokio::test]
+    async fn test_categorize_absolute_max_entries() {
+        let cache = CacheFileCreator::new(
+            "test_categorize_absolute_max_entries",
+            EXTRACT_PATH,
+            CATEGORIZE_PATH,
+        );
+        let mut config = cache.new_test_config();
+        config.absolute_max_entries = Some(3);
+        config.lists = ["small", "huge"]
+            .iter()
+            .map(|id| FilterList {
+                id: id.to_string(),
+                tags: vec![id.to_string()],
+                regex: r"(.*)".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        cache.write_input("small", "one.domain\ntwo.domain\n");
+        cache.write_input(
+            "huge",
+            "one.domain\ntwo.domain\nthree.domain\nfour.domain\n",
+        );
+
+        let mut categorize_controller = FilterController::<StageCategorize, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::new()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        let result = categorize_controller
+            .run(&cache.inpath, &cache.outpath)
+            .await;
+        let error = match result {
+            Ok(_) => panic!("categories exceeding the limit must fail the run"),
+            Err(e) => e.to_string(),
+        };
+        assert!(error.contains("HV3003 huge"));
+        assert_eq!(
+            cache.read_result("small").unwrap(),
+            "one.domain\ntwo.domain\n"
+        );
+        assert!(cache.read_result("huge").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stages_in_memory() {
+        let cache = CacheFileCreator::new("test_stages_in_memory", EXTRACT_PATH, CATEGORIZE_PATH);
+        let mut config = cache.new_test_config();
+        config.cache_dir = format!("{}/in-memory", config.cache_dir);
+        let list = |id: &str| FilterList {
+            id: id.to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"^0\.0\.0\.0 ([^\s#]+)".to_string(),
+            ..Default::default()
+        };
+        config.lists = vec![list("one"), list("two")];
+        let cache_dir = Path::new(&config.cache_dir);
+        memory_storage::put(
+            &cache_dir.join(DOWNLOAD_PATH).join("one"),
+            "# list one\n0.0.0.0 malicious.com\n0.0.0.0 unwanted.net\n",
+        );
+        memory_storage::put(
+            &cache_dir.join(DOWNLOAD_PATH).join("two"),
+            "0.0.0.0 malicious.com\n0.0.0.0 another.org\n",
+        );
+
+        let mut extract_controller = FilterController::<StageExtract, MemoryInput, MemoryList> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::new()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        let mut categorize_controller = extract_controller
+            .run(DOWNLOAD_PATH, EXTRACT_PATH)
+            .await
+            .unwrap();
+        assert_eq!(
+            memory_storage::get(&cache_dir.join(EXTRACT_PATH).join("one")).unwrap(),
+            "malicious.com\nunwanted.net\n"
+        );
+        categorize_controller
+            .run(EXTRACT_PATH, CATEGORIZE_PATH)
+            .await
+            .unwrap();
+        assert_eq!(
+            memory_storage::get(&cache_dir.join(CATEGORIZE_PATH).join("malware")).unwrap(),
+            "another.org\nmalicious.com\nunwanted.net\n"
+        );
+        // nothing was written to the file system
+        assert!(!cache_dir.exists());
+    }
 }
@@ -0,0 +1,145 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{BufRead, BufReader, Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{EntryOrder, ALLOW_CATEGORY, ALL_CATEGORY},
+    error_code::ErrorCode,
+    filter_controller::{FilterController, StageOutput},
+    input::file::FileInput,
+    io::atomic_file::AtomicFile,
+    observer::PipelineStage,
+    report::{CategoryReport, Status},
+};
+
+impl<'config> FilterController<'config, StageOutput, FileInput, File> {
+    /// Writes the built-in `all` category merging the entries of all other categories
+    /// but the allow category. The sorted category files are merged while they are
+    /// read, only categories written in another order are sorted in memory first.
+    ///
+    /// * `categorize_path`: the directory the category lists were stored in
+    pub(super) async fn combine(&mut self, categorize_path: &Path) -> anyhow::Result<()> {
+        if !self.config.all_category {
+            return Ok(());
+        }
+        let categories: Vec<String> = self
+            .config
+            .get_tags()
+            .into_iter()
+            .filter(|t| t != ALLOW_CATEGORY && t != ALL_CATEGORY)
+            .collect();
+        let path = categorize_path.join(ALL_CATEGORY);
+
+        // nothing to do if none of the categories changed since the last run
+        let cached_lists = self.cached_lists.as_mut().unwrap();
+        if path.exists() && categories.iter().all(|c| cached_lists.contains(c)) {
+            cached_lists.insert(ALL_CATEGORY.to_string());
+            self.report.lock().unwrap().category_unchanged(ALL_CATEGORY);
+            return Ok(());
+        }
+
+        // keep the last version if a category couldn't be written
+        if let Some(missing) = categories
+            .iter()
+            .find(|c| !categorize_path.join(c).exists())
+        {
+            let code = ErrorCode::ListIncomplete;
+            let error = format!("keeping previous version, category {missing} is incomplete");
+            self.observers
+                .error(PipelineStage::Output, Some(ALL_CATEGORY), code, &error);
+            self.report
+                .lock()
+                .unwrap()
+                .category_failed(ALL_CATEGORY, code, error);
+            return Ok(());
+        }
+
+        let inputs: Vec<(PathBuf, bool)> = categories
+            .iter()
+            .map(|c| {
+                let sorted = self.config.category_options(c).order == EntryOrder::Lexicographic;
+                (categorize_path.join(c), sorted)
+            })
+            .collect();
+        let entries =
+            tokio::task::spawn_blocking(move || merge_categories(&inputs, path)).await??;
+        self.report.lock().unwrap().categories.insert(
+            ALL_CATEGORY.to_string(),
+            CategoryReport {
+                status: Status::Updated,
+                entries,
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+}
+
+/// merges the category files into a single sorted file without duplicates and returns
+/// the number of entries written
+///
+/// * `inputs`: the category files and whether they are sorted
+/// * `path`: the file the merged entries are written to
+fn merge_categories(inputs: &[(PathBuf, bool)], path: PathBuf) -> anyhow::Result<usize> {
+    let mut readers: Vec<Box<dyn BufRead + Send>> = vec![];
+    for (input, sorted) in inputs.iter() {
+        if *sorted {
+            readers.push(Box::new(BufReader::new(File::open(input)?)));
+            continue;
+        }
+        let mut lines: Vec<String> = fs::read_to_string(input)?
+            .lines()
+            .map(String::from)
+            .collect();
+        lines.sort();
+        readers.push(Box::new(Cursor::new(lines.join("\n"))));
+    }
+    let mut file = AtomicFile::create(path)?;
+    match merge_sorted(readers, &mut file) {
+        Ok(written) => {
+            file.commit()?;
+            Ok(written)
+        }
+        Err(e) => {
+            file.discard();
+            Err(e.into())
+        }
+    }
+}
+
+/// merges the sorted lines of the readers into the writer skipping duplicates and
+/// returns the number of lines written
+///
+/// * `readers`: the sorted inputs
+/// * `out`: the merged lines are written here
+fn merge_sorted(
+    readers: Vec<Box<dyn BufRead + Send>>,
+    out: &mut impl Write,
+) -> std::io::Result<usize> {
+    let mut lines: Vec<_> = readers.into_iter().map(|r| r.lines()).collect();
+    // the smallest line of every reader, tagged with the reader's index
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in lines.iter_mut().enumerate() {
+        if let Some(line) = reader.next() {
+            heap.push(Reverse((line?, i)));
+        }
+    }
+    let mut last: Option<String> = None;
+    let mut written = 0;
+    while let Some(Reverse((line, i))) = heap.pop() {
+        if let Some(next) = lines[i].next() {
+            heap.push(Reverse((next?, i)));
+        }
+        if line.is_empty() || last.as_ref() == Some(&line) {
+            continue;
+        }
+        writeln!(out, "{line}")?;
+        written += 1;
+        last = Some(line);
+    }
+    Ok(written)
+}
@@ -1,59 +1,145 @@
 use std::{
-    collections::HashSet,
-    fs::File,
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{self, File},
+    io::Write,
     marker::PhantomData,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
-use futures::future::join_all;
+use anyhow::Context;
+use chrono::Utc;
+use futures::{future::join_all, lock::Mutex};
+use reqwest::Url;
 
 use crate::{
-    config::Config,
-    filter_controller::{process, FilterController, StageDownload, StageExtract},
-    input::{file::FileInput, url::UrlInput},
-    io::filter_list_io::FilterListIO,
+    archive::{expire, snapshot},
+    cache::{dir_size, prune, store_blob, BLOB_PATH},
+    config::{Config, FsyncPolicy},
+    error_code::ErrorCode,
+    filter_controller::{process, FilterController, StageDownload, StageExtract, Transformed},
+    filter_list::FilterList,
+    input::{ct_log::CtLogInput, file::FileInput, source::SourceInput, Input},
+    io::{filter_list_io::FilterListIO, IoFailure},
+    log_throttle,
+    manifest::Manifest,
+    observer::{PipelineStage, Plan},
+    report::Status,
+    run_state::RunState,
 };
 
-/// This implementation for UrlInput and File is the first phase where the lists
+/// seconds to wait for a server's answer if the list doesn't configure a timeout
+const DEFAULT_REQUEST_TIMEOUT: u64 = 30;
+
+/// Flushes a finished download to the disk through the handle it was written with
+/// and the directory holding it, so the download survives a crash
+///
+/// * `writer`: the download's writer, released by the finished download task
+/// * `download_path`: the file system path to the directory containing the raw lists
+async fn sync_download(
+    writer: Option<Arc<Mutex<File>>>,
+    download_path: &Path,
+) -> std::io::Result<()> {
+    let file = match writer.map(Arc::try_unwrap) {
+        Some(Ok(file)) => tokio::fs::File::from_std(file.into_inner()),
+        _ => {
+            return Err(std::io::Error::other(
+                "the download's writer is still in use",
+            ))
+        }
+    };
+    file.sync_all().await?;
+    tokio::fs::File::open(download_path).await?.sync_all().await
+}
+
+/// what the response's header section reveals about a list
+enum Preflight {
+    /// the server asked to retry later
+    Deferred(String),
+    /// the previous download is still current
+    Unmodified,
+    /// the list has to be downloaded
+    Modified,
+}
+
+/// Sends the list's request and decides by the response's header section whether the
+/// list has to be downloaded
+///
+/// * `list`: the list with an attached url reader
+/// * `has_previous`: whether the list was downloaded on an earlier run
+async fn preflight(
+    list: &mut FilterListIO<SourceInput, File>,
+    has_previous: bool,
+) -> anyhow::Result<Preflight> {
+    if let Some(retry_after) = list.retry_after().await {
+        return Ok(Preflight::Deferred(retry_after));
+    }
+    if has_previous && list.is_unmodified().await? {
+        return Ok(Preflight::Unmodified);
+    }
+    Ok(Preflight::Modified)
+}
+
+/// returns the key the downloads are sorted by, higher priorities first and smaller
+/// lists first among the same priority
+///
+/// * `list`: the list to be downloaded
+/// * `len`: the list's content length if the server announced it
+fn download_order(list: &FilterList, len: Option<u64>) -> (Reverse<i32>, u64) {
+    (
+        Reverse(list.priority.unwrap_or_default()),
+        len.unwrap_or(u64::MAX),
+    )
+}
+
+/// This implementation for SourceInput and File is the first phase where the lists
 /// are downloaded.
-impl<'config> FilterController<'config, StageDownload, UrlInput, File> {
-    pub fn new(config: &'config Config, is_processing: Arc<AtomicBool>) -> Self {
+impl<'config> FilterController<'config, StageDownload, SourceInput, File> {
+    pub fn new(
+        config: &'config Config,
+        manifest: Manifest,
+        is_processing: Arc<AtomicBool>,
+    ) -> Self {
         Self {
             stage: PhantomData,
             config,
             cached_lists: Some(HashSet::new()),
             filter_lists: vec![],
             category_lists: vec![],
+            manifest: Some(manifest),
+            report: Default::default(),
+            observers: Default::default(),
             is_processing,
         }
     }
 
-    /// Runs the data processing function with UrlInput as input source and a
+    /// Runs the data processing function with the lists' sources as input and a
     /// file as output destination. Returns the controller for the extract stage
     ///
     /// * `download_base_path`: target path for files being downloaded
     pub async fn run(
         &mut self,
         download_base_path: &str,
-    ) -> anyhow::Result<FilterController<StageExtract, FileInput, File>> {
+    ) -> anyhow::Result<FilterController<'config, StageExtract, FileInput, File>> {
         let mut download_path = PathBuf::from_str(&self.config.cache_dir)?;
         download_path.push(download_base_path);
 
         self.prepare_download(download_path.clone()).await?;
-        self.download().await?;
-        let extract_controller = FilterController::<StageExtract, FileInput, File> {
-            stage: PhantomData,
-            cached_lists: self.cached_lists.take(),
-            config: self.config,
-            filter_lists: vec![],
-            category_lists: vec![],
-            is_processing: self.is_processing.clone(),
-        };
+        self.check_disk_space().await?;
+        self.order_downloads().await;
+        // the former downloads are only replaced once there is enough space
+        for list in self.filter_lists.iter_mut() {
+            list.attach_new_file_writer(&download_path)?;
+        }
+        self.download(&download_path).await?;
+        self.tail_ct_logs(&download_path).await?;
+        let extract_controller = self.next_stage::<StageExtract, FileInput, File>();
         Ok(extract_controller)
     }
 
@@ -62,49 +148,782 @@ impl<'config> FilterController<'config, StageDownload, UrlInput, File> {
     /// * `download_path`: the file system path to the directory where the raw lists
     ///               are going to be downloaded
     async fn prepare_download(&mut self, download_path: PathBuf) -> anyhow::Result<()> {
-        let configured_lists: Vec<FilterListIO<UrlInput, File>> = self
+        let configured_lists: Vec<FilterListIO<SourceInput, File>> = self
             .config
             .lists
             .iter()
             .map(|f| FilterListIO::new(f.clone()))
             .collect();
 
+        let resumed = match self.config.resume {
+            true => RunState::load(Path::new(&self.config.cache_dir))
+                .unwrap_or_else(|e| {
+                    warn!("{:?}", e);
+                    None
+                })
+                .unwrap_or_else(|| {
+                    warn!("there is no interrupted run to be resumed");
+                    RunState::default()
+                }),
+            false => RunState::default(),
+        };
+
+        let mut plan = Plan::default();
+        let mut pending = vec![];
         for mut list in configured_lists.into_iter() {
             if !self.is_processing.load(Ordering::SeqCst) {
                 return Ok(());
             }
 
-            list.attach_url_reader()?;
+            // certificate transparency logs are tailed separately
+            if list.filter_list.ct_log.is_some() {
+                continue;
+            }
+
+            // the lists downloaded by the interrupted run are taken over unless their
+            // source changed
+            if resumed.downloaded.get(&list.filter_list.id) == Some(&list.filter_list.source)
+                && list.attach_existing_file_writer(&download_path).is_ok()
+            {
+                plan.unchanged += 1;
+                self.keep_previous(list.filter_list.id);
+                continue;
+            }
+
+            // without network access the lists downloaded on an earlier run are used
+            if self.config.offline {
+                if list.attach_existing_file_writer(&download_path).is_ok() {
+                    plan.unchanged += 1;
+                    self.keep_previous(list.filter_list.id);
+                } else {
+                    plan.failed += 1;
+                    let code = ErrorCode::NotAvailableOffline;
+                    let error = "not available offline".to_string();
+                    self.observers.error(
+                        PipelineStage::Download,
+                        Some(&list.filter_list.id),
+                        code,
+                        &error,
+                    );
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .list_failed(&list.filter_list.id, code, error);
+                }
+                continue;
+            }
 
-            let mut is_cached = false;
             // we can only check for a cached result if the former downloaded file is available
-            if list.attach_existing_file_writer(&download_path).is_ok() {
-                is_cached = list.is_cached().await?;
-            }
-            if !is_cached {
-                info!("Updated: {}", list.filter_list.id);
-                list.attach_new_file_writer(&download_path)?;
-                self.filter_lists.push(list);
-            } else {
-                info!("Unchanged: {}", list.filter_list.id);
-                self.cached_lists
-                    .as_mut()
+            let has_previous = list.attach_existing_file_writer(&download_path).is_ok();
+            let validators = self
+                .manifest
+                .as_ref()
+                .and_then(|m| m.http.get(&list.filter_list.id))
+                .filter(|_| has_previous)
+                .cloned();
+            if list.filter_list.insecure_tls {
+                warn!(
+                    "{}: TLS certificate verification is disabled for {}",
+                    list.filter_list.id, list.filter_list.source
+                );
+                self.report
+                    .lock()
                     .unwrap()
-                    .insert(list.filter_list.id);
+                    .list_insecure_tls(&list.filter_list.id);
+            }
+            list.attach_source_reader(
+                validators,
+                self.config.download_chunk_size(),
+                self.config.dns(&list.filter_list),
+            )?;
+            pending.push((list, has_previous));
+        }
+
+        // the requests of all lists are sent at once, only the header sections are
+        // awaited to decide which lists have to be downloaded
+        // a host which doesn't answer only delays its own list
+        let preflights = join_all(pending.iter_mut().map(|(list, has_previous)| async move {
+            let secs = list
+                .filter_list
+                .request_timeout
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT);
+            match tokio::time::timeout(Duration::from_secs(secs), preflight(list, *has_previous))
+                .await
+            {
+                Ok(preflight) => preflight,
+                Err(_) => Err(anyhow::anyhow!("no response within {} seconds", secs)),
+            }
+        }))
+        .await;
+        for ((list, has_previous), preflight) in pending.into_iter().zip(preflights) {
+            let preflight = match preflight {
+                Ok(p) => p,
+                Err(e) => {
+                    let code = ErrorCode::DownloadFailed;
+                    let error = e.to_string();
+                    self.observers.error(
+                        PipelineStage::Download,
+                        Some(&list.filter_list.id),
+                        code,
+                        &error,
+                    );
+                    if has_previous && self.may_use_previous(&list.filter_list) {
+                        plan.stale += 1;
+                        self.observers.list_done(
+                            PipelineStage::Download,
+                            &list.filter_list.id,
+                            Status::Stale,
+                        );
+                        self.report
+                            .lock()
+                            .unwrap()
+                            .list_stale(&list.filter_list.id, code, error);
+                        self.cached_lists
+                            .as_mut()
+                            .unwrap()
+                            .insert(list.filter_list.id);
+                    } else {
+                        plan.failed += 1;
+                        self.report
+                            .lock()
+                            .unwrap()
+                            .list_failed(&list.filter_list.id, code, error);
+                    }
+                    continue;
+                }
+            };
+            match preflight {
+                // a provider asking to come back later doesn't make the list fail as
+                // long as the former download can be used instead
+                Preflight::Deferred(retry_after) => {
+                    debug!("{}: retry after {}", list.filter_list.id, retry_after);
+                    if has_previous && self.may_use_previous(&list.filter_list) {
+                        plan.deferred += 1;
+                        self.observers.list_done(
+                            PipelineStage::Download,
+                            &list.filter_list.id,
+                            Status::Deferred,
+                        );
+                        self.report
+                            .lock()
+                            .unwrap()
+                            .list_deferred(&list.filter_list.id, retry_after);
+                        self.cached_lists
+                            .as_mut()
+                            .unwrap()
+                            .insert(list.filter_list.id);
+                    } else {
+                        plan.failed += 1;
+                        let code = ErrorCode::Deferred;
+                        let error = format!("deferred by the server, retry after {retry_after}");
+                        self.observers.error(
+                            PipelineStage::Download,
+                            Some(&list.filter_list.id),
+                            code,
+                            &error,
+                        );
+                        self.report
+                            .lock()
+                            .unwrap()
+                            .list_failed(&list.filter_list.id, code, error);
+                    }
+                }
+                Preflight::Modified => {
+                    plan.updated += 1;
+                    self.observers
+                        .list_start(PipelineStage::Download, &list.filter_list.id);
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .list_status(&list.filter_list.id, Status::Updated);
+                    self.filter_lists.push(list);
+                }
+                Preflight::Unmodified => {
+                    plan.unchanged += 1;
+                    self.manifest
+                        .as_mut()
+                        .unwrap()
+                        .mark_current(&list.filter_list.id, Utc::now().timestamp());
+                    // lists found unchanged by their length can be requested conditionally
+                    // from now on
+                    if let Some(validators) = list.validators().await {
+                        self.manifest
+                            .as_mut()
+                            .unwrap()
+                            .http
+                            .insert(list.filter_list.id.clone(), validators);
+                    }
+                    self.observers.list_done(
+                        PipelineStage::Download,
+                        &list.filter_list.id,
+                        Status::Unchanged,
+                    );
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .list_status(&list.filter_list.id, Status::Unchanged);
+                    self.cached_lists
+                        .as_mut()
+                        .unwrap()
+                        .insert(list.filter_list.id);
+                }
+            }
+        }
+        self.observers.plan(PipelineStage::Download, &plan);
+        Ok(())
+    }
+
+    /// Estimates the space the lists to be downloaded take up from their content lengths
+    /// and refuses to start the download if it would exhaust the cache partition or
+    /// exceed the cache quota. If configured, the artifacts of lists which are no longer
+    /// configured are pruned first.
+    async fn check_disk_space(&mut self) -> anyhow::Result<()> {
+        let mut required = self.config.disk.reserve;
+        for list in self.filter_lists.iter_mut() {
+            match list.reader_len().await {
+                Ok(len) => required += len,
+                Err(e) => debug!("{}: size unknown - {}", list.filter_list.id, e),
             }
         }
+        let cache_dir = Path::new(&self.config.cache_dir);
+        fs::create_dir_all(cache_dir).with_context(|| "could not create cache directory")?;
+
+        let mut shortage = self.space_shortage(cache_dir, required)?;
+        if shortage > 0 && self.config.disk.prune {
+            let freed = prune(self.config, self.manifest.as_mut().unwrap());
+            info!("pruned {} bytes of outdated cache files", freed);
+            shortage = self.space_shortage(cache_dir, required)?;
+        }
+        if shortage > 0 {
+            return Err(anyhow::anyhow!(
+                "not enough disk space: {} more bytes needed to download {} lists",
+                shortage,
+                self.filter_lists.len()
+            ));
+        }
         Ok(())
     }
 
-    /// downloads lists to temp files
-    async fn download(&mut self) -> anyhow::Result<()> {
+    /// Orders the downloads by the lists' priority and lists of the same priority by
+    /// their content length, so small lists are done early. Lists of unknown length
+    /// go last.
+    async fn order_downloads(&mut self) {
+        let mut keyed = vec![];
+        for mut list in self.filter_lists.drain(..) {
+            let len = list.reader_len().await.ok();
+            keyed.push((download_order(&list.filter_list, len), list));
+        }
+        keyed.sort_by_key(|(key, _)| *key);
+        self.filter_lists = keyed.into_iter().map(|(_, list)| list).collect();
+    }
+
+    /// returns the number of bytes missing for a download of the given size
+    ///
+    /// * `cache_dir`: the cache directory
+    /// * `required`: the number of bytes to be downloaded
+    fn space_shortage(&self, cache_dir: &Path, required: u64) -> anyhow::Result<u64> {
+        let available = fs2::available_space(cache_dir)
+            .with_context(|| "could not determine available disk space")?;
+        let mut shortage = required.saturating_sub(available);
+        if let Some(quota) = self.config.disk.quota {
+            let used = dir_size(cache_dir);
+            shortage = shortage.max((used + required).saturating_sub(quota));
+        }
+        Ok(shortage)
+    }
+
+    /// downloads lists to temp files and moves them to the content addressed blob store
+    ///
+    /// * `download_path`: the file system path to the directory containing the raw lists
+    async fn download(&mut self, download_path: &Path) -> anyhow::Result<()> {
+        // the readers are handed over to the downloads, their validators are taken
+        // from the responses' header sections beforehand
+        let mut validators = HashMap::new();
+        for list in self.filter_lists.iter() {
+            if let Some(v) = list.validators().await {
+                validators.insert(list.filter_list.id.clone(), v);
+            }
+        }
+        // the writers are kept to flush the downloads through the handles they were
+        // written with
+        let mut writers: HashMap<String, Arc<Mutex<File>>> = self
+            .filter_lists
+            .iter()
+            .filter_map(|l| Some((l.filter_list.id.clone(), l.writer.clone()?)))
+            .collect();
         let handles = process(
             &mut self.filter_lists,
-            &|_, chunk| async { Ok(chunk) },
+            &|_, chunk| async { Ok(Transformed::Write(chunk)) },
+            &self.config.regex_options,
+            ErrorCode::DownloadFailed,
+            self.config.max_parallel_downloads(),
             self.is_processing.clone(),
         )
         .await;
-        join_all(handles).await;
+        let mut finished = HashSet::new();
+        for result in join_all(handles).await.into_iter().flatten() {
+            finished.insert(result.id.clone());
+            let writer = writers.remove(&result.id);
+            if let Some(failure) = result.io_failure {
+                let list_path = download_path.join(&result.id);
+                self.restore_previous(&list_path, &result.id);
+                let error = result.error.unwrap_or_default();
+                self.observers.error(
+                    PipelineStage::Download,
+                    Some(&result.id),
+                    failure.into(),
+                    &error,
+                );
+                self.report
+                    .lock()
+                    .unwrap()
+                    .list_io_failed(&result.id, failure, error);
+                continue;
+            }
+            if let Some(e) = result.error {
+                let code = result.code.unwrap_or(ErrorCode::DownloadFailed);
+                self.observers
+                    .error(PipelineStage::Download, Some(&result.id), code, &e);
+                if self.fall_back(download_path, &result.id) {
+                    self.observers
+                        .list_done(PipelineStage::Download, &result.id, Status::Stale);
+                    self.report.lock().unwrap().list_stale(&result.id, code, e);
+                    // the extract of the previous download is still valid
+                    self.cached_lists.as_mut().unwrap().insert(result.id);
+                } else {
+                    self.report.lock().unwrap().list_failed(&result.id, code, e);
+                }
+                continue;
+            }
+            if result.interrupted {
+                self.interrupted(download_path, result.id);
+                continue;
+            }
+            // a download is only recorded once it survives a crash
+            let list_path = download_path.join(&result.id);
+            if self.config.disk.fsync == FsyncPolicy::Complete
+                && let Err(e) = sync_download(writer, download_path).await
+            {
+                let failure = IoFailure::classify(&e);
+                self.restore_previous(&list_path, &result.id);
+                let error = format!("could not flush {}: {}", list_path.display(), e);
+                self.observers.error(
+                    PipelineStage::Download,
+                    Some(&result.id),
+                    failure.into(),
+                    &error,
+                );
+                self.report
+                    .lock()
+                    .unwrap()
+                    .list_io_failed(&result.id, failure, error);
+                continue;
+            }
+            let manifest = self.manifest.as_mut().unwrap();
+            manifest.mark_current(&result.id, Utc::now().timestamp());
+            // the extract of the former download must not be taken over by a resumed run
+            manifest.extracts.remove(&result.id);
+            match validators.remove(&result.id) {
+                Some(v) => manifest.http.insert(result.id.clone(), v),
+                None => manifest.http.remove(&result.id),
+            };
+            let cache_dir = Path::new(&self.config.cache_dir);
+            if let Err(e) = store_blob(cache_dir, &list_path, &result.id, manifest) {
+                warn!("{}: {:?}", result.id, e);
+            }
+            if let Some(archive) = &self.config.archive
+                && let Err(e) = snapshot(archive, &result.id, &list_path, Utc::now())
+            {
+                warn!("{}: {:?}", result.id, e);
+            }
+            self.observers
+                .list_done(PipelineStage::Download, &result.id, Status::Updated);
+        }
+        // the downloads which weren't started when the downloads were cancelled
+        let pending: Vec<String> = self
+            .filter_lists
+            .iter()
+            .map(|l| l.filter_list.id.clone())
+            .filter(|id| !finished.contains(id))
+            .collect();
+        for id in pending {
+            self.interrupted(download_path, id);
+        }
+        if let Some(archive) = &self.config.archive {
+            match expire(archive, Utc::now()) {
+                Ok(removed) => debug!("removed {} expired snapshots", removed),
+                Err(e) => warn!("{:?}", e),
+            }
+        }
         Ok(())
     }
+
+    /// Replaces the incomplete download of a list by its previous version after the
+    /// downloads were cancelled. The list isn't recorded as downloaded, a resumed run
+    /// downloads it again.
+    ///
+    /// * `download_path`: the file system path to the directory containing the raw lists
+    /// * `id`: the list's id
+    fn interrupted(&mut self, download_path: &Path, id: String) {
+        let code = ErrorCode::DownloadFailed;
+        let error = "the download was cancelled".to_string();
+        if self.fall_back(download_path, &id) {
+            self.report.lock().unwrap().list_stale(&id, code, error);
+            // the extract of the previous download is still valid
+            self.cached_lists.as_mut().unwrap().insert(id);
+        } else {
+            self.report.lock().unwrap().list_failed(&id, code, error);
+        }
+    }
+
+    /// takes over the list's download of an earlier run unchanged
+    ///
+    /// * `id`: the list's id
+    fn keep_previous(&mut self, id: String) {
+        self.observers
+            .list_done(PipelineStage::Download, &id, Status::Unchanged);
+        self.report
+            .lock()
+            .unwrap()
+            .list_status(&id, Status::Unchanged);
+        self.cached_lists.as_mut().unwrap().insert(id);
+    }
+
+    /// Replaces a failed download by the list's previous version if there is one and it
+    /// isn't older than the list's `max_stale_age`. Returns true if the previous
+    /// version is used.
+    ///
+    /// * `download_path`: the file system path to the directory containing the raw lists
+    /// * `id`: the list's id
+    fn fall_back(&self, download_path: &Path, id: &str) -> bool {
+        let list_path = download_path.join(id);
+        match self.config.lists.iter().find(|l| l.id == id) {
+            Some(list) if self.may_use_previous(list) => self.restore_previous(&list_path, id),
+            _ => {
+                fs::remove_file(&list_path).ok();
+                false
+            }
+        }
+    }
+
+    /// returns true if the list's previous download is recent enough to stand in for a
+    /// download which failed or was deferred
+    ///
+    /// * `list`: the filter list
+    fn may_use_previous(&self, list: &FilterList) -> bool {
+        let max_stale_age = match list.max_stale_age {
+            Some(age) => age,
+            None => return true,
+        };
+        match self.manifest.as_ref().and_then(|m| m.ages.get(&list.id)) {
+            Some(age) => *age <= max_stale_age,
+            None => false,
+        }
+    }
+
+    /// Replaces an incomplete download by the list's previous version from the blob
+    /// store. Returns true if the previous version was restored.
+    ///
+    /// * `list_path`: the incomplete download
+    /// * `id`: the list's id
+    fn restore_previous(&self, list_path: &Path, id: &str) -> bool {
+        if let Err(e) = fs::remove_file(list_path) {
+            debug!("{}: {}", list_path.display(), e);
+        }
+        let hash = match self.manifest.as_ref().and_then(|m| m.blobs.get(id)) {
+            Some(h) => h,
+            None => return false,
+        };
+        let blob = Path::new(&self.config.cache_dir).join(BLOB_PATH).join(hash);
+        match fs::hard_link(&blob, list_path) {
+            Ok(()) => {
+                warn!("{}: keeping previous version", id);
+                true
+            }
+            Err(e) => {
+                error!("{}: could not restore previous version: {}", id, e);
+                false
+            }
+        }
+    }
+
+    /// Fetches the newly logged domains from all configured certificate transparency
+    /// logs and merges them into the lists' rolling download files
+    ///
+    /// * `download_path`: the file system path to the directory containing the raw lists
+    async fn tail_ct_logs(&mut self, download_path: &Path) -> anyhow::Result<()> {
+        if self.config.offline {
+            return Ok(());
+        }
+        for list in self.config.lists.iter().filter(|l| l.ct_log.is_some()) {
+            if !self.is_processing.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            self.observers.list_start(PipelineStage::Download, &list.id);
+            match self.tail_ct_log(list, download_path).await {
+                Ok(count) => {
+                    debug!("{}: {} new domains", list.id, count);
+                    self.observers
+                        .list_done(PipelineStage::Download, &list.id, Status::Updated);
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .list_status(&list.id, Status::Updated);
+                }
+                Err(e) => {
+                    let code = ErrorCode::CtLogFailed;
+                    self.observers.error(
+                        PipelineStage::Download,
+                        Some(&list.id),
+                        code,
+                        &format!("{e:?}"),
+                    );
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .list_failed(&list.id, code, e.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Tails a single certificate transparency log starting at the position stored in
+    /// the manifest and returns the number of newly seen domains
+    ///
+    /// * `list`: the filter list configuring the log
+    /// * `download_path`: the file system path to the directory containing the raw lists
+    async fn tail_ct_log(
+        &mut self,
+        list: &FilterList,
+        download_path: &Path,
+    ) -> anyhow::Result<usize> {
+        let ct_config = list.ct_log.clone().unwrap();
+        let max_entries = ct_config.max_entries;
+        let manifest = self.manifest.as_mut().unwrap();
+        let url =
+            Url::parse(&list.source).with_context(|| format!("config file error: {:?}", &list))?;
+        let position = manifest.ct_log_positions.get(&list.id).copied();
+        let mut input = CtLogInput::new(url, position, ct_config)?;
+
+        // the domains in the order they were seen and the set to look them up
+        let mut new_domains: Vec<String> = vec![];
+        let mut seen: HashSet<String> = HashSet::new();
+        while self.is_processing.load(Ordering::SeqCst) {
+            match input.chunk().await? {
+                Some(chunk) => {
+                    let domain = String::from_utf8(chunk)?.trim_end().to_string();
+                    if seen.insert(domain.clone()) {
+                        new_domains.push(domain);
+                    }
+                }
+                None => break,
+            }
+        }
+        log_throttle::summarize(input.url.as_str());
+
+        // the rolling list keeps the most recently seen domains at its end
+        let mut list_path = download_path.to_path_buf();
+        fs::create_dir_all(&list_path).with_context(|| "could not create out directory")?;
+        list_path.push(&list.id);
+        let mut domains: VecDeque<String> = fs::read_to_string(&list_path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|l| !l.is_empty() && !seen.contains(*l))
+            .map(String::from)
+            .collect();
+        let count = new_domains.len();
+        domains.extend(new_domains);
+        while domains.len() > max_entries {
+            domains.pop_front();
+        }
+        let mut out_file = File::create(&list_path).with_context(|| "could not write out file")?;
+        for domain in domains {
+            out_file.write_all(format!("{domain}\n").as_bytes())?;
+        }
+
+        if let Some(position) = input.position {
+            manifest.ct_log_positions.insert(list.id.clone(), position);
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tests::helper::{
+            cache_file_creator::{CacheFileCreator, TEST_CACHE},
+            list_server::ListServer,
+        },
+        DOWNLOAD_PATH, EXTRACT_PATH,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_fall_back() {
+        let cache = CacheFileCreator::new("test_fall_back", DOWNLOAD_PATH, EXTRACT_PATH);
+        let mut config = cache.new_test_config();
+        config.lists = ["fresh", "stale"]
+            .into_iter()
+            .map(|id| FilterList {
+                id: id.to_string(),
+                max_stale_age: Some(3600),
+                ..Default::default()
+            })
+            .collect();
+        let download_path = Path::new(&config.cache_dir).join(DOWNLOAD_PATH);
+        let mut manifest = Manifest::default();
+        for id in ["fresh", "stale"] {
+            cache.write_input(id, "domain.one\n");
+            store_blob(
+                Path::new(&config.cache_dir),
+                &download_path.join(id),
+                id,
+                &mut manifest,
+            )
+            .unwrap();
+        }
+        let now = Utc::now().timestamp();
+        manifest.fetched.insert("fresh".to_string(), now - 60);
+        manifest.fetched.insert("stale".to_string(), now - 7200);
+        manifest.tick(now);
+        let controller = FilterController::new(&config, manifest, Default::default());
+
+        // the failed download left partial files behind
+        for id in ["fresh", "stale"] {
+            fs::remove_file(download_path.join(id)).unwrap();
+            cache.write_input(id, "dom");
+        }
+        assert!(controller.fall_back(&download_path, "fresh"));
+        assert_eq!(
+            fs::read_to_string(download_path.join("fresh")).unwrap(),
+            "domain.one\n"
+        );
+        assert!(!controller.fall_back(&download_path, "stale"));
+        assert!(!download_path.join("stale").exists());
+    }
+
+    #[test]
+    fn test_download_order() {
+        let list = |id: &str, priority| FilterList {
+            id: id.to_string(),
+            priority,
+            ..Default::default()
+        };
+        let mut lists = [
+            (list("whale", None), Some(500_000_000)),
+            (list("unknown", None), None),
+            (list("urgent", Some(10)), Some(800_000_000)),
+            (list("small", None), Some(2_000)),
+            (list("late", Some(-1)), Some(100)),
+        ];
+        lists.sort_by_key(|(list, len)| download_order(list, *len));
+        let ids: Vec<&str> = lists.iter().map(|(l, _)| l.id.as_str()).collect();
+        assert_eq!(ids, vec!["urgent", "small", "whale", "unknown", "late"]);
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let cache = CacheFileCreator::new("test_resume", DOWNLOAD_PATH, EXTRACT_PATH);
+        let mut config = cache.new_test_config();
+        config.resume = true;
+        config.lists = ["resumed", "moved"]
+            .into_iter()
+            .map(|id| FilterList {
+                id: id.to_string(),
+                source: "http://127.0.0.1:1/list".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        for id in ["resumed", "moved"] {
+            cache.write_input(id, "domain.one\n");
+        }
+        let state = RunState {
+            downloaded: [
+                ("resumed", "http://127.0.0.1:1/list"),
+                ("moved", "http://127.0.0.1:1/old"),
+            ]
+            .into_iter()
+            .map(|(id, source)| (id.to_string(), source.to_string()))
+            .collect(),
+        };
+        state.save(Path::new(&config.cache_dir)).unwrap();
+        let download_path = Path::new(&config.cache_dir).join(DOWNLOAD_PATH);
+        let mut controller = FilterController::new(
+            &config,
+            Manifest::default(),
+            Arc::new(AtomicBool::new(true)),
+        );
+
+        controller.prepare_download(download_path).await.unwrap();
+        // the list whose source changed is requested again
+        let cached_lists = controller.cached_lists.as_ref().unwrap();
+        assert!(cached_lists.contains("resumed"));
+        assert!(!cached_lists.contains("moved"));
+        let report = controller.report.lock().unwrap();
+        assert_eq!(report.lists["resumed"].status, Status::Unchanged);
+        assert_ne!(report.lists["moved"].status, Status::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn test_preflight_timeout() {
+        // a server accepting connections without ever answering
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let cache = CacheFileCreator::new("test_preflight_timeout", DOWNLOAD_PATH, EXTRACT_PATH);
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "silent".to_string(),
+            source: format!("http://{}/list", listener.local_addr().unwrap()),
+            request_timeout: Some(1),
+            ..Default::default()
+        }];
+        let download_path = Path::new(&config.cache_dir).join(DOWNLOAD_PATH);
+        let mut controller = FilterController::new(
+            &config,
+            Manifest::default(),
+            Arc::new(AtomicBool::new(true)),
+        );
+
+        controller.prepare_download(download_path).await.unwrap();
+        assert!(controller.filter_lists.is_empty());
+        let report = controller.report.lock().unwrap();
+        assert_eq!(report.lists["silent"].status, Status::Failed);
+        assert_eq!(
+            report.lists["silent"].error.as_deref(),
+            Some("no response within 1 seconds")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_fsync_complete() {
+        fs::remove_dir_all(Path::new(TEST_CACHE).join("test_download_fsync_complete")).ok();
+        let server = ListServer::start();
+        server.set_list("/synced", "domain.one\n");
+        let cache =
+            CacheFileCreator::new("test_download_fsync_complete", DOWNLOAD_PATH, EXTRACT_PATH);
+        let mut config = cache.new_test_config();
+        config.disk.fsync = FsyncPolicy::Complete;
+        config.lists = vec![FilterList {
+            id: "synced".to_string(),
+            source: server.url("/synced"),
+            ..Default::default()
+        }];
+        let mut controller = FilterController::new(
+            &config,
+            Manifest::default(),
+            Arc::new(AtomicBool::new(true)),
+        );
+
+        controller.run(DOWNLOAD_PATH).await.unwrap();
+        // the download is flushed through its writer and recorded afterwards
+        let report = controller.report.lock().unwrap();
+        assert_eq!(report.lists["synced"].status, Status::Updated);
+        let download_path = Path::new(&config.cache_dir).join(DOWNLOAD_PATH);
+        assert_eq!(
+            fs::read_to_string(download_path.join("synced")).unwrap(),
+            "domain.one\n"
+        );
+    }
 }
@@ -1,46 +1,206 @@
-use std::{fs::File, marker::PhantomData, path::PathBuf, str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{atomic::Ordering, Arc, OnceLock, RwLock},
+    time::Duration,
+};
 
+use anyhow::Context;
 use futures::future::join_all;
-use regex::Regex;
+use log::Level;
+use regex::{Regex, RegexBuilder};
 
 use crate::{
-    filter_controller::{process, FilterController, StageCategorize, StageExtract},
+    config::RegexOptions,
+    entry::Entry,
+    error_code::ErrorCode,
+    filter_controller::{
+        process, ChunkContext, FilterController, ProcessResult, SkipReason, StageCategorize,
+        StageExtract, Transformed,
+    },
     filter_list::FilterList,
-    input::file::FileInput,
-    io::filter_list_io::FilterListIO,
+    input::command::{pipe_through_command, DEFAULT_COMMAND_TIMEOUT},
+    io::{
+        filter_list_io::FilterListIO,
+        storage::{ListReader, ListWriter},
+    },
+    log_throttle,
+    manifest::ExtractRecord,
+    observer::PipelineStage,
+    report::Status,
+    theme,
+    transform::get_transform,
+    TRANSFORM_PATH,
 };
 
-/// regex_match matches a line against a regex an extracts the first match group
+/// compiled regexes by their pattern and limits, lists sharing a pattern share the
+/// compiled regex
+static REGEXES: OnceLock<RwLock<HashMap<RegexKey, Arc<Regex>>>> = OnceLock::new();
+
+/// the pattern, size limit and DFA size limit a regex was compiled with
+type RegexKey = (String, usize, usize);
+
+/// Compiles a regex within the configured limits and stores it for use by regex_match.
+/// Patterns which have already been compiled within the same limits are not compiled
+/// again.
+///
+/// * `pattern`: the regex as given in the configuration file
+/// * `options`: the limits the compiled regex must stay within
+fn compile_regex(pattern: &str, options: &RegexOptions) -> anyhow::Result<Arc<Regex>> {
+    let regexes = REGEXES.get_or_init(Default::default);
+    let key = (
+        pattern.to_string(),
+        options.size_limit,
+        options.dfa_size_limit,
+    );
+    if let Some(re) = regexes.read().unwrap().get(&key) {
+        return Ok(re.clone());
+    }
+    let re = RegexBuilder::new(pattern)
+        .size_limit(options.size_limit)
+        .dfa_size_limit(options.dfa_size_limit)
+        .build()?;
+    let re = Arc::new(re);
+    regexes.write().unwrap().insert(key, re.clone());
+    Ok(re)
+}
+
+/// number of bytes at the start of a list inspected for binary data
+const SNIFF_LEN: usize = 8192;
+
+/// Inspects the start of a list and returns a description of its contents if they
+/// aren't text, e.g. because the list is compressed but no compression is configured
+///
+/// * `sample`: the first bytes of the list
+fn detect_binary(sample: &[u8]) -> Option<String> {
+    let magic: [(&[u8], &str); 5] = [
+        (&[0x1f, 0x8b], "gzip"),
+        (b"BZh", "bzip2"),
+        (&[0x28, 0xb5, 0x2f, 0xfd], "zstd"),
+        (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], "xz"),
+        (b"PK\x03\x04", "zip"),
+    ];
+    if let Some((_, format)) = magic.iter().find(|(m, _)| sample.starts_with(m)) {
+        return Some(match *format {
+            "gzip" => {
+                "gzip compressed data, set \"compression\" to \"Gz\" or \"TarGz\"".to_string()
+            }
+            f => format!("{f} compressed data, decompress it with a \"transform_cmd\""),
+        });
+    }
+    if sample.contains(&0) {
+        return Some(
+            "binary data containing NUL bytes, check the \"compression\" setting".to_string(),
+        );
+    }
+    // count the bytes of invalid UTF-8 sequences, a sequence cut off at the end of the
+    // sample doesn't count
+    let mut invalid = 0;
+    let mut rest = sample;
+    while let Err(e) = std::str::from_utf8(rest) {
+        match e.error_len() {
+            Some(len) => {
+                invalid += len;
+                rest = &rest[e.valid_up_to() + len..];
+            }
+            None => break,
+        }
+    }
+    if invalid * 10 > sample.len() {
+        return Some(format!(
+            "binary data, {invalid} of the first {} bytes aren't valid UTF-8, check the \"compression\" setting",
+            sample.len()
+        ));
+    }
+    None
+}
+
+/// regex_match matches a line against a regex an extracts the first match group as
+/// a typed entry
 ///
-/// * `flist`: FilterList where the chunk to be matched belongs to
+/// * `context`: the context of the chunk including the FilterList it belongs to
 /// * `chunk`: A line from a list of URL to be matched against
-async fn regex_match(
-    flist: Arc<FilterList>,
-    chunk: Option<Vec<u8>>,
-) -> anyhow::Result<Option<Vec<u8>>> {
-    if chunk.is_none() {
-        return Ok(None);
-    }
-    let str_chunk = match String::from_utf8(chunk.unwrap()) {
+pub async fn regex_match(context: ChunkContext, chunk: Vec<u8>) -> anyhow::Result<Transformed> {
+    let flist = &context.list;
+    let str_chunk = match String::from_utf8(chunk) {
         Ok(s) => s,
         Err(e) => {
-            return Err(anyhow::anyhow!("Error: {}", e));
+            return Err(anyhow::anyhow!(
+                "{} at {}",
+                e.utf8_error(),
+                context.describe(e.as_bytes())
+            ));
         }
     };
-    let re = match Regex::new(&flist.regex) {
+    let re = match compile_regex(&flist.regex, &context.regex_options) {
         Ok(r) => r,
-        Err(e) => return Err(anyhow::anyhow!(format!("List {} - {}", flist.id, e))),
+        Err(e) => {
+            return Err(anyhow::anyhow!(
+                "invalid regex at {}: {}",
+                context.describe(str_chunk.as_bytes()),
+                e
+            ))
+        }
     };
-    if let Some(caps) = re.captures(&str_chunk) && let Some(cap) = caps.get(1) {
-                    let result = cap.as_str().to_owned() + "\n";
-                    return Ok(Some(result.as_bytes().to_owned()));
+    if let Some(caps) = re.captures(&str_chunk)
+        && let Some(cap) = caps.get(1)
+    {
+        // lines which don't contain a valid entry are skipped
+        let entry: Entry = match cap.as_str().parse() {
+            Ok(e) => e,
+            Err(e) => {
+                log_throttle::log(
+                    Level::Debug,
+                    &flist.id,
+                    "invalid entry",
+                    format_args!("List {} line {} - {}", flist.id, context.line, e),
+                );
+                return Ok(Transformed::Skip(SkipReason::InvalidEntry));
+            }
+        };
+        // the transform script may rewrite or drop the entry
+        let entry = match &flist.transform {
+            Some(path) => {
+                let transformed = get_transform(path)?
+                    .apply(&entry.to_string(), &flist.id)
+                    .with_context(|| {
+                        format!(
+                            "transform failed at {}",
+                            context.describe(str_chunk.as_bytes())
+                        )
+                    })?;
+                match transformed.map(|t| t.parse::<Entry>()) {
+                    Some(Ok(e)) => e,
+                    Some(Err(e)) => {
+                        log_throttle::log(
+                            Level::Debug,
+                            &flist.id,
+                            "invalid transform result",
+                            format_args!(
+                                "List {} line {} - transform: {}",
+                                flist.id, context.line, e
+                            ),
+                        );
+                        return Ok(Transformed::Skip(SkipReason::InvalidEntry));
+                    }
+                    None => return Ok(Transformed::Skip(SkipReason::Dropped)),
                 }
-    Ok(None)
+            }
+            None => entry,
+        };
+        let result = entry.to_string() + "\n";
+        return Ok(Transformed::Write(result.as_bytes().to_owned()));
+    }
+    Ok(Transformed::Skip(SkipReason::NoMatch))
 }
 
-/// This implementation for FileInput and File is the second stage where URLs are
-/// being extracted
-impl<'config> FilterController<'config, StageExtract, FileInput, File> {
+/// This implementation is the second stage where URLs are being extracted. The
+/// extracts are written to and read from wherever the list writer and reader keep
+/// them, the file system for the pipeline.
+impl<'config, R: ListReader, W: ListWriter> FilterController<'config, StageExtract, R, W> {
     /// Runs the extract stage and returns the controller for the categorize stage
     ///
     /// * `download_base_path`: The path where downloaded lists have been stored
@@ -49,7 +209,7 @@ impl<'config> FilterController<'config, StageExtract, FileInput, File> {
         &mut self,
         download_base_path: &str,
         extract_base_path: &str,
-    ) -> anyhow::Result<FilterController<StageCategorize, FileInput, File>> {
+    ) -> anyhow::Result<FilterController<'config, StageCategorize, R, W>> {
         let mut download_path = PathBuf::from_str(&self.config.cache_dir)?;
         download_path.push(download_base_path);
         let mut extract_path = PathBuf::from_str(&self.config.cache_dir)?;
@@ -57,15 +217,8 @@ impl<'config> FilterController<'config, StageExtract, FileInput, File> {
 
         self.prepare_extract(download_path.clone(), extract_path.clone())
             .await?;
-        self.extract().await?;
-        let categorize_controller = FilterController::<StageCategorize, FileInput, File> {
-            stage: PhantomData,
-            config: self.config,
-            cached_lists: self.cached_lists.take(),
-            filter_lists: vec![],
-            category_lists: vec![],
-            is_processing: self.is_processing.clone(),
-        };
+        self.extract(&extract_path).await?;
+        let categorize_controller = self.next_stage::<StageCategorize, R, W>();
         Ok(categorize_controller)
     }
 
@@ -78,7 +231,7 @@ impl<'config> FilterController<'config, StageExtract, FileInput, File> {
         download_path: PathBuf,
         extract_path: PathBuf,
     ) -> anyhow::Result<()> {
-        let configured_lists: Vec<FilterListIO<FileInput, File>> = self
+        let configured_lists: Vec<FilterListIO<R, W>> = self
             .config
             .lists
             .iter()
@@ -91,21 +244,56 @@ impl<'config> FilterController<'config, StageExtract, FileInput, File> {
                 .as_ref()
                 .unwrap()
                 .contains(&list.filter_list.id)
+                && self.is_extract_valid(&list.filter_list, &extract_path)
                 && list
                     .attach_existing_input_file(&download_path, None)
                     .is_ok()
                 && list.attach_existing_file_writer(&extract_path).is_ok()
             {
                 list.writer = None;
-                info!("Unchanged: {}", list.filter_list.id);
+                self.observers.list_done(
+                    PipelineStage::Extract,
+                    &list.filter_list.id,
+                    Status::Unchanged,
+                );
             } else {
                 self.cached_lists
                     .as_mut()
                     .unwrap()
                     .retain(|l| l != &list.filter_list.id);
-                info!("Updated: {}", list.filter_list.id);
-                let compression = list.filter_list.compression.clone();
-                list.attach_existing_input_file(&download_path, compression)?;
+                // a regex exceeding the limits, a broken transform script or a failing
+                // transform command only fails its own list
+                let prepared =
+                    match compile_regex(&list.filter_list.regex, &self.config.regex_options) {
+                        Ok(_) => self
+                            .prepare_input(&mut list, &download_path)
+                            .await
+                            .map_err(|e| (ErrorCode::TransformFailed, e))
+                            .and_then(|_| {
+                                self.check_text(&list.filter_list, &download_path)
+                                    .map_err(|e| (ErrorCode::BinaryContent, e))
+                            }),
+                        Err(e) => Err((ErrorCode::RegexFailed, e)),
+                    };
+                if let Err((code, e)) = prepared {
+                    self.observers.error(
+                        PipelineStage::Extract,
+                        Some(&list.filter_list.id),
+                        code,
+                        &format!("{e:?}"),
+                    );
+                    if let Some(m) = self.manifest.as_mut() {
+                        m.extracts.remove(&list.filter_list.id);
+                    }
+                    self.report.lock().unwrap().list_failed(
+                        &list.filter_list.id,
+                        code,
+                        e.to_string(),
+                    );
+                    continue;
+                }
+                self.observers
+                    .list_start(PipelineStage::Extract, &list.filter_list.id);
                 list.attach_new_file_writer(&extract_path)?;
                 self.filter_lists.push(list);
             }
@@ -113,24 +301,229 @@ impl<'config> FilterController<'config, StageExtract, FileInput, File> {
         Ok(())
     }
 
+    /// checks the list's extract file against the record of the last run. The extract
+    /// is outdated if the list's settings changed or the file doesn't have the recorded
+    /// number of lines, e.g. because it was truncated.
+    ///
+    /// * `list`: the list to be checked
+    /// * `extract_path`: the file system path to where the extracted URLs are written to
+    fn is_extract_valid(&self, list: &FilterList, extract_path: &Path) -> bool {
+        let record = match self
+            .manifest
+            .as_ref()
+            .and_then(|m| m.extracts.get(&list.id))
+        {
+            Some(r) => r,
+            None => return false,
+        };
+        if record.settings != list.settings_hash() {
+            debug!("{}: list settings changed", list.id);
+            return false;
+        }
+        match W::count_lines(&extract_path.join(&list.id)) {
+            Ok(lines) if lines == record.lines => true,
+            Ok(lines) => {
+                debug!(
+                    "{}: {} lines extracted, {} expected",
+                    list.id, lines, record.lines
+                );
+                false
+            }
+            Err(e) => {
+                debug!("{}: {}", list.id, e);
+                false
+            }
+        }
+    }
+
+    /// fails if the data the list is extracted from isn't text. Lists decompressed by
+    /// harvester are not checked.
+    ///
+    /// * `list`: the list to be extracted
+    /// * `download_path`: the file system path to where the downloaded lists were stored
+    fn check_text(&self, list: &FilterList, download_path: &Path) -> anyhow::Result<()> {
+        let path = match (&list.transform_cmd, &list.compression) {
+            (Some(_), _) => Path::new(&self.config.cache_dir)
+                .join(TRANSFORM_PATH)
+                .join(&list.id),
+            (None, None) => download_path.join(&list.id),
+            (None, Some(_)) => return Ok(()),
+        };
+        let mut sample = Vec::with_capacity(SNIFF_LEN);
+        match File::open(&path) {
+            Ok(f) => f.take(SNIFF_LEN as u64).read_to_end(&mut sample)?,
+            // lists without a download are reported elsewhere
+            Err(_) => return Ok(()),
+        };
+        match detect_binary(&sample) {
+            Some(description) => Err(anyhow::anyhow!("the list contains {}", description)),
+            None => Ok(()),
+        }
+    }
+
+    /// Attaches the downloaded list as input. Lists with a transform command are piped
+    /// through the command first and the command's output becomes the input.
+    ///
+    /// * `list`: the list to be extracted
+    /// * `download_path`: the file system path to where the downloaded lists were stored
+    async fn prepare_input(
+        &self,
+        list: &mut FilterListIO<R, W>,
+        download_path: &Path,
+    ) -> anyhow::Result<()> {
+        if let Some(path) = &list.filter_list.transform {
+            get_transform(path)?;
+        }
+        let compression = list.filter_list.compression.clone();
+        list.attach_existing_input_file(download_path, compression)?;
+        let command = match &list.filter_list.transform_cmd {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        let mut transform_path = PathBuf::from_str(&self.config.cache_dir)?;
+        transform_path.push(TRANSFORM_PATH);
+        let mut output = W::create(&transform_path.join(&list.filter_list.id))?;
+        // an empty download has no reader attached
+        if let Some(reader) = list.reader.take() {
+            let timeout = list
+                .filter_list
+                .transform_cmd_timeout
+                .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+            let mut input = reader.lock().await;
+            pipe_through_command(
+                command,
+                &mut *input,
+                &mut output,
+                Duration::from_secs(timeout),
+            )
+            .await?;
+        }
+        list.attach_existing_input_file(&transform_path, None)
+    }
+
     /// extracts URLs from lines by employing the regex given in the configuration file
-    async fn extract(&mut self) -> anyhow::Result<()> {
+    ///
+    /// * `extract_path`: the file system path to where the extracted URLs are written to
+    async fn extract(&mut self, extract_path: &Path) -> anyhow::Result<()> {
         let handles = process(
             &mut self.filter_lists,
             &regex_match,
+            &self.config.regex_options,
+            ErrorCode::ExtractFailed,
+            self.config.max_parallel_extracts(),
             self.is_processing.clone(),
         )
         .await;
-        join_all(handles).await;
+        // the handles are returned in the order of the filter lists
+        let ids: Vec<String> = self
+            .filter_lists
+            .iter()
+            .map(|l| l.filter_list.id.clone())
+            .collect();
+        let time_limit = self
+            .config
+            .regex_options
+            .time_limit
+            .map(Duration::from_secs);
+        let results = join_all(
+            handles
+                .into_iter()
+                .zip(ids)
+                .map(|(mut handle, id)| async move {
+                    let time_limit = match time_limit {
+                        Some(t) => t,
+                        None => return handle.await.ok(),
+                    };
+                    match tokio::time::timeout(time_limit, &mut handle).await {
+                        Ok(result) => result.ok(),
+                        Err(_) => {
+                            handle.abort();
+                            Some(ProcessResult {
+                                id,
+                                error: Some("extraction exceeded the time limit".to_string()),
+                                code: Some(ErrorCode::TimeLimitExceeded),
+                                ..Default::default()
+                            })
+                        }
+                    }
+                }),
+        )
+        .await;
+        let interrupted = !self.is_processing.load(Ordering::SeqCst);
+        let manifest = self.manifest.as_mut().unwrap();
+        let mut report = self.report.lock().unwrap();
+        for result in results.into_iter().flatten() {
+            // an extract file is only valid for the next run if it was written completely
+            match self.config.lists.iter().find(|l| l.id == result.id) {
+                Some(list) if result.error.is_none() && !interrupted => {
+                    let record = ExtractRecord {
+                        lines: result.lines_matched,
+                        settings: list.settings_hash(),
+                    };
+                    manifest.extracts.insert(result.id.clone(), record);
+                    // a sharp drop of the match rate means the list's format changed
+                    // more often than not, the regex needs updating then
+                    let lines = result.lines_matched + result.lines_skipped;
+                    let rate = match lines {
+                        0 => 0.0,
+                        _ => result.lines_matched as f64 / lines as f64,
+                    };
+                    let drop = self.config.match_rate_drop();
+                    if let Some(previous) = manifest.record_match_rate(&result.id, rate, drop) {
+                        warn!(
+                            "{}",
+                            theme::banner(&format!(
+                                "{}: only {:.1}% of the lines matched instead of {:.1}%, the list's format probably changed",
+                                result.id,
+                                rate * 100.0,
+                                previous * 100.0
+                            ))
+                        );
+                        report.list_match_rate_dropped(&result.id, previous, rate);
+                    }
+                }
+                _ => {
+                    manifest.extracts.remove(&result.id);
+                }
+            }
+            if let Some(e) = &result.error {
+                let code = result.code.unwrap_or(ErrorCode::ExtractFailed);
+                self.observers
+                    .error(PipelineStage::Extract, Some(&result.id), code, e);
+            } else {
+                self.observers
+                    .list_done(PipelineStage::Extract, &result.id, Status::Updated);
+            }
+            match (result.error, result.io_failure) {
+                (Some(e), Some(failure)) => {
+                    // an incomplete file must not be taken for the list's extract on the next run
+                    W::remove(&extract_path.join(&result.id));
+                    report.list_io_failed(&result.id, failure, e)
+                }
+                (Some(e), None) => {
+                    let code = result.code.unwrap_or(ErrorCode::ExtractFailed);
+                    report.list_failed(&result.id, code, e)
+                }
+                (None, _) => {
+                    report.list_status(&result.id, Status::Updated);
+                    let list = report.lists.get_mut(&result.id).unwrap();
+                    list.lines_extracted = Some(result.lines_matched);
+                    list.skip_reasons = result.skip_reasons;
+                }
+            }
+        }
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashSet, sync::atomic::AtomicBool};
+    use std::{collections::HashSet, marker::PhantomData, sync::atomic::AtomicBool};
 
-    use crate::{tests::helper::cache_file_creator::CacheFileCreator, DOWNLOAD_PATH, EXTRACT_PATH};
+    use crate::{
+        input::file::FileInput, manifest::Manifest,
+        tests::helper::cache_file_creator::CacheFileCreator, DOWNLOAD_PATH, EXTRACT_PATH,
+    };
 
     use super::*;
 
@@ -146,6 +539,7 @@ mod tests {
             tags: vec![],
             // the regex for matching lines
             regex: r"127.0.0.1 (.*)".to_string(),
+            ..Default::default()
         }];
         // prepare the file to extract from
         cache.write_input(
@@ -162,6 +556,9 @@ mod tests {
             config: &config,
             filter_lists: vec![],
             category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
             is_processing: Arc::new(AtomicBool::new(true)),
         };
         if let Err(e) = extract_controller.run(&cache.inpath, &cache.outpath).await {
@@ -175,6 +572,58 @@ another.domain
         assert_eq!(want, got);
     }
 
+    #[tokio::test]
+    async fn test_extract_cache_invalidated() {
+        let cache = CacheFileCreator::new(
+            "test_extract_cache_invalidated",
+            DOWNLOAD_PATH,
+            EXTRACT_PATH,
+        );
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "test".to_string(),
+            regex: r"127.0.0.1 (.*)".to_string(),
+            ..Default::default()
+        }];
+        cache.write_input("test", "127.0.0.1 one.domain\n127.0.0.1 another.domain\n");
+
+        config.match_rate_drop = Some(0.25);
+        let mut manifest = Some(Manifest::default());
+        for (regex, want, dropped) in [
+            (r"127.0.0.1 (.*)", "one.domain\nanother.domain\n", None),
+            // the download is unchanged but the regex was edited, the match rate halves
+            (
+                r"127.0.0.1 (another.*)",
+                "another.domain\n",
+                Some((1.0, 0.5)),
+            ),
+        ] {
+            config.lists[0].regex = regex.to_string();
+            let mut extract_controller = FilterController::<StageExtract, FileInput, File> {
+                stage: PhantomData,
+                cached_lists: Some(HashSet::from(["test".to_string()])),
+                config: &config,
+                filter_lists: vec![],
+                category_lists: vec![],
+                manifest: manifest.take(),
+                report: Default::default(),
+                observers: Default::default(),
+                is_processing: Arc::new(AtomicBool::new(true)),
+            };
+            let mut categorize_controller = extract_controller
+                .run(&cache.inpath, &cache.outpath)
+                .await
+                .unwrap();
+            assert_eq!(cache.read_result("test").unwrap(), want);
+            manifest = categorize_controller.manifest.take();
+            let record = &manifest.as_ref().unwrap().extracts["test"];
+            assert_eq!(record.lines, want.lines().count());
+            assert_eq!(record.settings, config.lists[0].settings_hash());
+            let report = categorize_controller.report.lock().unwrap();
+            assert_eq!(report.lists["test"].match_rate_drop, dropped);
+        }
+    }
+
     #[tokio::test]
     async fn test_regex_match_positive() {
         let regex = "^0.0.0.0 (.*)".to_string();
@@ -185,18 +634,46 @@ another.domain
             source: "".to_string(),
             tags: vec![],
             regex,
+            ..Default::default()
         };
         let chunk = Vec::from("0.0.0.0 domain.tech\n");
 
-        let got = regex_match(Arc::new(filter_list), Some(chunk))
-            .await
-            .unwrap()
-            .unwrap();
-        let want = Vec::from("domain.tech\n");
+        let got = regex_match(
+            ChunkContext::new(Arc::new(filter_list), RegexOptions::default()),
+            chunk,
+        )
+        .await
+        .unwrap();
+        let want = Transformed::Write(Vec::from("domain.tech\n"));
 
         assert_eq!(got, want);
     }
 
+    #[tokio::test]
+    async fn test_regex_match_error_context() {
+        let mut context = ChunkContext::new(
+            Arc::new(FilterList {
+                id: "test_list".to_string(),
+                regex: "^0.0.0.0 (.*)".to_string(),
+                ..Default::default()
+            }),
+            RegexOptions::default(),
+        );
+        context.line = 42;
+        let mut chunk = format!("0.0.0.0 {}", "a".repeat(100)).into_bytes();
+        chunk[20] = 0xff;
+
+        let e = regex_match(context, chunk).await.unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "invalid utf-8 sequence of 1 bytes from index 20 at list test_list line 42: \"0.0.0.0 {}\u{fffd}{}…\"",
+                "a".repeat(12),
+                "a".repeat(59)
+            )
+        );
+    }
+
     #[tokio::test]
     async fn test_regex_no_match_comment() {
         let regex = "^0.0.0.0 (.*)".to_string();
@@ -207,14 +684,76 @@ another.domain
             source: "".to_string(),
             tags: vec![],
             regex,
+            ..Default::default()
         };
         let chunk = Vec::from("# some comment\n");
 
-        let got = regex_match(Arc::new(filter_list), Some(chunk))
-            .await
-            .unwrap();
-        let want: Option<Vec<u8>> = None;
+        let got = regex_match(
+            ChunkContext::new(Arc::new(filter_list), RegexOptions::default()),
+            chunk,
+        )
+        .await
+        .unwrap();
+        let want = Transformed::Skip(SkipReason::NoMatch);
 
         assert_eq!(got, want);
     }
+
+    #[test]
+    fn test_compile_regex_limits() {
+        let options = RegexOptions {
+            size_limit: 10000,
+            ..Default::default()
+        };
+        assert!(compile_regex(r"^0\.0\.0\.0 ([^\s#]+)", &options).is_ok());
+        assert!(compile_regex(r"^(\w{1000})", &options).is_err());
+        // the compiled regex is shared among lists with the same pattern
+        let one = compile_regex(r"^([^#]+)", &options).unwrap();
+        let two = compile_regex(r"^([^#]+)", &options).unwrap();
+        assert!(Arc::ptr_eq(&one, &two));
+    }
+
+    #[tokio::test]
+    async fn test_regex_match_limits() {
+        // a pattern compiled within the default limits before doesn't escape the
+        // configured ones
+        let regex = r"^([a-z]{1000}) extract".to_string();
+        assert!(compile_regex(&regex, &RegexOptions::default()).is_ok());
+        let options = RegexOptions {
+            size_limit: 10000,
+            ..Default::default()
+        };
+        let context = ChunkContext::new(
+            Arc::new(FilterList {
+                id: "test_list".to_string(),
+                regex,
+                ..Default::default()
+            }),
+            options,
+        );
+        let e = regex_match(context, Vec::from("domain.tech\n"))
+            .await
+            .unwrap_err();
+        assert!(e.to_string().starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_detect_binary() {
+        assert_eq!(
+            detect_binary("domain.one\nbücher.example\n".as_bytes()),
+            None
+        );
+        // a multi byte character cut off at the end of the sample is fine
+        assert_eq!(detect_binary(&"bücher".as_bytes()[..2]), None);
+        assert!(detect_binary(&[0x1f, 0x8b, 0x08, 0x00])
+            .unwrap()
+            .contains("\"Gz\""));
+        assert!(detect_binary(b"BZh91AY&SY").unwrap().starts_with("bzip2"));
+        assert!(detect_binary(b"domain.one\0\0").unwrap().contains("NUL"));
+        let mut latin1 = b"domain.one\n".to_vec();
+        latin1.extend([0xe4, 0xf6, 0xfc, 0xe4, 0xf6, 0xfc]);
+        assert!(detect_binary(&latin1)
+            .unwrap()
+            .contains("6 of the first 17 bytes"));
+    }
 }
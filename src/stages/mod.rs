@@ -1,4 +1,5 @@
 mod categorize;
+mod combine;
 mod download;
-mod extract;
+pub mod extract;
 mod output;
@@ -1,17 +1,30 @@
 use std::{
+    collections::HashSet,
     fs::File,
-    path::PathBuf,
+    io::Write,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{atomic::Ordering, Arc},
 };
 
-use futures::future::join_all;
+use futures::lock::Mutex;
 use tokio::task::JoinHandle;
 
 use crate::{
+    config::ALLOW_CATEGORY,
+    error_code::ErrorCode,
     filter_controller::{FilterController, StageOutput},
     input::file::FileInput,
-    io::category_list_io::CategoryListIO,
+    io::{
+        category_list_io::{CategoryListIO, FormatFiles},
+        IoFailure,
+    },
+    manifest::count_lines,
+    observer::PipelineStage,
+    output::{
+        write_outputs, CategoryMetadata, ConversionReport, FormatWriter, MetadataBanner, OutputType,
+    },
+    report::Status,
 };
 
 impl<'config> FilterController<'config, StageOutput, FileInput, File> {
@@ -21,74 +34,238 @@ impl<'config> FilterController<'config, StageOutput, FileInput, File> {
     pub async fn run(&mut self, categorize_base_path: &str) -> anyhow::Result<()> {
         let mut categorize_path = PathBuf::from_str(&self.config.cache_dir)?;
         categorize_path.push(categorize_base_path);
-        let out_path = PathBuf::from_str(&self.config.output_dir)?;
 
-        self.prepare_output(categorize_path.clone(), out_path)?;
-        self.output().await?;
+        self.combine(&categorize_path).await?;
+        self.prepare_output(categorize_path.clone())?;
+        self.output(&categorize_path).await?;
         Ok(())
     }
 
     /// Attaches the readers and writers to the CategoryListIO objects
     ///
     /// * `categorize_path`: the file system path to where the category lists where stored
-    /// * `output_path`: the file system path for the lists in the final result format
-    fn prepare_output(
-        &mut self,
-        categorize_path: PathBuf,
-        output_path: PathBuf,
-    ) -> anyhow::Result<()> {
+    fn prepare_output(&mut self, categorize_path: PathBuf) -> anyhow::Result<()> {
+        let failed: HashSet<String> = self
+            .report
+            .lock()
+            .unwrap()
+            .categories
+            .iter()
+            .filter(|(_, c)| c.status == Status::Failed)
+            .map(|(name, _)| name.clone())
+            .collect();
         self.category_lists = self
             .config
-            .get_tags()
+            .category_names()
             .iter()
+            // the allow category is only used to filter the other categories
+            .filter(|t| *t != ALLOW_CATEGORY)
+            // the previous outputs of categories which failed to be built are kept
+            .filter(|t| !failed.contains(*t))
             .map(|t| CategoryListIO::new(&t.clone()))
             .collect();
+        let manifest = self.manifest.as_ref();
+        // the files of the output directories which couldn't be written to
+        let mut skipped: Vec<PathBuf> = vec![];
         self.category_lists
             .iter_mut()
             .try_for_each(|list| -> anyhow::Result<()> {
                 // set readers
                 list.attach_existing_input_file(&categorize_path)?;
 
-                // set writers
+                // set writers, lists without writers will be skipped in the output method
+                let output_files: Vec<(OutputType, PathBuf)> = self
+                    .config
+                    .category_output_paths(&list.name)
+                    .into_iter()
+                    .map(|(format, dir)| {
+                        let file = dir.join(self.config.output_file_name(&format, &list.name));
+                        (format, file)
+                    })
+                    .collect();
+                // outputs which kept their previous version on the last run are outdated
+                let outdated = |file: &PathBuf| {
+                    !file.exists() || manifest.is_some_and(|m| m.is_output_failed(file))
+                };
                 if self.cached_lists.as_ref().unwrap().contains(&list.name)
-                    && list.attach_existing_input_file(&categorize_path).is_ok()
-                    && list.attach_existing_file_writer(&output_path).is_ok()
+                    && !output_files.iter().any(|(_, file)| outdated(file))
                 {
-                    // set writer to None so it will be skipped in the output method
-                    list.writer = None;
                     return Ok(());
                 }
-                list.attach_new_file_writer(&output_path)?;
+                skipped
+                    .extend(list.attach_new_format_writers(&output_files, self.config.gzip_output));
                 Ok(())
             })?;
+        // their previous versions are outdated as well
+        if let Some(m) = self.manifest.as_mut() {
+            skipped.iter().for_each(|file| m.output_failed(file));
+        }
         Ok(())
     }
 
     /// generates the final result lists
-    async fn output(&mut self) -> anyhow::Result<()> {
-        let mut handles: Vec<JoinHandle<()>> = vec![];
+    ///
+    /// * `categorize_path`: the file system path to where the category lists where stored
+    async fn output(&mut self, categorize_path: &Path) -> anyhow::Result<()> {
+        let mut handles: Vec<(String, FormatFiles, JoinHandle<Vec<ConversionReport>>)> = vec![];
         for list in self.category_lists.iter_mut() {
             if !self.is_processing.load(Ordering::SeqCst) {
                 return Ok(());
             }
             // do nothing if the list was already written on the last run
-            if self.cached_lists.as_ref().unwrap().contains(&list.name) && list.writer.is_none() {
-                info!("Unchanged: {}", list.name);
+            if list.format_writers.is_empty() {
+                self.observers
+                    .list_done(PipelineStage::Output, &list.name, Status::Unchanged);
                 continue;
             }
-            info!("Updated: {}", list.name);
+            self.observers.list_start(PipelineStage::Output, &list.name);
             let reader = Arc::clone(&list.reader.take().unwrap());
-            let writer = Arc::clone(&list.writer.take().unwrap());
-            let output_adapter =
-                self.config
-                    .output_format
-                    .get_adapter(reader, writer, self.is_processing.clone());
-            let handle = tokio::spawn(async move {
-                output_adapter.await;
-            });
-            handles.push(handle);
+            // the category is read once and written in all output formats
+            let writers: FormatFiles = list.format_writers.drain(..).collect();
+            let metadata = match self.config.output_banner {
+                true => {
+                    let entries = count_lines(&categorize_path.join(&list.name)).unwrap_or(0);
+                    Some(CategoryMetadata::new(self.config, &list.name, entries))
+                }
+                false => None,
+            };
+            let outputs: anyhow::Result<Vec<FormatWriter>> = writers
+                .iter()
+                .map(|(format, writer)| {
+                    let writer: Arc<Mutex<dyn Write + Send>> = writer.clone();
+                    let adapter = format.get_adapter(self.config, &list.name)?;
+                    let adapter = match &metadata {
+                        Some(m) => MetadataBanner::wrap(adapter, m.clone()),
+                        None => adapter,
+                    };
+                    Ok((adapter, writer))
+                })
+                .collect();
+            let outputs = match outputs {
+                Ok(o) => o,
+                Err(e) => {
+                    // nothing was written, the previous versions are kept
+                    for (_, writer) in writers.iter() {
+                        let mut writer = writer.lock().await;
+                        writer.discard();
+                        if let Some(m) = self.manifest.as_mut() {
+                            m.output_failed(writer.path());
+                        }
+                    }
+                    let code = ErrorCode::OutputFailed;
+                    self.observers.error(
+                        PipelineStage::Output,
+                        Some(&list.name),
+                        code,
+                        &format!("{e:?}"),
+                    );
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .category_failed(&list.name, code, format!("{e:#}"));
+                    continue;
+                }
+            };
+            let handle = tokio::spawn(write_outputs(
+                outputs,
+                list.name.clone(),
+                reader,
+                self.is_processing.clone(),
+            ));
+            handles.push((list.name.clone(), writers, handle));
+        }
+        for (name, writers, handle) in handles {
+            let reports = match handle.await {
+                Ok(r) => r,
+                Err(e) => {
+                    // the outputs are incomplete, the previous versions are kept
+                    for (_, writer) in writers.iter() {
+                        let mut writer = writer.lock().await;
+                        writer.discard();
+                        if let Some(m) = self.manifest.as_mut() {
+                            m.output_failed(writer.path());
+                        }
+                    }
+                    let code = ErrorCode::OutputFailed;
+                    self.observers
+                        .error(PipelineStage::Output, Some(&name), code, &e.to_string());
+                    self.report
+                        .lock()
+                        .unwrap()
+                        .category_failed(&name, code, e.to_string());
+                    continue;
+                }
+            };
+            // every output file is replaced independently so a failing directory
+            // doesn't keep the others from being updated
+            let mut failed = false;
+            for (report, (format, writer)) in reports.iter().zip(writers.iter()) {
+                report.log(&name, format);
+                let mut writer = writer.lock().await;
+                if !self.is_processing.load(Ordering::SeqCst) {
+                    writer.discard();
+                    if let Some(m) = self.manifest.as_mut() {
+                        m.output_failed(writer.path());
+                    }
+                    debug!(
+                        "{}: keeping previous version of {}",
+                        name,
+                        writer.path().display()
+                    );
+                } else if report.write_errors > 0 {
+                    writer.discard();
+                    if let Some(m) = self.manifest.as_mut() {
+                        m.output_failed(writer.path());
+                    }
+                    failed = true;
+                    let error = format!(
+                        "could not write {}, keeping previous version",
+                        writer.path().display()
+                    );
+                    let code = report
+                        .io_failure
+                        .map_or(ErrorCode::OutputFailed, From::from);
+                    self.observers
+                        .error(PipelineStage::Output, Some(&name), code, &error);
+                    let mut run_report = self.report.lock().unwrap();
+                    match report.io_failure {
+                        Some(failure) => run_report.category_io_failed(
+                            &name,
+                            failure,
+                            format!("{failure}: {error}"),
+                        ),
+                        None => run_report.category_failed(&name, code, error),
+                    }
+                } else if let Err(e) = writer.commit() {
+                    writer.discard();
+                    if let Some(m) = self.manifest.as_mut() {
+                        m.output_failed(writer.path());
+                    }
+                    failed = true;
+                    let failure = IoFailure::of(&e).filter(|f| f.is_fatal());
+                    let code = failure.map_or(ErrorCode::OutputFailed, ErrorCode::from);
+                    self.observers.error(
+                        PipelineStage::Output,
+                        Some(&name),
+                        code,
+                        &format!("{e:?}"),
+                    );
+                    let mut run_report = self.report.lock().unwrap();
+                    match failure {
+                        Some(failure) => {
+                            run_report.category_io_failed(&name, failure, format!("{failure}: {e}"))
+                        }
+                        None => run_report.category_failed(&name, code, e.to_string()),
+                    }
+                } else if let Some(m) = self.manifest.as_mut() {
+                    m.output_written(writer.path());
+                }
+            }
+            if !failed && self.is_processing.load(Ordering::SeqCst) {
+                self.observers
+                    .list_done(PipelineStage::Output, &name, Status::Updated);
+            }
         }
-        join_all(handles).await;
         Ok(())
     }
 }
@@ -97,13 +274,17 @@ impl<'config> FilterController<'config, StageOutput, FileInput, File> {
 mod tests {
 
     use std::{
-        collections::{HashMap, HashSet},
+        collections::{BTreeSet, HashMap, HashSet},
+        io::Read,
         marker::PhantomData,
         sync::{atomic::AtomicBool, Arc},
     };
 
     use crate::{
-        filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator,
+        config::{CategoryOptions, EntryOrder, ALL_CATEGORY},
+        filter_list::FilterList,
+        manifest::Manifest,
+        tests::helper::cache_file_creator::CacheFileCreator,
         CATEGORIZE_PATH,
     };
 
@@ -123,6 +304,7 @@ mod tests {
                 source: "".to_string(),
                 tags: vec!["advertising".to_string()],
                 regex: r"(.*)".to_string(),
+                ..Default::default()
             },
             FilterList {
                 id: "malware".to_string(),
@@ -131,6 +313,7 @@ mod tests {
                 source: "".to_string(),
                 tags: vec!["malware".to_string()],
                 regex: r"(.*)".to_string(),
+                ..Default::default()
             },
         ];
         // the contents of each filter list
@@ -169,6 +352,9 @@ mod tests {
             config: &config,
             filter_lists: vec![],
             category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
             is_processing: Arc::new(AtomicBool::new(true)),
         };
         if let Err(e) = output_controller.run(&cache.inpath).await {
@@ -194,4 +380,267 @@ mod tests {
             assert_eq!(want, &got);
         }
     }
+
+    #[tokio::test]
+    async fn test_output_all_category() {
+        let cache = CacheFileCreator::new("test_output_all_category", CATEGORIZE_PATH, "output");
+        let mut config = cache.new_test_config();
+        config.all_category = true;
+        config.lists = ["advertising", "malware", ALLOW_CATEGORY]
+            .iter()
+            .map(|tag| FilterList {
+                id: tag.to_string(),
+                tags: vec![tag.to_string()],
+                ..Default::default()
+            })
+            .collect();
+        config.categories.insert(
+            "malware".to_string(),
+            CategoryOptions {
+                order: EntryOrder::Insertion,
+                ..Default::default()
+            },
+        );
+        cache.write_input(
+            "advertising",
+            "ads.domain
+shared.domain
+tracker.domain
+",
+        );
+        cache.write_input(
+            "malware",
+            "shared.domain
+bad.domain
+",
+        );
+        cache.write_input(
+            ALLOW_CATEGORY,
+            "good.domain
+",
+        );
+
+        let mut output_controller = FilterController::<StageOutput, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::new()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        output_controller.run(&cache.inpath).await.unwrap();
+
+        assert_eq!(
+            cache.read_result(ALL_CATEGORY).unwrap(),
+            "0.0.0.0 ads.domain\n0.0.0.0 bad.domain\n0.0.0.0 shared.domain\n0.0.0.0 tracker.domain\n"
+        );
+        let report = output_controller.report.lock().unwrap();
+        assert_eq!(report.categories[ALL_CATEGORY].entries, 4);
+    }
+
+    #[tokio::test]
+    async fn test_output_multiple_formats() {
+        let cache =
+            CacheFileCreator::new("test_output_multiple_formats", CATEGORIZE_PATH, "output");
+        let mut config = cache.new_test_config();
+        config.output_format = vec![OutputType::Hostsfile, OutputType::Lua];
+        let list = |tag: &str| FilterList {
+            id: tag.to_string(),
+            tags: vec![tag.to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        };
+        config.lists = vec![list("malware"), list("ads")];
+        // the ads category is written in its own format only
+        config.categories.insert(
+            "ads".to_string(),
+            CategoryOptions {
+                output_format: vec![OutputType::Domains],
+                ..Default::default()
+            },
+        );
+        cache.write_input("malware", "domain.one\ndomain.two\n");
+        cache.write_input("ads", "ads.one\n");
+
+        let mut output_controller = FilterController::<StageOutput, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::new()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        if let Err(e) = output_controller.run(&cache.inpath).await {
+            error!("{}", e);
+        }
+
+        // every output format is written to its own subdirectory
+        let got = cache.read_result("hostsfile/malware").unwrap();
+        assert_eq!("0.0.0.0 domain.one\n0.0.0.0 domain.two\n", got);
+        let got = cache.read_result("lua/malware").unwrap();
+        assert_eq!("return {\n  \"domain.one\",\n  \"domain.two\",\n}", got);
+        assert_eq!(cache.read_result("domains/ads").unwrap(), "ads.one\n");
+        assert!(cache.read_result("hostsfile/ads").is_err());
+        assert!(cache.read_result("domains/malware").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_output_failed_retried() {
+        let cache = CacheFileCreator::new("test_output_failed_retried", CATEGORIZE_PATH, "output");
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "malware".to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        }];
+        cache.write_input("malware", "domain.one\n");
+        // a directory in place of the output file can't be replaced
+        let output_file = PathBuf::from(&config.output_dir[0]).join("malware");
+        std::fs::remove_file(&output_file).ok();
+        std::fs::create_dir_all(&output_file).unwrap();
+
+        let controller = |cached: &[&str], manifest: Manifest| FilterController::<
+            StageOutput,
+            FileInput,
+            File,
+        > {
+            stage: PhantomData,
+            cached_lists: Some(cached.iter().map(|c| c.to_string()).collect()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(manifest),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        let mut output_controller = controller(&[], Manifest::default());
+        output_controller.run(&cache.inpath).await.unwrap();
+        let manifest = output_controller.manifest.take().unwrap();
+        assert!(manifest.is_output_failed(&output_file));
+
+        // the unchanged category is written again on the next run
+        std::fs::remove_dir(&output_file).unwrap();
+        std::fs::write(&output_file, "0.0.0.0 stale.domain\n").unwrap();
+        let mut output_controller = controller(&["malware"], manifest);
+        output_controller.run(&cache.inpath).await.unwrap();
+        assert_eq!(
+            cache.read_result("malware").unwrap(),
+            "0.0.0.0 domain.one\n"
+        );
+        assert!(output_controller
+            .manifest
+            .unwrap()
+            .failed_outputs
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_output_failed_directory_retried() {
+        let cache = CacheFileCreator::new(
+            "test_output_failed_directory_retried",
+            CATEGORIZE_PATH,
+            "output",
+        );
+        let mut config = cache.new_test_config();
+        let failing_dir = PathBuf::from(&config.cache_dir).join("failing_output");
+        config
+            .output_dir
+            .push(failing_dir.to_string_lossy().to_string());
+        config.lists = vec![FilterList {
+            id: "malware".to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        }];
+        cache.write_input("malware", "domain.one\n");
+        // a file in place of the second output directory can't be written to
+        std::fs::remove_dir_all(&failing_dir).ok();
+        std::fs::write(&failing_dir, "").unwrap();
+
+        let controller = |cached: &[&str], manifest: Manifest| FilterController::<
+            StageOutput,
+            FileInput,
+            File,
+        > {
+            stage: PhantomData,
+            cached_lists: Some(cached.iter().map(|c| c.to_string()).collect()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(manifest),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        let mut output_controller = controller(&[], Manifest::default());
+        output_controller.run(&cache.inpath).await.unwrap();
+        let manifest = output_controller.manifest.take().unwrap();
+        assert_eq!(
+            cache.read_result("malware").unwrap(),
+            "0.0.0.0 domain.one\n"
+        );
+        assert_eq!(
+            manifest.failed_outputs,
+            BTreeSet::from([failing_dir.join("malware").to_string_lossy().to_string()])
+        );
+
+        // the directory holding an outdated version is written again on the next run
+        std::fs::remove_file(&failing_dir).unwrap();
+        std::fs::create_dir_all(&failing_dir).unwrap();
+        std::fs::write(failing_dir.join("malware"), "0.0.0.0 stale.domain\n").unwrap();
+        let mut output_controller = controller(&["malware"], manifest);
+        output_controller.run(&cache.inpath).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(failing_dir.join("malware")).unwrap(),
+            "0.0.0.0 domain.one\n"
+        );
+        assert!(output_controller
+            .manifest
+            .unwrap()
+            .failed_outputs
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_output_gzip() {
+        let cache = CacheFileCreator::new("test_output_gzip", CATEGORIZE_PATH, "output");
+        let mut config = cache.new_test_config();
+        config.gzip_output = true;
+        config.lists = vec![FilterList {
+            id: "malware".to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"(.*)".to_string(),
+            ..Default::default()
+        }];
+        cache.write_input("malware", "domain.one\ndomain.two\n");
+
+        let mut output_controller = FilterController::<StageOutput, FileInput, File> {
+            stage: PhantomData,
+            cached_lists: Some(HashSet::new()),
+            config: &config,
+            filter_lists: vec![],
+            category_lists: vec![],
+            manifest: Some(Manifest::default()),
+            report: Default::default(),
+            observers: Default::default(),
+            is_processing: Arc::new(AtomicBool::new(true)),
+        };
+        output_controller.run(&cache.inpath).await.unwrap();
+
+        let path = PathBuf::from(&config.output_dir[0]).join("malware.gz");
+        let mut got = String::new();
+        flate2::read::GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut got)
+            .unwrap();
+        assert_eq!(got, "0.0.0.0 domain.one\n0.0.0.0 domain.two\n");
+        assert!(cache.read_result("malware").is_err());
+    }
 }
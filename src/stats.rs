@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+    sync::Arc,
+};
+
+use futures::future::join_all;
+
+use crate::{
+    config::{Config, RegexOptions},
+    filter_controller::{ChunkContext, SkipReason, Transformed},
+    filter_list::FilterList,
+    input::Input,
+    log_throttle,
+    stages::extract::regex_match,
+    verify::{list_input, sample},
+};
+
+/// the number of characters of a line pattern unmatched lines are clustered by
+const PATTERN_LEN: usize = 40;
+
+/// Cluster groups the lines of a list which weren't extracted by their pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    pub pattern: String,
+    pub lines: usize,
+    /// the first line of the cluster
+    pub example: String,
+}
+
+/// ListStats tells how a list's regex performs on the list's cached download
+#[derive(Debug, Default)]
+pub struct ListStats {
+    pub id: String,
+    pub lines: usize,
+    pub matched: usize,
+    pub skip_reasons: BTreeMap<SkipReason, usize>,
+    /// the lines the extract stage would fail the list on
+    pub errors: usize,
+    pub first_error: Option<String>,
+    /// the largest clusters of lines which weren't extracted, largest first
+    pub unmatched: Vec<Cluster>,
+    /// entries spread evenly over all extracted entries
+    pub captures: Vec<String>,
+}
+
+impl ListStats {
+    /// returns the percentage of lines an entry was extracted from
+    pub fn match_rate(&self) -> f64 {
+        match self.lines {
+            0 => 0.0,
+            lines => self.matched as f64 * 100.0 / lines as f64,
+        }
+    }
+
+    /// returns a single line summary of the list
+    pub fn summary(&self) -> String {
+        format!(
+            "{}: {} of {} lines matched ({:.1}%)",
+            self.id,
+            self.matched,
+            self.lines,
+            self.match_rate()
+        )
+    }
+}
+
+impl Display for ListStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        for (reason, lines) in self.skip_reasons.iter() {
+            writeln!(f, "  skipped, {reason}: {lines}")?;
+        }
+        if let Some(error) = &self.first_error {
+            writeln!(f, "  errors: {} (first: {})", self.errors, error)?;
+        }
+        if !self.unmatched.is_empty() {
+            writeln!(f, "top unmatched line patterns:")?;
+            for cluster in self.unmatched.iter() {
+                writeln!(
+                    f,
+                    "  {:>8}  {:<width$}  {:?}",
+                    cluster.lines,
+                    cluster.pattern,
+                    cluster.example,
+                    width = PATTERN_LEN + 1
+                )?;
+            }
+        }
+        if !self.captures.is_empty() {
+            writeln!(f, "sample captures:")?;
+            for capture in self.captures.iter() {
+                writeln!(f, "  {capture}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the pattern unmatched lines are clustered by. Runs of letters become `a`,
+/// runs of digits `0` and runs of whitespace a single space, other characters are
+/// kept.
+///
+/// * `line`: the unmatched line
+fn line_pattern(line: &str) -> String {
+    let mut pattern = String::new();
+    let mut last = None;
+    for c in line.trim().chars() {
+        let class = match c {
+            c if c.is_alphabetic() => 'a',
+            c if c.is_numeric() => '0',
+            c if c.is_whitespace() => ' ',
+            c => c,
+        };
+        if last == Some(class) && matches!(class, 'a' | '0' | ' ') {
+            continue;
+        }
+        last = Some(class);
+        pattern.push(class);
+    }
+    if pattern.chars().count() > PATTERN_LEN {
+        pattern = pattern.chars().take(PATTERN_LEN).collect();
+        pattern.push('…');
+    }
+    pattern
+}
+
+/// Extracts the entries of a list from its cached download without writing anything
+/// and collects how the lines fared
+///
+/// * `cache_dir`: the cache directory
+/// * `list`: the analysed list
+/// * `regex_options`: the configured regex limits
+/// * `top`: the number of unmatched line clusters and captures returned
+pub async fn list_stats(
+    cache_dir: &Path,
+    list: Arc<FilterList>,
+    regex_options: &RegexOptions,
+    top: usize,
+) -> anyhow::Result<ListStats> {
+    let mut input = list_input(cache_dir, &list);
+    let mut stats = ListStats {
+        id: list.id.clone(),
+        ..Default::default()
+    };
+    let mut entries = vec![];
+    // the number of lines and the first line per pattern
+    let mut clusters: HashMap<String, (usize, String)> = HashMap::new();
+    let mut context = ChunkContext::new(list, regex_options.clone());
+    // the context is advanced before each line
+    context.line = 0;
+    while let Some(chunk) = input.chunk().await? {
+        let line = String::from_utf8_lossy(&chunk).trim_end().to_string();
+        context.line += 1;
+        match regex_match(context.clone(), chunk).await {
+            Ok(Transformed::Write(entry)) => {
+                entries.push(String::from_utf8_lossy(&entry).trim_end().to_string());
+                context.lines_matched += 1;
+                continue;
+            }
+            Ok(Transformed::Skip(reason)) => {
+                *stats.skip_reasons.entry(reason).or_default() += 1;
+            }
+            Err(e) => {
+                stats.errors += 1;
+                stats.first_error.get_or_insert_with(|| format!("{e:#}"));
+            }
+        }
+        let cluster = clusters
+            .entry(line_pattern(&line))
+            .or_insert_with(|| (0, line));
+        cluster.0 += 1;
+        context.lines_skipped += 1;
+    }
+    log_throttle::summarize(&stats.id);
+    stats.lines = context.line;
+    stats.matched = context.lines_matched;
+
+    let mut unmatched: Vec<Cluster> = clusters
+        .into_iter()
+        .map(|(pattern, (lines, example))| Cluster {
+            pattern,
+            lines,
+            example,
+        })
+        .collect();
+    unmatched.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.pattern.cmp(&b.pattern)));
+    unmatched.truncate(top);
+    stats.unmatched = unmatched;
+    stats.captures = sample(&entries, top).into_iter().cloned().collect();
+    Ok(stats)
+}
+
+/// Collects the statistics of all lists or of a single one, the lists with the
+/// lowest match rate first
+///
+/// * `config`: the configuration the lists are defined in
+/// * `id`: the id of the single analysed list
+/// * `top`: the number of unmatched line clusters and captures returned per list
+pub async fn stats(
+    config: &Config,
+    id: Option<&str>,
+    top: usize,
+) -> anyhow::Result<Vec<anyhow::Result<ListStats>>> {
+    let lists: Vec<&FilterList> = config
+        .lists
+        .iter()
+        .filter(|l| id.is_none_or(|id| l.id == id))
+        .collect();
+    if let Some(id) = id
+        && lists.is_empty()
+    {
+        return Err(anyhow::anyhow!("there is no list {id}"));
+    }
+    let cache_dir = Path::new(&config.cache_dir);
+    let mut results = join_all(lists.into_iter().map(|list| async move {
+        list_stats(
+            cache_dir,
+            Arc::new(list.clone()),
+            &config.regex_options,
+            top,
+        )
+        .await
+        .map_err(|e| e.context(format!("{}: could not analyse the list", list.id)))
+    }))
+    .await;
+    results.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => a.match_rate().total_cmp(&b.match_rate()),
+        (a, b) => b.is_ok().cmp(&a.is_ok()),
+    });
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{tests::helper::cache_file_creator::CacheFileCreator, DOWNLOAD_PATH};
+
+    use super::*;
+
+    #[test]
+    fn test_line_pattern() {
+        assert_eq!(line_pattern("0.0.0.0 malicious.com"), "0.0.0.0 a.a");
+        assert_eq!(line_pattern("# Updated:   2023-01-31\n"), "# a: 0-0-0");
+        assert_eq!(line_pattern("||ads.example^$third-party"), "||a.a^$a-a");
+        assert_eq!(
+            line_pattern(&"a.".repeat(30)).chars().count(),
+            PATTERN_LEN + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let cache = CacheFileCreator::new("test_stats", "in", "out");
+        let mut config = cache.new_test_config();
+        let list = |id: &str| FilterList {
+            id: id.to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"^0\.0\.0\.0 ([^\s#]+)".to_string(),
+            ..Default::default()
+        };
+        config.lists = vec![list("one"), list("two"), list("missing")];
+        let download_dir = Path::new(&config.cache_dir).join(DOWNLOAD_PATH);
+        fs::create_dir_all(&download_dir).unwrap();
+        fs::write(
+            download_dir.join("one"),
+            "# list\n# version 2\n0.0.0.0 malicious.com\n0.0.0.0 unwanted.net\n0.0.0.0 ..\n",
+        )
+        .unwrap();
+        fs::write(
+            download_dir.join("two"),
+            "malicious.com\n0.0.0.0 unwanted.net\n",
+        )
+        .unwrap();
+
+        let stats = list_stats(
+            Path::new(&config.cache_dir),
+            Arc::new(list("one")),
+            &config.regex_options,
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.lines, 5);
+        assert_eq!(stats.matched, 2);
+        assert_eq!(stats.skip_reasons[&SkipReason::NoMatch], 2);
+        assert_eq!(stats.skip_reasons[&SkipReason::InvalidEntry], 1);
+        assert_eq!(
+            stats.unmatched,
+            vec![Cluster {
+                pattern: "# a".to_string(),
+                lines: 1,
+                example: "# list".to_string(),
+            }]
+        );
+        assert_eq!(stats.captures, vec!["malicious.com"]);
+        assert_eq!(stats.summary(), "one: 2 of 5 lines matched (40.0%)");
+
+        let all = super::stats(&config, None, 10).await.unwrap();
+        let ids: Vec<_> = all
+            .iter()
+            .map(|s| s.as_ref().map(|s| s.id.as_str()).unwrap_or("error"))
+            .collect();
+        assert_eq!(ids, vec!["one", "two", "error"]);
+        assert!(super::stats(&config, Some("none"), 10).await.is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::{os::unix::net::UnixDatagram, process};
+
+use anyhow::Context;
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::report::{RunReport, Status};
+
+/// the facility of the messages sent to syslog, daemon
+const FACILITY: u8 = 3;
+
+/// the id of the structured data element in syslog messages, 32473 is the enterprise
+/// number reserved for examples and documentation
+const SD_ID: &str = "harvester@32473";
+
+/// SyslogTarget is the kind of log daemon the category summaries are sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SyslogTarget {
+    /// RFC 5424 messages with the fields as structured data
+    Syslog,
+    /// journald's native protocol with the fields as journal fields
+    Journald,
+}
+
+impl SyslogTarget {
+    /// the socket the daemon receives messages on
+    fn default_socket(&self) -> &'static str {
+        match self {
+            SyslogTarget::Syslog => "/dev/log",
+            SyslogTarget::Journald => "/run/systemd/journal/socket",
+        }
+    }
+}
+
+/// SyslogConfig configures the log daemon a summary of every category is sent to
+/// after each run
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyslogConfig {
+    pub target: SyslogTarget,
+    /// the daemon's socket if it's not at the default location
+    pub socket: Option<String>,
+    /// the name the messages are logged under
+    #[serde(default = "default_identifier")]
+    pub identifier: String,
+}
+
+fn default_identifier() -> String {
+    "harvester".to_string()
+}
+
+/// Summary describes the changes of a single category
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// syslog severity of the message
+    pub severity: u8,
+    pub message: String,
+    /// the summary's fields by lowercase name
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl Summary {
+    /// formats the summary as RFC 5424 message
+    ///
+    /// * `identifier`: the name the message is logged under
+    fn to_syslog(&self, identifier: &str) -> String {
+        let params: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, value)| {
+                let value = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace(']', "\\]");
+                format!("{name}=\"{value}\"")
+            })
+            .collect();
+        format!(
+            "<{}>1 {} - {} {} - [{} {}] {}",
+            FACILITY * 8 + self.severity,
+            Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+            identifier,
+            process::id(),
+            SD_ID,
+            params.join(" "),
+            self.message
+        )
+    }
+
+    /// formats the summary as message of journald's native protocol
+    ///
+    /// * `identifier`: the name the message is logged under
+    fn to_journald(&self, identifier: &str) -> String {
+        let mut message = format!(
+            "PRIORITY={}\nSYSLOG_IDENTIFIER={}\nMESSAGE={}\n",
+            self.severity, identifier, self.message
+        );
+        for (name, value) in self.fields.iter() {
+            // values containing line breaks would require the binary format
+            let value = value.replace('\n', " ");
+            message.push_str(&format!("HARVESTER_{}={}\n", name.to_uppercase(), value));
+        }
+        message
+    }
+}
+
+/// returns the summaries of the categories written or kept by the run followed by
+/// warnings about the lists whose format probably changed
+///
+/// * `report`: the report collected during the run
+/// * `run_id`: the id of the run
+pub fn summaries(report: &RunReport, run_id: &str) -> Vec<Summary> {
+    let format_changes = report.lists.iter().filter_map(|(id, list)| {
+        let (previous, rate) = list.match_rate_drop?;
+        Some(Summary {
+            severity: 4,
+            message: format!(
+                "list {} matched {:.1}% of its lines instead of {:.1}%, its format probably changed",
+                id,
+                rate * 100.0,
+                previous * 100.0
+            ),
+            fields: vec![
+                ("run", run_id.to_string()),
+                ("list", id.clone()),
+                ("match_rate", format!("{rate:.3}")),
+                ("previous_match_rate", format!("{previous:.3}")),
+            ],
+        })
+    });
+    report
+        .categories
+        .iter()
+        .map(|(name, category)| {
+            let status = format!("{:?}", category.status).to_lowercase();
+            let (severity, message) = match category.status {
+                Status::Failed => (
+                    4,
+                    format!("category {name} failed, kept its previous version"),
+                ),
+                _ => (
+                    5,
+                    format!(
+                        "category {} {}: {} entries, {} added, {} removed",
+                        name, status, category.entries, category.added, category.removed
+                    ),
+                ),
+            };
+            Summary {
+                severity,
+                message,
+                fields: vec![
+                    ("run", run_id.to_string()),
+                    ("category", name.clone()),
+                    ("status", status),
+                    ("entries", category.entries.to_string()),
+                    ("added", category.added.to_string()),
+                    ("removed", category.removed.to_string()),
+                ],
+            }
+        })
+        .chain(format_changes)
+        .collect()
+}
+
+/// Sends a summary of every category to the configured log daemon. Returns the
+/// number of messages sent.
+///
+/// * `config`: the log daemon's settings
+/// * `report`: the report collected during the run
+/// * `run_id`: the id of the run
+pub fn send(config: &SyslogConfig, report: &RunReport, run_id: &str) -> anyhow::Result<usize> {
+    let socket_path = config
+        .socket
+        .as_deref()
+        .unwrap_or(config.target.default_socket());
+    let socket = UnixDatagram::unbound()?;
+    socket
+        .connect(socket_path)
+        .with_context(|| format!("could not connect to {socket_path}"))?;
+    let summaries = summaries(report, run_id);
+    for summary in summaries.iter() {
+        let message = match config.target {
+            SyslogTarget::Syslog => summary.to_syslog(&config.identifier),
+            SyslogTarget::Journald => summary.to_journald(&config.identifier),
+        };
+        socket
+            .send(message.as_bytes())
+            .with_context(|| format!("could not send to {socket_path}"))?;
+    }
+    Ok(summaries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::tests::helper::cache_file_creator::CacheFileCreator;
+
+    use super::*;
+
+    #[test]
+    fn test_send_syslog() {
+        let mut report = RunReport::default();
+        let previous = HashSet::from(["old.domain".to_string()]);
+        let entries = ["new.domain".to_string(), "kept.domain".to_string()];
+        report.category_updated("malware", entries.iter(), &previous);
+
+        let cache = CacheFileCreator::new("test_send_syslog", "in", "out");
+        let socket_path = std::path::Path::new(&cache.new_test_config().cache_dir).join("log");
+        std::fs::remove_file(&socket_path).ok();
+        let daemon = UnixDatagram::bind(&socket_path).unwrap();
+        let mut buf = [0; 1024];
+
+        let mut config = SyslogConfig {
+            target: SyslogTarget::Journald,
+            socket: Some(socket_path.to_string_lossy().to_string()),
+            identifier: default_identifier(),
+        };
+        assert_eq!(send(&config, &report, "run-1").unwrap(), 1);
+        let len = daemon.recv(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf[..len]),
+            "PRIORITY=5\nSYSLOG_IDENTIFIER=harvester\n\
+             MESSAGE=category malware updated: 2 entries, 2 added, 1 removed\n\
+             HARVESTER_RUN=run-1\nHARVESTER_CATEGORY=malware\nHARVESTER_STATUS=updated\n\
+             HARVESTER_ENTRIES=2\nHARVESTER_ADDED=2\nHARVESTER_REMOVED=1\n"
+        );
+
+        config.target = SyslogTarget::Syslog;
+        send(&config, &report, "run-1").unwrap();
+        let len = daemon.recv(&mut buf).unwrap();
+        let message = String::from_utf8_lossy(&buf[..len]).to_string();
+        assert!(message.starts_with("<29>1 "));
+        assert!(message.ends_with(
+            " - [harvester@32473 run=\"run-1\" category=\"malware\" status=\"updated\" \
+             entries=\"2\" added=\"2\" removed=\"1\"] \
+             category malware updated: 2 entries, 2 added, 1 removed"
+        ));
+
+        report.list_match_rate_dropped("one", 0.9, 0.05);
+        let summaries = summaries(&report, "run-1");
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[1].severity, 4);
+        assert_eq!(
+            summaries[1].message,
+            "list one matched 5.0% of its lines instead of 90.0%, its format probably changed"
+        );
+    }
+}
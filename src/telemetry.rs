@@ -0,0 +1,115 @@
+use std::{env, time::Duration};
+
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+
+use crate::{
+    config::{Config, ALLOW_CATEGORY, TOP_SITES_ID},
+    output::OutputType,
+};
+
+/// the url the usage statistics are sent to, embedded at build time. Builds without
+/// it don't send anything even if telemetry is enabled.
+const TELEMETRY_URL: Option<&str> = option_env!("HARVESTER_TELEMETRY_URL");
+
+/// Payload is everything sent by the usage statistics ping. It contains counts and
+/// names of built-in formats only, no list sources, ids, categories or entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Payload {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// number of configured lists
+    pub lists: usize,
+    /// number of categories written
+    pub categories: usize,
+    /// the configured output formats
+    pub output_formats: Vec<OutputType>,
+}
+
+impl Payload {
+    /// collects the usage statistics of a configuration
+    ///
+    /// * `config`: the configuration being processed
+    pub fn new(config: &Config) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            os: env::consts::OS,
+            arch: env::consts::ARCH,
+            lists: config.lists.iter().filter(|l| l.id != TOP_SITES_ID).count(),
+            categories: config
+                .get_tags()
+                .iter()
+                .filter(|t| *t != ALLOW_CATEGORY)
+                .count(),
+            output_formats: config.output_formats(),
+        }
+    }
+}
+
+/// Sends the usage statistics of the run if the user opted in. Failures are only
+/// logged, telemetry never affects the run.
+///
+/// * `config`: the configuration being processed
+pub async fn send(config: &Config) {
+    if !config.telemetry {
+        return;
+    }
+    let url = match TELEMETRY_URL {
+        Some(u) => u,
+        None => {
+            debug!("this build doesn't contain a telemetry url, nothing sent");
+            return;
+        }
+    };
+    let payload = Payload::new(config);
+    debug!("sending usage statistics {:?}", payload);
+    let result = async {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()?
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(&payload)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+    if let Err(e) = result {
+        debug!("could not send usage statistics: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator};
+
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let cache = CacheFileCreator::new("test_payload", "in", "out");
+        let mut config = cache.new_test_config();
+        let list = |id: &str, tags: &[&str]| FilterList {
+            id: id.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        config.lists = vec![
+            list("one", &["ads", "tracking"]),
+            list("two", &["ads"]),
+            list(TOP_SITES_ID, &[ALLOW_CATEGORY]),
+        ];
+        config.output_format = vec![OutputType::Hostsfile, OutputType::Json];
+
+        let payload = Payload::new(&config);
+        assert_eq!(payload.lists, 2);
+        assert_eq!(payload.categories, 2);
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap()["output_formats"],
+            serde_json::json!(["Hostsfile", "Json"])
+        );
+    }
+}
@@ -59,11 +59,53 @@ impl CacheFileCreator {
                 .to_str()
                 .unwrap()
                 .to_string(),
-            output_dir: namespace_path(&self.namespace, Some("output"))
+            output_dir: vec![namespace_path(&self.namespace, Some("output"))
                 .to_str()
                 .unwrap()
-                .to_string(),
-            output_format: crate::output::OutputType::Hostsfile,
+                .to_string()],
+            output_format: vec![crate::output::OutputType::Hostsfile],
+            gzip_output: false,
+            output_banner: false,
+            hosts_ip: None,
+            hosts_ipv6: None,
+            top_sites: None,
+            categories: Default::default(),
+            report: None,
+            default_tags: vec![],
+            defaults: Default::default(),
+            groups: Default::default(),
+            offline: false,
+            resume: false,
+            shutdown: Default::default(),
+            tuning: Default::default(),
+            review: false,
+            run_id: String::new(),
+            regex_options: Default::default(),
+            disk: Default::default(),
+            archive: None,
+            cache_backend: None,
+            audit_log: None,
+            geoip: None,
+            suricata: None,
+            json: None,
+            coredns: None,
+            blocky: None,
+            bloom: None,
+            wildcard: None,
+            template: None,
+            sqlite: None,
+            all_category: false,
+            palette: Default::default(),
+            telemetry: false,
+            crowdsec: None,
+            syslog: None,
+            dns: None,
+            absolute_max_entries: None,
+            match_rate_drop: None,
+            max_parallel_downloads: None,
+            download_chunk_size: None,
+            max_parallel_extracts: None,
+            harvester_version: None,
             cached_config: None,
         }
     }
@@ -0,0 +1,93 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// ListServer serves filter lists over HTTP the way list providers do. Every list
+/// is answered with an ETag, requests carrying the list's current ETag are answered
+/// with 304 Not Modified. The lists can be changed while the server is running.
+pub struct ListServer {
+    pub address: String,
+    lists: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl ListServer {
+    /// starts the server on a free port of the loopback interface
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let lists: Arc<Mutex<HashMap<String, String>>> = Arc::default();
+        let served = lists.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(len) => request.extend_from_slice(&buf[..len]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+                let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+                let body = served.lock().unwrap().get(&path).cloned();
+                stream.write_all(respond(&request, body).as_bytes()).ok();
+            }
+        });
+        Self { address, lists }
+    }
+
+    /// serves the list's contents under the path from now on
+    ///
+    /// * `path`: the path of the list's url, e.g. `/list`
+    /// * `contents`: the list's contents
+    pub fn set_list(&self, path: &str, contents: &str) {
+        self.lists
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), contents.to_string());
+    }
+
+    /// returns the url of the list served under the path
+    ///
+    /// * `path`: the path of the list's url, e.g. `/list`
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.address, path)
+    }
+}
+
+/// returns the raw response to the lowercased request
+///
+/// * `request`: the request's header section
+/// * `body`: the contents of the requested list if there is one
+fn respond(request: &str, body: Option<String>) -> String {
+    let body = match body {
+        Some(b) => b,
+        None => {
+            return "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                .to_string()
+        }
+    };
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:x}\"", hasher.finish());
+    if request.contains(&format!("if-none-match: {etag}")) {
+        return format!(
+            "HTTP/1.1 304 Not Modified\r\netag: {etag}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+        );
+    }
+    format!(
+        "HTTP/1.1 200 OK\r\netag: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        etag,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    input::{file::Compression, Input},
+    io::storage::{ListReader, ListWriter},
+};
+
+/// the lists kept in memory by their path
+static LISTS: OnceLock<Mutex<HashMap<PathBuf, Vec<u8>>>> = OnceLock::new();
+
+fn lists() -> std::sync::MutexGuard<'static, HashMap<PathBuf, Vec<u8>>> {
+    LISTS.get_or_init(Default::default).lock().unwrap()
+}
+
+/// stores a list in memory
+///
+/// * `path`: the list's path
+/// * `content`: the list's content
+pub fn put(path: &Path, content: &str) {
+    lists().insert(path.to_path_buf(), content.as_bytes().to_vec());
+}
+
+/// returns the list stored in memory at the path
+///
+/// * `path`: the list's path
+pub fn get(path: &Path) -> Option<String> {
+    lists()
+        .get(path)
+        .map(|l| String::from_utf8_lossy(l).to_string())
+}
+
+/// MemoryInput reads a list kept in memory line by line
+#[derive(Debug)]
+pub struct MemoryInput {
+    path: PathBuf,
+    /// the lines not read yet, taken from the list on the first read
+    lines: Option<Vec<Vec<u8>>>,
+}
+
+#[async_trait]
+impl Input for MemoryInput {
+    async fn chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        if self.lines.is_none() {
+            let content = lists().get(&self.path).cloned().unwrap_or_default();
+            let mut lines: Vec<Vec<u8>> = content
+                .split_inclusive(|b| *b == b'\n')
+                .map(|l| l.to_vec())
+                .collect();
+            lines.reverse();
+            self.lines = Some(lines);
+        }
+        Ok(self.lines.as_mut().unwrap().pop())
+    }
+
+    async fn reset(&mut self) -> anyhow::Result<()> {
+        self.lines = None;
+        Ok(())
+    }
+
+    async fn len(&mut self) -> anyhow::Result<u64> {
+        Self::stored_len(&self.path)
+    }
+}
+
+impl ListReader for MemoryInput {
+    fn open(
+        path: &Path,
+        _compression: Option<Compression>,
+        _max_line_length: usize,
+    ) -> anyhow::Result<Self> {
+        Self::stored_len(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            lines: None,
+        })
+    }
+
+    fn stored_len(path: &Path) -> anyhow::Result<u64> {
+        lists()
+            .get(path)
+            .map(|l| l.len() as u64)
+            .ok_or_else(|| anyhow::anyhow!("list not found: {}", path.display()))
+    }
+}
+
+/// MemoryList writes a list kept in memory
+#[derive(Debug)]
+pub struct MemoryList {
+    path: PathBuf,
+}
+
+impl Write for MemoryList {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        lists()
+            .entry(self.path.clone())
+            .or_default()
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ListWriter for MemoryList {
+    fn create(path: &Path) -> anyhow::Result<Self> {
+        lists().insert(path.to_path_buf(), vec![]);
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        MemoryInput::stored_len(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn written_len(&self) -> anyhow::Result<u64> {
+        MemoryInput::stored_len(&self.path)
+    }
+
+    fn read(path: &Path) -> anyhow::Result<String> {
+        get(path).ok_or_else(|| anyhow::anyhow!("list not found: {}", path.display()))
+    }
+
+    fn count_lines(path: &Path) -> anyhow::Result<usize> {
+        Ok(Self::read(path)?.matches('\n').count())
+    }
+
+    fn remove(path: &Path) {
+        lists().remove(path);
+    }
+}
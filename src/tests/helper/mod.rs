@@ -2,3 +2,7 @@
 pub mod cache_file_creator;
 #[cfg(test)]
 pub mod cursor_input;
+#[cfg(test)]
+pub mod list_server;
+#[cfg(test)]
+pub mod memory_storage;
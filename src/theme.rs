@@ -0,0 +1,88 @@
+use std::{env, io::IsTerminal, sync::OnceLock};
+
+use clap::ValueEnum;
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+
+/// the palette of the configuration, the default palette is used until it's loaded
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// ColorChoice determines whether the terminal output is colored
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// color the output of terminals unless NO_COLOR is set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// returns true if the output is colored
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::io::stderr().is_terminal()
+            }
+        }
+    }
+
+    /// returns the write style of the log messages
+    pub fn log_style(self) -> &'static str {
+        if self.enabled() {
+            "always"
+        } else {
+            "never"
+        }
+    }
+
+    /// enables or disables the colors of the messages for the rest of the run
+    pub fn apply(self) {
+        colored::control::set_override(self.enabled());
+    }
+}
+
+/// Palette holds the colors of the terminal output. The defaults are distinguishable
+/// with the common forms of color blindness and readable on light and dark terminals.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Palette {
+    /// color of the banners announcing the stages, e.g. "blue" or "bright cyan"
+    #[serde(default = "default_banner")]
+    pub banner: String,
+}
+
+fn default_banner() -> String {
+    "blue".to_string()
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            banner: default_banner(),
+        }
+    }
+}
+
+impl Palette {
+    /// makes the palette the one used for the rest of the run, only the first call
+    /// takes effect
+    pub fn apply(self) {
+        if self.banner.parse::<Color>().is_err() {
+            warn!("unknown banner color {}, using the default", self.banner);
+        }
+        let _ = PALETTE.set(self);
+    }
+}
+
+/// returns a banner announcing what harvester does next in the palette's color
+///
+/// * `text`: the banner's text
+pub fn banner(text: &str) -> ColoredString {
+    let color = PALETTE
+        .get()
+        .and_then(|p| p.banner.parse::<Color>().ok())
+        .unwrap_or(Color::Blue);
+    text.color(color).bold()
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+#[cfg(feature = "lua")]
+use std::sync::Mutex;
+
+use anyhow::Context;
+#[cfg(feature = "lua")]
+use mlua::{Function, Lua, Value};
+
+/// name of the function a transform script has to define
+#[cfg(feature = "lua")]
+const TRANSFORM_FUNCTION: &str = "transform";
+
+/// loaded transform scripts by their path, lists sharing a script share its state
+static TRANSFORMS: OnceLock<RwLock<HashMap<String, Arc<LuaTransform>>>> = OnceLock::new();
+
+/// LuaTransform runs a user supplied Lua script on single entries. The script defines
+/// a function `transform(entry, name)` receiving the entry and the id of the list or
+/// the name of the category. It returns the rewritten entry or nil to drop the entry.
+pub struct LuaTransform {
+    path: String,
+    #[cfg(feature = "lua")]
+    lua: Mutex<Lua>,
+}
+
+impl std::fmt::Debug for LuaTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LuaTransform")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl LuaTransform {
+    /// Loads a script and checks that it defines the transform function
+    ///
+    /// * `path`: file system path of the Lua script
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let script = fs::read_to_string(path)
+            .with_context(|| format!("could not read transform script {path}"))?;
+        Self::from_source(path, &script)
+    }
+
+    #[cfg(not(feature = "lua"))]
+    fn from_source(path: &str, _script: &str) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!(
+            "{}: harvester was built without the lua feature",
+            path
+        ))
+    }
+
+    #[cfg(feature = "lua")]
+    fn from_source(path: &str, script: &str) -> anyhow::Result<Self> {
+        let lua = Lua::new();
+        lua.load(script)
+            .set_name(path)
+            .exec()
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        lua.globals()
+            .get::<_, Function>(TRANSFORM_FUNCTION)
+            .map_err(|_| {
+                anyhow::anyhow!("{}: function {} not defined", path, TRANSFORM_FUNCTION)
+            })?;
+        Ok(Self {
+            path: path.to_string(),
+            lua: Mutex::new(lua),
+        })
+    }
+
+    /// Applies the script to a single entry
+    ///
+    /// * `entry`: the entry to be transformed
+    /// * `name`: the id of the list or the name of the category the entry belongs to
+    #[cfg(not(feature = "lua"))]
+    pub fn apply(&self, _entry: &str, _name: &str) -> anyhow::Result<Option<String>> {
+        Err(anyhow::anyhow!(
+            "{}: harvester was built without the lua feature",
+            self.path
+        ))
+    }
+
+    /// Applies the script to a single entry
+    ///
+    /// * `entry`: the entry to be transformed
+    /// * `name`: the id of the list or the name of the category the entry belongs to
+    #[cfg(feature = "lua")]
+    pub fn apply(&self, entry: &str, name: &str) -> anyhow::Result<Option<String>> {
+        let lua = self.lua.lock().unwrap();
+        let function: Function = lua
+            .globals()
+            .get(TRANSFORM_FUNCTION)
+            .map_err(|e| anyhow::anyhow!("{}: {}", self.path, e))?;
+        let result: Value = function
+            .call((entry, name))
+            .map_err(|e| anyhow::anyhow!("{}: {}", self.path, e))?;
+        match result {
+            Value::Nil => Ok(None),
+            Value::String(s) => Ok(Some(s.to_str()?.to_string())),
+            v => Err(anyhow::anyhow!(
+                "{}: transform returned {} instead of a string",
+                self.path,
+                v.type_name()
+            )),
+        }
+    }
+}
+
+/// Returns the transform loaded from the given script, loading it on first use
+///
+/// * `path`: file system path of the Lua script
+pub fn get_transform(path: &str) -> anyhow::Result<Arc<LuaTransform>> {
+    let transforms = TRANSFORMS.get_or_init(Default::default);
+    if let Some(t) = transforms.read().unwrap().get(path) {
+        return Ok(t.clone());
+    }
+    let transform = Arc::new(LuaTransform::load(path)?);
+    transforms
+        .write()
+        .unwrap()
+        .insert(path.to_string(), transform.clone());
+    Ok(transform)
+}
+
+#[cfg(all(test, feature = "lua"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lua_transform() {
+        let script = r#"
+            function transform(entry, name)
+              if entry:find("^ads%.") then
+                return nil
+              end
+              return entry:gsub("^www%.", "") .. ""
+            end
+        "#;
+        let transform = LuaTransform::from_source("test.lua", script).unwrap();
+        assert_eq!(
+            transform.apply("www.domain.one", "list").unwrap(),
+            Some("domain.one".to_string())
+        );
+        assert_eq!(transform.apply("ads.domain.one", "list").unwrap(), None);
+
+        assert!(LuaTransform::from_source("empty.lua", "x = 1").is_err());
+        let invalid = LuaTransform::from_source("num.lua", "function transform() return 1 end");
+        assert!(invalid.unwrap().apply("domain.one", "list").is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+
+/// machines with less memory run with small buffers and limited concurrency
+const LOW_MEMORY: u64 = 1024 * 1024 * 1024;
+/// machines with more memory run with large buffers
+const HIGH_MEMORY: u64 = 16 * 1024 * 1024 * 1024;
+
+/// Tuning holds the defaults of the performance settings derived from the resources
+/// of the machine harvester runs on. The settings of the configuration file take
+/// precedence, None leaves the built-in default in place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tuning {
+    pub download_chunk_size: Option<usize>,
+    pub max_parallel_downloads: Option<usize>,
+    pub max_parallel_extracts: Option<usize>,
+}
+
+impl Tuning {
+    /// derives the defaults from the resources of the machine harvester runs on
+    pub fn detect() -> Self {
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        let memory = available_memory();
+        let tuning = Self::for_resources(memory, cpus);
+        debug!(
+            "{} CPUs, {} bytes of memory: {:?}",
+            cpus,
+            memory.map_or("unknown".to_string(), |m| m.to_string()),
+            tuning
+        );
+        tuning
+    }
+
+    /// returns the defaults for a machine with the given resources
+    ///
+    /// * `memory`: the memory available in bytes if known
+    /// * `cpus`: the number of CPUs available
+    pub fn for_resources(memory: Option<u64>, cpus: usize) -> Self {
+        match memory {
+            Some(m) if m < LOW_MEMORY => Self {
+                download_chunk_size: Some(64 * 1024),
+                max_parallel_downloads: Some(cpus.clamp(2, 4)),
+                max_parallel_extracts: Some(cpus),
+            },
+            Some(m) if m >= HIGH_MEMORY => Self {
+                download_chunk_size: Some(1024 * 1024),
+                ..Default::default()
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+/// returns the physical memory or the memory limit of the cgroup harvester runs in
+/// if it's lower, e.g. in a container
+fn available_memory() -> Option<u64> {
+    // SAFETY: sysconf only reads configuration values of the system
+    let (pages, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_PHYS_PAGES),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    let physical = (pages > 0 && page_size > 0).then(|| pages as u64 * page_size as u64);
+    let limit = fs::read_to_string("/sys/fs/cgroup/memory.max")
+        .ok()
+        .and_then(|l| l.trim().parse::<u64>().ok());
+    match (physical, limit) {
+        (Some(p), Some(l)) => Some(p.min(l)),
+        (p, l) => p.or(l),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuning_for_resources() {
+        let router = Tuning::for_resources(Some(512 * 1024 * 1024), 1);
+        assert_eq!(router.download_chunk_size, Some(65536));
+        assert_eq!(router.max_parallel_downloads, Some(2));
+        assert_eq!(router.max_parallel_extracts, Some(1));
+
+        let server = Tuning::for_resources(Some(256 * 1024 * 1024 * 1024), 64);
+        assert_eq!(server.download_chunk_size, Some(1048576));
+        assert_eq!(server.max_parallel_downloads, None);
+
+        assert_eq!(Tuning::for_resources(None, 8), Tuning::default());
+        assert!(Tuning::detect().max_parallel_extracts.is_none_or(|n| n > 0));
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+use futures::future::join_all;
+
+use crate::{
+    config::{Config, RegexOptions},
+    filter_controller::{ChunkContext, Transformed},
+    filter_list::FilterList,
+    input::{file::FileInput, Input},
+    log_throttle,
+    normalize::normalize,
+    output::sources::EntrySources,
+    stages::extract::regex_match,
+    transform::get_transform,
+    CATEGORIZE_PATH, DOWNLOAD_PATH, TRANSFORM_PATH,
+};
+
+/// returns up to `size` entries spread evenly over all entries
+///
+/// * `entries`: the entries of a category
+/// * `size`: the number of entries to be returned at most
+pub fn sample(entries: &[String], size: usize) -> Vec<&String> {
+    if entries.len() <= size {
+        return entries.iter().collect();
+    }
+    (0..size)
+        .map(|i| &entries[i * entries.len() / size])
+        .collect()
+}
+
+/// returns the input the extract stage read a list's cached download from
+///
+/// * `cache_dir`: the cache directory
+/// * `list`: the list to be extracted
+pub fn list_input(cache_dir: &Path, list: &FilterList) -> FileInput {
+    // lists with a transform command were extracted from the command's output
+    let mut input = match list.transform_cmd {
+        Some(_) => FileInput::new(cache_dir.join(TRANSFORM_PATH).join(&list.id), None),
+        None => FileInput::new(
+            cache_dir.join(DOWNLOAD_PATH).join(&list.id),
+            list.compression.clone(),
+        ),
+    };
+    if let Some(max_line_length) = list.max_line_length {
+        input = input.with_max_line_length(max_line_length);
+    }
+    input
+}
+
+/// Extracts the entries of a list from its download again, the way the extract stage
+/// did, and returns them
+///
+/// * `cache_dir`: the cache directory
+/// * `list`: the list to be extracted
+/// * `regex_options`: the configured regex limits
+async fn extract_list(
+    cache_dir: &Path,
+    list: Arc<FilterList>,
+    regex_options: RegexOptions,
+) -> anyhow::Result<Vec<String>> {
+    let mut input = list_input(cache_dir, &list);
+    let mut entries = vec![];
+    let mut context = ChunkContext::new(list, regex_options);
+    while let Some(chunk) = input.chunk().await? {
+        if let Ok(Transformed::Write(entry)) = regex_match(context.clone(), chunk).await {
+            entries.push(String::from_utf8_lossy(&entry).trim_end().to_string());
+            context.lines_matched += 1;
+        } else {
+            context.lines_skipped += 1;
+        }
+        context.line += 1;
+    }
+    log_throttle::summarize(&context.list.id);
+    Ok(entries)
+}
+
+/// Traces a sample of every category's entries back to the downloaded lists they
+/// were taken from. The lists are extracted again and the entries of the sample have
+/// to be found among the entries the lists yield after normalization and the
+/// category's transform script. Returns a description of every entry which couldn't
+/// be traced.
+///
+/// * `config`: the configuration the categories are defined in
+/// * `sample_size`: the number of entries checked per category
+pub async fn verify_deep(config: &Config, sample_size: usize) -> anyhow::Result<Vec<String>> {
+    let cache_dir = Path::new(&config.cache_dir);
+    let mut categories = config.get_tags();
+    categories.sort();
+
+    // the sampled entries and the lists they were taken from per category
+    let mut samples: BTreeMap<String, Vec<(String, Vec<String>)>> = BTreeMap::new();
+    for category in categories {
+        let contents = match fs::read_to_string(cache_dir.join(CATEGORIZE_PATH).join(&category)) {
+            Ok(c) => c,
+            Err(e) => {
+                debug!("{}: {}", category, e);
+                continue;
+            }
+        };
+        let entries: Vec<String> = contents.lines().map(String::from).collect();
+        let sources = EntrySources::load(config, &category);
+        let checks = sample(&entries, sample_size)
+            .into_iter()
+            .map(|e| {
+                let lists = match e.parse() {
+                    Ok(entry) => sources.lists(&entry).to_vec(),
+                    Err(_) => sources.fallback.clone(),
+                };
+                (e.clone(), lists)
+            })
+            .collect();
+        samples.insert(category, checks);
+    }
+
+    // every list involved is extracted once, all of them in parallel
+    let ids: BTreeSet<&String> = samples
+        .values()
+        .flatten()
+        .flat_map(|(_, lists)| lists)
+        .collect();
+    let handles = ids
+        .into_iter()
+        .filter_map(|id| config.lists.iter().find(|l| &l.id == id))
+        .map(|list| {
+            let cache_dir = cache_dir.to_path_buf();
+            let list = Arc::new(list.clone());
+            let regex_options = config.regex_options.clone();
+            tokio::spawn(async move {
+                let entries = extract_list(&cache_dir, list.clone(), regex_options).await;
+                (list.id.clone(), entries)
+            })
+        });
+    let mut extracted: HashMap<String, Vec<String>> = HashMap::new();
+    let mut problems = vec![];
+    for result in join_all(handles).await {
+        match result? {
+            (id, Ok(entries)) => {
+                extracted.insert(id, entries);
+            }
+            (id, Err(e)) => problems.push(format!("{id}: could not extract the list: {e}")),
+        }
+    }
+
+    for (category, checks) in samples {
+        let options = config.category_options(&category);
+        let transform = match &options.transform {
+            Some(path) => Some(get_transform(path)?),
+            None => None,
+        };
+        // the entries each list contributes to the category
+        let mut contributed: HashMap<&String, HashSet<String>> = HashMap::new();
+        for (entry, lists) in checks.iter() {
+            let found = lists.iter().any(|id| {
+                let entries = match extracted.get(id) {
+                    Some(e) => e,
+                    None => return false,
+                };
+                contributed
+                    .entry(id)
+                    .or_insert_with(|| {
+                        entries
+                            .iter()
+                            .flat_map(|e| normalize(e, options.idn))
+                            .filter_map(|e| match &transform {
+                                Some(t) => t.apply(&e, &category).ok().flatten(),
+                                None => Some(e),
+                            })
+                            .collect()
+                    })
+                    .contains(entry)
+            });
+            if !found {
+                problems.push(format!(
+                    "{}: {} is not found in its lists {}",
+                    category,
+                    entry,
+                    lists.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{filter_list::FilterList, tests::helper::cache_file_creator::CacheFileCreator};
+
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let entries: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        assert_eq!(sample(&entries, 20).len(), 10);
+        assert_eq!(sample(&entries, 3), vec!["0", "3", "6"]);
+        assert!(sample(&entries, 0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_verify_deep() {
+        let cache = CacheFileCreator::new("test_verify_deep", "in", "out");
+        let mut config = cache.new_test_config();
+        config.lists = vec![FilterList {
+            id: "one".to_string(),
+            tags: vec!["malware".to_string()],
+            regex: r"^0\.0\.0\.0 ([^\s#]+)".to_string(),
+            ..Default::default()
+        }];
+        let cache_dir = Path::new(&config.cache_dir);
+        fs::create_dir_all(cache_dir.join(DOWNLOAD_PATH)).unwrap();
+        fs::create_dir_all(cache_dir.join(CATEGORIZE_PATH)).unwrap();
+        fs::write(
+            cache_dir.join(DOWNLOAD_PATH).join("one"),
+            "# list\n0.0.0.0 Malicious.COM\n0.0.0.0 unwanted.net\n",
+        )
+        .unwrap();
+
+        let categorized = cache_dir.join(CATEGORIZE_PATH).join("malware");
+        fs::write(&categorized, "malicious.com\nunwanted.net\n").unwrap();
+        assert!(verify_deep(&config, 10).await.unwrap().is_empty());
+
+        fs::write(&categorized, "malicious.com\ncorrupted.net\n").unwrap();
+        assert_eq!(
+            verify_deep(&config, 10).await.unwrap(),
+            vec!["malware: corrupted.net is not found in its lists one"]
+        );
+    }
+}